@@ -0,0 +1,893 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    desugar::CONTRACT_RESULT_NAME,
+    diagnostic::Diagnostic,
+    parser::{Builtin, SugaredExpr, SugaredProgram, SugaredStatement},
+    pretty::pretty_expr,
+    tokenizer::{Operator::*, Span},
+};
+
+/// A construct of the language that still works but will be removed in a future version. Uses of
+/// deprecated constructs are reported as warnings by [analyze] so that scripts can be migrated
+/// before then.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Deprecation {
+    /// A call of the `list` builtin, e.g. `list(1, 2)`, which is replaced by a list literal, e.g.
+    /// `[1, 2]`.
+    ListBuiltin,
+}
+
+impl Deprecation {
+    /// How to replace this construct, which is given as the help text of the warning.
+    fn replacement(self) -> &'static str {
+        match self {
+            Deprecation::ListBuiltin => {
+                "write the elements in a list literal instead, e.g. \"[1, 2]\" rather than \"list(1, 2)\""
+            }
+        }
+    }
+
+    /// Returns the warning about a use of this construct which starts at `span`.
+    fn warning(self, span: Span) -> Diagnostic {
+        let message = format!(
+            "\"{}\" is deprecated and will be removed @ ({}, {})",
+            self, span.line, span.column
+        );
+        let help = self.replacement().to_string();
+        return Diagnostic::warning(message, span.position()).with_help(help);
+    }
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Deprecation::ListBuiltin => write!(f, "list(...)"),
+        }
+    }
+}
+
+/// Analyzes the top-level definitions of a program as written by the user and returns
+/// [warnings](Diagnostic::warning) about code which is valid but likely to be a mistake, or which
+/// uses a [deprecated](Deprecation) construct.
+pub fn analyze(program: &SugaredProgram) -> Vec<Diagnostic> {
+    let mut warnings = vec![];
+    for (_, expr) in &program.constants {
+        analyze_expr(expr, &mut warnings);
+    }
+    for procedure in &program.procedures {
+        for contract in &procedure.contracts {
+            analyze_expr(&contract.condition, &mut warnings);
+        }
+        analyze_statement(&procedure.body, false, &mut warnings);
+    }
+    return warnings;
+}
+
+fn analyze_statement(statement: &SugaredStatement, in_loop: bool, warnings: &mut Vec<Diagnostic>) {
+    match statement {
+        SugaredStatement::Expr(expr)
+        | SugaredStatement::Let(_, expr)
+        | SugaredStatement::LetPattern(_, expr)
+        | SugaredStatement::Const(_, expr)
+        | SugaredStatement::Assign(_, expr)
+        | SugaredStatement::OperatorAssignment(_, _, expr)
+        | SugaredStatement::Return(Some(expr))
+        | SugaredStatement::Break(Some(expr)) => analyze_expr(expr, warnings),
+        SugaredStatement::PlaceAssign(target, _, expr) => {
+            analyze_expr(target, warnings);
+            analyze_expr(expr, warnings);
+        }
+        SugaredStatement::MultiAssign(_, exprs) => {
+            for expr in exprs {
+                analyze_expr(expr, warnings);
+            }
+        }
+        SugaredStatement::Block(statements) => analyze_block(statements, in_loop, warnings),
+        SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => {
+            analyze_expr(cond, warnings);
+            analyze_statement(then_block, in_loop, warnings);
+            for (else_if_cond, else_if_block) in else_ifs {
+                analyze_expr(else_if_cond, warnings);
+                analyze_statement(else_if_block, in_loop, warnings);
+            }
+            if let Some(else_block) = else_block_option {
+                analyze_statement(else_block, in_loop, warnings);
+            }
+        }
+        SugaredStatement::While(cond, body) => {
+            analyze_expr(cond, warnings);
+            analyze_statement(body, true, warnings);
+        }
+        SugaredStatement::Loop(body) => {
+            analyze_statement(body, true, warnings);
+            if !exits_loop(body, false) {
+                warnings.push(Diagnostic::warning(
+                    "\"loop\" never ends, since it has no reachable \"break\" or \"return\""
+                        .to_string(),
+                    None,
+                ));
+            }
+        }
+        SugaredStatement::For(init, cond, update, body) => {
+            analyze_statement(init, in_loop, warnings);
+            analyze_expr(cond, warnings);
+            analyze_statement(update, in_loop, warnings);
+            analyze_block(body, true, warnings);
+        }
+        SugaredStatement::Return(None)
+        | SugaredStatement::Break(None)
+        | SugaredStatement::Continue
+        | SugaredStatement::Line(..) => (),
+    }
+}
+
+/// Warns about statements in a block which follow a `return` statement, or a `break` or `continue`
+/// statement inside of a loop, since they can never be executed. A `break` or `continue` outside of
+/// a loop is already an error, so it is not reported again here.
+fn analyze_block(statements: &[SugaredStatement], in_loop: bool, warnings: &mut Vec<Diagnostic>) {
+    // the value of the last statement of a block is the value of the block, so it is not unused
+    let last = statements
+        .iter()
+        .rposition(|statement| !matches!(statement, SugaredStatement::Line(..)));
+    for (index, statement) in statements.iter().enumerate() {
+        analyze_statement(statement, in_loop, warnings);
+        match statement {
+            SugaredStatement::Expr(expr) if Some(index) != last && is_pure(expr) => {
+                warnings.push(
+                    Diagnostic::warning(
+                        format!("unused result of \"{}\"", pretty_expr(expr)),
+                        None,
+                    )
+                    .with_help(
+                        "assign the result to a variable, or remove the expression".to_string(),
+                    ),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    let jump = statements
+        .iter()
+        .enumerate()
+        .find_map(|(index, statement)| {
+            let keyword = match statement {
+                SugaredStatement::Return(_) => "return",
+                SugaredStatement::Break(_) if in_loop => "break",
+                SugaredStatement::Continue if in_loop => "continue",
+                _ => return None,
+            };
+            Some((index, keyword))
+        });
+
+    if let Some((index, keyword)) = jump {
+        let unreachable = statements[index + 1..]
+            .iter()
+            .any(|statement| !matches!(statement, SugaredStatement::Line(..)));
+        if unreachable {
+            warnings.push(Diagnostic::warning(
+                format!("unreachable statement after \"{keyword}\""),
+                None,
+            ));
+        }
+    }
+}
+
+/// Returns true if `statement`, which is part of the body of a loop, contains a reachable `break` or
+/// `return` statement that ends the loop. A `break` statement only ends the loop if it is not
+/// `nested` in another loop inside of it.
+fn exits_loop(statement: &SugaredStatement, nested: bool) -> bool {
+    return match statement {
+        SugaredStatement::Break(_) => !nested,
+        SugaredStatement::Return(_) => true,
+        SugaredStatement::Block(statements) => block_exits_loop(statements, nested),
+        SugaredStatement::If(_, then_block, else_ifs, else_block_option) => {
+            exits_loop(then_block, nested)
+                || else_ifs
+                    .iter()
+                    .any(|(_, else_if_block)| exits_loop(else_if_block, nested))
+                || else_block_option
+                    .as_ref()
+                    .is_some_and(|else_block| exits_loop(else_block, nested))
+        }
+        SugaredStatement::While(_, body) | SugaredStatement::Loop(body) => exits_loop(body, true),
+        SugaredStatement::For(_, _, _, body) => block_exits_loop(body, true),
+        _ => false,
+    };
+}
+
+/// Returns true if one of `statements` [exits the loop](exits_loop) before a jump which makes the
+/// statements after it unreachable.
+fn block_exits_loop(statements: &[SugaredStatement], nested: bool) -> bool {
+    for statement in statements {
+        if exits_loop(statement, nested) {
+            return true;
+        }
+        if matches!(
+            statement,
+            SugaredStatement::Break(_) | SugaredStatement::Continue
+        ) {
+            return false;
+        }
+    }
+    return false;
+}
+
+/// Returns true if evaluating `expr` has no effect other than producing its value, so that an
+/// expression statement made up of it does nothing. Calls are never pure, since the procedure that
+/// is called may print or reassign variables.
+fn is_pure(expr: &SugaredExpr) -> bool {
+    return match expr {
+        SugaredExpr::Nil
+        | SugaredExpr::Num(_)
+        | SugaredExpr::Bool(_)
+        | SugaredExpr::Str(_)
+        | SugaredExpr::Var(_)
+        | SugaredExpr::Lambda(..) => true,
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            is_pure(left) && is_pure(right)
+        }
+        SugaredExpr::Unary(PreIncrement | PostIncrement | PreDecrement | PostDecrement, _) => false,
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => is_pure(operand),
+        SugaredExpr::StructUpdate(_, base, fields) => {
+            is_pure(base) && fields.iter().all(|(_, value)| is_pure(value))
+        }
+        SugaredExpr::List(elements) => elements.iter().all(is_pure),
+        SugaredExpr::PrimitiveCall(..)
+        | SugaredExpr::Call(..)
+        | SugaredExpr::KeywordCall(..)
+        | SugaredExpr::Statement(_) => false,
+    };
+}
+
+fn analyze_expr(expr: &SugaredExpr, warnings: &mut Vec<Diagnostic>) {
+    match expr {
+        SugaredExpr::Nil
+        | SugaredExpr::Num(_)
+        | SugaredExpr::Bool(_)
+        | SugaredExpr::Str(_)
+        | SugaredExpr::Var(_) => (),
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            analyze_expr(left, warnings);
+            analyze_expr(right, warnings);
+        }
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => {
+            analyze_expr(operand, warnings)
+        }
+        SugaredExpr::StructUpdate(_, base, fields) => {
+            analyze_expr(base, warnings);
+            for (_, value) in fields {
+                analyze_expr(value, warnings);
+            }
+        }
+        SugaredExpr::PrimitiveCall(builtin, args, span) => {
+            if *builtin == Builtin::List {
+                warnings.push(Deprecation::ListBuiltin.warning(*span));
+            }
+            for arg in args {
+                analyze_expr(arg, warnings);
+            }
+        }
+        SugaredExpr::List(elements) => {
+            for element in elements {
+                analyze_expr(element, warnings);
+            }
+        }
+        SugaredExpr::Call(proc_expr, args, _) => {
+            analyze_expr(proc_expr, warnings);
+            for arg in args {
+                analyze_expr(arg, warnings);
+            }
+        }
+        SugaredExpr::KeywordCall(proc_expr, args, keyword_args, _) => {
+            analyze_expr(proc_expr, warnings);
+            for arg in args.iter().chain(keyword_args.iter().map(|(_, arg)| arg)) {
+                analyze_expr(arg, warnings);
+            }
+        }
+        // the body of a lambda is not part of any enclosing loop
+        SugaredExpr::Lambda(_, body) => analyze_statement(body, false, warnings),
+        // `break` and `continue` cannot jump out of an expression
+        SugaredExpr::Statement(statement) => analyze_statement(statement, false, warnings),
+    }
+}
+
+/// Where a name which is in scope while looking for [shadowing] was declared.
+#[derive(Clone, Copy)]
+enum Declaration<'a> {
+    /// A parameter of the procedure with the given name, or of a lambda if there is no name.
+    Param(Option<&'a str>),
+    /// A `let` or `const` statement on the given line.
+    Local(usize),
+}
+
+/// Returns a [warning](Diagnostic::warning) for every local declaration in the procedures of
+/// `program` which shadows a variable declared in an enclosing block, or a parameter of the
+/// procedure or lambda it is in. Declaring a name again in the same block is not reported, since
+/// the first variable can no longer be seen at all. These warnings are opt-in, since shadowing is
+/// often done on purpose.
+pub fn shadowing(program: &SugaredProgram) -> Vec<Diagnostic> {
+    let mut warnings = vec![];
+    for procedure in &program.procedures {
+        let param = Declaration::Param(Some(procedure.name.as_str()));
+        let mut scopes = vec![procedure
+            .params
+            .iter()
+            .map(|name| (name.as_str(), param))
+            .collect::<HashMap<&str, Declaration>>()];
+        let mut line = 0;
+        shadowing_in_statement(&procedure.body, &mut scopes, &mut line, &mut warnings);
+    }
+    return warnings;
+}
+
+/// Declares `name` on `line` in the innermost of `scopes`, warning if it shadows a name declared in
+/// one of the others.
+fn declare_shadowing<'a>(
+    name: &'a str,
+    line: usize,
+    scopes: &mut [HashMap<&'a str, Declaration<'a>>],
+    warnings: &mut Vec<Diagnostic>,
+) {
+    let Some((scope, outer_scopes)) = scopes.split_last_mut() else {
+        return;
+    };
+    let shadowed = match scope.contains_key(name) {
+        true => None,
+        false => outer_scopes.iter().rev().find_map(|outer| outer.get(name)),
+    };
+    let message = match shadowed {
+        Some(Declaration::Local(outer_line)) => Some(format!(
+            "\"{name}\" on line {line} shadows the variable declared on line {outer_line}"
+        )),
+        Some(Declaration::Param(Some(proc_name))) => Some(format!(
+            "\"{name}\" on line {line} shadows a parameter of \"{proc_name}\""
+        )),
+        Some(Declaration::Param(None)) => Some(format!(
+            "\"{name}\" on line {line} shadows a parameter of a lambda"
+        )),
+        None => None,
+    };
+    if let Some(message) = message {
+        let help = "rename the new variable, or assign to the existing one if that was intended";
+        warnings.push(Diagnostic::warning(message, None).with_help(help.to_string()));
+    }
+    scope.insert(name, Declaration::Local(line));
+}
+
+fn shadowing_in_statement<'a>(
+    statement: &'a SugaredStatement,
+    scopes: &mut Vec<HashMap<&'a str, Declaration<'a>>>,
+    line: &mut usize,
+    warnings: &mut Vec<Diagnostic>,
+) {
+    match statement {
+        SugaredStatement::Line(statement_line, _) => *line = *statement_line,
+        SugaredStatement::Expr(expr)
+        | SugaredStatement::Assign(_, expr)
+        | SugaredStatement::OperatorAssignment(_, _, expr)
+        | SugaredStatement::Return(Some(expr))
+        | SugaredStatement::Break(Some(expr)) => shadowing_in_expr(expr, scopes, line, warnings),
+        SugaredStatement::Let(name, expr) | SugaredStatement::Const(name, expr) => {
+            shadowing_in_expr(expr, scopes, line, warnings);
+            declare_shadowing(name, *line, scopes, warnings);
+        }
+        SugaredStatement::LetPattern(pattern, expr) => {
+            shadowing_in_expr(expr, scopes, line, warnings);
+            for name in pattern.names() {
+                declare_shadowing(name, *line, scopes, warnings);
+            }
+        }
+        SugaredStatement::PlaceAssign(target, _, expr) => {
+            shadowing_in_expr(target, scopes, line, warnings);
+            shadowing_in_expr(expr, scopes, line, warnings);
+        }
+        SugaredStatement::MultiAssign(_, exprs) => {
+            for expr in exprs {
+                shadowing_in_expr(expr, scopes, line, warnings);
+            }
+        }
+        SugaredStatement::Block(statements) => {
+            scopes.push(HashMap::new());
+            for statement in statements {
+                shadowing_in_statement(statement, scopes, line, warnings);
+            }
+            scopes.pop();
+        }
+        SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => {
+            shadowing_in_expr(cond, scopes, line, warnings);
+            shadowing_in_statement(then_block, scopes, line, warnings);
+            for (else_if_cond, else_if_block) in else_ifs {
+                shadowing_in_expr(else_if_cond, scopes, line, warnings);
+                shadowing_in_statement(else_if_block, scopes, line, warnings);
+            }
+            if let Some(else_block) = else_block_option {
+                shadowing_in_statement(else_block, scopes, line, warnings);
+            }
+        }
+        SugaredStatement::While(cond, body) => {
+            shadowing_in_expr(cond, scopes, line, warnings);
+            shadowing_in_statement(body, scopes, line, warnings);
+        }
+        SugaredStatement::Loop(body) => shadowing_in_statement(body, scopes, line, warnings),
+        SugaredStatement::For(init, cond, update, body) => {
+            scopes.push(HashMap::new());
+            shadowing_in_statement(init, scopes, line, warnings);
+            shadowing_in_expr(cond, scopes, line, warnings);
+            shadowing_in_statement(update, scopes, line, warnings);
+            scopes.push(HashMap::new());
+            for statement in body {
+                shadowing_in_statement(statement, scopes, line, warnings);
+            }
+            scopes.pop();
+            scopes.pop();
+        }
+        SugaredStatement::Return(None)
+        | SugaredStatement::Break(None)
+        | SugaredStatement::Continue => (),
+    }
+}
+
+fn shadowing_in_expr<'a>(
+    expr: &'a SugaredExpr,
+    scopes: &mut Vec<HashMap<&'a str, Declaration<'a>>>,
+    line: &mut usize,
+    warnings: &mut Vec<Diagnostic>,
+) {
+    match expr {
+        SugaredExpr::Nil
+        | SugaredExpr::Num(_)
+        | SugaredExpr::Bool(_)
+        | SugaredExpr::Str(_)
+        | SugaredExpr::Var(_) => (),
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            shadowing_in_expr(left, scopes, line, warnings);
+            shadowing_in_expr(right, scopes, line, warnings);
+        }
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => {
+            shadowing_in_expr(operand, scopes, line, warnings)
+        }
+        SugaredExpr::StructUpdate(_, base, fields) => {
+            shadowing_in_expr(base, scopes, line, warnings);
+            for (_, value) in fields {
+                shadowing_in_expr(value, scopes, line, warnings);
+            }
+        }
+        SugaredExpr::PrimitiveCall(_, args, _) | SugaredExpr::List(args) => {
+            for arg in args {
+                shadowing_in_expr(arg, scopes, line, warnings);
+            }
+        }
+        SugaredExpr::Call(proc_expr, args, _) => {
+            shadowing_in_expr(proc_expr, scopes, line, warnings);
+            for arg in args {
+                shadowing_in_expr(arg, scopes, line, warnings);
+            }
+        }
+        SugaredExpr::KeywordCall(proc_expr, args, keyword_args, _) => {
+            shadowing_in_expr(proc_expr, scopes, line, warnings);
+            for arg in args.iter().chain(keyword_args.iter().map(|(_, arg)| arg)) {
+                shadowing_in_expr(arg, scopes, line, warnings);
+            }
+        }
+        SugaredExpr::Lambda(params, body) => {
+            scopes.push(
+                params
+                    .iter()
+                    .map(|name| (name.as_str(), Declaration::Param(None)))
+                    .collect(),
+            );
+            shadowing_in_statement(body, scopes, line, warnings);
+            scopes.pop();
+        }
+        SugaredExpr::Statement(statement) => {
+            shadowing_in_statement(statement, scopes, line, warnings)
+        }
+    }
+}
+
+/// Returns an [error](Diagnostic::error) for every side effect in the body of a procedure of
+/// `program` which is marked `pure`. A side effect is a call to a builtin which is not
+/// [pure](crate::parser::Builtin::is_pure), a call to a top-level procedure which is not marked
+/// `pure`, or an assignment to a variable which is not declared in the procedure. A procedure
+/// which passes this check always returns the same value for the same arguments, so its calls can
+/// be memoized or run in parallel. Calls to lambdas and parameters are not checked, since the
+/// procedure they call is not known until the program runs.
+pub fn purity(program: &SugaredProgram) -> Vec<Diagnostic> {
+    let impure_procs: HashSet<&str> = program
+        .procedures
+        .iter()
+        .filter(|procedure| !procedure.pure)
+        .map(|procedure| procedure.name.as_str())
+        .collect();
+    let mut errors = vec![];
+    for procedure in program.procedures.iter().filter(|procedure| procedure.pure) {
+        let mut check = PurityCheck {
+            proc_name: &procedure.name,
+            impure_procs: &impure_procs,
+            scopes: vec![procedure.params.iter().map(String::as_str).collect()],
+            line: 0,
+            errors: vec![],
+        };
+        check.statement(&procedure.body);
+        errors.append(&mut check.errors);
+    }
+    return errors;
+}
+
+/// The state of [purity] while it checks the body of one pure procedure.
+struct PurityCheck<'a> {
+    proc_name: &'a str,
+    /// The names of the top-level procedures which are not marked `pure`.
+    impure_procs: &'a HashSet<&'a str>,
+    /// The names declared in each enclosing block, innermost last.
+    scopes: Vec<HashSet<&'a str>>,
+    /// The line of the statement being checked.
+    line: usize,
+    errors: Vec<Diagnostic>,
+}
+
+impl<'a> PurityCheck<'a> {
+    fn is_declared(&self, name: &str) -> bool {
+        return self.scopes.iter().any(|scope| scope.contains(name));
+    }
+
+    fn declare(&mut self, name: &'a str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name);
+        }
+    }
+
+    fn assignment(&mut self, name: &str) {
+        if self.is_declared(name) {
+            return;
+        }
+        let message = format!(
+            "pure procedure \"{}\" assigns to \"{name}\" on line {}, which is not declared in it",
+            self.proc_name, self.line
+        );
+        let help =
+            "declare a local variable with `let` instead, or remove `pure` from the procedure";
+        self.errors
+            .push(Diagnostic::error(message, None).with_help(help.to_string()));
+    }
+
+    fn statement(&mut self, statement: &'a SugaredStatement) {
+        match statement {
+            SugaredStatement::Line(line, _) => self.line = *line,
+            SugaredStatement::Expr(expr)
+            | SugaredStatement::Return(Some(expr))
+            | SugaredStatement::Break(Some(expr)) => self.expr(expr),
+            SugaredStatement::Let(name, expr) | SugaredStatement::Const(name, expr) => {
+                self.expr(expr);
+                self.declare(name);
+            }
+            SugaredStatement::LetPattern(pattern, expr) => {
+                self.expr(expr);
+                for name in pattern.names() {
+                    self.declare(name);
+                }
+            }
+            SugaredStatement::Assign(name, expr)
+            | SugaredStatement::OperatorAssignment(_, name, expr) => {
+                self.expr(expr);
+                self.assignment(name);
+            }
+            SugaredStatement::PlaceAssign(target, _, expr) => {
+                self.expr(target);
+                self.expr(expr);
+                if let Some(name) = assigned_variable(target) {
+                    self.assignment(name);
+                }
+            }
+            SugaredStatement::MultiAssign(names, exprs) => {
+                for expr in exprs {
+                    self.expr(expr);
+                }
+                for name in names {
+                    self.assignment(name);
+                }
+            }
+            SugaredStatement::Block(statements) => {
+                self.scopes.push(HashSet::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => {
+                self.expr(cond);
+                self.statement(then_block);
+                for (else_if_cond, else_if_block) in else_ifs {
+                    self.expr(else_if_cond);
+                    self.statement(else_if_block);
+                }
+                if let Some(else_block) = else_block_option {
+                    self.statement(else_block);
+                }
+            }
+            SugaredStatement::While(cond, body) => {
+                self.expr(cond);
+                self.statement(body);
+            }
+            SugaredStatement::Loop(body) => self.statement(body),
+            SugaredStatement::For(init, cond, update, body) => {
+                self.scopes.push(HashSet::new());
+                self.statement(init);
+                self.expr(cond);
+                self.statement(update);
+                self.scopes.push(HashSet::new());
+                for statement in body {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+                self.scopes.pop();
+            }
+            SugaredStatement::Return(None)
+            | SugaredStatement::Break(None)
+            | SugaredStatement::Continue => (),
+        }
+    }
+
+    fn call(&mut self, proc_expr: &'a SugaredExpr, args: impl Iterator<Item = &'a SugaredExpr>) {
+        if let SugaredExpr::Var(name) = proc_expr {
+            if !self.is_declared(name) && self.impure_procs.contains(name.as_str()) {
+                let message = format!(
+                    "pure procedure \"{}\" calls \"{name}\" on line {}, which is not pure",
+                    self.proc_name, self.line
+                );
+                let help = format!(
+                    "mark \"{name}\" as `pure`, or remove `pure` from \"{}\"",
+                    self.proc_name
+                );
+                self.errors
+                    .push(Diagnostic::error(message, None).with_help(help));
+            }
+        }
+        self.expr(proc_expr);
+        for arg in args {
+            self.expr(arg);
+        }
+    }
+
+    fn expr(&mut self, expr: &'a SugaredExpr) {
+        match expr {
+            SugaredExpr::Nil
+            | SugaredExpr::Num(_)
+            | SugaredExpr::Bool(_)
+            | SugaredExpr::Str(_)
+            | SugaredExpr::Var(_) => (),
+            SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            SugaredExpr::Unary(
+                PreIncrement | PostIncrement | PreDecrement | PostDecrement,
+                operand,
+            ) => {
+                self.expr(operand);
+                if let Some(name) = assigned_variable(operand) {
+                    self.assignment(name);
+                }
+            }
+            SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => self.expr(operand),
+            SugaredExpr::StructUpdate(_, base, fields) => {
+                self.expr(base);
+                for (_, value) in fields {
+                    self.expr(value);
+                }
+            }
+            SugaredExpr::PrimitiveCall(builtin, args, _) => {
+                if !builtin.is_pure() {
+                    let message = format!(
+                        "pure procedure \"{}\" calls the impure builtin \"{}\" on line {}",
+                        self.proc_name,
+                        builtin.name(),
+                        self.line
+                    );
+                    let help = "remove the call, or remove `pure` from the procedure";
+                    self.errors
+                        .push(Diagnostic::error(message, None).with_help(help.to_string()));
+                }
+                for arg in args {
+                    self.expr(arg);
+                }
+            }
+            SugaredExpr::List(elements) => {
+                for element in elements {
+                    self.expr(element);
+                }
+            }
+            SugaredExpr::Call(proc_expr, args, _) => self.call(proc_expr, args.iter()),
+            SugaredExpr::KeywordCall(proc_expr, args, keyword_args, _) => self.call(
+                proc_expr,
+                args.iter().chain(keyword_args.iter().map(|(_, arg)| arg)),
+            ),
+            SugaredExpr::Lambda(params, body) => {
+                let params = params.iter().map(String::as_str).collect();
+                self.scopes.push(params);
+                self.statement(body);
+                self.scopes.pop();
+            }
+            SugaredExpr::Statement(statement) => self.statement(statement),
+        }
+    }
+}
+
+/// Returns the name of the variable which is changed by assigning to `expr`, e.g. `xs` for
+/// `xs[i][j]`, or `None` if `expr` is not a place.
+fn assigned_variable(expr: &SugaredExpr) -> Option<&str> {
+    return match expr {
+        SugaredExpr::Var(name) => Some(name),
+        SugaredExpr::Index(indexable, _, _) => assigned_variable(indexable),
+        SugaredExpr::Field(object, _) => assigned_variable(object),
+        _ => None,
+    };
+}
+
+/// Returns the names referenced by the top-level definitions of `program` which are not bound by a
+/// parameter or a local declaration in scope at the point of reference. These are the names that
+/// must be resolved to a top-level definition, an import, or a binding provided by the host.
+pub fn free_variables(program: &SugaredProgram) -> HashSet<String> {
+    let mut free = HashSet::new();
+    for (_, expr) in &program.constants {
+        free_variables_in_expr(expr, &mut vec![], &mut free);
+    }
+    for procedure in &program.procedures {
+        let mut scopes = vec![procedure
+            .params
+            .iter()
+            .cloned()
+            .collect::<HashSet<String>>()];
+        for contract in &procedure.contracts {
+            scopes.push(HashSet::from([CONTRACT_RESULT_NAME.to_string()]));
+            free_variables_in_expr(&contract.condition, &mut scopes, &mut free);
+            scopes.pop();
+        }
+        free_variables_in_statement(&procedure.body, &mut scopes, &mut free);
+    }
+    return free;
+}
+
+fn reference(name: &str, scopes: &[HashSet<String>], free: &mut HashSet<String>) {
+    if !scopes.iter().any(|scope| scope.contains(name)) {
+        free.insert(name.to_string());
+    }
+}
+
+fn declare(name: &str, scopes: &mut [HashSet<String>]) {
+    if let Some(scope) = scopes.last_mut() {
+        scope.insert(name.to_string());
+    }
+}
+
+fn free_variables_in_statement(
+    statement: &SugaredStatement,
+    scopes: &mut Vec<HashSet<String>>,
+    free: &mut HashSet<String>,
+) {
+    match statement {
+        SugaredStatement::Expr(expr)
+        | SugaredStatement::Return(Some(expr))
+        | SugaredStatement::Break(Some(expr)) => free_variables_in_expr(expr, scopes, free),
+        SugaredStatement::Let(name, expr) | SugaredStatement::Const(name, expr) => {
+            free_variables_in_expr(expr, scopes, free);
+            declare(name, scopes);
+        }
+        SugaredStatement::LetPattern(pattern, expr) => {
+            free_variables_in_expr(expr, scopes, free);
+            for name in pattern.names() {
+                declare(name, scopes);
+            }
+        }
+        SugaredStatement::Assign(name, expr)
+        | SugaredStatement::OperatorAssignment(_, name, expr) => {
+            free_variables_in_expr(expr, scopes, free);
+            reference(name, scopes, free);
+        }
+        SugaredStatement::PlaceAssign(target, _, expr) => {
+            free_variables_in_expr(target, scopes, free);
+            free_variables_in_expr(expr, scopes, free);
+        }
+        SugaredStatement::MultiAssign(names, exprs) => {
+            for expr in exprs {
+                free_variables_in_expr(expr, scopes, free);
+            }
+            for name in names {
+                reference(name, scopes, free);
+            }
+        }
+        SugaredStatement::Block(statements) => {
+            scopes.push(HashSet::new());
+            for statement in statements {
+                free_variables_in_statement(statement, scopes, free);
+            }
+            scopes.pop();
+        }
+        SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => {
+            free_variables_in_expr(cond, scopes, free);
+            free_variables_in_statement(then_block, scopes, free);
+            for (else_if_cond, else_if_block) in else_ifs {
+                free_variables_in_expr(else_if_cond, scopes, free);
+                free_variables_in_statement(else_if_block, scopes, free);
+            }
+            if let Some(else_block) = else_block_option {
+                free_variables_in_statement(else_block, scopes, free);
+            }
+        }
+        SugaredStatement::While(cond, body) => {
+            free_variables_in_expr(cond, scopes, free);
+            free_variables_in_statement(body, scopes, free);
+        }
+        SugaredStatement::Loop(body) => free_variables_in_statement(body, scopes, free),
+        SugaredStatement::For(init, cond, update, body) => {
+            scopes.push(HashSet::new());
+            free_variables_in_statement(init, scopes, free);
+            free_variables_in_expr(cond, scopes, free);
+            free_variables_in_statement(update, scopes, free);
+            scopes.push(HashSet::new());
+            for statement in body {
+                free_variables_in_statement(statement, scopes, free);
+            }
+            scopes.pop();
+            scopes.pop();
+        }
+        SugaredStatement::Return(None)
+        | SugaredStatement::Break(None)
+        | SugaredStatement::Continue
+        | SugaredStatement::Line(..) => (),
+    }
+}
+
+fn free_variables_in_expr(
+    expr: &SugaredExpr,
+    scopes: &mut Vec<HashSet<String>>,
+    free: &mut HashSet<String>,
+) {
+    match expr {
+        SugaredExpr::Nil | SugaredExpr::Num(_) | SugaredExpr::Bool(_) | SugaredExpr::Str(_) => (),
+        SugaredExpr::Var(name) => reference(name, scopes, free),
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            free_variables_in_expr(left, scopes, free);
+            free_variables_in_expr(right, scopes, free);
+        }
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => {
+            free_variables_in_expr(operand, scopes, free)
+        }
+        // like a call of its constructor, an update refers to the struct by name
+        SugaredExpr::StructUpdate(name, base, fields) => {
+            reference(name, scopes, free);
+            free_variables_in_expr(base, scopes, free);
+            for (_, value) in fields {
+                free_variables_in_expr(value, scopes, free);
+            }
+        }
+        SugaredExpr::PrimitiveCall(_, args, _) | SugaredExpr::List(args) => {
+            for arg in args {
+                free_variables_in_expr(arg, scopes, free);
+            }
+        }
+        SugaredExpr::Call(proc_expr, args, _) => {
+            free_variables_in_expr(proc_expr, scopes, free);
+            for arg in args {
+                free_variables_in_expr(arg, scopes, free);
+            }
+        }
+        SugaredExpr::KeywordCall(proc_expr, args, keyword_args, _) => {
+            free_variables_in_expr(proc_expr, scopes, free);
+            for arg in args.iter().chain(keyword_args.iter().map(|(_, arg)| arg)) {
+                free_variables_in_expr(arg, scopes, free);
+            }
+        }
+        SugaredExpr::Lambda(params, body) => {
+            scopes.push(params.iter().cloned().collect());
+            free_variables_in_statement(body, scopes, free);
+            scopes.pop();
+        }
+        SugaredExpr::Statement(statement) => free_variables_in_statement(statement, scopes, free),
+    }
+}
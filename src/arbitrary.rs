@@ -0,0 +1,461 @@
+//! Random generators of parsed programs for property-based tests with
+//! [proptest](https://docs.rs/proptest), e.g. that printing a program with
+//! [pretty_program](crate::pretty::pretty_program) and parsing the result gives back the same
+//! program.
+//!
+//! The generated programs are syntactically valid, but are not meant to run: they refer to
+//! undefined variables, call numbers, and so on. They have no [Line](SugaredStatement::Line)
+//! markers, so a parsed program should be compared to them after removing its markers with
+//! [without_line_markers].
+
+use std::collections::HashSet;
+
+use proptest::{
+    collection::{btree_map, vec},
+    option,
+    prelude::*,
+    sample::{select, subsequence},
+};
+
+use crate::{
+    builtins::BUILTINS,
+    parser::{
+        tokens_to_source, Builtin, ContractKind, Pattern, SugaredContract, SugaredExpr,
+        SugaredImport, SugaredProcedure, SugaredProgram, SugaredStatement, SugaredStruct,
+    },
+    pretty::pretty_expr,
+    tokenizer::{tokenize, AssignOp, Operator, Span},
+};
+
+/// The names of the generated variables, parameters, procedures, and constants. None of them is
+/// the name of a builtin or starts with a keyword, which the tokenizer would split off, e.g. `iffy`
+/// is tokenized as `if` followed by `fy`.
+const NAMES: &[&str] = &[
+    "a", "b", "c", "n", "x", "y", "acc", "count", "total", "values",
+];
+
+const BINARY_OPERATORS: &[Operator] = &[
+    Operator::Plus,
+    Operator::Minus,
+    Operator::Times,
+    Operator::Eq,
+    Operator::Ne,
+    Operator::LT,
+    Operator::GT,
+    Operator::LTE,
+    Operator::GTE,
+    Operator::Mod,
+    Operator::Div,
+    Operator::FloorDiv,
+    Operator::LogicOr,
+    Operator::LogicAnd,
+    Operator::BitAnd,
+    Operator::BitOr,
+    Operator::BitXor,
+    Operator::ShiftLeft,
+    Operator::ShiftRight,
+];
+
+const UNARY_OPERATORS: &[Operator] = &[
+    Operator::Minus,
+    Operator::LogicNot,
+    Operator::BitNot,
+    Operator::PreIncrement,
+    Operator::PostIncrement,
+    Operator::PreDecrement,
+    Operator::PostDecrement,
+];
+
+const INCREMENT_OPERATORS: &[Operator] = &[
+    Operator::PreIncrement,
+    Operator::PostIncrement,
+    Operator::PreDecrement,
+    Operator::PostDecrement,
+];
+
+/// Generates a program with unique top-level names.
+pub fn program() -> impl Strategy<Value = SugaredProgram> {
+    let import =
+        (string(), option::of(name())).prop_map(|(path, alias)| SugaredImport { path, alias });
+    return (
+        vec(import, 0..3),
+        vec((name(), expr()), 0..3),
+        vec(procedure(), 0..4),
+        vec((name(), vec(name(), 0..3)), 0..2),
+    )
+        .prop_map(|(imports, constants, procedures, structs)| {
+            let mut names = HashSet::new();
+            let constants = constants
+                .into_iter()
+                .filter(|(name, _)| names.insert(name.clone()))
+                .collect();
+            let procedures = procedures
+                .into_iter()
+                .filter(|proc| names.insert(proc.name.clone()))
+                .collect();
+            let structs = structs
+                .into_iter()
+                .filter(|(name, _)| names.insert(name.clone()))
+                .map(|(name, mut fields)| {
+                    let mut unique_fields = HashSet::new();
+                    fields.retain(|field| unique_fields.insert(field.clone()));
+                    SugaredStruct { name, fields }
+                })
+                .collect();
+            let imports = imports
+                .into_iter()
+                .filter(|import| match &import.alias {
+                    Some(alias) => names.insert(alias.clone()),
+                    None => true,
+                })
+                .collect();
+            SugaredProgram {
+                imports,
+                constants,
+                procedures,
+                structs,
+            }
+        });
+}
+
+/// Generates a procedure definition.
+pub fn procedure() -> impl Strategy<Value = SugaredProcedure> {
+    let contract = (
+        prop_oneof![Just(ContractKind::Requires), Just(ContractKind::Ensures)],
+        expr(),
+    )
+        .prop_map(|(kind, condition)| {
+            let tokens = tokenize(&pretty_expr(&condition))
+                .expect("a printed expression should be tokenizable");
+            SugaredContract {
+                kind,
+                source: tokens_to_source(&tokens),
+                condition,
+            }
+        });
+    return (
+        name(),
+        vec(name(), 0..3),
+        vec(contract, 0..2),
+        vec(statement(), 0..4),
+        any::<bool>(),
+        any::<bool>(),
+    )
+        .prop_map(
+            |(name, params, contracts, body, public, pure)| SugaredProcedure {
+                name,
+                params,
+                contracts,
+                body: SugaredStatement::Block(body),
+                public,
+                pure,
+            },
+        );
+}
+
+/// Generates a statement, which may contain nested statements.
+pub fn statement() -> impl Strategy<Value = SugaredStatement> {
+    return statement_with(expr().boxed());
+}
+
+/// Generates an expression, which may contain nested expressions and statements.
+pub fn expr() -> impl Strategy<Value = SugaredExpr> {
+    let leaf = prop_oneof![
+        Just(SugaredExpr::Nil),
+        num().prop_map(SugaredExpr::Num),
+        any::<bool>().prop_map(SugaredExpr::Bool),
+        string().prop_map(SugaredExpr::Str),
+        name().prop_map(SugaredExpr::Var),
+    ];
+    return leaf.prop_recursive(4, 32, 3, |inner| {
+        let inner = inner.boxed();
+        let block = vec(simple_statement(inner.clone()), 0..3)
+            .prop_map(SugaredStatement::Block)
+            .boxed();
+        let lambda_body = prop_oneof![
+            inner.clone().prop_map(SugaredStatement::Expr),
+            block.clone(),
+        ];
+        prop_oneof![
+            (select(BINARY_OPERATORS), inner.clone(), inner.clone()).prop_map(
+                |(op, left, right)| {
+                    let spans = (Span::default(), Span::default());
+                    SugaredExpr::Binary(op, Box::new(left), Box::new(right), spans)
+                }
+            ),
+            (select(UNARY_OPERATORS), inner.clone())
+                .prop_map(|(op, operand)| SugaredExpr::Unary(op, Box::new(operand))),
+            vec(inner.clone(), 0..3).prop_map(SugaredExpr::List),
+            (builtin(), vec(inner.clone(), 0..3)).prop_map(|(builtin, args)| {
+                SugaredExpr::PrimitiveCall(builtin, args, Span::default())
+            }),
+            (inner.clone(), vec(inner.clone(), 0..3)).prop_map(|(callee, args)| SugaredExpr::Call(
+                Box::new(callee),
+                args,
+                Span::default()
+            )),
+            (
+                inner.clone(),
+                vec(inner.clone(), 0..2),
+                btree_map(name(), inner.clone(), 1..3)
+            )
+                .prop_map(|(callee, args, keyword_args)| SugaredExpr::KeywordCall(
+                    Box::new(callee),
+                    args,
+                    keyword_args.into_iter().collect(),
+                    Span::default()
+                )),
+            (vec(name(), 0..3), lambda_body)
+                .prop_map(|(params, body)| SugaredExpr::Lambda(params, Box::new(body))),
+            (inner.clone(), inner.clone()).prop_map(|(indexable, index)| SugaredExpr::Index(
+                Box::new(indexable),
+                Box::new(index),
+                Span::default()
+            )),
+            prop_oneof![
+                block.clone(),
+                if_statement(inner, block.clone()),
+                block.prop_map(|body| SugaredStatement::Loop(Box::new(body))),
+            ]
+            .prop_map(|statement| SugaredExpr::Statement(Box::new(statement))),
+        ]
+    });
+}
+
+/// Removes the [Line](SugaredStatement::Line) markers from every block of `program`.
+pub fn without_line_markers(mut program: SugaredProgram) -> SugaredProgram {
+    for (_, expr) in &mut program.constants {
+        strip_expr(expr);
+    }
+    for proc in &mut program.procedures {
+        for contract in &mut proc.contracts {
+            strip_expr(&mut contract.condition);
+        }
+        strip_statement(&mut proc.body);
+    }
+    return program;
+}
+
+fn strip_statements(statements: &mut Vec<SugaredStatement>) {
+    statements.retain(|statement| !matches!(statement, SugaredStatement::Line(..)));
+    statements.iter_mut().for_each(strip_statement);
+}
+
+fn strip_statement(statement: &mut SugaredStatement) {
+    match statement {
+        SugaredStatement::Expr(expr)
+        | SugaredStatement::Let(_, expr)
+        | SugaredStatement::LetPattern(_, expr)
+        | SugaredStatement::Const(_, expr)
+        | SugaredStatement::Assign(_, expr)
+        | SugaredStatement::OperatorAssignment(_, _, expr)
+        | SugaredStatement::Return(Some(expr))
+        | SugaredStatement::Break(Some(expr)) => strip_expr(expr),
+        SugaredStatement::PlaceAssign(target, _, expr) => {
+            strip_expr(target);
+            strip_expr(expr);
+        }
+        SugaredStatement::MultiAssign(_, exprs) => exprs.iter_mut().for_each(strip_expr),
+        SugaredStatement::Block(statements) => strip_statements(statements),
+        SugaredStatement::If(cond, then_block, else_ifs, else_block) => {
+            strip_expr(cond);
+            strip_statement(then_block);
+            for (else_if_cond, else_if_block) in else_ifs {
+                strip_expr(else_if_cond);
+                strip_statement(else_if_block);
+            }
+            if let Some(else_block) = else_block {
+                strip_statement(else_block);
+            }
+        }
+        SugaredStatement::While(cond, body) => {
+            strip_expr(cond);
+            strip_statement(body);
+        }
+        SugaredStatement::Loop(body) => strip_statement(body),
+        SugaredStatement::For(init, cond, update, body) => {
+            strip_statement(init);
+            strip_expr(cond);
+            strip_statement(update);
+            strip_statements(body);
+        }
+        SugaredStatement::Break(None)
+        | SugaredStatement::Continue
+        | SugaredStatement::Return(None)
+        | SugaredStatement::Line(..) => {}
+    }
+}
+
+fn strip_expr(expr: &mut SugaredExpr) {
+    match expr {
+        SugaredExpr::Nil
+        | SugaredExpr::Num(_)
+        | SugaredExpr::Bool(_)
+        | SugaredExpr::Str(_)
+        | SugaredExpr::Var(_) => {}
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            strip_expr(left);
+            strip_expr(right);
+        }
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => strip_expr(operand),
+        SugaredExpr::StructUpdate(_, base, fields) => {
+            strip_expr(base);
+            fields.iter_mut().for_each(|(_, value)| strip_expr(value));
+        }
+        SugaredExpr::PrimitiveCall(_, args, _) | SugaredExpr::List(args) => {
+            args.iter_mut().for_each(strip_expr)
+        }
+        SugaredExpr::Call(callee, args, _) => {
+            strip_expr(callee);
+            args.iter_mut().for_each(strip_expr);
+        }
+        SugaredExpr::KeywordCall(callee, args, keyword_args, _) => {
+            strip_expr(callee);
+            args.iter_mut().for_each(strip_expr);
+            keyword_args.iter_mut().for_each(|(_, arg)| strip_expr(arg));
+        }
+        SugaredExpr::Lambda(_, body) => strip_statement(body),
+        SugaredExpr::Statement(statement) => strip_statement(statement),
+    }
+}
+
+/// Generates a statement made up of expressions generated by `expr`, which may contain nested
+/// statements.
+fn statement_with(expr: BoxedStrategy<SugaredExpr>) -> impl Strategy<Value = SugaredStatement> {
+    return simple_statement(expr.clone()).prop_recursive(3, 16, 3, move |inner| {
+        let block = vec(inner.clone(), 0..3)
+            .prop_map(SugaredStatement::Block)
+            .boxed();
+        let var = name().prop_map(SugaredExpr::Var);
+        let increment = (select(INCREMENT_OPERATORS), prop_oneof![var, expr.clone()]).prop_map(
+            |(op, operand)| SugaredStatement::Expr(SugaredExpr::Unary(op, Box::new(operand))),
+        );
+        let assignment = prop_oneof![assignment(expr.clone()), increment];
+        let initialization = prop_oneof![
+            (name(), expr.clone()).prop_map(|(name, expr)| SugaredStatement::Let(name, expr)),
+            assignment.clone(),
+        ];
+        prop_oneof![
+            block.clone(),
+            if_statement(expr.clone(), block.clone()),
+            (expr.clone(), block.clone())
+                .prop_map(|(cond, body)| SugaredStatement::While(cond, Box::new(body))),
+            block.prop_map(|body| SugaredStatement::Loop(Box::new(body))),
+            (initialization, expr.clone(), assignment, vec(inner, 0..3)).prop_map(
+                |(init, cond, update, body)| SugaredStatement::For(
+                    Box::new(init),
+                    cond,
+                    Box::new(update),
+                    body
+                )
+            ),
+        ]
+    });
+}
+
+/// Generates a statement which does not contain nested statements, apart from those in the
+/// expressions generated by `expr`.
+fn simple_statement(expr: BoxedStrategy<SugaredExpr>) -> impl Strategy<Value = SugaredStatement> {
+    return prop_oneof![
+        expr.clone().prop_map(SugaredStatement::Expr),
+        (name(), expr.clone()).prop_map(|(name, expr)| SugaredStatement::Let(name, expr)),
+        (pattern(), expr.clone())
+            .prop_map(|(pattern, expr)| SugaredStatement::LetPattern(pattern, expr)),
+        (name(), expr.clone()).prop_map(|(name, expr)| SugaredStatement::Const(name, expr)),
+        assignment(expr.clone()),
+        option::of(expr.clone()).prop_map(SugaredStatement::Break),
+        Just(SugaredStatement::Continue),
+        option::of(expr).prop_map(SugaredStatement::Return),
+    ];
+}
+
+/// Generates the pattern of a destructuring `let` statement, whose names are distinct.
+fn pattern() -> impl Strategy<Value = Pattern> {
+    let names = subsequence(NAMES, 0..4).prop_map(|names| {
+        names
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<String>>()
+    });
+    return prop_oneof![
+        (names.clone(), any::<bool>()).prop_map(|(mut names, has_rest)| {
+            let rest = if has_rest { names.pop() } else { None };
+            Pattern::List(names, rest)
+        }),
+        names.prop_map(Pattern::Map),
+    ];
+}
+
+/// Generates an assignment, with or without an operator or an index, of expressions generated by
+/// `expr`.
+fn assignment(expr: BoxedStrategy<SugaredExpr>) -> impl Strategy<Value = SugaredStatement> {
+    return prop_oneof![
+        (name(), expr.clone()).prop_map(|(name, expr)| SugaredStatement::Assign(name, expr)),
+        (assign_op(), name(), expr.clone())
+            .prop_map(|(op, name, expr)| SugaredStatement::OperatorAssignment(op, name, expr)),
+        (
+            expr.clone(),
+            expr.clone(),
+            option::of(assign_op()),
+            expr.clone()
+        )
+            .prop_map(|(indexable, index, op, expr)| {
+                let target =
+                    SugaredExpr::Index(Box::new(indexable), Box::new(index), Span::default());
+                SugaredStatement::PlaceAssign(target, op, expr)
+            }),
+        vec((name(), expr), 2..4).prop_map(|assignments| {
+            let (names, exprs) = assignments.into_iter().unzip();
+            SugaredStatement::MultiAssign(names, exprs)
+        }),
+    ];
+}
+
+/// Generates an `if` statement with conditions generated by `expr` and blocks generated by `block`.
+fn if_statement(
+    expr: BoxedStrategy<SugaredExpr>,
+    block: BoxedStrategy<SugaredStatement>,
+) -> impl Strategy<Value = SugaredStatement> {
+    return (
+        expr.clone(),
+        block.clone(),
+        vec((expr, block.clone()), 0..2),
+        option::of(block),
+    )
+        .prop_map(|(cond, then_block, else_ifs, else_block)| {
+            SugaredStatement::If(
+                cond,
+                Box::new(then_block),
+                else_ifs,
+                else_block.map(Box::new),
+            )
+        });
+}
+
+fn name() -> impl Strategy<Value = String> {
+    return select(NAMES).prop_map(str::to_string);
+}
+
+fn assign_op() -> impl Strategy<Value = AssignOp> {
+    return select(&[
+        AssignOp::Plus,
+        AssignOp::Minus,
+        AssignOp::Times,
+        AssignOp::Div,
+        AssignOp::Mod,
+    ]);
+}
+
+/// Generates a number literal, which cannot be negative since `-1` is parsed as a unary minus.
+fn num() -> impl Strategy<Value = f64> {
+    return prop_oneof![(0..1000u32).prop_map(f64::from), 0.0..1e9f64];
+}
+
+/// Generates a string with some of the characters which need escaping in a string literal.
+fn string() -> impl Strategy<Value = String> {
+    return "(\\PC|[\n\r\t\\x00\"\\\\]){0,8}";
+}
+
+fn builtin() -> impl Strategy<Value = Builtin> {
+    return select(BUILTINS).prop_map(|spec| spec.builtin);
+}
@@ -0,0 +1,194 @@
+//! The registry of builtin procedures. Every [Builtin] has a [BuiltinSpec] in [BUILTINS] that
+//! gives its name, which the parser uses to recognize calls to it, and its arity and handler, which
+//! the interpreter uses to evaluate those calls.
+
+use std::fmt;
+
+use crate::{
+    desugar::Expr,
+    environment::Environment,
+    error::RuntimeError,
+    interpreter::{builtins, Value},
+    parser::Builtin,
+    Writer,
+};
+
+/// The number of arguments a builtin procedure accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    /// Between the first and second number of arguments (inclusive).
+    Between(usize, usize),
+    /// Any even number of arguments, e.g. the keys and values of a map.
+    Pairs,
+}
+
+impl Arity {
+    /// Returns true if a call with `count` arguments is accepted.
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exact(n) => count == n,
+            Arity::AtLeast(min) => count >= min,
+            Arity::Between(min, max) => min <= count && count <= max,
+            Arity::Pairs => count.is_multiple_of(2),
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(min) => write!(f, "at least {}", min),
+            Arity::Between(min, max) => write!(f, "{} to {}", min, max),
+            Arity::Pairs => write!(f, "an even number of"),
+        }
+    }
+}
+
+/// A function which evaluates a call to a builtin procedure with the given (unevaluated) arguments.
+pub type BuiltinHandler = fn(&mut Environment, &[Expr], &mut Writer) -> Result<Value, RuntimeError>;
+
+/// The description of a builtin procedure.
+#[derive(Clone, Copy, Debug)]
+pub struct BuiltinSpec {
+    pub builtin: Builtin,
+    /// The name that the builtin is called by.
+    pub name: &'static str,
+    pub arity: Arity,
+    /// A description of the arguments of the builtin, which is shown when it is called with the
+    /// wrong number of arguments.
+    pub usage: &'static str,
+    pub handler: BuiltinHandler,
+}
+
+macro_rules! builtin {
+    ($builtin:ident, $name:literal, $arity:expr, $usage:literal, $handler:path) => {
+        BuiltinSpec {
+            builtin: Builtin::$builtin,
+            name: $name,
+            arity: $arity,
+            usage: $usage,
+            handler: $handler,
+        }
+    };
+}
+
+/// Every builtin procedure of the language, in the order of the variants of [Builtin], so that the
+/// spec of a builtin is at the index of its discriminant (see [Builtin::spec]).
+#[rustfmt::skip]
+pub const BUILTINS: &[BuiltinSpec] = &[
+    builtin!(Print, "print", Arity::AtLeast(0), "print(values...)", builtins::print),
+    builtin!(List, "list", Arity::AtLeast(0), "list(values...)", builtins::list),
+    builtin!(IsEmpty, "is_empty", Arity::Exact(1), "is_empty(list)", builtins::is_empty),
+    builtin!(IsNil, "is_nil", Arity::Exact(1), "is_nil(value)", builtins::is_nil),
+    builtin!(Head, "head", Arity::Exact(1), "head(list)", builtins::head),
+    builtin!(Rest, "rest", Arity::Exact(1), "rest(list)", builtins::rest),
+    builtin!(Zip, "zip", Arity::Exact(2), "zip(list, list)", builtins::zip),
+    builtin!(Enumerate, "enumerate", Arity::Exact(1), "enumerate(list)", builtins::enumerate),
+    builtin!(Range, "range", Arity::Between(1, 3), "range([start,] stop[, step])", builtins::range),
+    builtin!(Sort, "sort", Arity::Exact(1), "sort(list)", builtins::sort),
+    builtin!(SortBy, "sort_by", Arity::Exact(2), "sort_by(list, comparator)", builtins::sort_by),
+    builtin!(IndexOf, "index_of", Arity::Exact(2), "index_of(list, target)", builtins::index_of),
+    builtin!(Find, "find", Arity::Exact(2), "find(list, target)", builtins::find),
+    builtin!(Any, "any", Arity::Exact(2), "any(list, target)", builtins::any),
+    builtin!(All, "all", Arity::Exact(2), "all(list, target)", builtins::all),
+    builtin!(Flat, "flat", Arity::Exact(1), "flat(list)", builtins::flat),
+    builtin!(FlatMap, "flat_map", Arity::Exact(2), "flat_map(list, procedure)", builtins::flat_map),
+    builtin!(Unique, "unique", Arity::Exact(1), "unique(list)", builtins::unique),
+    builtin!(Reverse, "reverse", Arity::Exact(1), "reverse(list or string)", builtins::reverse),
+    builtin!(Take, "take", Arity::Exact(2), "take(list, count)", builtins::take),
+    builtin!(Drop, "drop", Arity::Exact(2), "drop(list, count)", builtins::drop),
+    builtin!(Copy, "copy", Arity::Exact(1), "copy(value)", builtins::copy),
+    builtin!(Freeze, "freeze", Arity::Exact(1), "freeze(variable)", builtins::freeze),
+    builtin!(DivMod, "divmod", Arity::Exact(2), "divmod(dividend, divisor)", builtins::divmod),
+    builtin!(Dict, "dict", Arity::Pairs, "dict(key, value, ...)", builtins::dict),
+    builtin!(HasKey, "has_key", Arity::Exact(2), "has_key(map, key)", builtins::has_key),
+    builtin!(Set, "set", Arity::Exact(3), "set(map, key, value)", builtins::set),
+    builtin!(Keys, "keys", Arity::Exact(1), "keys(map)", builtins::keys),
+    builtin!(Join, "join", Arity::Exact(2), "join(list, separator)", builtins::join),
+    builtin!(Split, "split", Arity::Exact(2), "split(string, separator)", builtins::split),
+    builtin!(EqualsIgnoreCase, "equals_ignore_case", Arity::Exact(2), "equals_ignore_case(string, string)", builtins::equals_ignore_case),
+    builtin!(Compare, "compare", Arity::Between(2, 3), "compare(a, b[, ignore_case])", builtins::compare),
+    builtin!(Chars, "chars", Arity::Exact(1), "chars(string)", builtins::chars),
+    builtin!(Words, "words", Arity::Exact(1), "words(string)", builtins::words),
+    builtin!(Lines, "lines", Arity::Exact(1), "lines(string)", builtins::lines),
+    #[cfg(feature = "csv")]
+    builtin!(CsvParse, "csv_parse", Arity::Between(1, 2), "csv_parse(text[, has_header])", builtins::csv_parse),
+    #[cfg(feature = "csv")]
+    builtin!(CsvStringify, "csv_stringify", Arity::Exact(1), "csv_stringify(records)", builtins::csv_stringify),
+    builtin!(ReadFileBytes, "read_file_bytes", Arity::Exact(1), "read_file_bytes(path)", builtins::read_file_bytes),
+    builtin!(BytesLen, "bytes_len", Arity::Exact(1), "bytes_len(bytes)", builtins::bytes_len),
+    builtin!(ByteAt, "byte_at", Arity::Exact(2), "byte_at(bytes, index)", builtins::byte_at),
+    builtin!(SliceBytes, "slice_bytes", Arity::Exact(3), "slice_bytes(bytes, start, end)", builtins::slice_bytes),
+    builtin!(HexEncode, "hex_encode", Arity::Exact(1), "hex_encode(bytes)", builtins::hex_encode),
+    builtin!(HexDecode, "hex_decode", Arity::Exact(1), "hex_decode(string)", builtins::hex_decode),
+    #[cfg(feature = "hash")]
+    builtin!(Md5, "md5", Arity::Exact(1), "md5(string or bytes)", builtins::md5),
+    #[cfg(feature = "hash")]
+    builtin!(Sha256, "sha256", Arity::Exact(1), "sha256(string or bytes)", builtins::sha256),
+    builtin!(Base64Encode, "base64_encode", Arity::Exact(1), "base64_encode(string or bytes)", builtins::base64_encode),
+    builtin!(Base64Decode, "base64_decode", Arity::Exact(1), "base64_decode(string)", builtins::base64_decode),
+    builtin!(UrlEncode, "url_encode", Arity::Exact(1), "url_encode(string or bytes)", builtins::url_encode),
+    builtin!(UrlDecode, "url_decode", Arity::Exact(1), "url_decode(string)", builtins::url_decode),
+    builtin!(ParseNum, "parse_num", Arity::Exact(1), "parse_num(string)", builtins::parse_num),
+    builtin!(ToStrRadix, "to_str_radix", Arity::Exact(2), "to_str_radix(integer, base)", builtins::to_str_radix),
+    builtin!(ParseInt, "parse_int", Arity::Exact(2), "parse_int(string, base)", builtins::parse_int),
+    builtin!(Debug, "debug", Arity::Exact(1), "debug(value)", builtins::debug),
+    builtin!(TypeAssert, "type_assert", Arity::Exact(2), "type_assert(value, type)", builtins::type_assert),
+    builtin!(Panic, "panic", Arity::Exact(1), "panic(message)", builtins::panic),
+    builtin!(Random, "random", Arity::Exact(0), "random()", builtins::random),
+    builtin!(NowMillis, "now_millis", Arity::Exact(0), "now_millis()", builtins::now_millis),
+    builtin!(Input, "input", Arity::Exact(0), "input()", builtins::input),
+    builtin!(ScriptName, "script_name", Arity::Exact(0), "script_name()", builtins::script_name),
+    builtin!(ScriptVersion, "script_version", Arity::Exact(0), "script_version()", builtins::script_version),
+];
+
+// checks at compile time that every spec is at the index of the discriminant of its builtin
+const _: () = {
+    let mut index = 0;
+    while index < BUILTINS.len() {
+        assert!(
+            BUILTINS[index].builtin as usize == index,
+            "BUILTINS should be in the order of the variants of Builtin"
+        );
+        index += 1;
+    }
+};
+
+impl Builtin {
+    /// Returns the builtin procedure called `name`, if there is one.
+    pub fn from_name(name: &str) -> Option<Builtin> {
+        return BUILTINS
+            .iter()
+            .find(|spec| spec.name == name)
+            .map(|spec| spec.builtin);
+    }
+
+    /// Returns the description of this builtin procedure.
+    pub fn spec(self) -> &'static BuiltinSpec {
+        return BUILTINS
+            .get(self as usize)
+            .expect("every builtin should be registered in BUILTINS");
+    }
+
+    /// The name that this builtin procedure is called by.
+    pub fn name(self) -> &'static str {
+        return self.spec().name;
+    }
+
+    /// Returns false if a call to this builtin procedure has an effect outside of the program, such
+    /// as printing or reading input, or may return a different value each time it is made. Pure
+    /// procedures may not call these builtins (see [analysis::purity](crate::analysis::purity)).
+    pub fn is_pure(self) -> bool {
+        return !matches!(
+            self,
+            Builtin::Print
+                | Builtin::Input
+                | Builtin::ReadFileBytes
+                | Builtin::Random
+                | Builtin::NowMillis
+        );
+    }
+}
@@ -0,0 +1,652 @@
+//! Compiled programs, which are parsed and desugared programs stored in a binary format so that they
+//! can be run without being tokenized and parsed again (see `linger build`).
+//!
+//! A compiled program starts with [MAGIC] and a format version, followed by the encoded [Program].
+//! Integers are stored as little endian `u32`s, numbers as little endian `f64`s, strings and lists
+//! as their length followed by their contents, and enums as a tag byte followed by their fields.
+
+use std::sync::Arc;
+
+use crate::{
+    desugar::{shared_exprs, shared_statements, Contract, Expr, Procedure, Statement},
+    error::ParseError::{self, InvalidCompiledProgram},
+    metadata::Metadata,
+    parser::{Builtin, ContractKind, Module, Program},
+    symbol::Symbol,
+    tokenizer::{Operator, Span},
+};
+
+/// The bytes that every compiled program starts with.
+pub const MAGIC: &[u8] = b"LINGC";
+
+/// The version of the format of compiled programs, which is increased whenever the format changes
+/// so that programs compiled by another version of Linger are rejected rather than misread.
+const FORMAT_VERSION: u8 = 13;
+
+/// Every [Operator], in the order of their tags.
+const OPERATORS: &[Operator] = &[
+    Operator::Plus,
+    Operator::Minus,
+    Operator::Times,
+    Operator::Eq,
+    Operator::Ne,
+    Operator::LT,
+    Operator::GT,
+    Operator::LTE,
+    Operator::GTE,
+    Operator::Mod,
+    Operator::Div,
+    Operator::FloorDiv,
+    Operator::LogicOr,
+    Operator::LogicAnd,
+    Operator::LogicNot,
+    Operator::PreIncrement,
+    Operator::PostIncrement,
+    Operator::PreDecrement,
+    Operator::PostDecrement,
+    Operator::BitAnd,
+    Operator::BitOr,
+    Operator::BitXor,
+    Operator::BitNot,
+    Operator::ShiftLeft,
+    Operator::ShiftRight,
+];
+
+/// Returns true if `bytes` are a compiled program, rather than the source of a program.
+pub fn is_compiled_program(bytes: &[u8]) -> bool {
+    return bytes.starts_with(MAGIC);
+}
+
+/// Encodes `program` as a compiled program.
+pub fn encode_program(program: &Program) -> Vec<u8> {
+    let mut encoder = Encoder {
+        bytes: MAGIC.to_vec(),
+    };
+    encoder.u8(FORMAT_VERSION);
+    encoder.program(program);
+    return encoder.bytes;
+}
+
+/// Decodes the compiled program `bytes`. This function returns an [InvalidCompiledProgram] error if
+/// `bytes` are not a compiled program, or were compiled by an incompatible version of Linger.
+pub fn decode_program(bytes: &[u8]) -> Result<Program, ParseError> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        return Err(InvalidCompiledProgram("not a compiled program".to_string()));
+    };
+    let mut decoder = Decoder { bytes: rest };
+    if decoder.u8()? != FORMAT_VERSION {
+        return Err(InvalidCompiledProgram(
+            "compiled by an incompatible version of linger".to_string(),
+        ));
+    }
+    let program = decoder.program()?;
+    if !decoder.bytes.is_empty() {
+        return Err(InvalidCompiledProgram(
+            "unexpected trailing data".to_string(),
+        ));
+    }
+    return Ok(program);
+}
+
+struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    fn u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn len(&mut self, n: usize) {
+        let n = u32::try_from(n).expect("lengths in a program should fit in 32 bits");
+        self.bytes.extend(n.to_le_bytes());
+    }
+
+    fn bool(&mut self, b: bool) {
+        self.u8(b as u8);
+    }
+
+    fn num(&mut self, n: f64) {
+        self.bytes.extend(n.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+
+    fn strs(&mut self, strs: &[String]) {
+        self.len(strs.len());
+        for s in strs {
+            self.str(s);
+        }
+    }
+
+    fn symbol(&mut self, symbol: Symbol) {
+        self.str(symbol.as_str());
+    }
+
+    fn symbols(&mut self, symbols: &[Symbol]) {
+        self.len(symbols.len());
+        for symbol in symbols {
+            self.symbol(*symbol);
+        }
+    }
+
+    fn option_str(&mut self, s: &Option<String>) {
+        match s {
+            Some(s) => {
+                self.u8(1);
+                self.str(s);
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn program(&mut self, program: &Program) {
+        self.len(program.procedures.len());
+        for procedure in &program.procedures {
+            self.procedure(procedure);
+        }
+        self.statement(&program.main);
+        self.len(program.constants.len());
+        for (name, expr) in &program.constants {
+            self.str(name);
+            self.expr(expr);
+        }
+        self.len(program.modules.len());
+        for module in &program.modules {
+            self.module(module);
+        }
+        self.option_str(&program.metadata.name);
+        self.option_str(&program.metadata.version);
+    }
+
+    fn module(&mut self, module: &Module) {
+        self.str(&module.path);
+        self.option_str(&module.alias);
+        self.program(&module.program);
+        self.strs(&module.exports);
+    }
+
+    fn procedure(&mut self, procedure: &Procedure) {
+        self.symbol(procedure.name);
+        self.symbols(&procedure.params);
+        self.statement(&procedure.body);
+    }
+
+    fn statements(&mut self, statements: &[Statement]) {
+        self.len(statements.len());
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expr(expr) => {
+                self.u8(0);
+                self.expr(expr);
+            }
+            Statement::Let(name, expr) => {
+                self.u8(1);
+                self.symbol(*name);
+                self.expr(expr);
+            }
+            Statement::Const(name, expr) => {
+                self.u8(2);
+                self.symbol(*name);
+                self.expr(expr);
+            }
+            Statement::Assign(name, expr) => {
+                self.u8(3);
+                self.symbol(*name);
+                self.expr(expr);
+            }
+            Statement::If(cond, then_block, else_block) => {
+                self.u8(4);
+                self.expr(cond);
+                self.statement(then_block);
+                match else_block {
+                    Some(else_block) => {
+                        self.u8(1);
+                        self.statement(else_block);
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Statement::While(cond, body) => {
+                self.u8(5);
+                self.expr(cond);
+                self.statement(body);
+            }
+            Statement::Block(statements) => {
+                self.u8(6);
+                self.statements(statements);
+            }
+            Statement::Return(expr) => {
+                self.u8(7);
+                match expr {
+                    Some(expr) => {
+                        self.u8(1);
+                        self.expr(expr);
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Statement::Break(expr) => {
+                self.u8(8);
+                match expr {
+                    Some(expr) => {
+                        self.u8(1);
+                        self.expr(expr);
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Statement::Continue => self.u8(9),
+            Statement::Contract(contract) => {
+                self.u8(10);
+                self.bool(contract.kind == ContractKind::Requires);
+                self.str(&contract.proc_name);
+                self.expr(&contract.condition);
+                self.str(&contract.source);
+            }
+            Statement::Line(line, source) => {
+                self.u8(11);
+                self.len(*line);
+                self.str(source);
+            }
+            Statement::PlaceAssign(target, op, expr) => {
+                self.u8(12);
+                self.expr(target);
+                match op {
+                    Some(op) => {
+                        self.u8(1);
+                        self.operator(*op);
+                    }
+                    None => self.u8(0),
+                }
+                self.expr(expr);
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        self.len(exprs.len());
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn operator(&mut self, op: Operator) {
+        let tag = OPERATORS
+            .iter()
+            .position(|other| *other == op)
+            .expect("every operator should have a tag");
+        self.u8(tag as u8);
+    }
+
+    fn span(&mut self, span: Span) {
+        self.len(span.line);
+        self.len(span.column);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Nil => self.u8(0),
+            Expr::Num(n) => {
+                self.u8(1);
+                self.num(*n);
+            }
+            Expr::Bool(b) => {
+                self.u8(2);
+                self.bool(*b);
+            }
+            Expr::Str(s) => {
+                self.u8(3);
+                self.str(s);
+            }
+            Expr::Var(name) => {
+                self.u8(4);
+                self.symbol(*name);
+            }
+            Expr::Binary(op, left, right, (left_span, right_span)) => {
+                self.u8(5);
+                self.operator(*op);
+                self.expr(left);
+                self.expr(right);
+                self.span(*left_span);
+                self.span(*right_span);
+            }
+            Expr::Unary(op, expr) => {
+                self.u8(6);
+                self.operator(*op);
+                self.expr(expr);
+            }
+            Expr::PrimitiveCall(builtin, args, span) => {
+                self.u8(7);
+                self.str(builtin.name());
+                self.exprs(args);
+                self.span(*span);
+            }
+            Expr::Call(f, args, span) => {
+                self.u8(8);
+                self.expr(f);
+                self.exprs(args);
+                self.span(*span);
+            }
+            Expr::Lambda(params, body, captures) => {
+                self.u8(9);
+                self.symbols(params);
+                self.statement(body);
+                self.symbols(captures);
+            }
+            Expr::Index(indexable, index, span) => {
+                self.u8(10);
+                self.expr(indexable);
+                self.expr(index);
+                self.span(*span);
+            }
+            Expr::Statement(statement) => {
+                self.u8(11);
+                self.statement(statement);
+            }
+            Expr::KeywordCall(f, args, keyword_args, span) => {
+                self.u8(12);
+                self.expr(f);
+                self.exprs(args);
+                self.len(keyword_args.len());
+                for (name, arg) in keyword_args {
+                    self.symbol(*name);
+                    self.expr(arg);
+                }
+                self.span(*span);
+            }
+            Expr::Field(object, field) => {
+                self.u8(13);
+                self.expr(object);
+                self.symbol(*field);
+            }
+            Expr::Struct(name, fields) => {
+                self.u8(14);
+                self.symbol(*name);
+                self.len(fields.len());
+                for (field, value) in fields {
+                    self.symbol(*field);
+                    self.expr(value);
+                }
+            }
+            Expr::StructUpdate(name, base, fields) => {
+                self.u8(15);
+                self.symbol(*name);
+                self.expr(base);
+                self.len(fields.len());
+                for (field, value) in fields {
+                    self.symbol(*field);
+                    self.expr(value);
+                }
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+/// Returns the error for a compiled program which ends before all of its data was read.
+fn truncated() -> ParseError {
+    return InvalidCompiledProgram("unexpected end of data".to_string());
+}
+
+/// Returns the error for an unknown tag of the enum `kind`.
+fn bad_tag(kind: &str, tag: u8) -> ParseError {
+    return InvalidCompiledProgram(format!("unknown {kind} tag {tag}"));
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ParseError> {
+        if self.bytes.len() < n {
+            return Err(truncated());
+        }
+        let (taken, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        return Ok(taken);
+    }
+
+    fn u8(&mut self) -> Result<u8, ParseError> {
+        return Ok(self.take(1)?[0]);
+    }
+
+    fn len(&mut self) -> Result<usize, ParseError> {
+        let bytes = self.take(4)?.try_into().map_err(|_| truncated())?;
+        return Ok(u32::from_le_bytes(bytes) as usize);
+    }
+
+    fn bool(&mut self) -> Result<bool, ParseError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            tag => Err(bad_tag("boolean", tag)),
+        }
+    }
+
+    fn num(&mut self) -> Result<f64, ParseError> {
+        let bytes = self.take(8)?.try_into().map_err(|_| truncated())?;
+        return Ok(f64::from_le_bytes(bytes));
+    }
+
+    fn str(&mut self) -> Result<String, ParseError> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        return String::from_utf8(bytes.to_vec())
+            .map_err(|_| InvalidCompiledProgram("invalid string".to_string()));
+    }
+
+    fn strs(&mut self) -> Result<Vec<String>, ParseError> {
+        let len = self.len()?;
+        return (0..len).map(|_| self.str()).collect();
+    }
+
+    fn symbol(&mut self) -> Result<Symbol, ParseError> {
+        return Ok(Symbol::from(self.str()?));
+    }
+
+    fn symbols(&mut self) -> Result<Vec<Symbol>, ParseError> {
+        let len = self.len()?;
+        return (0..len).map(|_| self.symbol()).collect();
+    }
+
+    fn option_str(&mut self) -> Result<Option<String>, ParseError> {
+        return match self.bool()? {
+            true => Ok(Some(self.str()?)),
+            false => Ok(None),
+        };
+    }
+
+    fn program(&mut self) -> Result<Program, ParseError> {
+        let len = self.len()?;
+        let procedures = (0..len)
+            .map(|_| self.procedure())
+            .collect::<Result<_, _>>()?;
+        let main = self.shared_statement()?;
+        let len = self.len()?;
+        let constants = (0..len)
+            .map(|_| Ok((self.str()?, self.expr()?)))
+            .collect::<Result<_, _>>()?;
+        let len = self.len()?;
+        let modules = (0..len).map(|_| self.module()).collect::<Result<_, _>>()?;
+        let metadata = Metadata {
+            name: self.option_str()?,
+            version: self.option_str()?,
+        };
+        return Ok(Program {
+            procedures,
+            main,
+            constants,
+            modules,
+            metadata,
+        });
+    }
+
+    fn module(&mut self) -> Result<Module, ParseError> {
+        return Ok(Module {
+            path: self.str()?,
+            alias: self.option_str()?,
+            program: self.program()?,
+            exports: self.strs()?,
+        });
+    }
+
+    fn procedure(&mut self) -> Result<Procedure, ParseError> {
+        return Ok(Procedure {
+            name: self.symbol()?,
+            params: self.symbols()?,
+            body: self.shared_statement()?,
+        });
+    }
+
+    fn statements(&mut self) -> Result<Arc<[Statement]>, ParseError> {
+        let len = self.len()?;
+        let statements = (0..len)
+            .map(|_| self.statement())
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        return Ok(shared_statements(statements));
+    }
+
+    fn shared_statement(&mut self) -> Result<Arc<Statement>, ParseError> {
+        return Ok(Arc::new(self.statement()?));
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError> {
+        let statement = match self.u8()? {
+            0 => Statement::Expr(self.expr()?),
+            1 => Statement::Let(self.symbol()?, self.expr()?),
+            2 => Statement::Const(self.symbol()?, self.expr()?),
+            3 => Statement::Assign(self.symbol()?, self.expr()?),
+            4 => {
+                let cond = self.expr()?;
+                let then_block = self.shared_statement()?;
+                let else_block = match self.bool()? {
+                    true => Some(self.shared_statement()?),
+                    false => None,
+                };
+                Statement::If(cond, then_block, else_block)
+            }
+            5 => Statement::While(self.expr()?, self.shared_statement()?),
+            6 => Statement::Block(self.statements()?),
+            7 => Statement::Return(match self.bool()? {
+                true => Some(self.expr()?),
+                false => None,
+            }),
+            8 => Statement::Break(match self.bool()? {
+                true => Some(self.expr()?),
+                false => None,
+            }),
+            9 => Statement::Continue,
+            10 => Statement::Contract(Box::new(Contract {
+                kind: match self.bool()? {
+                    true => ContractKind::Requires,
+                    false => ContractKind::Ensures,
+                },
+                proc_name: self.str()?,
+                condition: self.expr()?,
+                source: self.str()?,
+            })),
+            11 => Statement::Line(self.len()?, self.str()?),
+            12 => Statement::PlaceAssign(
+                self.expr()?,
+                match self.bool()? {
+                    true => Some(self.operator()?),
+                    false => None,
+                },
+                self.expr()?,
+            ),
+            tag => return Err(bad_tag("statement", tag)),
+        };
+        return Ok(statement);
+    }
+
+    fn exprs(&mut self) -> Result<Arc<[Expr]>, ParseError> {
+        let len = self.len()?;
+        let exprs = (0..len)
+            .map(|_| self.expr())
+            .collect::<Result<Vec<_>, ParseError>>()?;
+        return Ok(shared_exprs(exprs));
+    }
+
+    fn operator(&mut self) -> Result<Operator, ParseError> {
+        let tag = self.u8()?;
+        return match OPERATORS.get(tag as usize) {
+            Some(op) => Ok(*op),
+            None => Err(bad_tag("operator", tag)),
+        };
+    }
+
+    fn span(&mut self) -> Result<Span, ParseError> {
+        return Ok(Span {
+            line: self.len()?,
+            column: self.len()?,
+        });
+    }
+
+    fn shared_expr(&mut self) -> Result<Arc<Expr>, ParseError> {
+        return Ok(Arc::new(self.expr()?));
+    }
+
+    fn expr(&mut self) -> Result<Expr, ParseError> {
+        let expr = match self.u8()? {
+            0 => Expr::Nil,
+            1 => Expr::Num(self.num()?),
+            2 => Expr::Bool(self.bool()?),
+            3 => Expr::Str(self.str()?),
+            4 => Expr::Var(self.symbol()?),
+            5 => {
+                let (op, left, right) =
+                    (self.operator()?, self.shared_expr()?, self.shared_expr()?);
+                Expr::Binary(op, left, right, (self.span()?, self.span()?))
+            }
+            6 => Expr::Unary(self.operator()?, self.shared_expr()?),
+            7 => {
+                let name = self.str()?;
+                let builtin = Builtin::from_name(&name)
+                    .ok_or_else(|| InvalidCompiledProgram(format!("unknown builtin \"{name}\"")))?;
+                Expr::PrimitiveCall(builtin, self.exprs()?, self.span()?)
+            }
+            8 => Expr::Call(self.shared_expr()?, self.exprs()?, self.span()?),
+            9 => Expr::Lambda(self.symbols()?, self.shared_statement()?, self.symbols()?),
+            10 => Expr::Index(self.shared_expr()?, self.shared_expr()?, self.span()?),
+            11 => Expr::Statement(self.shared_statement()?),
+            12 => {
+                let f = self.shared_expr()?;
+                let args = self.exprs()?;
+                let len = self.len()?;
+                let keyword_args = (0..len)
+                    .map(|_| Ok((self.symbol()?, self.expr()?)))
+                    .collect::<Result<_, ParseError>>()?;
+                Expr::KeywordCall(f, args, keyword_args, self.span()?)
+            }
+            13 => Expr::Field(self.shared_expr()?, self.symbol()?),
+            14 => {
+                let name = self.symbol()?;
+                let len = self.len()?;
+                let fields = (0..len)
+                    .map(|_| Ok((self.symbol()?, self.expr()?)))
+                    .collect::<Result<_, ParseError>>()?;
+                Expr::Struct(name, fields)
+            }
+            15 => {
+                let name = self.symbol()?;
+                let base = self.shared_expr()?;
+                let len = self.len()?;
+                let fields = (0..len)
+                    .map(|_| Ok((self.symbol()?, self.expr()?)))
+                    .collect::<Result<_, ParseError>>()?;
+                Expr::StructUpdate(name, base, fields)
+            }
+            tag => return Err(bad_tag("expression", tag)),
+        };
+        return Ok(expr);
+    }
+}
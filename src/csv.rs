@@ -0,0 +1,102 @@
+//! Reading and writing comma-separated values for the `csv_parse` and `csv_stringify` builtins.
+//! Records are separated by line breaks (`\n` or `\r\n`) and fields by commas. A field may be
+//! quoted with `"`, in which case it may contain commas, line breaks, and quotes written as `""`.
+//! This module is only compiled with the `csv` feature.
+
+/// Parses `text` into its records, each of which is a list of fields. A line break at the end of
+/// the text does not start another record. On failure, this function returns a description of why
+/// `text` is not valid CSV.
+pub fn parse(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+
+    if text.is_empty() {
+        return Ok(records);
+    }
+
+    loop {
+        // the start of a field
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let start_line = line;
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => {
+                        if c == '\n' {
+                            line += 1;
+                        }
+                        field.push(c);
+                    }
+                    None => return Err(format!("unterminated quoted field on line {start_line}")),
+                }
+            }
+            if !matches!(chars.peek(), None | Some(',' | '\n' | '\r')) {
+                return Err(format!("unexpected text after quoted field on line {line}"));
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                match c {
+                    ',' | '\n' | '\r' => break,
+                    '"' => {
+                        return Err(format!("unexpected quote in unquoted field on line {line}"))
+                    }
+                    c => field.push(c),
+                }
+                chars.next();
+            }
+        }
+
+        // the end of a field
+        record.push(std::mem::take(&mut field));
+        match chars.next() {
+            Some(',') => {}
+            Some('\r') if chars.peek() == Some(&'\n') => {
+                chars.next();
+                line += 1;
+                records.push(std::mem::take(&mut record));
+                if chars.peek().is_none() {
+                    return Ok(records);
+                }
+            }
+            Some('\n') => {
+                line += 1;
+                records.push(std::mem::take(&mut record));
+                if chars.peek().is_none() {
+                    return Ok(records);
+                }
+            }
+            Some(_) => return Err(format!("unexpected carriage return on line {line}")),
+            None => {
+                records.push(record);
+                return Ok(records);
+            }
+        }
+    }
+}
+
+/// Writes `records` as CSV, ending each record with `\n`. Fields which contain a comma, a quote, or
+/// a line break are quoted.
+pub fn stringify(records: &[Vec<String>]) -> String {
+    let mut text = String::new();
+    for record in records {
+        let fields: Vec<String> = record.iter().map(|field| quote(field)).collect();
+        text.push_str(&fields.join(","));
+        text.push('\n');
+    }
+    return text;
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", field.replace('"', "\"\""));
+    }
+    return field.to_string();
+}
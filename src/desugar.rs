@@ -1,28 +1,56 @@
-use crate::tokenizer::AssignOp;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
+};
+
+use crate::fold::fold_constant;
+use crate::symbol::Symbol;
 use crate::{
-    parser::{Builtin, SugaredExpr, SugaredStatement},
-    tokenizer::Operator,
+    parser::{
+        Builtin, ContractKind, Pattern, SugaredExpr, SugaredProcedure, SugaredStatement,
+        SugaredStruct,
+    },
+    tokenizer::{Operator, Span},
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Procedure {
-    pub name: String,
-    pub params: Vec<String>,
-    pub body: Statement,
+    pub name: Symbol,
+    pub params: Vec<Symbol>,
+    pub body: Arc<Statement>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Statement {
     Expr(Expr),
-    Let(String, Expr),
-    Const(String, Expr),
-    Assign(String, Expr),
-    If(Expr, Box<Statement>, Option<Box<Statement>>),
-    While(Expr, Box<Statement>),
-    Block(Vec<Statement>),
+    Let(Symbol, Expr),
+    Const(Symbol, Expr),
+    Assign(Symbol, Expr),
+    /// See [SugaredStatement::PlaceAssign]. The operator of a compound assignment is the binary
+    /// operator it applies, e.g. `+` for `+=`.
+    PlaceAssign(Expr, Option<Operator>, Expr),
+    If(Expr, Arc<Statement>, Option<Arc<Statement>>),
+    While(Expr, Arc<Statement>),
+    Block(Arc<[Statement]>),
     Return(Option<Expr>),
-    Break,
+    Break(Option<Expr>),
     Continue,
+    Contract(Box<Contract>),
+    /// See [SugaredStatement::Line].
+    Line(usize, String),
+}
+
+/// A check of a procedure's `requires` or `ensures` clause. Evaluating this statement results in a
+/// [ContractViolation](crate::error::RuntimeError::ContractViolation) error if the condition is
+/// false.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contract {
+    pub kind: ContractKind,
+    /// The name of the procedure the contract belongs to.
+    pub proc_name: String,
+    pub condition: Expr,
+    /// The source text of the condition.
+    pub source: String,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -31,33 +59,253 @@ pub enum Expr {
     Num(f64),
     Bool(bool),
     Str(String),
-    Var(String),
-    Binary(Operator, Box<Expr>, Box<Expr>),
-    Unary(Operator, Box<Expr>),
-    PrimitiveCall(Builtin, Vec<Expr>),
-    Call(Box<Expr>, Vec<Expr>),
-    Lambda(Vec<String>, Box<Statement>),
-    Index(Box<Expr>, Box<Expr>),
+    Var(Symbol),
+    /// See [SugaredExpr::Binary].
+    Binary(Operator, Arc<Expr>, Arc<Expr>, (Span, Span)),
+    Unary(Operator, Arc<Expr>),
+    /// See [SugaredExpr::PrimitiveCall].
+    PrimitiveCall(Builtin, Arc<[Expr]>, Span),
+    /// See [SugaredExpr::Call].
+    Call(Arc<Expr>, Arc<[Expr]>, Span),
+    /// See [SugaredExpr::KeywordCall].
+    KeywordCall(Arc<Expr>, Arc<[Expr]>, Vec<(Symbol, Expr)>, Span),
+    /// A lambda with its parameters, body, and the names of the variables it captures from the
+    /// environment it is created in (see [free_variables]).
+    Lambda(Vec<Symbol>, Arc<Statement>, Vec<Symbol>),
+    /// See [SugaredExpr::Index].
+    Index(Arc<Expr>, Arc<Expr>, Span),
+    /// See [SugaredExpr::Field].
+    Field(Arc<Expr>, Symbol),
+    /// A new instance of the struct with the given name, with each of its fields set to the value
+    /// of an expression. This is only created by the constructor of a struct (see
+    /// [desugar_struct]).
+    Struct(Symbol, Vec<(Symbol, Expr)>),
+    /// See [SugaredExpr::StructUpdate].
+    StructUpdate(Symbol, Arc<Expr>, Vec<(Symbol, Expr)>),
+    /// A statement used as an expression, which evaluates to the value the statement produces, or
+    /// to `nil` if it produces no value.
+    Statement(Arc<Statement>),
 }
 
-fn desugar_statements(sugared_statements: Vec<SugaredStatement>) -> Vec<Statement> {
-    sugared_statements
+/// Moves the statements of a block into a single allocation. The children of [Expr] and
+/// [Statement] nodes are reference counted so that procedure values can share their bodies
+/// without copying them, and a node is freed once nothing refers to it any more.
+pub fn shared_statements<I>(statements: I) -> Arc<[Statement]>
+where
+    I: IntoIterator<Item = Statement>,
+    I::IntoIter: ExactSizeIterator,
+{
+    static EMPTY: OnceLock<Arc<[Statement]>> = OnceLock::new();
+    return shared_slice(statements.into_iter(), &EMPTY);
+}
+
+/// Moves the arguments of a call into a single shared allocation (see [shared_statements]).
+pub fn shared_exprs<I>(exprs: I) -> Arc<[Expr]>
+where
+    I: IntoIterator<Item = Expr>,
+    I::IntoIter: ExactSizeIterator,
+{
+    static EMPTY: OnceLock<Arc<[Expr]>> = OnceLock::new();
+    return shared_slice(exprs.into_iter(), &EMPTY);
+}
+
+/// Collects `values` straight into an `Arc<[T]>`, which takes a single allocation when the length
+/// of `values` is known up front, rather than collecting them into a `Vec` and copying that. Every
+/// empty slice shares `empty`, so an empty block or argument list does not allocate at all.
+fn shared_slice<T>(
+    values: impl ExactSizeIterator<Item = T>,
+    empty: &OnceLock<Arc<[T]>>,
+) -> Arc<[T]> {
+    if values.len() == 0 {
+        return Arc::clone(empty.get_or_init(|| Arc::new([])));
+    }
+    return values.collect();
+}
+
+/// The name that the return value of a procedure is bound to in its `ensures` clauses.
+pub const CONTRACT_RESULT_NAME: &str = "result";
+
+/// Desugars a procedure. The `requires` clauses of the procedure are checked before its body. If the
+/// procedure has `ensures` clauses, its body is run as an immediately called lambda so that every
+/// `return` statement in the body produces the value that the `ensures` clauses are checked against.
+pub fn desugar_procedure(proc: SugaredProcedure) -> Procedure {
+    let body = desugar_statement(proc.body);
+    if proc.contracts.is_empty() {
+        return Procedure {
+            name: Symbol::from(proc.name),
+            params: symbols(proc.params),
+            body: Arc::new(body),
+        };
+    }
+
+    let (requires, ensures): (Vec<Contract>, Vec<Contract>) = proc
+        .contracts
+        .into_iter()
+        .map(|contract| Contract {
+            kind: contract.kind,
+            proc_name: proc.name.to_string(),
+            condition: desugar_expression(contract.condition),
+            source: contract.source,
+        })
+        .partition(|contract| contract.kind == ContractKind::Requires);
+
+    let mut statements: Vec<Statement> = requires
+        .into_iter()
+        .map(|contract| Statement::Contract(Box::new(contract)))
+        .collect();
+    if ensures.is_empty() {
+        statements.push(body);
+    } else {
+        statements.push(Statement::Const(
+            Symbol::from(CONTRACT_RESULT_NAME),
+            Expr::Call(
+                Arc::new(lambda(vec![], body)),
+                shared_exprs([]),
+                Span::default(),
+            ),
+        ));
+        statements.extend(
+            ensures
+                .into_iter()
+                .map(|contract| Statement::Contract(Box::new(contract))),
+        );
+        statements.push(Statement::Return(Some(Expr::Var(Symbol::from(
+            CONTRACT_RESULT_NAME,
+        )))));
+    }
+
+    return Procedure {
+        name: Symbol::from(proc.name),
+        params: symbols(proc.params),
+        body: Arc::new(Statement::Block(shared_statements(statements))),
+    };
+}
+
+/// Desugars a struct declaration into its constructor, which is a procedure with the name of the
+/// struct whose parameters are its fields, e.g. `struct Point { x, y }` becomes
+/// `proc Point(x, y) { return <a Point with x and y>; }`. Since the constructor is an ordinary
+/// procedure, its arguments can be given by position or by keyword.
+pub fn desugar_struct(definition: SugaredStruct) -> Procedure {
+    let name = Symbol::from(definition.name);
+    let fields = symbols(definition.fields);
+    let field_exprs = fields
         .iter()
-        .map(|s| desugar_statement(s.clone()))
-        .collect()
+        .map(|field| (*field, Expr::Var(*field)))
+        .collect();
+    let instance = Expr::Struct(name, field_exprs);
+    return Procedure {
+        name,
+        params: fields,
+        body: Arc::new(Statement::Return(Some(instance))),
+    };
+}
+
+/// The name of the temporary that the value of a destructuring `let` statement is bound to. Like
+/// the temporaries of a multiple assignment, it cannot be written in a program.
+const DESTRUCTURED_NAME: &str = "#destructured";
+
+fn desugar_statements(sugared_statements: Vec<SugaredStatement>) -> Vec<Statement> {
+    let mut statements = vec![];
+    for sugared_statement in sugared_statements {
+        match sugared_statement {
+            // the names bound by a destructuring `let` statement belong to the enclosing block
+            SugaredStatement::LetPattern(pattern, sugared_expr) => {
+                statements.extend(desugar_let_pattern(pattern, sugared_expr))
+            }
+            sugared_statement => statements.push(desugar_statement(sugared_statement)),
+        }
+    }
+    return statements;
+}
+
+/// Desugars a destructuring `let` statement into a `let` statement which binds the value to a
+/// temporary, followed by a `let` statement for each name of `pattern` which indexes the temporary,
+/// e.g. `let [a, rest...] = xs;` binds `a` to `#destructured[0]` and `rest` to
+/// `drop(#destructured, 1)`.
+fn desugar_let_pattern(pattern: Pattern, sugared_expr: SugaredExpr) -> Vec<Statement> {
+    let temporary = Symbol::from(DESTRUCTURED_NAME);
+    let part = |key: Expr| {
+        Expr::Index(
+            Arc::new(Expr::Var(temporary)),
+            Arc::new(key),
+            Span::default(),
+        )
+    };
+    let mut statements = vec![Statement::Let(temporary, desugar_expression(sugared_expr))];
+    match pattern {
+        Pattern::List(names, rest) => {
+            let count = names.len();
+            statements.extend(names.into_iter().enumerate().map(|(index, name)| {
+                Statement::Let(Symbol::from(name), part(Expr::Num(index as f64)))
+            }));
+            if let Some(rest) = rest {
+                let args = vec![Expr::Var(temporary), Expr::Num(count as f64)];
+                statements.push(Statement::Let(
+                    Symbol::from(rest),
+                    Expr::PrimitiveCall(Builtin::Drop, shared_exprs(args), Span::default()),
+                ));
+            }
+        }
+        Pattern::Map(names) => {
+            statements.extend(
+                names
+                    .into_iter()
+                    .map(|name| Statement::Let(Symbol::from(name.as_str()), part(Expr::Str(name)))),
+            );
+        }
+    }
+    return statements;
 }
 
 pub fn desugar_statement(sugared_statement: SugaredStatement) -> Statement {
     match sugared_statement {
         SugaredStatement::Expr(sugared_expr) => Statement::Expr(desugar_expression(sugared_expr)),
         SugaredStatement::Let(name, sugared_expr) => {
-            Statement::Let(name, desugar_expression(sugared_expr))
+            Statement::Let(Symbol::from(name), desugar_expression(sugared_expr))
         }
-        SugaredStatement::Const(name, sugared_expr) => {
-            Statement::Const(name, desugar_expression(sugared_expr))
+        // outside of a block, the names bound by the pattern are only in scope of each other
+        SugaredStatement::LetPattern(pattern, sugared_expr) => {
+            Statement::Block(shared_statements(desugar_let_pattern(pattern, sugared_expr)))
         }
+        SugaredStatement::Const(name, sugared_expr) => Statement::Const(
+            Symbol::from(name),
+            fold_constant(desugar_expression(sugared_expr), &HashMap::new()),
+        ),
         SugaredStatement::Assign(name, sugared_expr) => {
-            Statement::Assign(name, desugar_expression(sugared_expr))
+            Statement::Assign(Symbol::from(name), desugar_expression(sugared_expr))
+        }
+        // unlike `x += e`, a compound assignment to a place is not desugared to `xs[i] = xs[i] + e`,
+        // which would evaluate the target and its indexes twice
+        SugaredStatement::PlaceAssign(sugared_target, assign_op, sugared_expr) => {
+            Statement::PlaceAssign(
+                desugar_expression(sugared_target),
+                assign_op.map(|assign_op| assign_op.binary_operator()),
+                desugar_expression(sugared_expr),
+            )
+        }
+        SugaredStatement::MultiAssign(names, sugared_exprs) => {
+            // every value is bound to a temporary before any variable is assigned, so that e.g.
+            // `a, b = b, a` swaps the values of `a` and `b`. The names of the temporaries cannot
+            // be written in a program, so they never shadow a variable of the program.
+            let temporaries: Vec<Symbol> = (0..names.len())
+                .map(|index| Symbol::from(format!("#{index}")))
+                .collect();
+            let mut statements: Vec<Statement> = temporaries
+                .iter()
+                .zip(sugared_exprs)
+                .map(|(temporary, sugared_expr)| {
+                    Statement::Let(*temporary, desugar_expression(sugared_expr))
+                })
+                .collect();
+            statements.extend(
+                names
+                    .into_iter()
+                    .zip(temporaries)
+                    .map(|(name, temporary)| {
+                        Statement::Assign(Symbol::from(name), Expr::Var(temporary))
+                    }),
+            );
+            return Statement::Block(shared_statements(statements));
         }
         SugaredStatement::If(if_cond, then_block, else_ifs, else_option) => {
             let desugared_else_option = match else_option {
@@ -70,23 +318,20 @@ pub fn desugar_statement(sugared_statement: SugaredStatement) -> Statement {
                 |acc, (cur_sugared_cond_expr, cur_sugared_block)| {
                     return Some(Statement::If(
                         desugar_expression(cur_sugared_cond_expr),
-                        Box::new(desugar_statement(cur_sugared_block)),
-                        match acc {
-                            Some(acc) => Some(Box::new(acc)),
-                            None => None,
-                        },
+                        Arc::new(desugar_statement(cur_sugared_block)),
+                        acc.map(Arc::new),
                     ));
                 },
             );
 
             let nested_else_ifs = match nested_else_ifs {
-                Some(statement) => Some(Box::new(statement)),
+                Some(statement) => Some(Arc::new(statement)),
                 None => None,
             };
 
             return Statement::If(
                 desugar_expression(if_cond),
-                Box::new(desugar_statement(*then_block)),
+                Arc::new(desugar_statement(*then_block)),
                 nested_else_ifs,
             );
         }
@@ -99,8 +344,11 @@ pub fn desugar_statement(sugared_statement: SugaredStatement) -> Statement {
         }
         SugaredStatement::While(sugared_while_cond, sugared_while_body) => Statement::While(
             desugar_expression(sugared_while_cond),
-            Box::new(desugar_statement(*sugared_while_body)),
+            Arc::new(desugar_statement(*sugared_while_body)),
         ),
+        SugaredStatement::Loop(sugared_loop_body) => {
+            Statement::While(Expr::Bool(true), Arc::new(desugar_statement(*sugared_loop_body)))
+        }
         SugaredStatement::For(
             sugared_var_statement,
             sugared_stop_cond,
@@ -116,70 +364,229 @@ pub fn desugar_statement(sugared_statement: SugaredStatement) -> Statement {
 
             let while_statement = Statement::While(
                 desugared_stop_cond,
-                Box::new(Statement::Block(while_block_statements)),
+                Arc::new(Statement::Block(shared_statements(while_block_statements))),
             );
 
-            return Statement::Block(vec![desugared_var_statement, while_statement]);
+            return Statement::Block(shared_statements(vec![
+                desugared_var_statement,
+                while_statement,
+            ]));
+        }
+        SugaredStatement::Break(sugared_expr_option) => {
+            Statement::Break(sugared_expr_option.map(desugar_expression))
         }
-        SugaredStatement::Break => Statement::Break,
+        SugaredStatement::Line(line, source) => Statement::Line(line, source),
         SugaredStatement::Continue => Statement::Continue,
         SugaredStatement::Block(sugared_statements) => {
-            Statement::Block(desugar_statements(sugared_statements))
+            Statement::Block(shared_statements(desugar_statements(sugared_statements)))
         }
-        SugaredStatement::OperatorAssignment(assign_op, id, expr) => match assign_op {
-            AssignOp::Plus => Statement::Assign(
-                id.to_string(),
+        SugaredStatement::OperatorAssignment(assign_op, id, expr) => {
+            let id = Symbol::from(id);
+            Statement::Assign(
+                id,
                 Expr::Binary(
-                    Operator::Plus,
-                    Box::new(Expr::Var(id)),
-                    Box::new(desugar_expression(expr)),
+                    assign_op.binary_operator(),
+                    Arc::new(Expr::Var(id)),
+                    Arc::new(desugar_expression(expr)),
+                    (Span::default(), Span::default()),
                 ),
-            ),
-            AssignOp::Minus => Statement::Assign(
-                id.to_string(),
-                Expr::Binary(
-                    Operator::Minus,
-                    Box::new(Expr::Var(id)),
-                    Box::new(desugar_expression(expr)),
-                ),
-            ),
-        },
+            )
+        }
     }
 }
 
-fn desugar_expression(sugared_expr: SugaredExpr) -> Expr {
+pub fn desugar_expression(sugared_expr: SugaredExpr) -> Expr {
     match sugared_expr {
         SugaredExpr::Nil => Expr::Nil,
         SugaredExpr::Num(n) => Expr::Num(n),
         SugaredExpr::Bool(b) => Expr::Bool(b),
         SugaredExpr::Str(s) => Expr::Str(s),
-        SugaredExpr::Var(id) => Expr::Var(id),
-        SugaredExpr::Binary(op, left_sugared_expr, right_sugared_expr) => Expr::Binary(
+        SugaredExpr::Var(id) => Expr::Var(Symbol::from(id)),
+        SugaredExpr::Binary(op, left_sugared_expr, right_sugared_expr, spans) => Expr::Binary(
             op,
-            Box::new(desugar_expression(*left_sugared_expr)),
-            Box::new(desugar_expression(*right_sugared_expr)),
+            Arc::new(desugar_expression(*left_sugared_expr)),
+            Arc::new(desugar_expression(*right_sugared_expr)),
+            spans,
         ),
-        SugaredExpr::Unary(op, expr) => Expr::Unary(op, Box::new(desugar_expression(*expr))),
-        SugaredExpr::PrimitiveCall(name, sugared_args) => Expr::PrimitiveCall(
+        SugaredExpr::Unary(op, expr) => Expr::Unary(op, Arc::new(desugar_expression(*expr))),
+        // a list literal is evaluated like a call of the `list` builtin
+        SugaredExpr::List(sugared_elements) => Expr::PrimitiveCall(
+            Builtin::List,
+            shared_exprs(sugared_elements.into_iter().map(desugar_expression)),
+            Span::default(),
+        ),
+        SugaredExpr::PrimitiveCall(name, sugared_args, span) => Expr::PrimitiveCall(
             name,
-            sugared_args
-                .iter()
-                .map(|sugared_arg_expr| desugar_expression(sugared_arg_expr.clone()))
-                .collect(),
+            shared_exprs(sugared_args.into_iter().map(desugar_expression)),
+            span,
         ),
-        SugaredExpr::Call(sugared_proc_expr, sugared_args) => Expr::Call(
-            Box::new(desugar_expression(*sugared_proc_expr)),
-            sugared_args
-                .iter()
-                .map(|sugared_arg_expr| desugar_expression(sugared_arg_expr.clone()))
-                .collect(),
+        SugaredExpr::Call(sugared_proc_expr, sugared_args, span) => Expr::Call(
+            Arc::new(desugar_expression(*sugared_proc_expr)),
+            shared_exprs(sugared_args.into_iter().map(desugar_expression)),
+            span,
         ),
+        SugaredExpr::KeywordCall(sugared_proc_expr, sugared_args, sugared_keyword_args, span) => {
+            Expr::KeywordCall(
+                Arc::new(desugar_expression(*sugared_proc_expr)),
+                shared_exprs(sugared_args.into_iter().map(desugar_expression)),
+                sugared_keyword_args
+                    .into_iter()
+                    .map(|(name, sugared_arg_expr)| {
+                        (Symbol::from(name), desugar_expression(sugared_arg_expr))
+                    })
+                    .collect(),
+                span,
+            )
+        }
         SugaredExpr::Lambda(params, sugared_body) => {
-            Expr::Lambda(params, Box::new(desugar_statement(*sugared_body)))
+            lambda(symbols(params), desugar_statement(*sugared_body))
         }
-        SugaredExpr::Index(sugared_indexable_expr, sugared_index_expr) => Expr::Index(
-            Box::new(desugar_expression(*sugared_indexable_expr)),
-            Box::new(desugar_expression(*sugared_index_expr)),
+        SugaredExpr::Index(sugared_indexable_expr, sugared_index_expr, span) => Expr::Index(
+            Arc::new(desugar_expression(*sugared_indexable_expr)),
+            Arc::new(desugar_expression(*sugared_index_expr)),
+            span,
         ),
+        SugaredExpr::Field(sugared_expr, field) => Expr::Field(
+            Arc::new(desugar_expression(*sugared_expr)),
+            Symbol::from(field),
+        ),
+        SugaredExpr::StructUpdate(name, sugared_base, sugared_fields) => Expr::StructUpdate(
+            Symbol::from(name),
+            Arc::new(desugar_expression(*sugared_base)),
+            sugared_fields
+                .into_iter()
+                .map(|(field, sugared_expr)| {
+                    (Symbol::from(field), desugar_expression(sugared_expr))
+                })
+                .collect(),
+        ),
+        SugaredExpr::Statement(sugared_statement) => {
+            Expr::Statement(Arc::new(desugar_statement(*sugared_statement)))
+        }
+    }
+}
+
+/// Creates a lambda expression which captures only the free variables of its body, so that
+/// creating a closure does not copy every variable in scope.
+pub fn lambda(params: Vec<Symbol>, body: Statement) -> Expr {
+    let captures = free_variables(&params, &body);
+    return Expr::Lambda(params, Arc::new(body), captures);
+}
+
+/// Returns the names of the variables which `body` refers to that are not `params` or declared in
+/// `body` before they are referred to, in the order they are first referred to. Top-level procedures
+/// are included if they are referred to, though they do not need to be captured.
+pub fn free_variables(params: &[Symbol], body: &Statement) -> Vec<Symbol> {
+    let mut scopes = vec![params.iter().copied().collect()];
+    let mut free = vec![];
+    statement_free_variables(body, &mut scopes, &mut free);
+    return free;
+}
+
+/// Records a reference to `name` in `free` if it is not declared in any of `scopes`.
+fn reference(name: Symbol, scopes: &[HashSet<Symbol>], free: &mut Vec<Symbol>) {
+    let declared = scopes.iter().any(|scope| scope.contains(&name));
+    if !declared && !free.contains(&name) {
+        free.push(name);
+    }
+}
+
+fn statement_free_variables(
+    statement: &Statement,
+    scopes: &mut Vec<HashSet<Symbol>>,
+    free: &mut Vec<Symbol>,
+) {
+    match statement {
+        Statement::Expr(expr) | Statement::Return(Some(expr)) | Statement::Break(Some(expr)) => {
+            expr_free_variables(expr, scopes, free)
+        }
+        Statement::Let(name, expr) | Statement::Const(name, expr) => {
+            expr_free_variables(expr, scopes, free);
+            if let Some(scope) = scopes.last_mut() {
+                scope.insert(*name);
+            }
+        }
+        Statement::Assign(name, expr) => {
+            reference(*name, scopes, free);
+            expr_free_variables(expr, scopes, free);
+        }
+        Statement::PlaceAssign(target, _, expr) => {
+            expr_free_variables(target, scopes, free);
+            expr_free_variables(expr, scopes, free);
+        }
+        Statement::If(cond, then_block, else_block) => {
+            expr_free_variables(cond, scopes, free);
+            statement_free_variables(then_block, scopes, free);
+            if let Some(else_block) = else_block {
+                statement_free_variables(else_block, scopes, free);
+            }
+        }
+        Statement::While(cond, body) => {
+            expr_free_variables(cond, scopes, free);
+            statement_free_variables(body, scopes, free);
+        }
+        Statement::Block(statements) => {
+            scopes.push(HashSet::new());
+            for statement in statements.iter() {
+                statement_free_variables(statement, scopes, free);
+            }
+            scopes.pop();
+        }
+        Statement::Contract(contract) => expr_free_variables(&contract.condition, scopes, free),
+        Statement::Return(None)
+        | Statement::Break(None)
+        | Statement::Continue
+        | Statement::Line(..) => {}
     }
 }
+
+fn expr_free_variables(expr: &Expr, scopes: &mut Vec<HashSet<Symbol>>, free: &mut Vec<Symbol>) {
+    match expr {
+        Expr::Nil | Expr::Num(_) | Expr::Bool(_) | Expr::Str(_) => {}
+        Expr::Var(name) => reference(*name, scopes, free),
+        Expr::Binary(_, left, right, _) | Expr::Index(left, right, _) => {
+            expr_free_variables(left, scopes, free);
+            expr_free_variables(right, scopes, free);
+        }
+        Expr::Unary(_, expr) | Expr::Field(expr, _) => expr_free_variables(expr, scopes, free),
+        Expr::Struct(_, fields) => {
+            for (_, expr) in fields {
+                expr_free_variables(expr, scopes, free);
+            }
+        }
+        Expr::StructUpdate(_, base, fields) => {
+            expr_free_variables(base, scopes, free);
+            for (_, expr) in fields {
+                expr_free_variables(expr, scopes, free);
+            }
+        }
+        Expr::PrimitiveCall(_, args, _) => {
+            for arg in args.iter() {
+                expr_free_variables(arg, scopes, free);
+            }
+        }
+        Expr::Call(f, args, _) => {
+            expr_free_variables(f, scopes, free);
+            for arg in args.iter() {
+                expr_free_variables(arg, scopes, free);
+            }
+        }
+        Expr::KeywordCall(f, args, keyword_args, _) => {
+            expr_free_variables(f, scopes, free);
+            for arg in args.iter().chain(keyword_args.iter().map(|(_, arg)| arg)) {
+                expr_free_variables(arg, scopes, free);
+            }
+        }
+        // the captures of a nested lambda are the free variables of its body
+        Expr::Lambda(_, _, captures) => {
+            for name in captures {
+                reference(*name, scopes, free);
+            }
+        }
+        Expr::Statement(statement) => statement_free_variables(statement, scopes, free),
+    }
+}
+
+fn symbols(names: Vec<String>) -> Vec<Symbol> {
+    return names.into_iter().map(Symbol::from).collect();
+}
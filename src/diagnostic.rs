@@ -0,0 +1,150 @@
+use std::fmt::{self, Display};
+
+use crate::error::{ParseError, RuntimeError, TokenizerError};
+
+/// The severity of a [Diagnostic].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A message about a Linger program reported by the tokenizer, parser, or interpreter, along with
+/// the position in the source it refers to if it is known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// The line and column number in the source that this diagnostic refers to.
+    pub position: Option<(usize, usize)>,
+    /// A suggestion of how to address the diagnostic.
+    pub help: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: String, position: Option<(usize, usize)>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message,
+            position,
+            help: None,
+        }
+    }
+
+    pub fn warning(message: String, position: Option<(usize, usize)>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message,
+            position,
+            help: None,
+        }
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help = Some(help);
+        return self;
+    }
+
+    /// Returns this diagnostic as a single line JSON object with the fields `severity` (`"error"`
+    /// or `"warning"`), `message`, `position` (an object with the fields `line` and `column`, or
+    /// `null`), and `help` (a string or `null`).
+    pub fn to_json(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let position = match self.position {
+            Some((line, column)) => format!("{{\"line\":{line},\"column\":{column}}}"),
+            None => "null".to_string(),
+        };
+        let help = match &self.help {
+            Some(help) => json_string(help),
+            None => "null".to_string(),
+        };
+        return format!(
+            "{{\"severity\":\"{severity}\",\"message\":{},\"position\":{position},\"help\":{help}}}",
+            json_string(&self.message)
+        );
+    }
+}
+
+/// Returns `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    return json;
+}
+
+impl From<TokenizerError> for Diagnostic {
+    fn from(e: TokenizerError) -> Self {
+        Diagnostic::error(e.to_string(), None)
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(e: ParseError) -> Self {
+        let position = match &e {
+            ParseError::UnexpectedToken(token) => Some((token.1, token.2)),
+            ParseError::Expected(_, token) => Some((token.1, token.2)),
+            ParseError::NestingTooDeep(token) => Some((token.1, token.2)),
+            ParseError::ChainTooLong(token) => Some((token.1, token.2)),
+            ParseError::KeywordAsArg(token) => Some((token.1, token.2)),
+            _ => None,
+        };
+        let diagnostic = Diagnostic::error(e.to_string(), position);
+        return match e {
+            ParseError::KeywordAsArg(token) => diagnostic.with_help(format!(
+                "keywords cannot be used as names, so rename the variable, or put the word in quotes \
+                 (\"{}\") to pass it as a string",
+                token.0
+            )),
+            _ => diagnostic,
+        };
+    }
+}
+
+impl From<RuntimeError> for Diagnostic {
+    fn from(e: RuntimeError) -> Self {
+        return match &e {
+            // the error is reported at the left operand, and the help points out the right one
+            RuntimeError::CannotCompare(_, _, right, (left_span, right_span)) => {
+                let diagnostic = Diagnostic::error(e.to_string(), left_span.position());
+                match right_span.position() {
+                    Some((line, column)) => diagnostic.with_help(format!(
+                        "the right operand, which is a {}, starts on line {line}, column {column}",
+                        right.type_name()
+                    )),
+                    None => diagnostic,
+                }
+            }
+            RuntimeError::IndexOutOfBounds(_, _, span)
+            | RuntimeError::ArgMismatch(.., span)
+            | RuntimeError::BuiltinArgMismatch(.., span) => {
+                Diagnostic::error(e.to_string(), span.position())
+            }
+            RuntimeError::CallTooDeep => Diagnostic::error(e.to_string(), None).with_help(
+                "check that the recursion stops, or make the recursive call a tail call, e.g. \
+                 \"return f(n - 1);\", which does not count towards the limit"
+                    .to_string(),
+            ),
+            _ => Diagnostic::error(e.to_string(), None),
+        };
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
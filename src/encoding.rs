@@ -0,0 +1,88 @@
+//! The Base64 and URL encodings used by the `base64_encode`, `base64_decode`, `url_encode`, and
+//! `url_decode` builtins.
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `data` with the standard Base64 alphabet, padding the result with `=`.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let group = match *chunk {
+            [a] => u32::from_be_bytes([0, a, 0, 0]),
+            [a, b] => u32::from_be_bytes([0, a, b, 0]),
+            [a, b, c] => u32::from_be_bytes([0, a, b, c]),
+            _ => unreachable!("chunks have between 1 and 3 bytes"),
+        };
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = (group >> (18 - 6 * i)) & 0b111111;
+                encoded.push(BASE64_ALPHABET[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    return encoded;
+}
+
+/// Decodes Base64 written with the standard alphabet. Padding is optional, but if it is present
+/// the encoded text must be a multiple of 4 characters long. Returns `None` if `encoded` is not
+/// valid Base64.
+pub fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+    let unpadded = encoded.trim_end_matches('=');
+    let padding = encoded.len() - unpadded.len();
+    if padding > 2 || (padding > 0 && !encoded.len().is_multiple_of(4)) || unpadded.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut decoded = Vec::with_capacity(unpadded.len() * 3 / 4);
+    for chunk in unpadded.as_bytes().chunks(4) {
+        let mut group = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let sextet = BASE64_ALPHABET.iter().position(|a| a == c)? as u32;
+            group |= sextet << (18 - 6 * i);
+        }
+        let bytes = group.to_be_bytes();
+        // a chunk of n characters holds n - 1 bytes, and the bits after them must be zero
+        let length = chunk.len() - 1;
+        if bytes[1 + length..].iter().any(|byte| *byte != 0) {
+            return None;
+        }
+        decoded.extend_from_slice(&bytes[1..1 + length]);
+    }
+    return Some(decoded);
+}
+
+/// Percent-encodes every byte of `data` except the unreserved characters of RFC 3986 (letters,
+/// digits, `-`, `.`, `_`, and `~`), so that the result can be used in any part of a URL.
+pub fn url_encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len());
+    for byte in data {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(*byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    return encoded;
+}
+
+/// Decodes the percent-encoded bytes in `encoded`. A `+` is left as it is rather than decoded to a
+/// space. Returns `None` if a `%` is not followed by two hexadecimal digits or the decoded bytes are
+/// not valid UTF-8.
+pub fn url_decode(encoded: &str) -> Option<String> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut bytes = encoded.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let high = (bytes.next()? as char).to_digit(16)?;
+            let low = (bytes.next()? as char).to_digit(16)?;
+            decoded.push((high * 16 + low) as u8);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    return String::from_utf8(decoded).ok();
+}
@@ -1,9 +1,10 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt, sync::Arc};
 
 use crate::{
     desugar::{Procedure, Statement},
     error::RuntimeError::{self, *},
-    interpreter::Value,
+    interpreter::{Closure, Value},
+    symbol::Symbol,
 };
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -20,17 +21,35 @@ pub enum Mutability {
 
 #[derive(Debug, Clone)]
 struct TopLevelProcedure {
-    params: Vec<String>,
-    body: Statement,
+    params: Vec<Symbol>,
+    body: Arc<Statement>,
 }
 
+/// The maximum number of characters of a value shown when displaying an [Environment]. Longer
+/// values are truncated and end with "...".
+const MAX_DISPLAYED_VALUE_LENGTH: usize = 60;
+
 pub type Entry = (Value, AssignmentType, Mutability);
-pub type Binding = (String, Entry);
+pub type Binding = (Symbol, Entry);
+
+/// A difference between the variables of two environments, as returned by [Environment::diff].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BindingChange {
+    /// A variable which is only bound in the newer environment, with its value.
+    Added(Symbol, Value),
+    /// A variable which is only bound in the older environment, with the value it had.
+    Removed(Symbol, Value),
+    /// A variable which is bound to different values in the two environments, with its old value
+    /// followed by its new value.
+    Changed(Symbol, Value, Value),
+}
 
 #[derive(Debug, Clone)]
 pub struct Environment {
-    top_level_procedures: HashMap<String, TopLevelProcedure>,
-    values: HashMap<String, Entry>,
+    /// The top-level procedures, which are shared by every environment derived from this one so
+    /// that creating a closure does not copy the body of every procedure.
+    top_level_procedures: Arc<HashMap<Symbol, TopLevelProcedure>>,
+    values: HashMap<Symbol, Entry>,
 }
 
 impl Environment {
@@ -41,24 +60,77 @@ impl Environment {
         }
         Self {
             values: HashMap::new(),
-            top_level_procedures,
+            top_level_procedures: Arc::new(top_level_procedures),
         }
     }
 
-    pub fn get(&self, key: String) -> Result<Value, RuntimeError> {
+    /// Returns an [EnvironmentBuilder] for constructing an environment with preset bindings.
+    pub fn builder() -> EnvironmentBuilder {
+        EnvironmentBuilder::default()
+    }
+
+    /// Adds `procedures` to the top-level procedures of this environment. A procedure with the same
+    /// name as an existing top-level procedure replaces it.
+    pub fn with_procedures(mut self, procedures: Vec<Procedure>) -> Self {
+        self.add_procedures(procedures);
+        return self;
+    }
+
+    /// Like [Environment::with_procedures], except that `procedures` are added in place. The
+    /// top-level procedures shared with other environments are only copied if `procedures` is not
+    /// empty.
+    pub fn add_procedures(&mut self, procedures: Vec<Procedure>) {
+        if procedures.is_empty() {
+            return;
+        }
+        let top_level_procedures = Arc::make_mut(&mut self.top_level_procedures);
+        for Procedure { name, params, body } in procedures {
+            top_level_procedures.insert(name, TopLevelProcedure { params, body });
+        }
+    }
+
+    pub fn get(&self, key: impl Into<Symbol>) -> Result<Value, RuntimeError> {
+        let key = key.into();
         match self.values.get(&key) {
             Some((value, ..)) => Ok(value.clone()),
             None => match self.top_level_procedures.get(&key) {
-                Some(proc) => Ok(Value::Proc(
-                    proc.params.clone(),
-                    proc.body.clone(),
-                    self.clone(),
-                )),
-                None => Err(UnknownVariable(key)),
+                Some(proc) => Ok(Value::Proc(Box::new(Closure {
+                    params: proc.params.clone(),
+                    body: proc.body.clone(),
+                    env: self.clone(),
+                    name: Some(key),
+                }))),
+                None => Err(UnknownVariable(key.to_string())),
             },
         }
     }
 
+    /// Returns the name of the top-level procedure whose body is `body`, or `None` if `body` is the
+    /// body of a lambda.
+    pub fn procedure_name(&self, body: &Statement) -> Option<Symbol> {
+        return self
+            .top_level_procedures
+            .iter()
+            .find(|(_, proc)| std::ptr::eq(&*proc.body, body))
+            .map(|(name, _)| *name);
+    }
+
+    /// Returns an environment with the same top-level procedures as this one, in which only the
+    /// variables of this environment named in `names` are bound. This is the environment captured
+    /// by a closure which refers to `names`. The values are copied, so the closure keeps working
+    /// after the scope it was created in ends, and it is not affected by later changes to it (see
+    /// `docs/closures.md`).
+    pub fn capture(&self, names: &[Symbol]) -> Self {
+        let values = names
+            .iter()
+            .filter_map(|name| Some((*name, self.values.get(name)?.clone())))
+            .collect();
+        Self {
+            top_level_procedures: Arc::clone(&self.top_level_procedures),
+            values,
+        }
+    }
+
     pub fn extend(mut self, bindings: Vec<Binding>) -> Self {
         for (var, value) in bindings {
             self.values.insert(var, value);
@@ -66,21 +138,24 @@ impl Environment {
         return self;
     }
 
-    pub fn insert_new_mutable_value(&mut self, key: String, value: Value) {
+    pub fn insert_new_mutable_value(&mut self, key: impl Into<Symbol>, value: Value) {
+        let key = key.into();
         self.values.insert(
             key,
             (value, AssignmentType::Initialized, Mutability::Mutable),
         );
     }
 
-    pub fn insert_new_constant_value(&mut self, key: String, value: Value) {
+    pub fn insert_new_constant_value(&mut self, key: impl Into<Symbol>, value: Value) {
+        let key = key.into();
         self.values.insert(
             key,
             (value, AssignmentType::Initialized, Mutability::Constant),
         );
     }
 
-    pub fn reassign(&mut self, key: String, value: Value) -> Result<(), RuntimeError> {
+    pub fn reassign(&mut self, key: impl Into<Symbol>, value: Value) -> Result<(), RuntimeError> {
+        let key = key.into();
         match self.values.get(&key) {
             Some((_, _, Mutability::Mutable)) => {
                 self.values.insert(
@@ -89,28 +164,237 @@ impl Environment {
                 );
                 return Ok(());
             }
-            Some((_, _, Mutability::Constant)) => return Err(ReassignConstant(key)),
+            Some((_, _, Mutability::Constant)) => return Err(ReassignConstant(key.to_string())),
             None => match self.top_level_procedures.get(&key) {
-                Some(_) => return Err(ReassignTopLevelProc(key)),
-                None => return Err(UnknownVariable(key)),
+                Some(_) => return Err(ReassignTopLevelProc(key.to_string())),
+                None => return Err(UnknownVariable(key.to_string())),
             },
         }
     }
 
+    /// Returns the value of the variable `key` so that it can be changed in place, e.g. by an
+    /// assignment to one of its elements, and marks the variable as reassigned. Like
+    /// [Environment::reassign], this is an error if the variable is constant or does not exist.
+    pub fn get_mut(&mut self, key: impl Into<Symbol>) -> Result<&mut Value, RuntimeError> {
+        let key = key.into();
+        if !self.values.contains_key(&key) {
+            match self.top_level_procedures.get(&key) {
+                Some(_) => return Err(ReassignTopLevelProc(key.to_string())),
+                None => return Err(UnknownVariable(key.to_string())),
+            }
+        }
+        match self.values.get_mut(&key) {
+            Some((value, assignment_type, Mutability::Mutable)) => {
+                *assignment_type = AssignmentType::Reassigned;
+                return Ok(value);
+            }
+            _ => return Err(ReassignConstant(key.to_string())),
+        }
+    }
+
+    /// Makes the variable `key` constant so that any later attempt to reassign it results in a
+    /// [ReassignConstant] error. Freezing a variable that is already constant has no effect.
+    pub fn freeze(&mut self, key: impl Into<Symbol>) -> Result<(), RuntimeError> {
+        let key = key.into();
+        match self.values.get(&key) {
+            Some((value, _, Mutability::Mutable)) => {
+                self.values.insert(
+                    key,
+                    (value.clone(), AssignmentType::Reassigned, Mutability::Constant),
+                );
+                return Ok(());
+            }
+            Some((_, _, Mutability::Constant)) => return Ok(()),
+            None => match self.top_level_procedures.get(&key) {
+                Some(_) => return Ok(()),
+                None => return Err(UnknownVariable(key.to_string())),
+            },
+        }
+    }
+
+    /// Returns an iterator over the variables bound in this environment and their entries, in no
+    /// particular order. Top-level procedures are not included.
+    pub fn iter(&self) -> impl Iterator<Item = (&Symbol, &Entry)> {
+        self.values.iter()
+    }
+
     pub fn bindings(&self) -> Vec<Binding> {
         return self.values.clone().into_iter().collect();
     }
 
-    pub fn contains_key(&self, key: &String) -> bool {
+    pub fn contains_key(&self, key: &Symbol) -> bool {
         return self.values.contains_key(key);
     }
 
-    pub fn update_reassigned_entries(&mut self, other_env: &Self) -> Result<(), RuntimeError> {
-        for (id, (value, assignment_type, _)) in other_env.bindings() {
-            if self.contains_key(&id) && assignment_type == AssignmentType::Reassigned {
-                self.reassign(id, value)?;
+    /// Returns the changes to the variables of this environment which result in the variables of
+    /// `other`, sorted by name. This shows what running a statement did when this environment is
+    /// the one from before the statement and `other` is the one from after it. Top-level procedures
+    /// are not compared, and a variable whose value is unchanged is not reported even if it was
+    /// frozen. Procedures are compared by their bodies, since they are never equal as values.
+    pub fn diff(&self, other: &Self) -> Vec<BindingChange> {
+        let mut changes = vec![];
+        for (name, (old_value, ..)) in self.iter() {
+            match other.values.get(name) {
+                Some((new_value, ..)) if same_value(old_value, new_value) => (),
+                Some((new_value, ..)) => changes.push(BindingChange::Changed(
+                    *name,
+                    old_value.clone(),
+                    new_value.clone(),
+                )),
+                None => changes.push(BindingChange::Removed(*name, old_value.clone())),
             }
         }
+        for (name, (new_value, ..)) in other.iter() {
+            if !self.values.contains_key(name) {
+                changes.push(BindingChange::Added(*name, new_value.clone()));
+            }
+        }
+        changes.sort_by_key(|change| match change {
+            BindingChange::Added(name, _)
+            | BindingChange::Removed(name, _)
+            | BindingChange::Changed(name, ..) => name.as_str(),
+        });
+        return changes;
+    }
+
+    /// Copies the variables of this environment which were reassigned or frozen in `other_env`, the
+    /// environment of a block inside of this one, back into this environment. The variables named
+    /// in `declared` were declared by the block itself, so they only shadow the variables of this
+    /// environment and are not copied.
+    pub fn update_reassigned_entries(
+        &mut self,
+        other_env: &Self,
+        declared: &[Symbol],
+    ) -> Result<(), RuntimeError> {
+        for id in other_env.values.keys() {
+            if !declared.contains(id) {
+                self.update_reassigned_entry(other_env, *id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Like [Environment::update_reassigned_entries], except that only the variable `id` is
+    /// copied, e.g. just before the block declares a variable which shadows it.
+    pub fn update_reassigned_entry(
+        &mut self,
+        other_env: &Self,
+        id: Symbol,
+    ) -> Result<(), RuntimeError> {
+        match other_env.values.get(&id) {
+            Some((value, AssignmentType::Reassigned, mutability)) if self.contains_key(&id) => {
+                match mutability {
+                    Mutability::Mutable => self.reassign(id, value.clone())?,
+                    // the variable was frozen in the other environment
+                    Mutability::Constant => {
+                        self.values
+                            .insert(id, (value.clone(), AssignmentType::Reassigned, *mutability));
+                    }
+                }
+            }
+            _ => (),
+        }
         Ok(())
     }
 }
+
+/// Returns true if `left` and `right` should not be reported as a change by [Environment::diff].
+/// Unlike `==`, this treats NaN as unchanged and procedures with the same body as the same.
+fn same_value(left: &Value, right: &Value) -> bool {
+    return match (left, right) {
+        (Value::Num(left), Value::Num(right)) if left.is_nan() && right.is_nan() => true,
+        (Value::Proc(left), Value::Proc(right)) => Arc::ptr_eq(&left.body, &right.body),
+        _ => left == right,
+    };
+}
+
+/// A builder for an [Environment] with preset bindings. This allows hosts embedding Linger to seed
+/// a program with data before it runs.
+///
+/// ```
+/// use linger::{environment::Environment, interpreter::Value};
+///
+/// let env = Environment::builder()
+///     .define("greeting", Value::Str("hello".to_string()))
+///     .define_mutable("count", Value::Num(0.0))
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EnvironmentBuilder {
+    procedures: Vec<Procedure>,
+    values: Vec<(Symbol, Value, Mutability)>,
+}
+
+impl EnvironmentBuilder {
+    /// Binds `name` to `value` as a constant.
+    pub fn define(mut self, name: &str, value: Value) -> Self {
+        self.values
+            .push((Symbol::from(name), value, Mutability::Constant));
+        return self;
+    }
+
+    /// Binds `name` to `value` as a mutable variable.
+    pub fn define_mutable(mut self, name: &str, value: Value) -> Self {
+        self.values
+            .push((Symbol::from(name), value, Mutability::Mutable));
+        return self;
+    }
+
+    /// Adds `procedure` as a top-level procedure.
+    pub fn define_proc(mut self, procedure: Procedure) -> Self {
+        self.procedures.push(procedure);
+        return self;
+    }
+
+    pub fn build(self) -> Environment {
+        let mut env = Environment::new(self.procedures);
+        for (name, value, mutability) in self.values {
+            match mutability {
+                Mutability::Constant => env.insert_new_constant_value(name, value),
+                Mutability::Mutable => env.insert_new_mutable_value(name, value),
+            }
+        }
+        return env;
+    }
+}
+
+/// Displays the variables of the environment sorted by name, one per line, with a `let` or `const`
+/// marker for their mutability, followed by the top-level procedures and their arity. Long values
+/// are truncated.
+impl fmt::Display for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut variables: Vec<(&Symbol, &Entry)> = self.iter().collect();
+        variables.sort_by_key(|(name, _)| name.as_str());
+
+        let mut lines = vec![];
+        for (name, (value, _, mutability)) in variables {
+            let marker = match mutability {
+                Mutability::Constant => "const",
+                Mutability::Mutable => "let",
+            };
+
+            let value_string = value.to_string();
+            let value_string = if value_string.chars().count() > MAX_DISPLAYED_VALUE_LENGTH {
+                let truncated: String = value_string
+                    .chars()
+                    .take(MAX_DISPLAYED_VALUE_LENGTH)
+                    .collect();
+                format!("{truncated}...")
+            } else {
+                value_string
+            };
+
+            lines.push(format!("{marker} {name} = {value_string}"));
+        }
+
+        let mut procedures: Vec<(&Symbol, &TopLevelProcedure)> =
+            self.top_level_procedures.iter().collect();
+        procedures.sort_by_key(|(name, _)| name.as_str());
+
+        for (name, proc) in procedures {
+            lines.push(format!("proc {name}/{}", proc.params.len()));
+        }
+
+        write!(f, "{}", lines.join("\n"))
+    }
+}
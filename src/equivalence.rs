@@ -0,0 +1,290 @@
+//! Structural comparison of programs, e.g. for an autograder which checks that a submission is
+//! built the same way as a reference solution. Two programs are alpha-equivalent if they only
+//! differ in their formatting, their comments, and the names they give to their variables,
+//! parameters, top-level constants, and procedures other than `main`.
+//!
+//! The programs are compared once they are parsed, so any two ways of writing the same construct
+//! (e.g. `x += 1;` and `x = x + 1;`) are the same. Their top-level constants and procedures are
+//! paired up in the order they are defined, and the programs must import the same modules.
+
+use std::fmt;
+
+use crate::{
+    desugar::{Expr, Procedure, Statement},
+    parser::Program,
+    symbol::Symbol,
+};
+
+/// The first place where a submission differs from the reference it is compared to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// The name of the procedure of the submission that differs, or `None` if the programs differ
+    /// outside of any procedure, e.g. in their top-level constants.
+    pub procedure: Option<String>,
+    /// The line of the statement of the submission that differs, or 0 if it is not in a statement.
+    pub line: usize,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.procedure, self.line) {
+            (Some(procedure), 0) => write!(f, "the procedure \"{procedure}\" differs"),
+            (Some(procedure), line) => {
+                write!(f, "the procedure \"{procedure}\" differs on line {line}")
+            }
+            (None, _) => write!(f, "the top-level definitions differ"),
+        }
+    }
+}
+
+/// Returns true if `a` and `b` are alpha-equivalent (see [equivalence](self)).
+pub fn alpha_equivalent(a: &Program, b: &Program) -> bool {
+    return difference(a, b).is_none();
+}
+
+/// Returns the first place where `submission` differs from `reference`, or `None` if they are
+/// alpha-equivalent (see [equivalence](self)).
+pub fn difference(reference: &Program, submission: &Program) -> Option<Difference> {
+    let top_level = Difference {
+        procedure: None,
+        line: 0,
+    };
+    let same_imports = reference.modules.len() == submission.modules.len()
+        && (reference.modules.iter())
+            .zip(&submission.modules)
+            .all(|(a, b)| a.path == b.path && a.alias == b.alias);
+    if !same_imports
+        || reference.constants.len() != submission.constants.len()
+        || reference.procedures.len() != submission.procedures.len()
+    {
+        return Some(top_level);
+    }
+
+    // every top-level name is in scope everywhere in the program
+    let mut comparison = Comparison::default();
+    for ((a, _), (b, _)) in reference.constants.iter().zip(&submission.constants) {
+        comparison.bind(Symbol::from(a.as_str()), Symbol::from(b.as_str()));
+    }
+    for (a, b) in reference.procedures.iter().zip(&submission.procedures) {
+        comparison.bind(a.name, b.name);
+    }
+
+    for ((_, a), (_, b)) in reference.constants.iter().zip(&submission.constants) {
+        if !comparison.expr(a, b) {
+            return Some(top_level);
+        }
+    }
+    for (a, b) in reference.procedures.iter().zip(&submission.procedures) {
+        if !comparison.procedure(a, b) {
+            return Some(Difference {
+                procedure: Some(b.name.to_string()),
+                line: comparison.line,
+            });
+        }
+    }
+    comparison.line = 0;
+    if !comparison.statement(&reference.main, &submission.main) {
+        return Some(Difference {
+            procedure: Some("main".to_string()),
+            line: comparison.line,
+        });
+    }
+    return None;
+}
+
+/// The state of a comparison of two programs.
+#[derive(Default)]
+struct Comparison {
+    /// The pairs of names which are bound at the same point of the two programs, innermost last.
+    bindings: Vec<(Symbol, Symbol)>,
+    /// The line of the statement of the second program being compared.
+    line: usize,
+}
+
+impl Comparison {
+    fn bind(&mut self, a: Symbol, b: Symbol) {
+        self.bindings.push((a, b));
+    }
+
+    /// Returns true if `a` in the first program and `b` in the second program refer to the same
+    /// binding, or are both unbound and have the same name, e.g. the names of builtins.
+    fn same_name(&self, a: Symbol, b: Symbol) -> bool {
+        let a_binding = self.bindings.iter().rposition(|(name, _)| *name == a);
+        let b_binding = self.bindings.iter().rposition(|(_, name)| *name == b);
+        return match (a_binding, b_binding) {
+            (None, None) => a == b,
+            (a_binding, b_binding) => a_binding == b_binding,
+        };
+    }
+
+    /// Compares `a` and `b` with `names` bound, and then unbinds them.
+    fn scoped(
+        &mut self,
+        names: impl Iterator<Item = (Symbol, Symbol)>,
+        compare: impl FnOnce(&mut Self) -> bool,
+    ) -> bool {
+        let depth = self.bindings.len();
+        self.bindings.extend(names);
+        let same = compare(self);
+        self.bindings.truncate(depth);
+        return same;
+    }
+
+    fn procedure(&mut self, a: &Procedure, b: &Procedure) -> bool {
+        self.line = 0;
+        let params = a.params.iter().copied().zip(b.params.iter().copied());
+        return a.params.len() == b.params.len()
+            && self.scoped(params, |comparison| comparison.statement(&a.body, &b.body));
+    }
+
+    /// Compares the statements of two blocks, skipping their line markers. A name declared in a
+    /// block is in scope for the rest of the block.
+    fn statements(&mut self, a: &[Statement], b: &[Statement]) -> bool {
+        let depth = self.bindings.len();
+        let mut a = a
+            .iter()
+            .filter(|statement| !matches!(statement, Statement::Line(..)));
+        let mut b = b.iter();
+        let same = loop {
+            let b_statement = loop {
+                match b.next() {
+                    Some(Statement::Line(line, _)) => self.line = *line,
+                    b_statement => break b_statement,
+                }
+            };
+            match (a.next(), b_statement) {
+                (Some(a_statement), Some(b_statement)) => {
+                    if !self.statement(a_statement, b_statement) {
+                        break false;
+                    }
+                }
+                (None, None) => break true,
+                _ => break false,
+            }
+        };
+        self.bindings.truncate(depth);
+        return same;
+    }
+
+    /// Compares two statements. The name declared by a `let` or `const` statement stays bound, so
+    /// that it is in scope for the rest of the block that it is in.
+    fn statement(&mut self, a: &Statement, b: &Statement) -> bool {
+        return match (a, b) {
+            (Statement::Expr(a), Statement::Expr(b)) => self.expr(a, b),
+            (Statement::Let(a_name, a), Statement::Let(b_name, b))
+            | (Statement::Const(a_name, a), Statement::Const(b_name, b)) => {
+                let same = self.expr(a, b);
+                self.bind(*a_name, *b_name);
+                same
+            }
+            (Statement::Assign(a_name, a), Statement::Assign(b_name, b)) => {
+                self.same_name(*a_name, *b_name) && self.expr(a, b)
+            }
+            (
+                Statement::PlaceAssign(a_target, a_op, a),
+                Statement::PlaceAssign(b_target, b_op, b),
+            ) => a_op == b_op && self.expr(a_target, b_target) && self.expr(a, b),
+            (Statement::If(a_cond, a_then, a_else), Statement::If(b_cond, b_then, b_else)) => {
+                self.expr(a_cond, b_cond)
+                    && self.scoped_statement(a_then, b_then)
+                    && match (a_else, b_else) {
+                        (Some(a_else), Some(b_else)) => self.scoped_statement(a_else, b_else),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            (Statement::While(a_cond, a_body), Statement::While(b_cond, b_body)) => {
+                self.expr(a_cond, b_cond) && self.scoped_statement(a_body, b_body)
+            }
+            (Statement::Block(a), Statement::Block(b)) => self.statements(a, b),
+            (Statement::Return(a), Statement::Return(b))
+            | (Statement::Break(a), Statement::Break(b)) => match (a, b) {
+                (Some(a), Some(b)) => self.expr(a, b),
+                (None, None) => true,
+                _ => false,
+            },
+            (Statement::Continue, Statement::Continue) => true,
+            // the source of a contract is only used in its error message
+            (Statement::Contract(a), Statement::Contract(b)) => {
+                a.kind == b.kind && self.expr(&a.condition, &b.condition)
+            }
+            _ => false,
+        };
+    }
+
+    /// Compares two statements which are the body of another statement, so that a name they declare
+    /// is not in scope after them.
+    fn scoped_statement(&mut self, a: &Statement, b: &Statement) -> bool {
+        return self.scoped(std::iter::empty(), |comparison| comparison.statement(a, b));
+    }
+
+    fn exprs(&mut self, a: &[Expr], b: &[Expr]) -> bool {
+        return a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.expr(a, b));
+    }
+
+    fn expr(&mut self, a: &Expr, b: &Expr) -> bool {
+        return match (a, b) {
+            (Expr::Nil, Expr::Nil) => true,
+            (Expr::Num(a), Expr::Num(b)) => a == b,
+            (Expr::Bool(a), Expr::Bool(b)) => a == b,
+            (Expr::Str(a), Expr::Str(b)) => a == b,
+            (Expr::Var(a), Expr::Var(b)) => self.same_name(*a, *b),
+            (Expr::Binary(a_op, a_left, a_right, _), Expr::Binary(b_op, b_left, b_right, _)) => {
+                a_op == b_op && self.expr(a_left, b_left) && self.expr(a_right, b_right)
+            }
+            (Expr::Unary(a_op, a), Expr::Unary(b_op, b)) => a_op == b_op && self.expr(a, b),
+            (Expr::PrimitiveCall(a_builtin, a, _), Expr::PrimitiveCall(b_builtin, b, _)) => {
+                a_builtin == b_builtin && self.exprs(a, b)
+            }
+            (Expr::Call(a_callee, a, _), Expr::Call(b_callee, b, _)) => {
+                self.expr(a_callee, b_callee) && self.exprs(a, b)
+            }
+            // keyword arguments are compared by name, since they name the parameters of a
+            // procedure rather than being bound by the call
+            (
+                Expr::KeywordCall(a_callee, a, a_keyword_args, _),
+                Expr::KeywordCall(b_callee, b, b_keyword_args, _),
+            ) => {
+                self.expr(a_callee, b_callee)
+                    && self.exprs(a, b)
+                    && a_keyword_args.len() == b_keyword_args.len()
+                    && (a_keyword_args.iter().zip(b_keyword_args))
+                        .all(|((a_name, a), (b_name, b))| a_name == b_name && self.expr(a, b))
+            }
+            (
+                Expr::Lambda(a_params, a_body, a_captures),
+                Expr::Lambda(b_params, b_body, b_captures),
+            ) => {
+                let params = a_params.iter().copied().zip(b_params.iter().copied());
+                a_params.len() == b_params.len()
+                    && a_captures.len() == b_captures.len()
+                    && (a_captures.iter().zip(b_captures)).all(|(a, b)| self.same_name(*a, *b))
+                    && self.scoped(params, |comparison| comparison.statement(a_body, b_body))
+            }
+            (Expr::Index(a, a_index, _), Expr::Index(b, b_index, _)) => {
+                self.expr(a, b) && self.expr(a_index, b_index)
+            }
+            (Expr::Field(a, a_field), Expr::Field(b, b_field)) => {
+                a_field == b_field && self.expr(a, b)
+            }
+            (Expr::Struct(a_name, a_fields), Expr::Struct(b_name, b_fields)) => {
+                a_name == b_name
+                    && a_fields.len() == b_fields.len()
+                    && (a_fields.iter().zip(b_fields))
+                        .all(|((a_field, a), (b_field, b))| a_field == b_field && self.expr(a, b))
+            }
+            (
+                Expr::StructUpdate(a_name, a_base, a_fields),
+                Expr::StructUpdate(b_name, b_base, b_fields),
+            ) => {
+                a_name == b_name
+                    && self.expr(a_base, b_base)
+                    && a_fields.len() == b_fields.len()
+                    && (a_fields.iter().zip(b_fields))
+                        .all(|((a_field, a), (b_field, b))| a_field == b_field && self.expr(a, b))
+            }
+            (Expr::Statement(a), Expr::Statement(b)) => self.scoped_statement(a, b),
+            _ => false,
+        };
+    }
+}
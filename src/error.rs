@@ -2,7 +2,8 @@ use std::fmt::{self, Display};
 
 use crate::{
     interpreter::Value,
-    tokenizer::{Operator, Token, TokenValue},
+    parser::{Builtin, ContractKind, MAX_CHAIN_LENGTH, MAX_NESTING_DEPTH},
+    tokenizer::{Operator, Span, Token, TokenValue},
 };
 
 /// A Tokenizer Error
@@ -16,17 +17,46 @@ pub enum TokenizerError {
     UnterminatedStringLiteral,
     /// This error occurs when the tokenizer reaches an invalid escape sequence.
     InvalidEscapeSequence(char),
+    /// This error occurs when the tokenizer reaches the end of the program inside of a block
+    /// comment, i.e. when a `/*` is never closed by a `*/`.
+    UnterminatedBlockComment,
 }
 
 /// A Parse Error
 #[derive(Debug, Clone)]
 pub enum ParseError {
+    /// This error occurs when a program which is parsed as it is tokenized (see
+    /// [parse_sugared_program_from](crate::parser::parse_sugared_program_from)) cannot be
+    /// tokenized.
+    Tokenizer(TokenizerError),
     /// This error occurs when there is no `main` procedure.
     NoMain,
     /// This error occurs when there are multiple top-level procedures with the same name.
     MultipleSameNamedProcs(String),
+    /// This error occurs when a top-level constant has the same name as another top-level constant
+    /// or procedure.
+    DuplicateTopLevelName(String),
+    /// This error occurs when an imported module cannot be read, tokenized, or parsed. The first
+    /// field is the path of the module and the second field describes the failure.
+    ImportFailed(String, String),
+    /// This error occurs when a module imports itself, directly or through other modules.
+    CyclicImport(String),
+    /// This error occurs when a program refers to a procedure of an imported module which is not
+    /// marked `pub`. The first field is the name of the procedure and the second field is the path
+    /// of the module.
+    PrivateProcedure(String, String),
+    /// This error occurs when a compiled program (see [cache](crate::cache)) cannot be decoded.
+    InvalidCompiledProgram(String),
     /// This error occurs when there is an unexpected token consumed when parsing.
     UnexpectedToken(Token),
+    /// This error occurs when expressions or statements are nested deeper than
+    /// [MAX_NESTING_DEPTH](crate::parser::MAX_NESTING_DEPTH). The token is where the limit was
+    /// exceeded.
+    NestingTooDeep(Token),
+    /// This error occurs when a chain of binary operators is longer than
+    /// [MAX_CHAIN_LENGTH](crate::parser::MAX_CHAIN_LENGTH). The token is the first operator past the
+    /// limit.
+    ChainTooLong(Token),
     /// This error occurs when the parser unexpectedly reached the end of the file
     UnexpectedEOF,
     /// This error occurs when the consume token differs from the token that was expected.
@@ -37,6 +67,9 @@ pub enum ParseError {
     KeywordAsProc(String),
     /// This error occurs when a keyword is used as the name of a procedure parameter.
     KeywordAsParam(String),
+    /// This error occurs when a keyword which is not a value, such as `if`, is passed as an
+    /// argument to a call, e.g. `f(true, if)`.
+    KeywordAsArg(Token),
     /// This error occurs when the parser expects to parse a statement but was unsuccessful.
     ExpectedStatement,
     /// This error occurs when the parser expects to parse a block statement but was unsuccessful.
@@ -47,6 +80,31 @@ pub enum ParseError {
     /// This error occurs when the parser expects to parse an assignment statement or an
     /// initialization statement but was unsuccessful.
     ExpectedAssignmentOrInitialization,
+    /// This error occurs when the number of variables assigned by a multiple assignment differs
+    /// from the number of values. The first field is the number of variables and the second field
+    /// is the number of values.
+    AssignmentCountMismatch(usize, usize),
+    /// This error occurs when a positional argument of a call follows a keyword argument. The
+    /// field is the name of the keyword argument.
+    PositionalAfterKeywordArg(String),
+    /// This error occurs when a call has several keyword arguments with the same name.
+    DuplicateKeywordArg(String),
+    /// This error occurs when the pattern of a destructuring `let` statement binds the same name
+    /// more than once.
+    DuplicatePatternName(String),
+    /// This error occurs when a struct declaration has several fields with the same name. The
+    /// first field is the name of the struct and the second field is the name of the field.
+    DuplicateField(String, String),
+    /// This error occurs when a struct update, e.g. `Point { ...p, x: 1, x: 2 }`, gives the same
+    /// field a new value more than once. The first field is the name of the struct and the second
+    /// field is the name of the field.
+    DuplicateFieldUpdate(String, String),
+    /// This error occurs when a builtin procedure is called with keyword arguments. The field is
+    /// the name of the builtin.
+    KeywordArgToBuiltin(String),
+    /// This error occurs when the `#language` pragma of a program selects a version of the grammar
+    /// which does not exist (see [language](crate::language)).
+    UnknownLanguageVersion(String),
 }
 
 /// A Runtime Error
@@ -59,20 +117,31 @@ pub enum RuntimeError {
     /// This error occurs when multiple arguments to a procedure are incorrect.
     BadArgs(Vec<Value>),
     /// This error occurs when the number of arguments passed to a procedure is different from the
-    /// number of parameters defined for that procedure.
-    ArgMismatch(String, usize, usize),
+    /// number of parameters defined for that procedure. The fields are the name of the procedure,
+    /// the number of parameters, the number of arguments, and the span of the call.
+    ArgMismatch(String, usize, usize, Span),
+    /// This error occurs when the number of arguments passed to a builtin procedure is not accepted
+    /// by the [arity](crate::builtins::Arity) of the builtin. The last field is the span of the
+    /// call.
+    BuiltinArgMismatch(Builtin, usize, Span),
     /// This error occurs when a value is expected to be a boolean but is not.
     ExpectedBool(Value),
     /// This error occurs when a value is expected to be a integer but is not.
     ExpectedInteger(Value),
     /// This error occurs when a value is expected to be a list but is not
     ExpectedList(Value),
+    /// This error occurs when a value is expected to be a string but is not
+    ExpectedStr(Value),
+    /// This error occurs when a value is expected to be bytes but is not
+    ExpectedBytes(Value),
     /// This error occurs when a binary operator is used as a unary operator.
     BinaryAsUnary(Operator),
     /// This error occurs when a unary operator is used as a binary operator.
     UnaryAsBinary(Operator),
     /// This error occurs when a `break` statement occurs outside of a loop.
     BreakNotInLoop,
+    /// This error occurs when a `return` statement occurs inside of a block or `if` expression.
+    ReturnInExpression,
     /// This error occurs when a `continue` statement occurs outside of a loop.
     ContinueNotInLoop,
     /// This error occurs when an expression is not a variable expression
@@ -84,8 +153,133 @@ pub enum RuntimeError {
     /// This error occurs when attempting to index a non-indexable value
     NotIndexable(Value),
     /// This error occurs when trying to index a value and the index is out
-    /// of bounds
-    IndexOutOfBounds(i64),
+    /// of bounds. The fields are the index as it was given, the length of the
+    /// value being indexed, and the span of the index.
+    IndexOutOfBounds(Value, usize, Span),
+    /// This error occurs when repeating a string or list would produce a value longer than the
+    /// maximum allowed length
+    RepetitionTooLarge(usize),
+    /// This error occurs when the `range` builtin would produce a list longer than the maximum
+    /// allowed length
+    RangeTooLarge(usize),
+    /// This error occurs when a value which is not a number, string, or boolean is used as a map
+    /// key
+    InvalidMapKey(Value),
+    /// This error occurs when a value is expected to be a map but is not
+    ExpectedMap(Value),
+    /// This error occurs when dividing by zero with the floor division operator or `divmod`
+    DivisionByZero,
+    /// This error occurs when a comparison operator is applied to values which cannot be compared
+    /// with each other, such as values of different types. The last field is the spans of the
+    /// operands, which are not in the source if the values were compared by a builtin such as
+    /// `sort`
+    CannotCompare(Operator, Value, Value, (Span, Span)),
+    /// This error occurs when the condition of a procedure's `requires` or `ensures` clause is
+    /// false. The second field is the name of the procedure and the third field is the source text
+    /// of the condition.
+    ContractViolation(ContractKind, String, String),
+    /// This error occurs when `csv_parse` is given text which is not valid CSV, or when the records
+    /// given to `csv_stringify` cannot be written as CSV
+    InvalidCsv(String),
+    /// This error occurs when a file cannot be read. The first field is the path of the file and
+    /// the second field is the reason it could not be read.
+    FileError(String, String),
+    /// This error occurs when a value which has no JSON representation, such as a procedure, is
+    /// converted to JSON
+    NotJson(Value),
+    /// This error occurs when a program being replayed calls a nondeterministic builtin which the
+    /// replay log does not have the result of next (see [replay](crate::replay))
+    ReplayDiverged(String),
+    /// This error occurs when a keyword argument does not name a parameter of the procedure it is
+    /// passed to. The first field is the name of the procedure and the second field is the name of
+    /// the argument.
+    UnknownParam(String, String),
+    /// This error occurs when a keyword argument names a parameter which is already given a
+    /// positional argument. The first field is the name of the procedure and the second field is
+    /// the name of the parameter.
+    DuplicateArg(String, String),
+    /// This error occurs when a program prints more output than the limit it is run with (see
+    /// [InterpOptions::max_output](crate::InterpOptions::max_output)). The field is the limit in
+    /// bytes.
+    OutputLimitExceeded(usize),
+    /// This error occurs when more than [MAX_CALL_DEPTH](crate::interpreter::MAX_CALL_DEPTH)
+    /// procedure calls are in progress at once, or when they use more of the host stack than they
+    /// are allowed to (see [Writer::with_call_stack_size](crate::Writer::with_call_stack_size)),
+    /// e.g. because of unbounded recursion.
+    CallTooDeep,
+    /// This error occurs when the value given to `type_assert` does not have the expected type. The
+    /// first field is the name of the variable the value was given in, if it was given in one, and
+    /// the second field is the name of the expected type.
+    TypeAssertionFailed(Option<String>, String, Value),
+    /// This error occurs when a field is read from a value which does not have a field with that
+    /// name, such as a value which is not a struct instance. The second field is the name of the
+    /// field.
+    NoSuchField(Value, String),
+    /// This error occurs when the instance updated by a struct update, e.g. `Point { ...p, x: 5 }`,
+    /// is not an instance of that struct. The first field is the name of the struct.
+    NotAnInstance(String, Value),
+    /// This error occurs when a program calls `panic`, for an error it cannot recover from. The
+    /// first field is the message given to `panic`, the second field is the line of the statement
+    /// that called it, and the third field is the names of the procedures which were being called,
+    /// innermost first (see [RuntimeError::unwind]).
+    Panic(String, usize, Vec<String>),
+}
+
+impl RuntimeError {
+    /// Records that this error propagated out of a call to the procedure `name`, which adds it to
+    /// the call stack of a [Panic](RuntimeError::Panic). Other errors are returned as they are. A
+    /// call in tail position has already returned when the call it makes panics, so it is not on
+    /// the call stack.
+    pub(crate) fn unwind(self, name: &str) -> Self {
+        return match self {
+            RuntimeError::Panic(message, line, mut call_stack) => {
+                call_stack.push(name.to_string());
+                RuntimeError::Panic(message, line, call_stack)
+            }
+            e => e,
+        };
+    }
+
+    /// Returns a [CannotCompare](RuntimeError::CannotCompare) error whose operands are not in the
+    /// source, which is given the spans of the operands once they are known (see
+    /// [RuntimeError::with_operand_spans]).
+    pub(crate) fn cannot_compare(op: Operator, left: Value, right: Value) -> Self {
+        return RuntimeError::CannotCompare(op, left, right, Default::default());
+    }
+
+    /// Records the spans of the operands of the binary operator which caused this error, if it is
+    /// an error about the operands. Other errors are returned as they are.
+    pub(crate) fn with_operand_spans(self, spans: (Span, Span)) -> Self {
+        return match self {
+            RuntimeError::CannotCompare(op, left, right, _) => {
+                RuntimeError::CannotCompare(op, left, right, spans)
+            }
+            e => e,
+        };
+    }
+
+    /// Records `span` as where the index of an [IndexOutOfBounds](RuntimeError::IndexOutOfBounds)
+    /// error, or the call of an [ArgMismatch](RuntimeError::ArgMismatch) or
+    /// [BuiltinArgMismatch](RuntimeError::BuiltinArgMismatch) error, is, unless it is already known.
+    /// Other errors are returned as they are.
+    pub(crate) fn at(self, span: Span) -> Self {
+        return match self {
+            RuntimeError::IndexOutOfBounds(index, len, known) if known.position().is_none() => {
+                RuntimeError::IndexOutOfBounds(index, len, span)
+            }
+            RuntimeError::ArgMismatch(name, expected, actual, known)
+                if known.position().is_none() =>
+            {
+                RuntimeError::ArgMismatch(name, expected, actual, span)
+            }
+            RuntimeError::BuiltinArgMismatch(builtin, actual, known)
+                if known.position().is_none() =>
+            {
+                RuntimeError::BuiltinArgMismatch(builtin, actual, span)
+            }
+            e => e,
+        };
+    }
 }
 
 impl Display for ParseError {
@@ -111,16 +305,73 @@ impl Display for ParseError {
             ParseError::KeywordAsParam(keyword) => {
                 write!(f, "keyword \"{}\" used as parameter name", keyword)
             }
+            ParseError::KeywordAsArg(Token(keyword, line, col)) => {
+                write!(f, "keyword \"{keyword}\" used as argument @ ({line}, {col})")
+            }
             ParseError::ExpectedStatement => write!(f, "expected a statement"),
             ParseError::ExpectedBlock => write!(f, "expected a block"),
             ParseError::MultipleSameNamedProcs(proc_name) => {
                 write!(f, "multiple procedures with name \"{proc_name}\"")
             }
+            ParseError::DuplicateTopLevelName(name) => {
+                write!(f, "multiple top-level definitions with name \"{name}\"")
+            }
+            ParseError::Tokenizer(e) => write!(f, "{e}"),
+            ParseError::ImportFailed(path, reason) => {
+                write!(f, "could not import \"{path}\": {reason}")
+            }
+            ParseError::CyclicImport(path) => write!(f, "cyclic import of \"{path}\""),
+            ParseError::PrivateProcedure(name, path) => write!(
+                f,
+                "procedure \"{name}\" of module \"{path}\" is private (mark it \"pub\" to export it)"
+            ),
+            ParseError::InvalidCompiledProgram(reason) => {
+                write!(f, "invalid compiled program: {reason}")
+            }
+            ParseError::NestingTooDeep(token) => write!(
+                f,
+                "nested too deeply @ ({}, {}), expressions and statements can be nested at most {} levels deep",
+                token.1, token.2, MAX_NESTING_DEPTH
+            ),
+            ParseError::ChainTooLong(token) => write!(
+                f,
+                "operator chain too long @ ({}, {}), a chain can have at most {} operators",
+                token.1, token.2, MAX_CHAIN_LENGTH
+            ),
             ParseError::UnexpectedEOF => write!(f, "unexpected end of file"),
             ParseError::ExpectedAssignment => write!(f, "expected an assignment statement"),
             ParseError::ExpectedAssignmentOrInitialization => {
                 write!(f, "expected an assignment or initialization statement")
             }
+            ParseError::AssignmentCountMismatch(targets, values) => write!(
+                f,
+                "cannot assign {values} values to {targets} variables"
+            ),
+            ParseError::PositionalAfterKeywordArg(name) => write!(
+                f,
+                "positional argument after keyword argument \"{name}\""
+            ),
+            ParseError::DuplicateKeywordArg(name) => {
+                write!(f, "keyword argument \"{name}\" is given more than once")
+            }
+            ParseError::DuplicatePatternName(name) => {
+                write!(f, "\"{name}\" is bound more than once by the same pattern")
+            }
+            ParseError::DuplicateField(struct_name, field) => write!(
+                f,
+                "struct \"{struct_name}\" has more than one field named \"{field}\""
+            ),
+            ParseError::DuplicateFieldUpdate(struct_name, field) => write!(
+                f,
+                "field \"{field}\" of struct \"{struct_name}\" is updated more than once"
+            ),
+            ParseError::KeywordArgToBuiltin(name) => write!(
+                f,
+                "builtin procedure \"{name}\" does not take keyword arguments"
+            ),
+            ParseError::UnknownLanguageVersion(version) => {
+                write!(f, "unknown language version \"{version}\"")
+            }
         }
     }
 }
@@ -135,6 +386,7 @@ impl Display for TokenizerError {
             TokenizerError::InvalidEscapeSequence(char) => {
                 write!(f, "invalid escape sequence \"\\{char}\"")
             }
+            TokenizerError::UnterminatedBlockComment => write!(f, "unterminated block comment"),
         }
     }
 }
@@ -144,11 +396,19 @@ impl Display for RuntimeError {
         match self {
             RuntimeError::UnknownVariable(id) => write!(f, "unknown variable \"{}\"", id),
             RuntimeError::BadArg(v) => write!(f, "bad argument \"{}\"", v),
-            RuntimeError::ArgMismatch(proc_name, expected, actual) => write!(
+            RuntimeError::ArgMismatch(proc_name, expected, actual, _) => write!(
                 f,
                 "procedure \"{}\" expected {} args, instead got {}",
                 proc_name, expected, actual
             ),
+            RuntimeError::BuiltinArgMismatch(builtin, actual, _) => {
+                let spec = builtin.spec();
+                write!(
+                    f,
+                    "procedure \"{}\" expected {} args, instead got {} (usage: {})",
+                    spec.name, spec.arity, actual, spec.usage
+                )
+            }
             RuntimeError::ExpectedBool(v) => {
                 write!(f, "expected boolean value, instead got {}", v)
             }
@@ -164,6 +424,9 @@ impl Display for RuntimeError {
                 write!(f, "unary operator \"{}\" used as binary operator", op)
             }
             RuntimeError::BreakNotInLoop => write!(f, "break statement found outside of a loop"),
+            RuntimeError::ReturnInExpression => {
+                write!(f, "return statement found inside of an expression")
+            }
             RuntimeError::ContinueNotInLoop => {
                 write!(f, "continue statement found outside of a loop")
             }
@@ -179,11 +442,105 @@ impl Display for RuntimeError {
                 f,
                 "expected an integer but got \"{value}\", which is not an integer"
             ),
-            RuntimeError::IndexOutOfBounds(index) => write!(f, "index {index} is out of bounds"),
+            RuntimeError::IndexOutOfBounds(index, len, _) => {
+                write!(f, "index {index} is out of bounds for length {len}")
+            }
             RuntimeError::ExpectedList(value) => write!(
                 f,
                 "expected a list, instead got {value}, which is not a list"
             ),
+            RuntimeError::ExpectedStr(value) => write!(
+                f,
+                "expected a string, instead got {value}, which is not a string"
+            ),
+            RuntimeError::ExpectedBytes(value) => {
+                write!(f, "expected bytes, instead got {value}, which is not bytes")
+            }
+            RuntimeError::RepetitionTooLarge(max_length) => write!(
+                f,
+                "repetition would produce a value longer than the maximum length of {max_length}"
+            ),
+            RuntimeError::RangeTooLarge(max_length) => write!(
+                f,
+                "range would produce a list longer than the maximum length of {max_length}"
+            ),
+            RuntimeError::InvalidMapKey(value) => write!(
+                f,
+                "\"{value}\" cannot be used as a map key, only numbers, strings, and booleans can"
+            ),
+            RuntimeError::ExpectedMap(value) => {
+                write!(f, "expected a map, instead got {value}, which is not a map")
+            }
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+            RuntimeError::CannotCompare(op, left, right, _) => write!(
+                f,
+                "cannot compare {} \"{left}\" with {} \"{right}\" using \"{op}\"",
+                left.type_name(),
+                right.type_name()
+            ),
+            RuntimeError::ContractViolation(kind, proc_name, source) => {
+                let condition = match kind {
+                    ContractKind::Requires => "precondition",
+                    ContractKind::Ensures => "postcondition",
+                };
+                write!(f, "{condition} of \"{proc_name}\" failed: {kind}({source})")
+            }
+            RuntimeError::InvalidCsv(reason) => write!(f, "invalid CSV: {reason}"),
+            RuntimeError::FileError(path, reason) => {
+                write!(f, "could not read file \"{path}\": {reason}")
+            }
+            RuntimeError::NotJson(value) => write!(
+                f,
+                "{} \"{value}\" cannot be converted to JSON",
+                value.type_name()
+            ),
+            RuntimeError::ReplayDiverged(reason) => {
+                write!(f, "the program diverged from the replay log: {reason}")
+            }
+            RuntimeError::UnknownParam(proc_name, name) => write!(
+                f,
+                "procedure \"{proc_name}\" has no parameter named \"{name}\""
+            ),
+            RuntimeError::DuplicateArg(proc_name, name) => write!(
+                f,
+                "parameter \"{name}\" of procedure \"{proc_name}\" is given more than once"
+            ),
+            RuntimeError::CallTooDeep => {
+                write!(f, "too many procedure calls are in progress at once")
+            }
+            RuntimeError::OutputLimitExceeded(limit) => {
+                write!(f, "the program printed more than {limit} bytes of output")
+            }
+            RuntimeError::TypeAssertionFailed(name, expected, value) => {
+                let actual = value.type_name();
+                match name {
+                    Some(name) => write!(
+                        f,
+                        "expected \"{name}\" to be a {expected}, but it is the {actual} \"{value}\""
+                    ),
+                    None => write!(f, "expected a {expected}, but got the {actual} \"{value}\""),
+                }
+            }
+            RuntimeError::NoSuchField(Value::Struct(instance), field) => {
+                write!(f, "struct \"{}\" has no field \"{field}\"", instance.name)
+            }
+            RuntimeError::NoSuchField(value, field) => write!(
+                f,
+                "the {} \"{value}\" has no field \"{field}\"",
+                value.type_name()
+            ),
+            RuntimeError::NotAnInstance(name, value) => write!(
+                f,
+                "expected an instance of struct \"{name}\", but got the {} \"{value}\"",
+                value.type_name()
+            ),
+            RuntimeError::Panic(message, line, call_stack) => {
+                write!(f, "panicked on line {line}: {message}")?;
+                for name in call_stack {
+                    write!(f, "\n    in \"{name}\"")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,79 @@
+//! Evaluation of constant expressions when a program is desugared. The initializer of a `const`
+//! which only applies operators to literals and to earlier top-level constants is evaluated once
+//! and replaced by the literal it evaluates to, so the constant is fixed before the program runs.
+
+use std::{collections::HashMap, io, sync::Arc};
+
+use crate::{
+    desugar::Expr,
+    environment::Environment,
+    interpreter::{interp_expression, Value},
+    symbol::Symbol,
+    tokenizer::Operator,
+    Writer,
+};
+
+/// Returns the literal that `expr` evaluates to if it is a constant expression, or `expr` itself
+/// otherwise. The variables of a constant expression must be names in `constants`, which maps the
+/// names of constants to the literals they have been folded to. An expression whose evaluation
+/// results in an error is not folded, so that the error is reported when the program runs.
+pub fn fold_constant(expr: Expr, constants: &HashMap<Symbol, Expr>) -> Expr {
+    let substituted = match substitute_constants(&expr, constants) {
+        Some(substituted) => substituted,
+        None => return expr,
+    };
+    let mut env = Environment::new(vec![]);
+    let mut writer = Writer::new(Box::new(io::sink()));
+    return match interp_expression(&mut env, &substituted, &mut writer) {
+        Ok(Value::Nil) => Expr::Nil,
+        // NaN is not folded, so that a desugared program still compares equal to itself
+        Ok(Value::Num(n)) if !n.is_nan() => Expr::Num(n),
+        Ok(Value::Bool(b)) => Expr::Bool(b),
+        Ok(Value::Str(s)) => Expr::Str(s),
+        _ => expr,
+    };
+}
+
+/// Returns true if `expr` is a literal, which is what a folded constant is bound to.
+pub fn is_literal(expr: &Expr) -> bool {
+    return matches!(
+        expr,
+        Expr::Nil | Expr::Num(_) | Expr::Bool(_) | Expr::Str(_)
+    );
+}
+
+/// Returns `expr` with its variables replaced by the literals they name in `constants`, or `None`
+/// if `expr` is not a constant expression. Increment and decrement are not constant, since their
+/// operand must be a variable.
+fn substitute_constants(expr: &Expr, constants: &HashMap<Symbol, Expr>) -> Option<Expr> {
+    return match expr {
+        Expr::Nil | Expr::Num(_) | Expr::Bool(_) | Expr::Str(_) => Some(expr.clone()),
+        Expr::Var(name) => constants.get(name).cloned(),
+        Expr::Binary(op, left, right, spans) => Some(Expr::Binary(
+            *op,
+            Arc::new(substitute_constants(left, constants)?),
+            Arc::new(substitute_constants(right, constants)?),
+            *spans,
+        )),
+        Expr::Unary(op @ (Operator::Minus | Operator::LogicNot | Operator::BitNot), operand) => {
+            Some(Expr::Unary(
+                *op,
+                Arc::new(substitute_constants(operand, constants)?),
+            ))
+        }
+        Expr::Index(indexable, index, span) => Some(Expr::Index(
+            Arc::new(substitute_constants(indexable, constants)?),
+            Arc::new(substitute_constants(index, constants)?),
+            *span,
+        )),
+        Expr::Unary(..)
+        | Expr::PrimitiveCall(..)
+        | Expr::Call(..)
+        | Expr::KeywordCall(..)
+        | Expr::Lambda(..)
+        | Expr::Field(..)
+        | Expr::Struct(..)
+        | Expr::StructUpdate(..)
+        | Expr::Statement(..) => None,
+    };
+}
@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::free_variables,
+    error::ParseError::{self, *},
+    language::LanguageVersion,
+    parser::{
+        desugar_module, parse_sugared_program_from, qualified_name, Module, SugaredImport,
+        SugaredProgram,
+    },
+    tokenizer::TokenStream,
+};
+
+/// Finds the source of the modules imported by a program. Embedders can implement this to load
+/// modules from somewhere other than the local disk, such as a database, resources embedded in the
+/// host, or a virtual filesystem. [FileResolver] loads modules from the local disk.
+pub trait ImportResolver {
+    /// Returns the name which identifies the module imported as `path` by the module named
+    /// `importer`, or by the program itself if `importer` is `None`. Modules with the same name are
+    /// the same module, which is used to detect cyclic imports. By default, the name of a module is
+    /// the path it is imported by.
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String> {
+        let _ = importer;
+        return Ok(path.to_string());
+    }
+
+    /// Returns the source of the module named `name`. On failure, this function returns the reason
+    /// the module could not be loaded.
+    fn load(&self, name: &str) -> Result<String, String>;
+}
+
+/// An [ImportResolver] which loads modules from the local disk. Import paths are relative to the
+/// directory of the importing file, and the name of a module is its canonical path.
+#[derive(Clone, Debug)]
+pub struct FileResolver {
+    /// The directory of the file containing the program, which its imports are relative to.
+    base_dir: PathBuf,
+}
+
+impl FileResolver {
+    pub fn new(base_dir: &Path) -> Self {
+        Self {
+            base_dir: base_dir.to_path_buf(),
+        }
+    }
+}
+
+impl ImportResolver for FileResolver {
+    fn resolve(&self, path: &str, importer: Option<&str>) -> Result<String, String> {
+        let dir = match importer.and_then(|importer| Path::new(importer).parent()) {
+            Some(dir) => dir,
+            None => self.base_dir.as_path(),
+        };
+        return match fs::canonicalize(dir.join(path)) {
+            Ok(path) => Ok(path.display().to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    fn load(&self, name: &str) -> Result<String, String> {
+        return fs::read_to_string(name).map_err(|e| e.to_string());
+    }
+}
+
+/// Loads, parses, and desugars the modules imported by `program`, including the modules that they
+/// import. Import paths are resolved relative to `base_dir`, which should be the directory of the
+/// file containing `program`.
+///
+/// Only the procedures of a module marked `pub` are bound in the importing program, qualified by the
+/// alias of the import if it has one (e.g. `math.hypot`). Referring to any other procedure of an
+/// imported module results in a [PrivateProcedure] error.
+pub fn resolve_imports(
+    program: &SugaredProgram,
+    base_dir: &Path,
+) -> Result<Vec<Module>, ParseError> {
+    return resolve_imports_with(program, &FileResolver::new(base_dir));
+}
+
+/// Like [resolve_imports], except that the imported modules are found by `resolver`.
+pub fn resolve_imports_with(
+    program: &SugaredProgram,
+    resolver: &dyn ImportResolver,
+) -> Result<Vec<Module>, ParseError> {
+    return resolve_imports_from(program, resolver, &mut vec![]);
+}
+
+/// Loads the prelude script at `path`. A prelude is a module whose top-level procedures and
+/// constants are all bound in the program it is loaded into (see [Program::with_prelude](crate::parser::Program::with_prelude)), which
+/// makes it useful for project-specific helper libraries. The imports of the prelude are resolved
+/// relative to its directory.
+pub fn load_prelude(path: &Path) -> Result<Module, ParseError> {
+    let path_name = path.display().to_string();
+    let import_failed = |reason: String| ImportFailed(path_name.to_string(), reason);
+
+    let source = fs::read_to_string(path).map_err(|e| import_failed(e.to_string()))?;
+    let sugared_prelude = LanguageVersion::parse(&source)
+        .and_then(|language| parse_sugared_program_from(TokenStream::new(&source), language))
+        .map_err(|e| import_failed(e.to_string()))?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let modules = resolve_imports(&sugared_prelude, base_dir)?;
+
+    let constant_names = sugared_prelude.constants.iter().map(|(name, _)| name);
+    let proc_names = sugared_prelude.procedures.iter().map(|proc| &proc.name);
+    let exports = constant_names
+        .chain(proc_names)
+        .filter(|name| *name != "main")
+        .cloned()
+        .collect();
+
+    let mut program = desugar_module(sugared_prelude);
+    program.modules = modules;
+    return Ok(Module {
+        path: path_name,
+        alias: None,
+        program,
+        exports,
+    });
+}
+
+/// Resolves the imports of `program`, where `importing` holds the names of the modules currently
+/// being imported, which is used to detect cyclic imports. `program` is the last of these modules,
+/// or the program itself if `importing` is empty.
+fn resolve_imports_from(
+    program: &SugaredProgram,
+    resolver: &dyn ImportResolver,
+    importing: &mut Vec<String>,
+) -> Result<Vec<Module>, ParseError> {
+    let importer = importing.last().cloned();
+    let mut modules = vec![];
+    let mut imported_names: Vec<String> = vec![];
+    let mut private_names: Vec<(String, String)> = vec![];
+
+    for SugaredImport {
+        path: import_path,
+        alias,
+    } in &program.imports
+    {
+        let import_failed = |reason: String| ImportFailed(import_path.to_string(), reason);
+
+        let name = resolver
+            .resolve(import_path, importer.as_deref())
+            .map_err(import_failed)?;
+        if importing.contains(&name) {
+            return Err(CyclicImport(import_path.to_string()));
+        }
+
+        let source = resolver.load(&name).map_err(import_failed)?;
+        let sugared_module = LanguageVersion::parse(&source)
+            .and_then(|language| parse_sugared_program_from(TokenStream::new(&source), language))
+            .map_err(|e| import_failed(e.to_string()))?;
+
+        importing.push(name);
+        let nested_modules = resolve_imports_from(&sugared_module, resolver, importing)?;
+        importing.pop();
+
+        if let Some(alias) = alias {
+            if modules
+                .iter()
+                .any(|m: &Module| m.alias.as_ref() == Some(alias))
+            {
+                return Err(DuplicateTopLevelName(alias.to_string()));
+            }
+        }
+
+        let mut exports = vec![];
+        for proc in &sugared_module.procedures {
+            let binding_name = qualified_name(alias.as_deref(), &proc.name);
+            if !proc.public {
+                private_names.push((binding_name, import_path.to_string()));
+                continue;
+            }
+            if program.defines(&binding_name) || imported_names.contains(&binding_name) {
+                return Err(DuplicateTopLevelName(binding_name));
+            }
+            imported_names.push(binding_name);
+            exports.push(proc.name.to_string());
+        }
+
+        let mut module_program = desugar_module(sugared_module);
+        module_program.modules = nested_modules;
+        modules.push(Module {
+            path: import_path.to_string(),
+            alias: alias.clone(),
+            program: module_program,
+            exports,
+        });
+    }
+
+    let free = free_variables(program);
+    for (name, module_path) in private_names {
+        if free.contains(&name) && !program.defines(&name) && !imported_names.contains(&name) {
+            return Err(PrivateProcedure(name, module_path));
+        }
+    }
+
+    return Ok(modules);
+}
@@ -1,43 +1,419 @@
-use std::fmt;
+use std::{cmp::Ordering, collections::HashMap, fmt, sync::Arc};
 
-use crate::{desugar::Statement, environment::Environment, error::RuntimeError, parser::Program, Writer};
+use crate::{
+    desugar::{Expr, Statement},
+    environment::Environment,
+    error::RuntimeError,
+    parser::{qualified_name, Module, Program},
+    pretty::pretty_string,
+    symbol::Symbol,
+    Writer,
+};
 
-use self::statements::interp_statement;
+pub(crate) use self::expressions::interp_expression;
+
+use self::{
+    expressions::apply_call,
+    statements::{interp_statement, ControlFlow},
+    utils::hex_encode,
+};
 
 #[derive(Clone, Debug)]
 pub enum Value {
     Num(f64),
     Bool(bool),
     Str(String),
-    Proc(Vec<String>, Statement, Environment),
+    /// A procedure, which is boxed since its environment makes it much larger than the other
+    /// variants.
+    Proc(Box<Closure>),
     List(Vec<Value>),
+    /// A map, which is boxed for the same reason as [Value::Proc].
+    Map(Box<HashMap<MapKey, Value>>),
+    /// Binary data, such as the contents of a file read with `read_file_bytes`.
+    Bytes(Vec<u8>),
+    /// An instance of a struct, which is boxed for the same reason as [Value::Proc].
+    Struct(Box<Struct>),
     // ! consider if Nil should be an explicit value or just return an Option<Value> instead where None represents Nil
     Nil,
 }
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+// every value is moved and cloned often, so the large variants are boxed to keep it small
+const _: () = assert!(std::mem::size_of::<Value>() <= 32);
+
+/// A procedure with its parameters, its body, the environment it closes over, and its name. A
+/// top-level procedure is named by its definition, and a lambda is named after the variable it is
+/// bound to by the `let` or `const` which creates it, e.g. `add` in `let add = (a, b) -> a + b;`.
+#[derive(Clone, Debug)]
+pub struct Closure {
+    pub params: Vec<Symbol>,
+    pub body: Arc<Statement>,
+    pub env: Environment,
+    pub name: Option<Symbol>,
+}
+
+/// An instance of a struct, with the name of the struct and the value of each of its fields, in the
+/// order the fields are declared.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Struct {
+    pub name: Symbol,
+    pub fields: Vec<(Symbol, Value)>,
+}
+
+impl Struct {
+    /// Returns the value of the field named `field`, if this instance has one.
+    pub fn get(&self, field: Symbol) -> Option<&Value> {
+        return self
+            .fields
+            .iter()
+            .find(|(name, _)| *name == field)
+            .map(|(_, value)| value);
+    }
+}
+
+/// How numbers are formatted when values are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// The shortest representation which parses back to the same number with [parse_num], on every
+    /// platform and in every locale. Numbers of at least `1e21` or less than `1e-6` in magnitude
+    /// are written in scientific notation, e.g. `1.5e-7`. This can expose floating point artifacts,
+    /// e.g. `0.1 + 0.2` is printed as `0.30000000000000004`.
+    #[default]
+    Shortest,
+    /// The number rounded to the given number of significant digits, printed without trailing
+    /// zeros. With enough rounding, `0.1 + 0.2` is printed as `0.3` on every platform.
+    Significant(usize),
+}
+
+impl NumberFormat {
+    pub fn format(&self, n: f64) -> String {
+        match self {
+            NumberFormat::Shortest => format_shortest(n),
+            NumberFormat::Significant(digits) => {
+                if !n.is_finite() {
+                    return format_shortest(n);
+                }
+                let precision = digits.saturating_sub(1);
+                let rounded: f64 = format!("{:.*e}", precision, n)
+                    .parse()
+                    .expect("a number formatted in scientific notation should parse as a number");
+                // avoid printing "-0"
+                if rounded == 0.0 {
+                    return "0".to_string();
+                }
+                format_shortest(rounded)
+            }
+        }
+    }
+}
+
+/// Formats `n` as described for [NumberFormat::Shortest].
+fn format_shortest(n: f64) -> String {
+    // Rust's formatting of floats is locale independent and gives the fewest digits which round
+    // trip, but never uses scientific notation on its own, so `1e300` would be printed with 301
+    // digits
+    if n != 0.0 && (n.abs() >= 1e21 || n.abs() < 1e-6) {
+        return format!("{n:e}");
+    }
+    return n.to_string();
+}
+
+/// Parses a number as it is printed by a program with [NumberFormat::Shortest], e.g. `-12.5`,
+/// `1e21`, `inf`, or `NaN`. Returns `None` if `s` is not a number.
+pub fn parse_num(s: &str) -> Option<f64> {
+    return s.parse().ok();
+}
+
+/// The name of every type of value, as returned by [Value::type_name].
+pub const TYPE_NAMES: &[&str] = &[
+    "num", "bool", "str", "proc", "list", "map", "bytes", "struct", "nil",
+];
+
+impl Value {
+    /// Returns the name of the type of this value, as used in error messages.
+    pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Num(n) => write!(f, "{}", n),
-            Value::Bool(b) => write!(f, "{}", b),
-            Value::Nil => write!(f, "nil"),
-            Value::Str(s) => write!(f, "{}", s),
-            Value::Proc(..) => write!(f, "<lambda>"),
+            Value::Num(_) => "num",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "str",
+            Value::Proc(..) => "proc",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Bytes(_) => "bytes",
+            Value::Struct(_) => "struct",
+            Value::Nil => "nil",
+        }
+    }
+
+    /// Converts this value to a string in the same way as its [Display](fmt::Display)
+    /// implementation, except that numbers are formatted with `number_format`.
+    pub fn to_string_with(&self, number_format: NumberFormat) -> String {
+        match self {
+            Value::Num(n) => number_format.format(*n),
+            Value::Bool(b) => b.to_string(),
+            Value::Nil => "nil".to_string(),
+            Value::Str(s) => s.to_string(),
+            Value::Proc(..) => "<lambda>".to_string(),
             Value::List(list) => {
-                let values_as_strings: Vec<String> = list.iter().map(|v| v.to_string()).collect();
+                let values_as_strings: Vec<String> =
+                    list.iter().map(|v| v.to_string_with(number_format)).collect();
                 let list_string = values_as_strings.join(", ");
-                write!(f, "[{list_string}]")
+                format!("[{list_string}]")
+            }
+            Value::Map(map) => {
+                let mut entries: Vec<(&MapKey, &Value)> = map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                let entry_strings: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        format!(
+                            "{}: {}",
+                            k.to_value().to_string_with(number_format),
+                            v.to_string_with(number_format)
+                        )
+                    })
+                    .collect();
+                let map_string = entry_strings.join(", ");
+                format!("{{{map_string}}}")
+            }
+            Value::Bytes(bytes) => format!("<bytes {}>", hex_encode(bytes)),
+            Value::Struct(instance) => {
+                let field_strings: Vec<String> = instance
+                    .fields
+                    .iter()
+                    .map(|(name, v)| format!("{name}: {}", v.to_string_with(number_format)))
+                    .collect();
+                format!("{} {{{}}}", instance.name, field_strings.join(", "))
+            }
+        }
+    }
+
+    /// Converts this value to a string which tells apart values that are printed the same way, as
+    /// returned by the `debug` builtin. Strings are quoted, e.g. `"nil"` as opposed to `nil`, and
+    /// procedures are shown with their name and parameters, e.g. `<proc add(a, b)>`,
+    /// `<lambda inc(x)>`, or `<lambda(x)>`. Numbers are formatted with [NumberFormat::Shortest].
+    pub fn to_debug_string(&self) -> String {
+        match self {
+            Value::Str(s) => pretty_string(s),
+            Value::Proc(closure) => {
+                let params: Vec<&str> = closure.params.iter().map(|param| param.as_str()).collect();
+                match (closure.env.procedure_name(&closure.body), closure.name) {
+                    (Some(name), _) => format!("<proc {name}({})>", params.join(", ")),
+                    (None, Some(name)) => format!("<lambda {name}({})>", params.join(", ")),
+                    (None, None) => format!("<lambda({})>", params.join(", ")),
+                }
+            }
+            Value::List(list) => {
+                let values_as_strings: Vec<String> =
+                    list.iter().map(|v| v.to_debug_string()).collect();
+                format!("[{}]", values_as_strings.join(", "))
+            }
+            Value::Map(map) => {
+                let mut entries: Vec<(&MapKey, &Value)> = map.iter().collect();
+                entries.sort_by_key(|(key, _)| *key);
+                let entry_strings: Vec<String> = entries
+                    .iter()
+                    .map(|(k, v)| {
+                        format!("{}: {}", k.to_value().to_debug_string(), v.to_debug_string())
+                    })
+                    .collect();
+                format!("{{{}}}", entry_strings.join(", "))
+            }
+            Value::Struct(instance) => {
+                let field_strings: Vec<String> = instance
+                    .fields
+                    .iter()
+                    .map(|(name, v)| format!("{name}: {}", v.to_debug_string()))
+                    .collect();
+                format!("{} {{{}}}", instance.name, field_strings.join(", "))
+            }
+            Value::Num(_) | Value::Bool(_) | Value::Bytes(_) | Value::Nil => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_string_with(NumberFormat::Shortest))
+    }
+}
+
+/// A key of a [Value::Map]. Only numbers, strings, and booleans can be used as map keys. Numbers
+/// are stored by their bit pattern so that they can be hashed, with `-0` normalized to `0`. `NaN`
+/// is not a valid key since it is not equal to itself.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Num(u64),
+    Str(String),
+    Bool(bool),
+}
+
+impl MapKey {
+    /// Converts `value` into a map key. Returns an [InvalidMapKey](RuntimeError::InvalidMapKey)
+    /// error if `value` cannot be used as a map key.
+    pub fn from_value(value: Value) -> Result<Self, RuntimeError> {
+        match value {
+            Value::Num(n) if n.is_nan() => Err(RuntimeError::InvalidMapKey(Value::Num(n))),
+            Value::Num(0.0) => Ok(MapKey::Num(0.0_f64.to_bits())),
+            Value::Num(n) => Ok(MapKey::Num(n.to_bits())),
+            Value::Str(s) => Ok(MapKey::Str(s)),
+            Value::Bool(b) => Ok(MapKey::Bool(b)),
+            value => Err(RuntimeError::InvalidMapKey(value)),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        match self {
+            MapKey::Num(bits) => Value::Num(f64::from_bits(*bits)),
+            MapKey::Str(s) => Value::Str(s.to_string()),
+            MapKey::Bool(b) => Value::Bool(*b),
+        }
+    }
+}
+
+/// Map keys are ordered by type (numbers, then strings, then booleans) and then by value. This
+/// ordering is used to print maps deterministically.
+impl Ord for MapKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (MapKey::Num(left), MapKey::Num(right)) => {
+                f64::from_bits(*left).total_cmp(&f64::from_bits(*right))
             }
+            (MapKey::Str(left), MapKey::Str(right)) => left.cmp(right),
+            (MapKey::Bool(left), MapKey::Bool(right)) => left.cmp(right),
+            (MapKey::Num(_), _) => Ordering::Less,
+            (_, MapKey::Num(_)) => Ordering::Greater,
+            (MapKey::Str(_), _) => Ordering::Less,
+            (_, MapKey::Str(_)) => Ordering::Greater,
+        }
+    }
+}
+
+impl PartialOrd for MapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for MapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_value())
+    }
+}
+
+/// The maximum length of a string or list produced by repeating a value with the `*` operator.
+pub const MAX_REPETITION_LENGTH: usize = 1 << 24;
+
+/// The maximum length of a list produced by the `range` builtin.
+pub const MAX_RANGE_LENGTH: usize = 1 << 24;
+
+/// The maximum number of procedure calls which can be in progress at once. A call in tail position
+/// replaces the call it is made from rather than being made inside it (see
+/// [apply_call](expressions::apply_call)), so it does not count towards the limit. Every other call
+/// is interpreted on the host stack, so the calls in progress are also limited by the stack they
+/// use (see [DEFAULT_CALL_STACK_SIZE]). Going past either limit is reported as a
+/// [CallTooDeep](RuntimeError::CallTooDeep) error rather than overflowing the stack.
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// The number of bytes of the host stack which the procedure calls in progress may use by default
+/// (see [Writer::with_call_stack_size](crate::Writer::with_call_stack_size)). This is half of the
+/// 8 MiB stack of a main thread, which leaves the other half for the host and for the innermost
+/// call. A call takes tens of KiB of stack in a debug build, so a debug build reaches this limit
+/// after about a hundred nested calls, well before [MAX_CALL_DEPTH].
+pub const DEFAULT_CALL_STACK_SIZE: usize = 4 << 20;
+
+/// Values are compared structurally, and struct instances are only equal if they are instances of
+/// structs with the same name. Procedures are never equal to anything, including themselves,
+/// since comparing their bodies and closure environments would not be meaningful.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Num(left), Value::Num(right)) => left == right,
+            (Value::Bool(left), Value::Bool(right)) => left == right,
+            (Value::Str(left), Value::Str(right)) => left == right,
+            (Value::List(left), Value::List(right)) => left == right,
+            (Value::Map(left), Value::Map(right)) => left == right,
+            (Value::Bytes(left), Value::Bytes(right)) => left == right,
+            (Value::Struct(left), Value::Struct(right)) => left == right,
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
         }
     }
 }
 
+pub(crate) mod builtins;
 mod expressions;
+#[cfg(feature = "serde")]
+mod json;
+mod place;
 mod statements;
 mod utils;
 
 pub fn interp_program<'a>(p: Program, writer: &mut Writer) -> Result<Value, RuntimeError> {
-    return match interp_statement(&mut Environment::new(p.procedures), p.main, false, writer)? {
-        (value, _) => Ok(value),
+    return interp_program_with_env(p, Environment::new(vec![]), writer);
+}
+
+/// Binds the procedures exported by `modules` in `env`, and then evaluates `constants` in order and
+/// binds them in `env`.
+fn define_top_level(
+    env: &mut Environment,
+    modules: Vec<Module>,
+    constants: Vec<(String, Expr)>,
+    writer: &mut Writer,
+) -> Result<(), RuntimeError> {
+    for module in modules {
+        // the procedures of a module close over the module's own environment, so that they can
+        // call the private procedures of the module
+        let mut module_env = Environment::new(module.program.procedures);
+        define_top_level(
+            &mut module_env,
+            module.program.modules,
+            module.program.constants,
+            writer,
+        )?;
+        for name in module.exports {
+            let value = module_env.get(Symbol::from(name.as_str()))?;
+            env.insert_new_constant_value(
+                Symbol::from(qualified_name(module.alias.as_deref(), &name)),
+                value,
+            );
+        }
+    }
+    for (name, expr) in constants {
+        let value = interp_expression(env, &expr, writer)?;
+        env.insert_new_constant_value(Symbol::from(name), value);
+    }
+    return Ok(());
+}
+
+/// Interprets the program `p` in the environment `env`, which may contain bindings preset by the
+/// host (see [Environment::builder]). The top-level procedures of `p` and the procedures exported by
+/// the modules it imports are added to `env`, and then the top-level constants of `p` are evaluated
+/// in order and added to `env`.
+pub fn interp_program_with_env(
+    p: Program,
+    mut env: Environment,
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    return interp_program_in_env(p, &mut env, writer);
+}
+
+/// Like [interp_program_with_env], except that the top-level procedures and constants of `p` are
+/// left bound in `env` after it has run, so that they can be used by programs run in `env` later
+/// (see [Isolate](crate::isolate::Isolate)).
+pub fn interp_program_in_env(
+    p: Program,
+    env: &mut Environment,
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    writer.metadata = p.metadata;
+    env.add_procedures(p.procedures);
+    define_top_level(env, p.modules, p.constants, writer)?;
+    let result = interp_statement(env, &p.main, false, writer);
+    if result.is_err() {
+        writer.record_error_call("main");
+    }
+    return match result.map_err(|e| e.unwind("main"))? {
+        (_, ControlFlow::TailCall(call)) => apply_call(*call, writer),
+        (value, _) => Ok(value.unwrap_or(Value::Nil)),
     };
 }
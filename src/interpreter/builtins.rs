@@ -0,0 +1,1047 @@
+//! The handlers of the builtin procedures (see [BUILTINS](crate::builtins::BUILTINS)). The number
+//! of arguments passed to a handler has already been checked against the arity of its builtin.
+//! Handlers must evaluate their arguments from left to right, each at most once, like the
+//! arguments of any other call (see `docs/evaluation_order.md`).
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    desugar::Expr,
+    encoding,
+    environment::Environment,
+    error::RuntimeError::{self, *},
+    parser::Builtin,
+    tokenizer::Span,
+    Writer,
+};
+
+use super::{
+    expressions::{call_procedure, interp_expression},
+    utils::{
+        self, compare_values, ensure_args, ensure_bytes, ensure_bytes_or_str, ensure_count,
+        ensure_index, ensure_integer, ensure_list, ensure_map, ensure_radix, ensure_str,
+        exact_integer, floor_div, merge_sort,
+    },
+    MapKey, Value, MAX_RANGE_LENGTH, TYPE_NAMES,
+};
+
+pub(crate) fn print(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let mut values: Vec<Value> = vec![];
+    for expr in args {
+        values.push(interp_expression(env, expr, writer)?);
+    }
+    let values: Vec<String> = values
+        .iter()
+        .map(|v| v.to_string_with(writer.number_format))
+        .collect();
+    let values = values.join(" ");
+
+    writer.write_output(&values)?;
+    // print!("{}", values);
+    Ok(Value::Nil)
+}
+
+pub(crate) fn list(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let mut values = vec![];
+    for expr in args {
+        values.push(interp_expression(env, expr, writer)?);
+    }
+    Ok(Value::List(values))
+}
+
+pub(crate) fn is_empty(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::IsEmpty, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Bool(list.is_empty()))
+}
+
+pub(crate) fn is_nil(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::IsNil, args)?;
+    match interp_expression(env, arg, writer)? {
+        Value::Nil => Ok(Value::Bool(true)),
+        _ => Ok(Value::Bool(false)),
+    }
+}
+
+pub(crate) fn head(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Head, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    match list.as_slice() {
+        [hd, ..] => Ok(hd.clone()),
+        [] => Ok(Value::Nil),
+    }
+}
+
+pub(crate) fn rest(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Rest, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    match list.as_slice() {
+        [_, tail @ ..] => Ok(Value::List(tail.to_vec())),
+        [] => Ok(Value::Nil),
+    }
+}
+
+pub(crate) fn zip(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [first_arg, second_arg] = ensure_args(Builtin::Zip, args)?;
+    let first_list = ensure_list(interp_expression(env, first_arg, writer)?)?;
+    let second_list = ensure_list(interp_expression(env, second_arg, writer)?)?;
+
+    let pairs = first_list
+        .into_iter()
+        .zip(second_list)
+        .map(|(first, second)| Value::List(vec![first, second]))
+        .collect();
+    Ok(Value::List(pairs))
+}
+
+pub(crate) fn enumerate(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Enumerate, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    let pairs = list
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| Value::List(vec![Value::Num(index as f64), value]))
+        .collect();
+    Ok(Value::List(pairs))
+}
+
+pub(crate) fn range(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let mut bounds = vec![];
+    for expr in args {
+        bounds.push(ensure_integer(interp_expression(env, expr, writer)?)?);
+    }
+
+    let (start, stop, step) = match bounds.as_slice() {
+        [stop] => (0, *stop, 1),
+        [start, stop] => (*start, *stop, 1),
+        [start, stop, step] => (*start, *stop, *step),
+        _ => {
+            return Err(BuiltinArgMismatch(
+                Builtin::Range,
+                bounds.len(),
+                Span::default(),
+            ))
+        }
+    };
+
+    if step == 0 {
+        return Err(BadArg(Value::Num(0.0)));
+    }
+
+    // the number of elements is counted before any are produced, so that a huge range is an error
+    // rather than running out of memory
+    let distance = stop - start;
+    let length = if distance != 0 && (distance > 0) == (step > 0) {
+        (distance.unsigned_abs() - 1) / step.unsigned_abs() + 1
+    } else {
+        0
+    };
+    if length > MAX_RANGE_LENGTH as u64 {
+        return Err(RangeTooLarge(MAX_RANGE_LENGTH));
+    }
+
+    let values = (0..length as i64)
+        .map(|i| Value::Num((start + i * step) as f64))
+        .collect();
+    Ok(Value::List(values))
+}
+
+pub(crate) fn sort(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Sort, args)?;
+    let mut list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    // check that every element is comparable with the first one before sorting so
+    // that the reported error does not depend on the order of comparisons
+    if let Some(first) = list.first() {
+        for value in list.iter() {
+            compare_values(first, value)?;
+        }
+    }
+
+    list.sort_by(|left, right| compare_values(left, right).unwrap_or(Ordering::Equal));
+    Ok(Value::List(list))
+}
+
+pub(crate) fn sort_by(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, cmp_arg] = ensure_args(Builtin::SortBy, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let cmp = interp_expression(env, cmp_arg, writer)?;
+
+    let list = merge_sort(list, &mut |left, right| {
+        let arg_values = vec![left.clone(), right.clone()];
+        match call_procedure("<comparator>", cmp.clone(), arg_values, writer)? {
+            Value::Num(n) => Ok(n.partial_cmp(&0.0).unwrap_or(Ordering::Equal)),
+            v => Err(BadArg(v)),
+        }
+    })?;
+    Ok(Value::List(list))
+}
+
+pub(crate) fn index_of(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, target_arg] = ensure_args(Builtin::IndexOf, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let target = interp_expression(env, target_arg, writer)?;
+
+    for (index, value) in list.into_iter().enumerate() {
+        if matches_target("index_of", &target, value, writer)? {
+            return Ok(Value::Num(index as f64));
+        }
+    }
+    Ok(Value::Nil)
+}
+
+pub(crate) fn find(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, target_arg] = ensure_args(Builtin::Find, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let target = interp_expression(env, target_arg, writer)?;
+
+    for value in list {
+        if matches_target("find", &target, value.clone(), writer)? {
+            return Ok(value);
+        }
+    }
+    Ok(Value::Nil)
+}
+
+pub(crate) fn any(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, target_arg] = ensure_args(Builtin::Any, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let target = interp_expression(env, target_arg, writer)?;
+
+    for value in list {
+        if matches_target("any", &target, value, writer)? {
+            return Ok(Value::Bool(true));
+        }
+    }
+    Ok(Value::Bool(false))
+}
+
+pub(crate) fn all(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, target_arg] = ensure_args(Builtin::All, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let target = interp_expression(env, target_arg, writer)?;
+
+    for value in list {
+        if !matches_target("all", &target, value, writer)? {
+            return Ok(Value::Bool(false));
+        }
+    }
+    Ok(Value::Bool(true))
+}
+
+pub(crate) fn flat(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Flat, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    let mut values = vec![];
+    for value in list {
+        match value {
+            Value::List(mut inner) => values.append(&mut inner),
+            value => values.push(value),
+        }
+    }
+    Ok(Value::List(values))
+}
+
+pub(crate) fn flat_map(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, f_arg] = ensure_args(Builtin::FlatMap, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let f = interp_expression(env, f_arg, writer)?;
+
+    let mut values = vec![];
+    for value in list {
+        let mut mapped = ensure_list(call_procedure("flat_map", f.clone(), vec![value], writer)?)?;
+        values.append(&mut mapped);
+    }
+    Ok(Value::List(values))
+}
+
+pub(crate) fn unique(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Unique, args)?;
+    let list = ensure_list(interp_expression(env, arg, writer)?)?;
+
+    let mut values: Vec<Value> = vec![];
+    for value in list {
+        if !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    Ok(Value::List(values))
+}
+
+pub(crate) fn reverse(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Reverse, args)?;
+    match interp_expression(env, arg, writer)? {
+        Value::List(list) => Ok(Value::List(list.into_iter().rev().collect())),
+        Value::Str(str) => Ok(Value::Str(str.chars().rev().collect())),
+        bad_value => Err(ExpectedList(bad_value)),
+    }
+}
+
+pub(crate) fn take(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, count_arg] = ensure_args(Builtin::Take, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let count = ensure_count(interp_expression(env, count_arg, writer)?)?;
+    Ok(Value::List(list.into_iter().take(count).collect()))
+}
+
+pub(crate) fn drop(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, count_arg] = ensure_args(Builtin::Drop, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let count = ensure_count(interp_expression(env, count_arg, writer)?)?;
+    Ok(Value::List(list.into_iter().skip(count).collect()))
+}
+
+pub(crate) fn copy(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Copy, args)?;
+    // values own all of their data and are never shared between variables, so the
+    // evaluated value is already a deep copy
+    interp_expression(env, arg, writer)
+}
+
+pub(crate) fn freeze(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Freeze, args)?;
+    // compound values are copied when they are bound, so the only way to mutate a
+    // value is through the variable holding it. Freezing a value therefore makes that
+    // variable constant. Any other expression produces a temporary which cannot be
+    // mutated in the first place.
+    if let Expr::Var(ref id) = arg {
+        env.freeze(*id)?;
+    }
+    interp_expression(env, arg, writer)
+}
+
+pub(crate) fn divmod(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [dividend_arg, divisor_arg] = ensure_args(Builtin::DivMod, args)?;
+    match (
+        interp_expression(env, dividend_arg, writer)?,
+        interp_expression(env, divisor_arg, writer)?,
+    ) {
+        (Value::Num(dividend), Value::Num(divisor)) => {
+            let (quotient, remainder) = floor_div(dividend, divisor)?;
+            Ok(Value::List(vec![
+                Value::Num(quotient),
+                Value::Num(remainder),
+            ]))
+        }
+        (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
+    }
+}
+
+pub(crate) fn dict(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let mut values = vec![];
+    for expr in args {
+        values.push(interp_expression(env, expr, writer)?);
+    }
+
+    let mut map = HashMap::new();
+    let mut values = values.into_iter();
+    while let (Some(key), Some(value)) = (values.next(), values.next()) {
+        map.insert(MapKey::from_value(key)?, value);
+    }
+    Ok(Value::Map(Box::new(map)))
+}
+
+pub(crate) fn has_key(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [map_arg, key_arg] = ensure_args(Builtin::HasKey, args)?;
+    let map = ensure_map(interp_expression(env, map_arg, writer)?)?;
+    let key = MapKey::from_value(interp_expression(env, key_arg, writer)?)?;
+    Ok(Value::Bool(map.contains_key(&key)))
+}
+
+pub(crate) fn set(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [map_arg, key_arg, value_arg] = ensure_args(Builtin::Set, args)?;
+    let mut map = ensure_map(interp_expression(env, map_arg, writer)?)?;
+    let key = MapKey::from_value(interp_expression(env, key_arg, writer)?)?;
+    let value = interp_expression(env, value_arg, writer)?;
+    map.insert(key, value);
+    Ok(Value::Map(Box::new(map)))
+}
+
+pub(crate) fn keys(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Keys, args)?;
+    let map = ensure_map(interp_expression(env, arg, writer)?)?;
+    let mut keys: Vec<MapKey> = map.into_keys().collect();
+    keys.sort();
+    Ok(Value::List(keys.iter().map(|key| key.to_value()).collect()))
+}
+
+pub(crate) fn join(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [list_arg, separator_arg] = ensure_args(Builtin::Join, args)?;
+    let list = ensure_list(interp_expression(env, list_arg, writer)?)?;
+    let separator = ensure_str(interp_expression(env, separator_arg, writer)?)?;
+    let strings: Vec<String> = list
+        .into_iter()
+        // elements which are not strings are joined as they would be printed
+        .map(|value| match value {
+            Value::Str(str) => str,
+            value => value.to_string_with(writer.number_format),
+        })
+        .collect();
+    Ok(Value::Str(strings.join(&separator)))
+}
+
+pub(crate) fn split(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [str_arg, separator_arg] = ensure_args(Builtin::Split, args)?;
+    let str = ensure_str(interp_expression(env, str_arg, writer)?)?;
+    let separator = ensure_str(interp_expression(env, separator_arg, writer)?)?;
+    // an empty separator would split around every character and add empty strings at both ends
+    if separator.is_empty() {
+        return Err(BadArg(Value::Str(separator)));
+    }
+    Ok(Value::List(
+        str.split(separator.as_str())
+            .map(|part| Value::Str(part.to_string()))
+            .collect(),
+    ))
+}
+
+pub(crate) fn equals_ignore_case(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [left_arg, right_arg] = ensure_args(Builtin::EqualsIgnoreCase, args)?;
+    let left = ensure_str(interp_expression(env, left_arg, writer)?)?;
+    let right = ensure_str(interp_expression(env, right_arg, writer)?)?;
+    Ok(Value::Bool(left.to_lowercase() == right.to_lowercase()))
+}
+
+/// Returns -1, 0, or 1 if the first argument is less than, equal to, or greater than the second.
+/// Numbers are compared with numbers and strings with strings (see [compare_values]). If the
+/// optional third argument is true, strings are compared without regard to case.
+pub(crate) fn compare(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let (left_arg, right_arg, ignore_case_arg) = match args {
+        [left_arg, right_arg] => (left_arg, right_arg, None),
+        [left_arg, right_arg, ignore_case_arg] => (left_arg, right_arg, Some(ignore_case_arg)),
+        _ => {
+            return Err(BuiltinArgMismatch(
+                Builtin::Compare,
+                args.len(),
+                Span::default(),
+            ))
+        }
+    };
+    let left = interp_expression(env, left_arg, writer)?;
+    let right = interp_expression(env, right_arg, writer)?;
+    let ignore_case = match ignore_case_arg {
+        Some(arg) => match interp_expression(env, arg, writer)? {
+            Value::Bool(b) => b,
+            bad_value => return Err(ExpectedBool(bad_value)),
+        },
+        None => false,
+    };
+    let ordering = match (left, right) {
+        (Value::Str(left), Value::Str(right)) if ignore_case => {
+            left.to_lowercase().cmp(&right.to_lowercase())
+        }
+        (left, right) => compare_values(&left, &right)?,
+    };
+    Ok(Value::Num(ordering as i8 as f64))
+}
+
+pub(crate) fn chars(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [str_arg] = ensure_args(Builtin::Chars, args)?;
+    let str = ensure_str(interp_expression(env, str_arg, writer)?)?;
+    Ok(Value::List(
+        str.chars().map(|c| Value::Str(c.to_string())).collect(),
+    ))
+}
+
+/// Returns the parts of a string which are separated by whitespace, without any empty parts.
+pub(crate) fn words(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [str_arg] = ensure_args(Builtin::Words, args)?;
+    let str = ensure_str(interp_expression(env, str_arg, writer)?)?;
+    Ok(Value::List(
+        str.split_whitespace()
+            .map(|word| Value::Str(word.to_string()))
+            .collect(),
+    ))
+}
+
+/// Returns the lines of a string without their line endings, which may be `\n` or `\r\n`. A line
+/// ending at the end of the string does not start another line.
+pub(crate) fn lines(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [str_arg] = ensure_args(Builtin::Lines, args)?;
+    let str = ensure_str(interp_expression(env, str_arg, writer)?)?;
+    Ok(Value::List(
+        str.lines()
+            .map(|line| Value::Str(line.to_string()))
+            .collect(),
+    ))
+}
+
+#[cfg(feature = "csv")]
+pub(crate) fn csv_parse(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let (text_arg, has_header_arg) = match args {
+        [text_arg] => (text_arg, None),
+        [text_arg, has_header_arg] => (text_arg, Some(has_header_arg)),
+        _ => {
+            return Err(BuiltinArgMismatch(
+                Builtin::CsvParse,
+                args.len(),
+                Span::default(),
+            ))
+        }
+    };
+    let text = ensure_str(interp_expression(env, text_arg, writer)?)?;
+    let has_header = match has_header_arg {
+        Some(arg) => match interp_expression(env, arg, writer)? {
+            Value::Bool(b) => b,
+            bad_value => return Err(ExpectedBool(bad_value)),
+        },
+        None => false,
+    };
+    let mut records = crate::csv::parse(&text).map_err(InvalidCsv)?.into_iter();
+
+    if !has_header {
+        return Ok(Value::List(
+            records
+                .map(|record| Value::List(record.into_iter().map(Value::Str).collect()))
+                .collect(),
+        ));
+    }
+
+    // with a header, each record after the first becomes a map from the header's fields to its own
+    let header = records.next().unwrap_or_default();
+    let mut rows = vec![];
+    for (i, record) in records.enumerate() {
+        if record.len() != header.len() {
+            return Err(InvalidCsv(format!(
+                "record {} has {} of the {} fields in the header",
+                i + 2,
+                record.len(),
+                header.len()
+            )));
+        }
+        let row: HashMap<MapKey, Value> = header
+            .iter()
+            .zip(record)
+            .map(|(name, field)| (MapKey::Str(name.clone()), Value::Str(field)))
+            .collect();
+        rows.push(Value::Map(Box::new(row)));
+    }
+    Ok(Value::List(rows))
+}
+
+#[cfg(feature = "csv")]
+pub(crate) fn csv_stringify(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::CsvStringify, args)?;
+    let rows = ensure_list(interp_expression(env, arg, writer)?)?;
+    // fields which are not strings are written as they would be printed
+    let field = |value: Value| match value {
+        Value::Str(str) => str,
+        value => value.to_string_with(writer.number_format),
+    };
+
+    // maps are written with a header of their sorted keys, which every map must have
+    let header = match rows.first() {
+        Some(Value::Map(first)) => {
+            let mut keys: Vec<MapKey> = first.keys().cloned().collect();
+            keys.sort();
+            Some(keys)
+        }
+        _ => None,
+    };
+    let mut records = vec![];
+    if let Some(keys) = &header {
+        records.push(keys.iter().map(|key| field(key.to_value())).collect());
+    }
+    for row in rows {
+        let record = match (&header, row) {
+            (Some(keys), Value::Map(mut map)) => {
+                let mut record = vec![];
+                for key in keys {
+                    match map.remove(key) {
+                        Some(value) => record.push(field(value)),
+                        None => {
+                            return Err(InvalidCsv(format!(
+                                "a record has no \"{}\" field",
+                                key.to_value()
+                            )))
+                        }
+                    }
+                }
+                if !map.is_empty() {
+                    return Err(InvalidCsv(
+                        "every record must have the same fields as the first".to_string(),
+                    ));
+                }
+                record
+            }
+            (None, Value::List(list)) => list.into_iter().map(field).collect(),
+            (Some(_), bad_value) => return Err(ExpectedMap(bad_value)),
+            (None, bad_value) => return Err(ExpectedList(bad_value)),
+        };
+        records.push(record);
+    }
+    Ok(Value::Str(crate::csv::stringify(&records)))
+}
+
+pub(crate) fn read_file_bytes(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [path_arg] = ensure_args(Builtin::ReadFileBytes, args)?;
+    let path = ensure_str(interp_expression(env, path_arg, writer)?)?;
+    match std::fs::read(&path) {
+        Ok(bytes) => Ok(Value::Bytes(bytes)),
+        Err(e) => Err(FileError(path, e.to_string())),
+    }
+}
+
+pub(crate) fn bytes_len(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::BytesLen, args)?;
+    let bytes = ensure_bytes(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Num(bytes.len() as f64))
+}
+
+pub(crate) fn byte_at(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [bytes_arg, index_arg] = ensure_args(Builtin::ByteAt, args)?;
+    let bytes = ensure_bytes(interp_expression(env, bytes_arg, writer)?)?;
+    let index = ensure_index(interp_expression(env, index_arg, writer)?, bytes.len())?;
+    Ok(Value::Num(bytes[index] as f64))
+}
+
+pub(crate) fn slice_bytes(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [bytes_arg, start_arg, end_arg] = ensure_args(Builtin::SliceBytes, args)?;
+    let bytes = ensure_bytes(interp_expression(env, bytes_arg, writer)?)?;
+    let start = ensure_integer(interp_expression(env, start_arg, writer)?)?;
+    let end = ensure_integer(interp_expression(env, end_arg, writer)?)?;
+    let out_of_bounds =
+        |index: i64| IndexOutOfBounds(Value::Num(index as f64), bytes.len(), Span::default());
+    // the end is exclusive, so it may be equal to the length of the bytes
+    if end < 0 || end as usize > bytes.len() {
+        return Err(out_of_bounds(end));
+    }
+    if start < 0 || start > end {
+        return Err(out_of_bounds(start));
+    }
+    Ok(Value::Bytes(bytes[start as usize..end as usize].to_vec()))
+}
+
+pub(crate) fn hex_encode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::HexEncode, args)?;
+    let bytes = ensure_bytes(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Str(utils::hex_encode(&bytes)))
+}
+
+pub(crate) fn hex_decode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::HexDecode, args)?;
+    let hex = ensure_str(interp_expression(env, arg, writer)?)?;
+    match utils::hex_decode(&hex) {
+        Some(bytes) => Ok(Value::Bytes(bytes)),
+        None => Err(BadArg(Value::Str(hex))),
+    }
+}
+
+// digests are returned as hex strings, which is how checksums are usually published
+#[cfg(feature = "hash")]
+pub(crate) fn md5(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Md5, args)?;
+    let data = ensure_bytes_or_str(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Str(utils::hex_encode(&crate::hash::md5(&data))))
+}
+
+#[cfg(feature = "hash")]
+pub(crate) fn sha256(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Sha256, args)?;
+    let data = ensure_bytes_or_str(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Str(utils::hex_encode(&crate::hash::sha256(&data))))
+}
+
+pub(crate) fn base64_encode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Base64Encode, args)?;
+    let data = ensure_bytes_or_str(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Str(encoding::base64_encode(&data)))
+}
+
+pub(crate) fn base64_decode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Base64Decode, args)?;
+    let encoded = ensure_str(interp_expression(env, arg, writer)?)?;
+    match encoding::base64_decode(&encoded) {
+        Some(bytes) => Ok(Value::Bytes(bytes)),
+        None => Err(BadArg(Value::Str(encoded))),
+    }
+}
+
+pub(crate) fn url_encode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::UrlEncode, args)?;
+    let data = ensure_bytes_or_str(interp_expression(env, arg, writer)?)?;
+    Ok(Value::Str(encoding::url_encode(&data)))
+}
+
+pub(crate) fn url_decode(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::UrlDecode, args)?;
+    let encoded = ensure_str(interp_expression(env, arg, writer)?)?;
+    match encoding::url_decode(&encoded) {
+        Some(decoded) => Ok(Value::Str(decoded)),
+        None => Err(BadArg(Value::Str(encoded))),
+    }
+}
+
+pub(crate) fn parse_num(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::ParseNum, args)?;
+    let str = ensure_str(interp_expression(env, arg, writer)?)?;
+    match super::parse_num(&str) {
+        Some(n) => Ok(Value::Num(n)),
+        None => Err(BadArg(Value::Str(str))),
+    }
+}
+
+/// Returns the digits of an integer in a base from 2 to 36, using lowercase letters for the digits
+/// after `9` and a leading `-` for negative integers. Integers with a magnitude above 2^53 are a
+/// [BadArg] error, since they may not be exact.
+pub(crate) fn to_str_radix(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [integer_arg, radix_arg] = ensure_args(Builtin::ToStrRadix, args)?;
+    let integer = ensure_integer(interp_expression(env, integer_arg, writer)?)?;
+    let radix = ensure_radix(interp_expression(env, radix_arg, writer)?)?;
+    let mut magnitude = integer.unsigned_abs();
+    let mut digits = vec![];
+    loop {
+        let digit = (magnitude % radix as u64) as u32;
+        digits.push(char::from_digit(digit, radix).unwrap_or_default());
+        magnitude /= radix as u64;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if integer < 0 {
+        digits.push('-');
+    }
+    Ok(Value::Str(digits.into_iter().rev().collect()))
+}
+
+/// Parses an integer written in a base from 2 to 36 (see [to_str_radix]). The letters of the digits
+/// can be uppercase or lowercase. Like [to_str_radix], it is limited to integers which are exact.
+pub(crate) fn parse_int(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [str_arg, radix_arg] = ensure_args(Builtin::ParseInt, args)?;
+    let str = ensure_str(interp_expression(env, str_arg, writer)?)?;
+    let radix = ensure_radix(interp_expression(env, radix_arg, writer)?)?;
+    match i64::from_str_radix(&str, radix)
+        .ok()
+        .and_then(exact_integer)
+    {
+        Some(integer) => Ok(integer),
+        None => Err(BadArg(Value::Str(str))),
+    }
+}
+
+pub(crate) fn debug(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [arg] = ensure_args(Builtin::Debug, args)?;
+    let value = interp_expression(env, arg, writer)?;
+    Ok(Value::Str(value.to_debug_string()))
+}
+
+pub(crate) fn type_assert(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [value_arg, type_arg] = ensure_args(Builtin::TypeAssert, args)?;
+    let value = interp_expression(env, value_arg, writer)?;
+    let type_name = match interp_expression(env, type_arg, writer)? {
+        Value::Str(type_name) if TYPE_NAMES.contains(&type_name.as_str()) => type_name,
+        Value::Str(type_name) => return Err(BadArg(Value::Str(type_name))),
+        bad_value => return Err(ExpectedStr(bad_value)),
+    };
+    if value.type_name() == type_name {
+        return Ok(value);
+    }
+    // the error names the variable that was checked, which is usually a parameter
+    let name = match value_arg {
+        Expr::Var(name) => Some(name.to_string()),
+        _ => None,
+    };
+    Err(TypeAssertionFailed(name, type_name, value))
+}
+
+/// Stops the program with a [Panic] error, which carries the message, the line of the statement
+/// that panicked, and the procedures that were being called.
+pub(crate) fn panic(
+    env: &mut Environment,
+    args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let [message_arg] = ensure_args(Builtin::Panic, args)?;
+    let message = interp_expression(env, message_arg, writer)?;
+    return Err(Panic(message.to_string(), writer.line, vec![]));
+}
+
+pub(crate) fn random(
+    _env: &mut Environment,
+    _args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    return Ok(Value::Num(writer.nondeterminism.random()?));
+}
+
+pub(crate) fn now_millis(
+    _env: &mut Environment,
+    _args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    return Ok(Value::Num(writer.nondeterminism.now_millis()?));
+}
+
+pub(crate) fn input(
+    _env: &mut Environment,
+    _args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    // flush the output first so that a prompt printed without a line break is shown
+    let _ = writer.w.flush();
+    return Ok(match writer.nondeterminism.input()? {
+        Some(line) => Value::Str(line),
+        None => Value::Nil,
+    });
+}
+
+pub(crate) fn script_name(
+    _env: &mut Environment,
+    _args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    Ok(match &writer.metadata.name {
+        Some(name) => Value::Str(name.to_string()),
+        None => Value::Nil,
+    })
+}
+
+pub(crate) fn script_version(
+    _env: &mut Environment,
+    _args: &[Expr],
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    Ok(match &writer.metadata.version {
+        Some(version) => Value::Str(version.to_string()),
+        None => Value::Nil,
+    })
+}
+
+/// Checks whether `value` matches `target` for the searching builtins (`index_of`, `find`, `any`,
+/// and `all`). If `target` is a procedure, it is called with `value` as a predicate and must return a
+/// boolean. Otherwise, `value` matches if it is equal to `target`.
+fn matches_target(
+    builtin_name: &str,
+    target: &Value,
+    value: Value,
+    writer: &mut Writer,
+) -> Result<bool, RuntimeError> {
+    match target {
+        Value::Proc(..) => match call_procedure(builtin_name, target.clone(), vec![value], writer)?
+        {
+            Value::Bool(b) => Ok(b),
+            v => Err(ExpectedBool(v)),
+        },
+        target => Ok(*target == value),
+    }
+}
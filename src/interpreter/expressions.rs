@@ -1,373 +1,624 @@
+use std::sync::Arc;
+
 use crate::{
-    desugar::Expr,
+    desugar::{Expr, Statement},
     environment::{AssignmentType, Binding, Entry, Environment, Mutability},
     error::RuntimeError::{self, *},
-    tokenizer::Operator, Writer,
+    symbol::Symbol,
+    tokenizer::{Operator, Span},
+    Writer,
 };
 
 use super::{
-    statements::interp_statement,
-    utils::{ensure_list, ensure_single_arg},
-    Value,
+    place::Place,
+    statements::{interp_statement, ControlFlow},
+    utils::{ensure_index, ensure_integer, ensure_repetition_count, exact_integer, floor_div},
+    Closure, MapKey, Struct, Value, MAX_CALL_DEPTH,
 };
 
-pub fn interp_expression<'a>(env: &mut Environment, expr: Expr, writer: &mut Writer) -> Result<Value, RuntimeError> {
+/// A step of the evaluation of an expression by [interp_expression].
+enum Step<'e> {
+    /// Evaluates the expression and pushes its value.
+    Eval(&'e Expr),
+    /// Pops the values of the operands of the binary operator and pushes the result of the operator.
+    /// The spans of the operands are given to the errors about them.
+    Binary(Operator, (Span, Span)),
+    /// Pops the value of the left operand of `&&` or `||`, which decides whether the right operand
+    /// is evaluated.
+    ShortCircuit(Operator, &'e Expr),
+    /// Pops a value and ensures that it is a boolean before pushing it again.
+    EnsureBool,
+    /// Pops the value of the operand of the unary operator and pushes the result of the operator.
+    Unary(Operator),
+    /// Pops an index and the value being indexed and pushes the value at the index. The span of
+    /// the index is where an out of bounds index is reported.
+    Index(Span),
+}
+
+/// Evaluates `expr` in `env`. The operands of binary and unary operators and of indexing are
+/// evaluated with an explicit stack of [steps](Step) rather than by recursion, so that a long chain
+/// of operators such as `1 + 1 + ... + 1` does not overflow the host stack. Every other expression
+/// is evaluated by [interp_term]. Operands are evaluated from left to right, and the right operand
+/// of `&&` and `||` is only evaluated if the left operand does not decide the result (see
+/// `docs/evaluation_order.md`).
+pub fn interp_expression(env: &mut Environment, expr: &Expr, writer: &mut Writer) -> Result<Value, RuntimeError> {
+    if !matches!(expr, Expr::Binary(..) | Expr::Unary(..) | Expr::Index(..)) {
+        return interp_term(env, expr, writer);
+    }
+
+    let mut steps = vec![Step::Eval(expr)];
+    let mut values: Vec<Value> = vec![];
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::Eval(Expr::Binary(op @ (Operator::LogicAnd | Operator::LogicOr), left, right, _)) => {
+                steps.push(Step::ShortCircuit(*op, right));
+                steps.push(Step::Eval(left));
+            }
+            Step::Eval(Expr::Binary(op, left, right, spans)) => {
+                steps.push(Step::Binary(*op, *spans));
+                steps.push(Step::Eval(right));
+                steps.push(Step::Eval(left));
+            }
+            Step::Eval(
+                expr @ Expr::Unary(
+                    Operator::PreIncrement
+                    | Operator::PostIncrement
+                    | Operator::PreDecrement
+                    | Operator::PostDecrement,
+                    _,
+                ),
+            ) => values.push(interp_term(env, expr, writer)?),
+            Step::Eval(Expr::Unary(op, operand)) => {
+                steps.push(Step::Unary(*op));
+                steps.push(Step::Eval(operand));
+            }
+            Step::Eval(Expr::Index(indexable, index, span)) => {
+                steps.push(Step::Index(*span));
+                steps.push(Step::Eval(index));
+                steps.push(Step::Eval(indexable));
+            }
+            Step::Eval(expr) => values.push(interp_term(env, expr, writer)?),
+            Step::Binary(op, spans) => {
+                let right = pop_value(&mut values);
+                let left = pop_value(&mut values);
+                let value =
+                    apply_binary(op, left, right).map_err(|e| e.with_operand_spans(spans))?;
+                values.push(value);
+            }
+            Step::ShortCircuit(op, right) => match (op, pop_value(&mut values)) {
+                (Operator::LogicOr, Value::Bool(true)) => values.push(Value::Bool(true)),
+                (Operator::LogicAnd, Value::Bool(false)) => values.push(Value::Bool(false)),
+                (_, Value::Bool(_)) => {
+                    steps.push(Step::EnsureBool);
+                    steps.push(Step::Eval(right));
+                }
+                (_, left_value) => return Err(BadArg(left_value)),
+            },
+            Step::EnsureBool => match pop_value(&mut values) {
+                value @ Value::Bool(_) => values.push(value),
+                value => return Err(BadArg(value)),
+            },
+            Step::Unary(op) => {
+                let operand = pop_value(&mut values);
+                values.push(apply_unary(op, operand)?);
+            }
+            Step::Index(span) => {
+                let index = pop_value(&mut values);
+                let indexable = pop_value(&mut values);
+                values.push(apply_index(indexable, index).map_err(|e| e.at(span))?);
+            }
+        }
+    }
+    return Ok(pop_value(&mut values));
+}
+
+fn pop_value(values: &mut Vec<Value>) -> Value {
+    return values
+        .pop()
+        .expect("a step should only pop the values pushed by the steps before it");
+}
+
+/// Evaluates an expression other than an operator or indexing (see [interp_expression]). The
+/// increment and decrement operators are evaluated here too, since their operand is a place
+/// rather than a value.
+fn interp_term(env: &mut Environment, expr: &Expr, writer: &mut Writer) -> Result<Value, RuntimeError> {
     match expr {
         Expr::Nil => Ok(Value::Nil),
-        Expr::Num(n) => Ok(Value::Num(n)),
-        Expr::Bool(b) => Ok(Value::Bool(b)),
-        Expr::Str(s) => Ok(Value::Str(s)),
-        Expr::Lambda(params, body) => Ok(Value::Proc(params, *body, env.clone())),
-        Expr::Var(id) => match env.get(id.to_string())? {
+        Expr::Num(n) => Ok(Value::Num(*n)),
+        Expr::Bool(b) => Ok(Value::Bool(*b)),
+        Expr::Str(s) => Ok(Value::Str(s.clone())),
+        Expr::Lambda(params, body, captures) => {
+            Ok(Value::Proc(Box::new(Closure {
+                params: params.clone(),
+                body: body.clone(),
+                env: env.capture(captures),
+                name: None,
+            })))
+        }
+        // the statement is interpreted outside of any loop so that `break` and `continue` cannot
+        // jump out of the expression
+        Expr::Statement(statement) => match interp_statement(env, statement, false, writer)? {
+            (value, ControlFlow::Normal) => Ok(value.unwrap_or(Value::Nil)),
+            _ => Err(ReturnInExpression),
+        },
+        Expr::Var(id) => match env.get(*id)? {
             v => Ok(v),
         },
-        Expr::Binary(op, left, right) => match op {
-            Operator::Plus => {
-                match (
-                    interp_expression(env, *left, writer)?,
-                    interp_expression(env, *right, writer)?,
-                ) {
-                    (Value::Num(num_left), Value::Num(num_right)) => {
-                        Ok(Value::Num(num_left + num_right))
-                    }
-                    (Value::Str(num_left), Value::Str(num_right)) => {
-                        Ok(Value::Str(num_left + num_right.as_str()))
-                    }
-                    (Value::List(mut list_left), Value::List(mut list_right)) => {
-                        list_left.append(&mut list_right);
-                        Ok(Value::List(list_left))
-                    }
-                    (Value::Num(_), v) => Err(BadArg(v)),
-                    (v, _) => Err(BadArg(v)),
-                }
+        Expr::Field(object, field) => {
+            let value = interp_expression(env, object, writer)?;
+            return apply_field(value, *field);
+        }
+        Expr::Struct(name, field_exprs) => {
+            let mut fields = vec![];
+            for (field, field_expr) in field_exprs {
+                fields.push((*field, interp_expression(env, field_expr, writer)?));
             }
-            Operator::Minus => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Num(num_left - num_right))
-                }
-                (Value::Num(_), v) => Err(BadArg(v)),
-                (v, _) => Err(BadArg(v)),
-            },
-            Operator::Eq => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left == num_right))
-                }
-                (Value::Bool(bool_left), Value::Bool(bool_right)) => {
-                    Ok(Value::Bool(bool_left == bool_right))
-                }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::Ne => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left != num_right))
-                }
-                (Value::Bool(bool_left), Value::Bool(bool_right)) => {
-                    Ok(Value::Bool(bool_left != bool_right))
-                }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::LT => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left < num_right))
-                }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::GT => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left > num_right))
+            let instance = Struct {
+                name: *name,
+                fields,
+            };
+            return Ok(Value::Struct(Box::new(instance)));
+        }
+        Expr::StructUpdate(name, base, field_exprs) => {
+            let mut instance = match interp_expression(env, base, writer)? {
+                Value::Struct(instance) if instance.name == *name => instance,
+                value => return Err(NotAnInstance(name.to_string(), value)),
+            };
+            for (field, field_expr) in field_exprs {
+                let value = interp_expression(env, field_expr, writer)?;
+                match instance.fields.iter_mut().find(|(name, _)| name == field) {
+                    Some((_, field_value)) => *field_value = value,
+                    None => return Err(NoSuchField(Value::Struct(instance), field.to_string())),
                 }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::LTE => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
+            }
+            return Ok(Value::Struct(instance));
+        }
+        Expr::Unary(
+            op @ (Operator::PreIncrement
+            | Operator::PostIncrement
+            | Operator::PreDecrement
+            | Operator::PostDecrement),
+            operand,
+        ) => interp_increment(env, *op, operand, writer),
+        Expr::Call(f_expr, args, span) => {
+            let call = prepare_call(env, f_expr, args, &[], *span, writer)?;
+            return apply_call(call, writer);
+        }
+        Expr::KeywordCall(f_expr, args, keyword_args, span) => {
+            let call = prepare_call(env, f_expr, args, keyword_args, *span, writer)?;
+            return apply_call(call, writer);
+        }
+        // an index which is out of bounds of an argument is reported at the call
+        Expr::PrimitiveCall(builtin, args, span) => {
+            let spec = builtin.spec();
+            if !spec.arity.accepts(args.len()) {
+                return Err(BuiltinArgMismatch(*builtin, args.len(), *span));
+            }
+            (spec.handler)(env, args, writer).map_err(|e| e.at(*span))
+        }
+        // the remaining operators are evaluated by the steps of interp_expression
+        Expr::Binary(..) | Expr::Unary(..) | Expr::Index(..) => interp_expression(env, expr, writer),
+    }
+}
+
+/// Applies the binary operator `op` to the values of its operands. `&&` and `||` are applied by
+/// [interp_expression], since their right operand is not always evaluated.
+pub(super) fn apply_binary(op: Operator, left: Value, right: Value) -> Result<Value, RuntimeError> {
+    match op {
+        Operator::Plus => {
+            match (left, right) {
                 (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left <= num_right))
+                    Ok(Value::Num(num_left + num_right))
                 }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::GTE => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Bool(num_left >= num_right))
+                (Value::Str(num_left), Value::Str(num_right)) => {
+                    Ok(Value::Str(num_left + num_right.as_str()))
                 }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::LogicOr => match interp_expression(env, *left, writer)? {
-                Value::Bool(b) => match b {
-                    true => Ok(Value::Bool(true)),
-                    false => match interp_expression(env, *right, writer)? {
-                        Value::Bool(b) => Ok(Value::Bool(b)),
-                        right_value => Err(BadArg(right_value)),
-                    },
-                },
-                left_value => Err(BadArg(left_value)),
-            },
-            Operator::LogicAnd => match interp_expression(env, *left, writer)? {
-                Value::Bool(b) => match b {
-                    false => Ok(Value::Bool(false)),
-                    true => match interp_expression(env, *right, writer)? {
-                        Value::Bool(b) => Ok(Value::Bool(b)),
-                        right_value => Err(BadArg(right_value)),
-                    },
-                },
-                left_value => Err(BadArg(left_value)),
-            },
-            Operator::Times => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Num(num_left * num_right))
+                (Value::List(mut list_left), Value::List(mut list_right)) => {
+                    list_left.append(&mut list_right);
+                    Ok(Value::List(list_left))
                 }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::Mod => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Num(num_left % num_right))
-                }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            Operator::Div => match (
-                interp_expression(env, *left, writer)?,
-                interp_expression(env, *right, writer)?,
-            ) {
-                (Value::Num(num_left), Value::Num(num_right)) => {
-                    Ok(Value::Num(num_left / num_right))
-                }
-                (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
-            },
-            op => Err(UnaryAsBinary(op)),
+                (Value::Num(_), v) => Err(BadArg(v)),
+                (v, _) => Err(BadArg(v)),
+            }
+        }
+        Operator::Minus => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Num(num_left - num_right))
+            }
+            (Value::Num(_), v) => Err(BadArg(v)),
+            (v, _) => Err(BadArg(v)),
         },
-        Expr::Unary(op, operand) => match op {
-            Operator::PreIncrement => {
-                let var_name = match *operand {
-                    Expr::Var(ref id) => id.to_string(),
-                    _ => return Err(InvalidAssignmentTarget),
-                };
-
-                let num_value = match interp_expression(env, *operand, writer)? {
-                    Value::Num(n) => n,
-                    v => return Err(BadArg(v)),
-                };
-
-                env.reassign(var_name, Value::Num(num_value + 1.0))?;
-
-                return Ok(Value::Num(num_value + 1.0));
+        Operator::Eq => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left == num_right))
             }
-            Operator::PostIncrement => {
-                let var_name = match *operand {
-                    Expr::Var(ref id) => id.to_string(),
-                    _ => return Err(InvalidAssignmentTarget),
-                };
-
-                let original_num_value = match interp_expression(env, *operand, writer)? {
-                    Value::Num(n) => n,
-                    v => return Err(BadArg(v)),
-                };
-
-                env.reassign(var_name, Value::Num(original_num_value + 1.0))?;
-
-                return Ok(Value::Num(original_num_value));
+            (Value::Bool(bool_left), Value::Bool(bool_right)) => {
+                Ok(Value::Bool(bool_left == bool_right))
             }
-            Operator::PreDecrement => {
-                let var_name = match *operand {
-                    Expr::Var(ref id) => id.to_string(),
-                    _ => return Err(InvalidAssignmentTarget),
-                };
-
-                let num_value = match interp_expression(env, *operand, writer)? {
-                    Value::Num(n) => n,
-                    v => return Err(BadArg(v)),
-                };
-
-                env.reassign(var_name, Value::Num(num_value - 1.0))?;
-
-                return Ok(Value::Num(num_value - 1.0));
+            (Value::Str(str_left), Value::Str(str_right)) => {
+                Ok(Value::Bool(str_left == str_right))
             }
-            Operator::PostDecrement => {
-                let var_name = match *operand {
-                    Expr::Var(ref id) => id.to_string(),
-                    _ => return Err(InvalidAssignmentTarget),
-                };
-
-                let original_num_value = match interp_expression(env, *operand, writer)? {
-                    Value::Num(n) => n,
-                    v => return Err(BadArg(v)),
-                };
-
-                env.reassign(var_name, Value::Num(original_num_value - 1.0))?;
-
-                return Ok(Value::Num(original_num_value));
+            (v_left @ Value::Bytes(_), v_right @ Value::Bytes(_))
+            | (v_left @ Value::Struct(_), v_right @ Value::Struct(_)) => {
+                Ok(Value::Bool(v_left == v_right))
             }
-            Operator::Minus => match interp_expression(env, *operand, writer)? {
-                Value::Num(n) => Ok(Value::Num(-n)),
-                v => Err(BadArg(v)),
-            },
-            Operator::LogicNot => match interp_expression(env, *operand, writer)? {
-                Value::Bool(b) => Ok(Value::Bool(!b)),
-                v => Err(BadArg(v)),
-            },
-            op => Err(BinaryAsUnary(op)),
+            // nil is only equal to itself, so that any value can be checked for being nil
+            (Value::Nil, value) | (value, Value::Nil) => Ok(Value::Bool(value == Value::Nil)),
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::Eq, v_left, v_right)),
+        },
+        Operator::Ne => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left != num_right))
+            }
+            (Value::Bool(bool_left), Value::Bool(bool_right)) => {
+                Ok(Value::Bool(bool_left != bool_right))
+            }
+            (Value::Str(str_left), Value::Str(str_right)) => {
+                Ok(Value::Bool(str_left != str_right))
+            }
+            (v_left @ Value::Bytes(_), v_right @ Value::Bytes(_))
+            | (v_left @ Value::Struct(_), v_right @ Value::Struct(_)) => {
+                Ok(Value::Bool(v_left != v_right))
+            }
+            // nil is only equal to itself, so that any value can be checked for being nil
+            (Value::Nil, value) | (value, Value::Nil) => Ok(Value::Bool(value != Value::Nil)),
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::Ne, v_left, v_right)),
+        },
+        Operator::LT => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left < num_right))
+            }
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::LT, v_left, v_right)),
+        },
+        Operator::GT => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left > num_right))
+            }
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::GT, v_left, v_right)),
+        },
+        Operator::LTE => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left <= num_right))
+            }
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::LTE, v_left, v_right)),
+        },
+        Operator::GTE => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Bool(num_left >= num_right))
+            }
+            (v_left, v_right) => Err(RuntimeError::cannot_compare(Operator::GTE, v_left, v_right)),
+        },
+        Operator::Times => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Num(num_left * num_right))
+            }
+            (Value::Str(str), count) => {
+                let count = ensure_repetition_count(count, str.chars().count())?;
+                Ok(Value::Str(str.repeat(count)))
+            }
+            (Value::List(list), count) => {
+                let count = ensure_repetition_count(count, list.len())?;
+                Ok(Value::List((0..count).flat_map(|_| list.clone()).collect()))
+            }
+            (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
         },
-        Expr::Call(f_expr, args) => {
-            let f_name = match *f_expr {
-                Expr::Var(ref f_name) => f_name.to_string(),
-                _ => "<lambda>".to_string(),
+        Operator::Mod => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Num(num_left % num_right))
+            }
+            (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
+        },
+        Operator::Div => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                Ok(Value::Num(num_left / num_right))
+            }
+            (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
+        },
+        Operator::FloorDiv => match (left, right) {
+            (Value::Num(num_left), Value::Num(num_right)) => {
+                let (quotient, _) = floor_div(num_left, num_right)?;
+                Ok(Value::Num(quotient))
+            }
+            (v_left, v_right) => Err(BadArgs(vec![v_left, v_right])),
+        },
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+            let args = vec![left.clone(), right.clone()];
+            let (left, right) = (ensure_integer(left)?, ensure_integer(right)?);
+            let result = match op {
+                Operator::BitAnd => left & right,
+                Operator::BitOr => left | right,
+                _ => left ^ right,
             };
-
-            let (f_params, f_body, f_env) = match interp_expression(env, *f_expr, writer)? {
-                Value::Proc(params, body, env) => (params, body, env),
-                v => return Err(BadArg(v)),
+            exact_integer(result).ok_or(BadArgs(args))
+        }
+        Operator::ShiftLeft | Operator::ShiftRight => {
+            let args = vec![left.clone(), right.clone()];
+            let integer = ensure_integer(left)?;
+            // shifting by 64 or more bits, or by a negative number of bits, is an error rather
+            // than wrapping around
+            let bits = match u32::try_from(ensure_integer(right.clone())?) {
+                Ok(bits) if bits < 64 => bits,
+                _ => return Err(BadArg(right)),
             };
+            if op == Operator::ShiftRight {
+                return exact_integer(integer >> bits).ok_or(BadArgs(args));
+            }
+            // bits shifted out on the left are lost, in which case shifting back gives a different
+            // integer
+            let shifted = Some(integer << bits).filter(|shifted| shifted >> bits == integer);
+            shifted.and_then(exact_integer).ok_or(BadArgs(args))
+        }
+        op => Err(UnaryAsBinary(op)),
+    }
+}
+
+fn apply_unary(op: Operator, operand: Value) -> Result<Value, RuntimeError> {
+    match op {
+        Operator::Minus => match operand {
+            Value::Num(n) => Ok(Value::Num(-n)),
+            v => Err(BadArg(v)),
+        },
+        Operator::LogicNot => match operand {
+            Value::Bool(b) => Ok(Value::Bool(!b)),
+            v => Err(BadArg(v)),
+        },
+        Operator::BitNot => exact_integer(!ensure_integer(operand.clone())?).ok_or(BadArg(operand)),
+        op => Err(BinaryAsUnary(op)),
+    }
+}
 
-            if args.len() != f_params.len() {
-                return Err(ArgMismatch(
-                    f_name.to_string(),
-                    f_params.len(), // expected
-                    args.len(),     // actual
-                ));
+pub(super) fn apply_index(indexable: Value, index: Value) -> Result<Value, RuntimeError> {
+    match indexable {
+        Value::List(mut list) => {
+            let index = ensure_index(index, list.len())?;
+            Ok(list.swap_remove(index))
+        }
+        Value::Str(str) => {
+            let index = ensure_index(index, str.chars().count())?;
+            Ok(Value::Str(str.chars().skip(index).take(1).collect()))
+        }
+        Value::Map(map) => {
+            let key = MapKey::from_value(index)?;
+            match map.get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => Ok(Value::Nil),
             }
+        }
+        value => return Err(NotIndexable(value)),
+    }
+}
 
-            let arg_values_result: Result<Vec<Value>, RuntimeError> = args
-                .into_iter()
-                .map(|arg| interp_expression(env, arg, writer))
-                .collect();
-            let arg_values = match arg_values_result {
-                Ok(values) => values,
-                Err(e) => return Err(e),
-            };
+/// Returns the value of the field named `field` of `value`, which must be a struct instance with a
+/// field of that name.
+pub(super) fn apply_field(value: Value, field: Symbol) -> Result<Value, RuntimeError> {
+    return match value {
+        Value::Struct(instance) => match instance.get(field) {
+            Some(field_value) => Ok(field_value.clone()),
+            None => Err(NoSuchField(Value::Struct(instance), field.to_string())),
+        },
+        value => Err(NoSuchField(value, field.to_string())),
+    };
+}
 
-            let entries: Vec<Entry> = arg_values
-                .into_iter()
-                .map(|v| (v, AssignmentType::Initialized, Mutability::Constant))
-                .collect();
+/// Interprets the increment or decrement operator `op` applied to `operand`, which must be a
+/// [Place] holding a number. A prefix operator evaluates to the new value of the place and a
+/// postfix operator evaluates to its old value.
+fn interp_increment(
+    env: &mut Environment,
+    op: Operator,
+    operand: &Expr,
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let place = Place::resolve(env, operand, writer)?;
+    let old_value = match place.read(env)? {
+        Value::Num(n) => n,
+        v => return Err(BadArg(v)),
+    };
+    let new_value = match op {
+        Operator::PreIncrement | Operator::PostIncrement => old_value + 1.0,
+        _ => old_value - 1.0,
+    };
+    place.assign(env, Value::Num(new_value))?;
+    return match op {
+        Operator::PreIncrement | Operator::PreDecrement => Ok(Value::Num(new_value)),
+        _ => Ok(Value::Num(old_value)),
+    };
+}
 
-            let param_bindings: Vec<Binding> = f_params
-                .iter()
-                .map(|param| param.to_string())
-                .zip(entries)
-                .collect();
+/// Calls the procedure `f_value` with the already evaluated `arg_values`. This is used by builtin
+/// procedures which take a procedure as an argument, such as `sort_by`. The `f_name` is used when
+/// reporting an [ArgMismatch] error if the procedure has no name of its own. The error is reported
+/// at the call of the builtin once it propagates to it (see [RuntimeError::at]).
+pub fn call_procedure(
+    f_name: &str,
+    f_value: Value,
+    arg_values: Vec<Value>,
+    writer: &mut Writer,
+) -> Result<Value, RuntimeError> {
+    let Closure {
+        params: f_params,
+        body: f_body,
+        env: f_env,
+        name: proc_name,
+    } = match f_value {
+        Value::Proc(closure) => *closure,
+        v => return Err(BadArg(v)),
+    };
 
-            return match interp_statement(&mut f_env.extend(param_bindings), f_body, false, writer)? {
-                (value, _) => Ok(value),
-            };
-        }
-        Expr::PrimitiveCall(builtin, args) => match builtin {
-            crate::parser::Builtin::Print => {
-                let mut values: Vec<Value> = vec![];
-                for expr in args {
-                    values.push(interp_expression(env, expr, writer)?);
-                }
-                let values: Vec<String> = values.iter().map(|v| v.to_string()).collect();
-                let values = values.join(" ");
+    if arg_values.len() != f_params.len() {
+        return Err(ArgMismatch(
+            proc_name.map_or(f_name.to_string(), |name| name.to_string()),
+            f_params.len(),   // expected
+            arg_values.len(), // actual
+            Span::default(),
+        ));
+    }
 
-                // TODO: write a better expect message
-                writer.w.write(values.as_bytes()).expect("should be able to write");
-                // print!("{}", values);
-                Ok(Value::Nil)
-            }
-            crate::parser::Builtin::List => {
-                let mut values = vec![];
-                for expr in args {
-                    values.push(interp_expression(env, expr, writer)?);
-                }
-                Ok(Value::List(values))
-            }
-            crate::parser::Builtin::IsEmpty => {
-                let arg = ensure_single_arg(args)?;
-                let list = ensure_list(interp_expression(env, arg, writer)?)?;
-                Ok(Value::Bool(list.is_empty()))
-            }
-            crate::parser::Builtin::IsNil => {
-                let arg = ensure_single_arg(args)?;
-                match interp_expression(env, arg, writer)? {
-                    Value::Nil => Ok(Value::Bool(true)),
-                    _ => Ok(Value::Bool(false)),
-                }
-            }
-            crate::parser::Builtin::Head => {
-                let arg = ensure_single_arg(args)?;
-                let list = ensure_list(interp_expression(env, arg, writer)?)?;
+    return apply_call(
+        PendingCall {
+            name: proc_name,
+            params: f_params,
+            body: f_body,
+            env: f_env,
+            args: arg_values,
+        },
+        writer,
+    );
+}
 
-                match list.as_slice() {
-                    [hd, ..] => Ok(hd.clone()),
-                    [] => Ok(Value::Nil),
-                }
-            }
-            crate::parser::Builtin::Rest => {
-                let arg = ensure_single_arg(args)?;
-                let list = ensure_list(interp_expression(env, arg, writer)?)?;
+/// A call whose procedure and arguments have been evaluated, but which has not been applied yet.
+#[derive(Clone, Debug)]
+pub struct PendingCall {
+    /// The name of the procedure, or of the variable it is called through if it has no name of its
+    /// own, which is only used to record the call stack of a post-mortem.
+    name: Option<Symbol>,
+    params: Vec<Symbol>,
+    body: Arc<Statement>,
+    env: Environment,
+    args: Vec<Value>,
+}
 
-                match list.as_slice() {
-                    [_, tail @ ..] => Ok(Value::List(tail.to_vec())),
-                    [] => Ok(Value::Nil),
-                }
+/// Evaluates the procedure `f_expr` and the arguments `args` and `keyword_args` of a call without
+/// applying the procedure. The procedure is evaluated first and is checked to accept the given
+/// arguments before any of the arguments are evaluated, and then the arguments are evaluated from
+/// left to right. Parameters have no default values, so every parameter must be given an argument,
+/// either by position or by name. An [ArgMismatch] error is reported at `span`, the span of the
+/// call.
+pub fn prepare_call(
+    env: &mut Environment,
+    f_expr: &Expr,
+    args: &[Expr],
+    keyword_args: &[(Symbol, Expr)],
+    span: Span,
+    writer: &mut Writer,
+) -> Result<PendingCall, RuntimeError> {
+    let Closure {
+        params: f_params,
+        body: f_body,
+        env: f_env,
+        name: proc_name,
+    } = match interp_expression(env, f_expr, writer)? {
+        Value::Proc(closure) => *closure,
+        v => return Err(BadArg(v)),
+    };
+
+    // errors refer to the procedure by its own name, or else by the variable it is called through
+    let name = match (proc_name, f_expr) {
+        (Some(name), _) => Some(name),
+        (None, Expr::Var(name)) => Some(*name),
+        _ => None,
+    };
+    let f_name = name.map_or("<lambda>".to_string(), |name| name.to_string());
+
+    if args.len() > f_params.len() {
+        return Err(ArgMismatch(
+            f_name.to_string(),
+            f_params.len(),                   // expected
+            args.len() + keyword_args.len(), // actual
+            span,
+        ));
+    }
+
+    // the index of the parameter that each keyword argument is bound to
+    let mut keyword_indices = vec![];
+    for (name, _) in keyword_args {
+        match f_params.iter().position(|param| param == name) {
+            Some(index) if index < args.len() => {
+                return Err(DuplicateArg(f_name, name.to_string()))
             }
-        },
-        Expr::Index(indexable_expr, index_expr) => match interp_expression(env, *indexable_expr, writer)? {
-            Value::List(list) => match interp_expression(env, *index_expr, writer)? {
-                Value::Num(num) => {
-                    if num.fract() != 0.0 {
-                        return Err(ExpectedInteger(Value::Num(num)));
-                    }
+            Some(index) => keyword_indices.push(index),
+            None => return Err(UnknownParam(f_name, name.to_string())),
+        }
+    }
+
+    // each keyword argument names a different parameter than every other argument, so each
+    // parameter is given an argument once there are as many arguments as parameters
+    if args.len() + keyword_args.len() != f_params.len() {
+        return Err(ArgMismatch(
+            f_name,
+            f_params.len(),                   // expected
+            args.len() + keyword_args.len(), // actual
+            span,
+        ));
+    }
 
-                    let index = num as i64;
-                    if index < 0 {
-                        return Err(IndexOutOfBounds(index));
-                    }
+    let arg_values_result: Result<Vec<Value>, RuntimeError> = args
+        .iter()
+        .map(|arg| interp_expression(env, arg, writer))
+        .collect();
+    let mut arg_values = arg_values_result?;
+    // the placeholders are replaced with the values of the keyword arguments
+    arg_values.resize(f_params.len(), Value::Nil);
+    for (index, (_, arg)) in keyword_indices.into_iter().zip(keyword_args) {
+        arg_values[index] = interp_expression(env, arg, writer)?;
+    }
 
-                    let value = match list.into_iter().nth(index as usize) {
-                        Some(v) => v,
-                        None => return Err(IndexOutOfBounds(index)),
-                    };
+    return Ok(PendingCall {
+        name,
+        params: f_params,
+        body: f_body,
+        env: f_env,
+        args: arg_values,
+    });
+}
 
-                    return Ok(value);
-                }
-                bad_value => return Err(ExpectedInteger(bad_value)),
-            },
-            Value::Str(str) => match interp_expression(env, *index_expr, writer)? {
-                Value::Num(num) => {
-                    if num.fract() != 0.0 {
-                        return Err(ExpectedInteger(Value::Num(num)));
-                    }
+/// Binds the arguments of `call` to its parameters in its closure environment and interprets its
+/// body. The call evaluates to the value produced by the body, or to `nil` if the body produced no
+/// value (see [interp_statement]). If the body ends with a call in tail position (`return f(x);`), that call is returned to
+/// this function as a [TailCall](ControlFlow::TailCall) and applied here, after the frame of the
+/// current call has been discarded. This trampoline lets chains of tail calls, including mutually
+/// recursive procedures, run without growing the host stack.
+pub fn apply_call(call: PendingCall, writer: &mut Writer) -> Result<Value, RuntimeError> {
+    let stack_address = stack_address();
+    if writer.call_depth == 0 {
+        writer.call_stack_base = stack_address;
+    }
+    let stack_used = writer.call_stack_base.abs_diff(stack_address);
+    if writer.call_depth == MAX_CALL_DEPTH || stack_used > writer.call_stack_size {
+        return Err(CallTooDeep);
+    }
+    writer.call_depth += 1;
+    let mut call = call;
+    // the statement which made the call is still being executed once the call returns
+    let line = writer.line;
+    loop {
+        let PendingCall {
+            name,
+            params,
+            body,
+            env,
+            args,
+        } = call;
 
-                    let index = num as i64;
-                    if index < 0 {
-                        return Err(IndexOutOfBounds(index));
-                    }
+        let entries: Vec<Entry> = args
+            .into_iter()
+            .map(|v| (v, AssignmentType::Initialized, Mutability::Constant))
+            .collect();
 
-                    let character = match str.chars().nth(index as usize) {
-                        Some(char) => char.to_string(),
-                        None => return Err(IndexOutOfBounds(index)),
-                    };
+        let param_bindings: Vec<Binding> = params.into_iter().zip(entries).collect();
 
-                    return Ok(Value::Str(character));
-                }
-                bad_value => return Err(ExpectedInteger(bad_value)),
-            },
-            value => return Err(NotIndexable(value)),
-        },
+        let mut call_env = env.extend(param_bindings);
+        match interp_statement(&mut call_env, &body, false, writer) {
+            Ok((_, ControlFlow::TailCall(next_call))) => call = *next_call,
+            Ok((value, _)) => {
+                writer.line = line;
+                writer.call_depth -= 1;
+                return Ok(value.unwrap_or(Value::Nil));
+            }
+            Err(e) => {
+                writer.call_depth -= 1;
+                let name = name.map_or("<lambda>", |name| name.as_str());
+                writer.record_error_env(&call_env);
+                writer.record_error_call(name);
+                return Err(e.unwind(name));
+            }
+        }
     }
 }
+
+/// Returns the address of a local variable, which tells how much of the host stack is in use where
+/// it is called.
+fn stack_address() -> usize {
+    let marker = 0u8;
+    return std::hint::black_box(&marker) as *const u8 as usize;
+}
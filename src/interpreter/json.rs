@@ -0,0 +1,81 @@
+//! Conversions between [Value] and [serde_json::Value], so that hosts which exchange JSON with
+//! scripts do not have to convert it by hand. This module is only compiled with the `serde`
+//! feature.
+
+use serde_json::{Map, Number};
+
+use crate::error::RuntimeError::{self, *};
+
+use super::{MapKey, Value};
+
+/// JSON `null` becomes [Value::Nil], arrays become lists, and objects become maps with string keys.
+impl From<serde_json::Value> for Value {
+    fn from(json: serde_json::Value) -> Self {
+        match json {
+            serde_json::Value::Null => Value::Nil,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            // every JSON number can be represented as a float, although large integers may be
+            // rounded
+            serde_json::Value::Number(n) => Value::Num(n.as_f64().unwrap_or(f64::NAN)),
+            serde_json::Value::String(s) => Value::Str(s),
+            serde_json::Value::Array(array) => {
+                Value::List(array.into_iter().map(Value::from).collect())
+            }
+            serde_json::Value::Object(object) => Value::Map(Box::new(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (MapKey::Str(key), Value::from(value)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Numbers which are integers become JSON integers, the keys of maps are converted to strings in
+/// the same way as they are printed, and struct instances become objects of their fields. Procedures, bytes, and numbers which are not finite have no
+/// JSON representation, so converting them results in a [NotJson] error.
+impl TryFrom<Value> for serde_json::Value {
+    type Error = RuntimeError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Nil => Ok(serde_json::Value::Null),
+            Value::Bool(b) => Ok(serde_json::Value::Bool(b)),
+            Value::Num(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                Ok(serde_json::Value::Number(Number::from(n as i64)))
+            }
+            Value::Num(n) => match Number::from_f64(n) {
+                Some(number) => Ok(serde_json::Value::Number(number)),
+                None => Err(NotJson(Value::Num(n))),
+            },
+            Value::Str(s) => Ok(serde_json::Value::String(s)),
+            Value::List(list) => Ok(serde_json::Value::Array(
+                list.into_iter()
+                    .map(serde_json::Value::try_from)
+                    .collect::<Result<_, _>>()?,
+            )),
+            Value::Map(map) => {
+                // the entries are inserted in the order maps are printed in, so if two keys are
+                // converted to the same string, such as `1` and `"1"`, the one printed last is kept
+                let mut entries: Vec<(MapKey, Value)> = map.into_iter().collect();
+                entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+                let mut object = Map::new();
+                for (key, value) in entries {
+                    object.insert(
+                        key.to_value().to_string(),
+                        serde_json::Value::try_from(value)?,
+                    );
+                }
+                Ok(serde_json::Value::Object(object))
+            }
+            Value::Struct(instance) => {
+                let mut object = Map::new();
+                for (field, value) in instance.fields {
+                    object.insert(field.to_string(), serde_json::Value::try_from(value)?);
+                }
+                Ok(serde_json::Value::Object(object))
+            }
+            value @ (Value::Proc(..) | Value::Bytes(_)) => Err(NotJson(value)),
+        }
+    }
+}
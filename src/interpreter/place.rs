@@ -0,0 +1,146 @@
+//! Places, which are the expressions that can be assigned to. Every construct which changes the
+//! value of an existing place, such as an assignment or the `++` operator, first resolves its
+//! target to a [Place] and then reads and assigns through it, so that they all agree on which
+//! expressions are places and on how they are changed.
+
+use crate::{
+    desugar::Expr,
+    environment::Environment,
+    error::RuntimeError::{self, *},
+    symbol::Symbol,
+    tokenizer::Span,
+    Writer,
+};
+
+use super::{
+    expressions::{apply_field, apply_index, interp_expression},
+    utils::ensure_index,
+    MapKey, Value,
+};
+
+/// A location which holds a value that can be read and reassigned. Declarations, including
+/// destructuring `let` statements, bind new variables rather than assigning to places.
+#[derive(Clone, Debug)]
+pub enum Place {
+    Var(Symbol),
+    /// An element of a list, a character of a string, or the value at a key of a map, which is
+    /// held by another place, with its already evaluated index and the span of the index.
+    Index(Box<Place>, Value, Span),
+    /// A field of a struct instance which is held by another place.
+    Field(Box<Place>, Symbol),
+}
+
+impl Place {
+    /// Returns the place named by `expr`, or an [InvalidAssignmentTarget] error if `expr` is not a
+    /// place. The indexes of an indexing or field expression are evaluated from left to right, so
+    /// that the place can be read and assigned without evaluating them again.
+    pub fn resolve(
+        env: &mut Environment,
+        expr: &Expr,
+        writer: &mut Writer,
+    ) -> Result<Self, RuntimeError> {
+        return match expr {
+            Expr::Var(id) => Ok(Place::Var(*id)),
+            Expr::Index(indexable, index, span) => {
+                let place = Place::resolve(env, indexable, writer)?;
+                Ok(Place::Index(
+                    Box::new(place),
+                    interp_expression(env, index, writer)?,
+                    *span,
+                ))
+            }
+            Expr::Field(object, field) => Ok(Place::Field(
+                Box::new(Place::resolve(env, object, writer)?),
+                *field,
+            )),
+            _ => Err(InvalidAssignmentTarget),
+        };
+    }
+
+    /// Returns the value held by this place in `env`.
+    pub fn read(&self, env: &Environment) -> Result<Value, RuntimeError> {
+        return match self {
+            Place::Var(id) => env.get(*id),
+            Place::Index(place, index, span) => {
+                apply_index(place.read(env)?, index.clone()).map_err(|e| e.at(*span))
+            }
+            Place::Field(place, field) => apply_field(place.read(env)?, *field),
+        };
+    }
+
+    /// Replaces the value held by this place in `env` with `value`. The value being indexed by an
+    /// [Index](Place::Index) place is changed in place rather than copied.
+    pub fn assign(&self, env: &mut Environment, value: Value) -> Result<(), RuntimeError> {
+        return match self {
+            Place::Var(id) => env.reassign(*id, value),
+            Place::Index(place, index, span) => match place.get_mut(env)? {
+                Value::List(list) => {
+                    let index = ensure_index(index.clone(), list.len()).map_err(|e| e.at(*span))?;
+                    list[index] = value;
+                    Ok(())
+                }
+                Value::Str(str) => {
+                    let character = match value {
+                        Value::Str(character) if character.chars().count() == 1 => character,
+                        value => return Err(BadArg(value)),
+                    };
+                    let index = ensure_index(index.clone(), str.chars().count())
+                        .map_err(|e| e.at(*span))?;
+                    *str = (str.chars().enumerate())
+                        .map(|(i, c)| {
+                            if i == index {
+                                character.clone()
+                            } else {
+                                c.to_string()
+                            }
+                        })
+                        .collect();
+                    Ok(())
+                }
+                Value::Map(map) => {
+                    map.insert(MapKey::from_value(index.clone())?, value);
+                    Ok(())
+                }
+                value => Err(NotIndexable(value.clone())),
+            },
+            Place::Field(..) => {
+                *self.get_mut(env)? = value;
+                Ok(())
+            }
+        };
+    }
+
+    /// Returns the value held by this place in `env` so that it can be changed in place. A
+    /// character of a string cannot be changed in place, since it is not stored as a value.
+    fn get_mut<'e>(&self, env: &'e mut Environment) -> Result<&'e mut Value, RuntimeError> {
+        return match self {
+            Place::Var(id) => env.get_mut(*id),
+            Place::Index(place, index, span) => match place.get_mut(env)? {
+                Value::List(list) => {
+                    let index = ensure_index(index.clone(), list.len()).map_err(|e| e.at(*span))?;
+                    Ok(&mut list[index])
+                }
+                // a missing key reads as nil, which cannot be indexed
+                Value::Map(map) => match map.get_mut(&MapKey::from_value(index.clone())?) {
+                    Some(value) => Ok(value),
+                    None => Err(NotIndexable(Value::Nil)),
+                },
+                Value::Str(_) => Err(InvalidAssignmentTarget),
+                value => Err(NotIndexable(value.clone())),
+            },
+            // a struct instance only has the fields of its struct, so a field cannot be added
+            Place::Field(place, field) => match place.get_mut(env)? {
+                Value::Struct(instance) => {
+                    match instance.fields.iter().position(|(name, _)| name == field) {
+                        Some(position) => Ok(&mut instance.fields[position].1),
+                        None => Err(NoSuchField(
+                            Value::Struct(instance.clone()),
+                            field.to_string(),
+                        )),
+                    }
+                }
+                value => Err(NoSuchField(value.clone(), field.to_string())),
+            },
+        };
+    }
+}
@@ -1,93 +1,185 @@
 use crate::{
-    desugar::Statement,
+    desugar::{Contract, Expr, Statement},
     environment::Environment,
-    error::RuntimeError::{self, *}, Writer,
+    error::RuntimeError::{self, *},
+    symbol::Symbol, Writer,
 };
 
-use super::{expressions::interp_expression, Value};
+use super::{
+    expressions::{apply_binary, interp_expression, prepare_call, PendingCall},
+    place::Place,
+    Value,
+};
 
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+#[derive(Clone, Debug)]
 pub enum ControlFlow {
     Return,
     Normal,
     Break,
     Continue,
+    /// A `return` statement whose value is a call. The call is applied by the caller of the
+    /// procedure being returned from (see [apply_call](super::expressions::apply_call)).
+    TailCall(Box<PendingCall>),
 }
 
+/// Interprets `statement` in `env`. Along with how control leaves the statement, this function
+/// returns the value the statement produced, which is `None` for statements that do not produce a
+/// value. This is kept distinct from [Value::Nil] so that "no value" is never confused with the
+/// `nil` value a program can test for with `is_nil`:
+///
+/// - an expression statement produces the value of its expression
+/// - a block produces the value of its last statement, or no value if it is empty
+/// - an `if` statement produces the value of the branch that was taken, or no value if no branch
+///   was taken
+/// - a `return` statement produces the returned value, which is `nil` for an empty `return`
+/// - a `break` statement produces its value, if it has one, which is then produced by the loop it
+///   ends. A loop which ends without such a `break` statement produces no value
+/// - declarations, assignments, and `continue` produce no value
+///
+/// A procedure call evaluates to the value produced by the procedure's body, or to `nil` if the
+/// body produced no value (see [apply_call](super::expressions::apply_call)).
 pub fn interp_statement(
     env: &mut Environment,
-    statement: Statement,
+    statement: &Statement,
     in_loop: bool,
     writer: &mut Writer
-) -> Result<(Value, ControlFlow), RuntimeError> {
+) -> Result<(Option<Value>, ControlFlow), RuntimeError> {
     match statement {
         Statement::Expr(expr) => match interp_expression(env, expr, writer)? {
-            value => Ok((value, ControlFlow::Normal)),
+            value => Ok((Some(value), ControlFlow::Normal)),
         },
         Statement::Let(id, new_expr) => {
-            let new_value = interp_expression(env, new_expr, writer)?;
-            env.insert_new_mutable_value(id, new_value);
-            Ok((Value::Nil, ControlFlow::Normal))
+            let new_value = name_lambda(*id, new_expr, interp_expression(env, new_expr, writer)?);
+            env.insert_new_mutable_value(*id, new_value);
+            Ok((None, ControlFlow::Normal))
         }
         Statement::Const(id, new_expr) => {
-            let new_value = interp_expression(env, new_expr, writer)?;
-            env.insert_new_constant_value(id, new_value);
-            Ok((Value::Nil, ControlFlow::Normal))
+            let new_value = name_lambda(*id, new_expr, interp_expression(env, new_expr, writer)?);
+            env.insert_new_constant_value(*id, new_value);
+            Ok((None, ControlFlow::Normal))
         }
         Statement::Assign(id, expr) => {
             let value = interp_expression(env, expr, writer)?;
-            env.reassign(id, value)?;
-            Ok((Value::Nil, ControlFlow::Normal))
+            Place::Var(*id).assign(env, value)?;
+            Ok((None, ControlFlow::Normal))
+        }
+        Statement::PlaceAssign(target, op, expr) => {
+            let place = Place::resolve(env, target, writer)?;
+            let value = match op {
+                Some(op) => {
+                    let old_value = place.read(env)?;
+                    apply_binary(*op, old_value, interp_expression(env, expr, writer)?)?
+                }
+                None => interp_expression(env, expr, writer)?,
+            };
+            place.assign(env, value)?;
+            Ok((None, ControlFlow::Normal))
         }
         Statement::If(cond_expr, then_statement, else_statement_option) => {
+            let line = writer.line;
             let cond_bool = match interp_expression(env, cond_expr, writer)? {
                 Value::Bool(b) => b,
                 v => return Err(BadArg(v)),
             };
+            trace_condition(writer, line, cond_bool);
             if cond_bool {
-                interp_statement(env, *then_statement, in_loop, writer)
+                interp_statement(env, then_statement, in_loop, writer)
             } else {
                 match else_statement_option {
-                    Some(else_statement) => interp_statement(env, *else_statement, in_loop, writer),
-                    None => Ok((Value::Nil, ControlFlow::Normal)),
+                    Some(else_statement) => interp_statement(env, else_statement, in_loop, writer),
+                    None => Ok((None, ControlFlow::Normal)),
                 }
             }
         }
-        Statement::While(cond_expr, while_block) => Ok(loop {
-            let cond_bool = match interp_expression(env, cond_expr.clone(), writer)? {
-                Value::Bool(b) => b,
-                v => return Err(BadArg(v)),
-            };
-            if cond_bool {
-                match interp_statement(env, *while_block.clone(), true, writer)? {
-                    (value, ControlFlow::Return) => break (value, ControlFlow::Return),
-                    (_, ControlFlow::Break) => break (Value::Nil, ControlFlow::Normal),
-                    (_, ControlFlow::Normal) => (),
-                    (_, ControlFlow::Continue) => (),
+        Statement::While(cond_expr, while_block) => {
+            let line = writer.line;
+            Ok(loop {
+                let cond_bool = match interp_expression(env, cond_expr, writer)? {
+                    Value::Bool(b) => b,
+                    v => return Err(BadArg(v)),
                 };
-            } else {
-                break (Value::Nil, ControlFlow::Normal);
-            }
-        }),
+                trace_condition(writer, line, cond_bool);
+                if cond_bool {
+                    match interp_statement(env, while_block, true, writer)? {
+                        (value, ControlFlow::Return) => break (value, ControlFlow::Return),
+                        (value, ControlFlow::TailCall(call)) => {
+                            break (value, ControlFlow::TailCall(call))
+                        }
+                        (value, ControlFlow::Break) => break (value, ControlFlow::Normal),
+                        (_, ControlFlow::Normal) => (),
+                        (_, ControlFlow::Continue) => (),
+                    };
+                } else {
+                    break (None, ControlFlow::Normal);
+                }
+            })
+        }
+        Statement::Return(Some(Expr::Call(f_expr, args, span))) => {
+            let call = prepare_call(env, f_expr, args, &[], *span, writer)?;
+            Ok((None, ControlFlow::TailCall(Box::new(call))))
+        }
+        Statement::Return(Some(Expr::KeywordCall(f_expr, args, keyword_args, span))) => {
+            let call = prepare_call(env, f_expr, args, keyword_args, *span, writer)?;
+            Ok((None, ControlFlow::TailCall(Box::new(call))))
+        }
         Statement::Return(expr_option) => match expr_option {
-            Some(expr) => Ok((interp_expression(env, expr, writer)?, ControlFlow::Return)),
-            None => Ok((Value::Nil, ControlFlow::Return)),
+            Some(expr) => Ok((Some(interp_expression(env, expr, writer)?), ControlFlow::Return)),
+            None => Ok((Some(Value::Nil), ControlFlow::Return)),
         },
-        Statement::Break => Ok((Value::Nil, ControlFlow::Break)),
-        Statement::Continue => Ok((Value::Nil, ControlFlow::Continue)),
+        Statement::Break(expr_option) => match expr_option {
+            Some(expr) => Ok((Some(interp_expression(env, expr, writer)?), ControlFlow::Break)),
+            None => Ok((None, ControlFlow::Break)),
+        },
+        Statement::Continue => Ok((None, ControlFlow::Continue)),
+        Statement::Contract(contract) => {
+            let Contract {
+                kind,
+                proc_name,
+                condition,
+                source,
+            } = contract.as_ref();
+            match interp_expression(env, condition, writer)? {
+                Value::Bool(true) => Ok((None, ControlFlow::Normal)),
+                Value::Bool(false) => Err(ContractViolation(
+                    *kind,
+                    proc_name.to_string(),
+                    source.to_string(),
+                )),
+                v => Err(ExpectedBool(v)),
+            }
+        }
+        Statement::Line(line, source) => {
+            writer.trace(*line, source);
+            Ok((None, ControlFlow::Normal))
+        }
         Statement::Block(statements) => {
-            let mut block_value = Value::Nil;
+            let mut block_value = None;
             let mut block_env = env.clone();
-            for statement in statements {
-                let statement_value = match interp_statement(&mut block_env, statement, in_loop, writer)? {
+            // the variables declared by the block, which shadow any variable of the same name
+            // in `env` until the end of the block
+            let mut declared = vec![];
+            for statement in statements.iter() {
+                if let Statement::Let(id, _) | Statement::Const(id, _) = statement {
+                    if !declared.contains(id) {
+                        // an assignment to the shadowed variable before it was shadowed still
+                        // changes it
+                        env.update_reassigned_entry(&block_env, *id)?;
+                        declared.push(*id);
+                    }
+                }
+                let statement_result = interp_statement(&mut block_env, statement, in_loop, writer);
+                if statement_result.is_err() {
+                    writer.record_error_env(&block_env);
+                }
+                let statement_value = match statement_result? {
                     (value, ControlFlow::Normal) => value,
-                    (value, ControlFlow::Return) => {
-                        env.update_reassigned_entries(&block_env)?;
-                        return Ok((value, ControlFlow::Return));
+                    (value, control_flow @ (ControlFlow::Return | ControlFlow::TailCall(_))) => {
+                        env.update_reassigned_entries(&block_env, &declared)?;
+                        return Ok((value, control_flow));
                     }
                     (value, ControlFlow::Break) => {
                         if in_loop {
-                            env.update_reassigned_entries(&block_env)?;
+                            env.update_reassigned_entries(&block_env, &declared)?;
                             return Ok((value, ControlFlow::Break));
                         } else {
                             return Err(BreakNotInLoop);
@@ -95,7 +187,7 @@ pub fn interp_statement(
                     }
                     (value, ControlFlow::Continue) => {
                         if in_loop {
-                            env.update_reassigned_entries(&block_env)?;
+                            env.update_reassigned_entries(&block_env, &declared)?;
                             return Ok((value, ControlFlow::Continue));
                         } else {
                             return Err(ContinueNotInLoop);
@@ -104,8 +196,27 @@ pub fn interp_statement(
                 };
                 block_value = statement_value;
             }
-            env.update_reassigned_entries(&block_env)?;
+            env.update_reassigned_entries(&block_env, &declared)?;
             return Ok((block_value, ControlFlow::Normal));
         }
     }
 }
+
+/// Writes the value of the condition of the `if` or `while` statement on `line` to the trace.
+fn trace_condition(writer: &mut Writer, line: usize, value: bool) {
+    if writer.tracing() {
+        writer.trace(line, &format!("condition is {value}"));
+    }
+}
+
+/// Names the procedure `value` after the variable `name` it is bound to if it was created by the
+/// lambda `expr` (see [Closure](super::Closure)).
+fn name_lambda(name: Symbol, expr: &Expr, value: Value) -> Value {
+    match (expr, value) {
+        (Expr::Lambda(..), Value::Proc(mut closure)) if closure.name.is_none() => {
+            closure.name = Some(name);
+            Value::Proc(closure)
+        }
+        (_, value) => value,
+    }
+}
@@ -1,18 +1,24 @@
+use std::{cmp::Ordering, collections::HashMap};
+
 use crate::{
     desugar::Expr,
     error::RuntimeError::{self, *},
+    parser::Builtin,
+    tokenizer::{Operator, Span},
 };
 
-use super::Value;
-
-pub fn ensure_single_arg(args: Vec<Expr>) -> Result<Expr, RuntimeError> {
-    if args.len() > 1 {
-        return Err(ArgMismatch("is_empty".to_string(), args.len(), 1));
-    }
+use super::{MapKey, Value, MAX_REPETITION_LENGTH};
 
-    match args.first() {
-        Some(arg) => Ok(arg.clone()),
-        None => return Err(ArgMismatch("is_empty".to_string(), 0, 1)),
+/// Ensures that exactly `N` arguments were passed to the builtin procedure `builtin` and returns
+/// them as an array so they can be destructured by the caller.
+pub fn ensure_args<const N: usize>(
+    builtin: Builtin,
+    args: &[Expr],
+) -> Result<&[Expr; N], RuntimeError> {
+    let actual = args.len();
+    match args.try_into() {
+        Ok(args) => Ok(args),
+        Err(_) => Err(BuiltinArgMismatch(builtin, actual, Span::default())),
     }
 }
 
@@ -22,3 +28,173 @@ pub fn ensure_list(value: Value) -> Result<Vec<Value>, RuntimeError> {
         bad_value => Err(ExpectedList(bad_value)),
     }
 }
+
+pub fn ensure_str(value: Value) -> Result<String, RuntimeError> {
+    match value {
+        Value::Str(str) => Ok(str),
+        bad_value => Err(ExpectedStr(bad_value)),
+    }
+}
+
+pub fn ensure_bytes(value: Value) -> Result<Vec<u8>, RuntimeError> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        bad_value => Err(ExpectedBytes(bad_value)),
+    }
+}
+
+/// Ensures that `value` is bytes or a string, which is converted to its UTF-8 bytes.
+pub fn ensure_bytes_or_str(value: Value) -> Result<Vec<u8>, RuntimeError> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        Value::Str(str) => Ok(str.into_bytes()),
+        bad_value => Err(ExpectedBytes(bad_value)),
+    }
+}
+
+pub fn ensure_map(value: Value) -> Result<HashMap<MapKey, Value>, RuntimeError> {
+    match value {
+        Value::Map(map) => Ok(*map),
+        bad_value => Err(ExpectedMap(bad_value)),
+    }
+}
+
+/// The largest magnitude of an integer that every integer up to is held exactly by a number. Some
+/// larger integers, such as `2^53 + 1`, are rounded to a neighbor.
+pub const MAX_EXACT_INTEGER: i64 = 1 << 53;
+
+/// Ensures that `value` is an integer with a magnitude of at most [MAX_EXACT_INTEGER]. A larger
+/// integer is a [BadArg] error rather than being saturated, since it may not be exact.
+pub fn ensure_integer(value: Value) -> Result<i64, RuntimeError> {
+    match value {
+        Value::Num(num) if num.fract() == 0.0 && num.abs() <= MAX_EXACT_INTEGER as f64 => {
+            Ok(num as i64)
+        }
+        Value::Num(num) if num.fract() == 0.0 => Err(BadArg(value)),
+        bad_value => Err(ExpectedInteger(bad_value)),
+    }
+}
+
+/// Converts `integer` to a number, or returns `None` if its magnitude is larger than
+/// [MAX_EXACT_INTEGER], since the number may not be exact.
+pub fn exact_integer(integer: i64) -> Option<Value> {
+    if integer.unsigned_abs() > MAX_EXACT_INTEGER as u64 {
+        return None;
+    }
+    Some(Value::Num(integer as f64))
+}
+
+/// Ensures that `index` is the index of one of the `len` elements of a list, characters of a
+/// string, or bytes, and returns it. The span of an [IndexOutOfBounds] error is not known here, and
+/// is recorded by the caller (see [RuntimeError::at]).
+pub fn ensure_index(index: Value, len: usize) -> Result<usize, RuntimeError> {
+    match index {
+        Value::Num(num) if num.fract() == 0.0 && num >= 0.0 && num < len as f64 => Ok(num as usize),
+        Value::Num(num) if num.fract() == 0.0 => Err(IndexOutOfBounds(index, len, Span::default())),
+        bad_value => Err(ExpectedInteger(bad_value)),
+    }
+}
+
+/// Ensures that `value` is an integer from 2 to 36, which is a base that numbers can be written in
+/// with the digits `0`-`9` and the letters `a`-`z`.
+pub fn ensure_radix(value: Value) -> Result<u32, RuntimeError> {
+    match ensure_integer(value)? {
+        radix @ 2..=36 => Ok(radix as u32),
+        radix => Err(BadArg(Value::Num(radix as f64))),
+    }
+}
+
+/// Ensures that `count` is a non-negative integer and that repeating a value of length `length`
+/// `count` times stays within [MAX_REPETITION_LENGTH].
+pub fn ensure_repetition_count(count: Value, length: usize) -> Result<usize, RuntimeError> {
+    let count = ensure_count(count)?;
+
+    match length.checked_mul(count) {
+        Some(total) if total <= MAX_REPETITION_LENGTH => Ok(count),
+        _ => Err(RepetitionTooLarge(MAX_REPETITION_LENGTH)),
+    }
+}
+
+/// Compares two values for sorting. Only numbers can be compared with numbers and only strings can
+/// be compared with strings. Any other pair of values results in a [CannotCompare] error.
+pub fn compare_values(left: &Value, right: &Value) -> Result<Ordering, RuntimeError> {
+    match (left, right) {
+        (Value::Num(num_left), Value::Num(num_right)) => match num_left.partial_cmp(num_right) {
+            Some(ordering) => Ok(ordering),
+            None => Err(RuntimeError::cannot_compare(Operator::LT, left.clone(), right.clone())),
+        },
+        (Value::Str(str_left), Value::Str(str_right)) => Ok(str_left.cmp(str_right)),
+        _ => Err(RuntimeError::cannot_compare(Operator::LT, left.clone(), right.clone())),
+    }
+}
+
+/// Sorts `list` with a stable merge sort. Unlike [slice::sort_by], this never panics if `compare`
+/// is not a total order, e.g. a comparator written in Linger which returns random results, in
+/// which case the elements are returned in some unspecified order. The first error returned by
+/// `compare` stops the sort and is returned.
+pub fn merge_sort<T, E>(
+    list: Vec<T>,
+    compare: &mut impl FnMut(&T, &T) -> Result<Ordering, E>,
+) -> Result<Vec<T>, E> {
+    if list.len() <= 1 {
+        return Ok(list);
+    }
+    let mut left = list;
+    let right = left.split_off(left.len() / 2);
+    let mut left = merge_sort(left, compare)?.into_iter().peekable();
+    let mut right = merge_sort(right, compare)?.into_iter().peekable();
+
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    while let (Some(left_value), Some(right_value)) = (left.peek(), right.peek()) {
+        // an element of the right half only goes first if it is strictly less, which keeps the
+        // sort stable
+        let next = match compare(right_value, left_value)? {
+            Ordering::Less => right.next(),
+            _ => left.next(),
+        };
+        merged.extend(next);
+    }
+    merged.extend(left);
+    merged.extend(right);
+    return Ok(merged);
+}
+
+/// Ensures that `value` is a non-negative integer which can be used as a count of list elements.
+pub fn ensure_count(value: Value) -> Result<usize, RuntimeError> {
+    match ensure_integer(value.clone())? {
+        n if n < 0 => Err(BadArg(value)),
+        n => Ok(n as usize),
+    }
+}
+
+/// Divides `dividend` by `divisor`, rounding the quotient towards negative infinity. The remainder
+/// has the same sign as the divisor, so `dividend == divisor * quotient + remainder` holds.
+pub fn floor_div(dividend: f64, divisor: f64) -> Result<(f64, f64), RuntimeError> {
+    if divisor == 0.0 {
+        return Err(DivisionByZero);
+    }
+    let quotient = (dividend / divisor).floor();
+    return Ok((quotient, dividend - divisor * quotient));
+}
+
+/// Encodes `bytes` as a string of lowercase hexadecimal digits, two per byte.
+pub fn hex_encode(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+}
+
+/// Decodes a string of hexadecimal digits, two per byte, in either case. Returns `None` if `hex` has
+/// an odd length or contains a character which is not a hexadecimal digit.
+pub fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    return hex
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let high = (pair[0] as char).to_digit(16)?;
+            let low = (pair[1] as char).to_digit(16)?;
+            Some((high * 16 + low) as u8)
+        })
+        .collect();
+}
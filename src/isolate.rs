@@ -0,0 +1,80 @@
+//! Running several programs in one interpreter with separate globals, in the same way as the
+//! contexts of a JavaScript engine. An [Interpreter] holds the bindings which the host provides to
+//! every program, such as helper procedures, and each [Isolate] created from it has its own global
+//! environment on top of those bindings. Values are never shared between environments, so nothing a
+//! program does in one isolate can be observed from another.
+//!
+//! ```
+//! use linger::{environment::Environment, interpreter::Value, isolate::Interpreter, parse, Writer};
+//!
+//! let interpreter = Interpreter::new(
+//!     Environment::builder()
+//!         .define("greeting", Value::Str("hello".to_string()))
+//!         .build(),
+//! );
+//! let mut first = interpreter.isolate();
+//! let mut second = interpreter.isolate();
+//!
+//! let program = parse("const name = \"first\"; proc main() { return greeting; }").unwrap();
+//! let mut writer = Writer::new(Box::new(std::io::sink()));
+//! first.run(program, &mut writer).unwrap();
+//!
+//! assert_eq!(first.get("name"), Some(Value::Str("first".to_string())));
+//! assert_eq!(second.get("name"), None);
+//! ```
+
+use crate::{
+    environment::Environment,
+    error::RuntimeError,
+    interpreter::{interp_program_in_env, Value},
+    parser::Program,
+    symbol::Symbol,
+    Writer,
+};
+
+/// The bindings shared by every [Isolate] created by [Interpreter::isolate].
+#[derive(Clone, Debug)]
+pub struct Interpreter {
+    natives: Environment,
+}
+
+impl Interpreter {
+    /// Creates an interpreter whose isolates all start with the bindings of `natives` (see
+    /// [Environment::builder]).
+    pub fn new(natives: Environment) -> Self {
+        Self { natives }
+    }
+
+    /// Creates an isolate with its own global environment. The top-level procedures of the
+    /// interpreter are shared with the isolate rather than copied into it.
+    pub fn isolate(&self) -> Isolate {
+        Isolate {
+            env: self.natives.clone(),
+        }
+    }
+}
+
+/// A global environment in which programs are run (see the [module documentation](self)).
+#[derive(Clone, Debug)]
+pub struct Isolate {
+    env: Environment,
+}
+
+impl Isolate {
+    /// Runs `program` in this isolate and returns the return value of its main procedure. The
+    /// top-level procedures and constants of `program` stay bound in this isolate, so a program run
+    /// later can use them, while other isolates never see them.
+    pub fn run(&mut self, program: Program, writer: &mut Writer) -> Result<Value, RuntimeError> {
+        return interp_program_in_env(program, &mut self.env, writer);
+    }
+
+    /// Returns the value bound to the global `name` in this isolate, if there is one.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        return self.env.get(Symbol::from(name)).ok();
+    }
+
+    /// The global environment of this isolate.
+    pub fn env(&self) -> &Environment {
+        return &self.env;
+    }
+}
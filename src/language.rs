@@ -0,0 +1,68 @@
+//! The version of the Linger grammar that a program is written in, which is selected with a
+//! `#language <version>` pragma on the first line of the program, or on the line after its shebang
+//! line, e.g.
+//!
+//! ```text
+//! #!/usr/bin/env linger
+//! #language 2
+//! ```
+//!
+//! A program without the pragma is written in version 1. A new version of the grammar can change
+//! the syntax in ways which would break programs written for an earlier version, since those
+//! programs keep being parsed by the rules of the version they select.
+
+use std::fmt;
+
+use crate::error::ParseError::{self, *};
+
+/// The start of the line which selects the version of the grammar.
+pub const PRAGMA: &str = "#language";
+
+/// A version of the Linger grammar (see [language](self)).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LanguageVersion {
+    /// The grammar of programs without a pragma.
+    #[default]
+    V1,
+    /// Like [V1](LanguageVersion::V1), except that the semicolon after a statement can be left out
+    /// when the statement is the last one on its line or in its block.
+    V2,
+}
+
+impl LanguageVersion {
+    /// Returns the version selected by the pragma of the program `source`, or
+    /// [V1](LanguageVersion::V1) if it has no pragma. An [UnknownLanguageVersion] error is returned
+    /// if the pragma selects a version which does not exist.
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let Some(line) = pragma_line(source) else {
+            return Ok(LanguageVersion::V1);
+        };
+        let version = source.split('\n').nth(line).unwrap_or_default()[PRAGMA.len()..].trim();
+        return match version {
+            "1" => Ok(LanguageVersion::V1),
+            "2" => Ok(LanguageVersion::V2),
+            version => Err(UnknownLanguageVersion(version.to_string())),
+        };
+    }
+}
+
+impl fmt::Display for LanguageVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LanguageVersion::V1 => write!(f, "1"),
+            LanguageVersion::V2 => write!(f, "2"),
+        }
+    }
+}
+
+/// Returns the index of the line of the program `source` which holds its pragma, if it has one.
+pub fn pragma_line(source: &str) -> Option<usize> {
+    let mut lines = source
+        .split('\n')
+        .enumerate()
+        .skip_while(|(line_num, line)| *line_num == 0 && line.starts_with("#!"));
+    return match lines.next() {
+        Some((line_num, line)) if line.starts_with(PRAGMA) => Some(line_num),
+        _ => None,
+    };
+}
@@ -1,41 +1,351 @@
-use std::{fs::File, io::Write, path::Path};
+use std::{fmt, fs::File, io::Write, path::Path, sync::Arc};
 
-use interpreter::interp_program;
-use parser::parse_program;
-use tokenizer::tokenize;
+use diagnostic::Diagnostic;
+use environment::Environment;
+use error::RuntimeError;
+use interpreter::{interp_program, NumberFormat, Value};
+use metadata::Metadata;
+use imports::{resolve_imports_with, FileResolver, ImportResolver};
+use language::LanguageVersion;
+use parser::{desugar_program, parse_sugared_program_from, Module, Program};
+use post_mortem::PostMortem;
+use replay::{Nondeterminism, ReplayLog};
+use tokenizer::TokenStream;
 
-mod desugar;
+pub mod analysis;
+#[cfg(feature = "arbitrary")]
+pub mod arbitrary;
+pub mod builtins;
+pub mod cache;
+#[cfg(feature = "csv")]
+pub mod csv;
+pub mod desugar;
+pub mod diagnostic;
+pub mod encoding;
 pub mod environment;
+pub mod equivalence;
 pub mod error;
+pub mod fold;
+#[cfg(feature = "hash")]
+pub mod hash;
+pub mod imports;
 pub mod interpreter;
+pub mod isolate;
+pub mod language;
+pub mod metadata;
+pub mod mutation;
 pub mod parser;
+pub mod post_mortem;
+pub mod pretty;
+pub mod replay;
+pub mod reporting;
+pub mod symbol;
+#[cfg(feature = "serde")]
+pub mod syntax_json;
 pub mod tokenizer;
 
+/// Compile-time check that a parsed [Program], the [Values](Value) it produces, and the
+/// [Environment] they close over can be shared between threads. A host can wrap a [Program] in an
+/// `Arc` and interpret a clone of it per request without any locking.
+#[allow(dead_code)]
+fn assert_thread_safe() {
+    fn is_send_sync<T: Send + Sync>() {}
+    is_send_sync::<Program>();
+    is_send_sync::<Value>();
+    is_send_sync::<Environment>();
+}
 
 pub struct Writer<'a> {
     w: Box<dyn Write + 'a>,
+    number_format: NumberFormat,
+    /// The metadata of the program being run, which is set by
+    /// [interp_program_with_env](interpreter::interp_program_with_env).
+    metadata: Metadata,
+    /// Where the statements executed by the program are traced to, if tracing is on.
+    trace: Option<Box<dyn Write + 'a>>,
+    /// The line of the statement being executed, e.g. for the error raised by `panic`.
+    line: usize,
+    /// The results of the nondeterministic builtins, such as `random`.
+    nondeterminism: Nondeterminism,
+    /// Where the program was when it stopped with an error, if post-mortems are being recorded.
+    post_mortem: Option<PostMortem>,
+    /// The maximum number of bytes the program may print, if there is a limit.
+    max_output: Option<usize>,
+    /// The number of bytes the program has printed so far.
+    output_len: usize,
+    /// The number of procedure calls in progress (see [MAX_CALL_DEPTH](interpreter::MAX_CALL_DEPTH)).
+    call_depth: usize,
+    /// The number of bytes of the host stack which the calls in progress may use (see
+    /// [Writer::with_call_stack_size]).
+    call_stack_size: usize,
+    /// The address of the host stack at the outermost call in progress, from which the stack used
+    /// by the calls is measured.
+    call_stack_base: usize,
 }
 
 impl<'a> Writer<'a> {
-    pub fn new(w: Box<dyn Write + 'a>) -> Self { Self { w } }
+    pub fn new(w: Box<dyn Write + 'a>) -> Self {
+        Self {
+            w,
+            number_format: NumberFormat::default(),
+            metadata: Metadata::default(),
+            trace: None,
+            line: 0,
+            nondeterminism: Nondeterminism::default(),
+            post_mortem: None,
+            max_output: None,
+            output_len: 0,
+            call_depth: 0,
+            call_stack_size: interpreter::DEFAULT_CALL_STACK_SIZE,
+            call_stack_base: 0,
+        }
+    }
+
+    /// Sets the format used for numbers printed by the program.
+    pub fn with_number_format(mut self, number_format: NumberFormat) -> Self {
+        self.number_format = number_format;
+        return self;
+    }
+
+    /// Limits the output of the program to `max_output` bytes. A program which prints more than that
+    /// is stopped with an [OutputLimitExceeded](RuntimeError::OutputLimitExceeded) error, and the
+    /// output which would exceed the limit is not written.
+    pub fn with_max_output(mut self, max_output: usize) -> Self {
+        self.max_output = Some(max_output);
+        return self;
+    }
+
+    /// Limits the host stack used by the procedure calls of the program to `call_stack_size` bytes,
+    /// in place of [DEFAULT_CALL_STACK_SIZE](interpreter::DEFAULT_CALL_STACK_SIZE). A program which
+    /// recurses deeper than that is stopped with a [CallTooDeep](RuntimeError::CallTooDeep) error.
+    /// The limit should be well below the size of the stack of the thread the program runs on,
+    /// e.g. half of it.
+    pub fn with_call_stack_size(mut self, call_stack_size: usize) -> Self {
+        self.call_stack_size = call_stack_size;
+        return self;
+    }
+
+    /// Writes `output` printed by the program, unless it would exceed the limit set by
+    /// [Writer::with_max_output].
+    pub(crate) fn write_output(&mut self, output: &str) -> Result<(), RuntimeError> {
+        let output_len = self.output_len + output.len();
+        if let Some(max_output) = self.max_output {
+            if output_len > max_output {
+                return Err(RuntimeError::OutputLimitExceeded(max_output));
+            }
+        }
+        self.output_len = output_len;
+        // TODO: write a better expect message
+        self.w
+            .write_all(output.as_bytes())
+            .expect("should be able to write");
+        return Ok(());
+    }
+
+    /// Traces the program to `trace`: before each statement is executed, its line number and the
+    /// source on that line are written as `[line 3] let x = 1;`, and the value of the condition of
+    /// each `if` and `while` is written as `[line 4] condition is true` once it is evaluated.
+    pub fn with_trace(mut self, trace: Box<dyn Write + 'a>) -> Self {
+        self.trace = Some(trace);
+        return self;
+    }
+
+    /// Records the results of the nondeterministic builtins called by the program, which can be
+    /// retrieved with [Writer::recording] once it has run.
+    pub fn with_recording(mut self) -> Self {
+        self.nondeterminism = Nondeterminism::recording();
+        return self;
+    }
+
+    /// Makes the nondeterministic builtins called by the program return the results in `log`
+    /// rather than computing them, so that the program behaves like the run that was recorded.
+    pub fn with_replay(mut self, log: ReplayLog) -> Self {
+        self.nondeterminism = Nondeterminism::replaying(log);
+        return self;
+    }
+
+    /// The results recorded so far, or `None` if they are not being recorded (see
+    /// [Writer::with_recording]).
+    pub fn recording(&self) -> Option<ReplayLog> {
+        return self.nondeterminism.recorded();
+    }
+
+    /// Records where the program is when it stops with a runtime error, which can be retrieved with
+    /// [Writer::post_mortem] once it has run (see [post_mortem]).
+    pub fn with_post_mortem(mut self) -> Self {
+        self.post_mortem = Some(PostMortem::default());
+        return self;
+    }
+
+    /// Where the program was when it stopped with a runtime error, or `None` if post-mortems are not
+    /// being recorded (see [Writer::with_post_mortem]). The post-mortem is empty if the program did
+    /// not stop with an error.
+    pub fn post_mortem(&self) -> Option<&PostMortem> {
+        return self.post_mortem.as_ref();
+    }
+
+    /// Records that a runtime error occurred in, or propagated out of, a block or call whose
+    /// environment is `env`. Does nothing if post-mortems are not being recorded.
+    pub(crate) fn record_error_env(&mut self, env: &Environment) {
+        if let Some(post_mortem) = &mut self.post_mortem {
+            post_mortem.record_env(env, self.line);
+        }
+    }
+
+    /// Records that a runtime error propagated out of a call to the procedure `name`. Does nothing
+    /// if post-mortems are not being recorded.
+    pub(crate) fn record_error_call(&mut self, name: &str) {
+        if let Some(post_mortem) = &mut self.post_mortem {
+            post_mortem.record_call(name.to_string());
+        }
+    }
+
+    /// Whether the program is being traced (see [Writer::with_trace]).
+    pub(crate) fn tracing(&self) -> bool {
+        return self.trace.is_some();
+    }
+
+    /// Records that the statement on `line` is being executed and writes `message` for it to the
+    /// trace if the program is being traced.
+    pub(crate) fn trace(&mut self, line: usize, message: &str) {
+        self.line = line;
+        if let Some(trace) = &mut self.trace {
+            // flush the output first so that it is interleaved correctly with the trace when
+            // both go to a terminal
+            let _ = self.w.flush();
+            // the trace is a debugging aid, so failing to write it should not stop the program
+            let _ = writeln!(trace, "[line {line}] {message}");
+        }
+    }
+}
+
+/// Options for executing a linger program with [interp_with_options].
+#[derive(Clone, Default)]
+pub struct InterpOptions {
+    /// The format used for numbers printed by the program.
+    pub number_format: NumberFormat,
+    /// A prelude loaded into the program before it runs (see [imports::load_prelude]).
+    pub prelude: Option<Module>,
+    /// Finds the modules imported by the program. By default, they are loaded from the local disk
+    /// relative to the current directory.
+    pub import_resolver: Option<Arc<dyn ImportResolver + Send + Sync>>,
+    /// Whether local declarations which shadow another variable are reported as warnings (see
+    /// [analysis::shadowing]).
+    pub warn_shadowing: bool,
+    /// The maximum number of bytes the program may print, or `None` for no limit. A server which
+    /// runs untrusted programs can set this so that a program which prints forever is stopped
+    /// (see [Writer::with_max_output]).
+    pub max_output: Option<usize>,
+}
+
+impl fmt::Debug for InterpOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InterpOptions")
+            .field("number_format", &self.number_format)
+            .field("prelude", &self.prelude)
+            .field(
+                "import_resolver",
+                &self.import_resolver.as_ref().map(|_| ".."),
+            )
+            .field("warn_shadowing", &self.warn_shadowing)
+            .field("max_output", &self.max_output)
+            .finish()
+    }
+}
+
+/// Tokenizes and parses the linger program `source` without executing it. Imports are resolved
+/// relative to the current directory. On failure, this function returns the
+/// [Diagnostics](Diagnostic) describing why the program is invalid.
+pub fn parse(source: &str) -> Result<Program, Vec<Diagnostic>> {
+    return parse_with_resolver(source, &FileResolver::new(Path::new(".")));
+}
+
+/// Like [parse], except that the modules imported by `source` are found by `resolver`.
+pub fn parse_with_resolver(
+    source: &str,
+    resolver: &dyn ImportResolver,
+) -> Result<Program, Vec<Diagnostic>> {
+    return parse_and_analyze(source, resolver, false).map(|(program, _)| program);
+}
+
+/// Tokenizes, parses, and analyzes the linger program `source`, finding its imports with
+/// `resolver`. On success, this function returns the parsed program along with any warnings found
+/// by [analysis::analyze], and by [analysis::shadowing] if `warn_shadowing` is true. A program with
+/// a side effect in a pure procedure is invalid (see [analysis::purity]). On failure, this function
+/// returns the [Diagnostics](Diagnostic) describing why the program is invalid.
+pub fn parse_and_analyze(
+    source: &str,
+    resolver: &dyn ImportResolver,
+    warn_shadowing: bool,
+) -> Result<(Program, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let sugared_program = match LanguageVersion::parse(source)
+        .and_then(|language| parse_sugared_program_from(TokenStream::new(source), language))
+    {
+        Ok(sugared_program) => sugared_program,
+        Err(e) => return Err(vec![Diagnostic::from(e)]),
+    };
+    let errors = analysis::purity(&sugared_program);
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let mut warnings = analysis::analyze(&sugared_program);
+    if warn_shadowing {
+        warnings.extend(analysis::shadowing(&sugared_program));
+    }
+    let modules = match resolve_imports_with(&sugared_program, resolver) {
+        Ok(modules) => modules,
+        Err(e) => return Err(vec![Diagnostic::from(e)]),
+    };
+    return match desugar_program(sugared_program) {
+        Ok(program) => {
+            let metadata = Metadata::parse(source);
+            Ok((Program { modules, metadata, ..program }, warnings))
+        }
+        Err(e) => Err(vec![Diagnostic::from(e)]),
+    };
+}
+
+/// Joins the messages of `diagnostics` into a single string, one per line.
+fn diagnostics_to_string(diagnostics: Vec<Diagnostic>) -> String {
+    let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    return messages.join("\n");
+}
+
+/// Checks that the linger program `source` is valid without executing it. Imports are resolved
+/// relative to the current directory. On success, this function returns the warnings found in the
+/// program. On failure, this function returns the [Diagnostics](Diagnostic) describing why the
+/// program is invalid.
+pub fn check(source: &str) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    return check_in_dir(source, Path::new("."));
+}
+
+/// Like [check], except that imports are resolved relative to `base_dir`, which should be the
+/// directory of the file containing `source`.
+pub fn check_in_dir(source: &str, base_dir: &Path) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    return check_in_dir_with(source, base_dir, false);
+}
+
+/// Like [check_in_dir], except that local declarations which shadow another variable are also
+/// reported as warnings if `warn_shadowing` is true (see [analysis::shadowing]).
+pub fn check_in_dir_with(
+    source: &str,
+    base_dir: &Path,
+    warn_shadowing: bool,
+) -> Result<Vec<Diagnostic>, Vec<Diagnostic>> {
+    let resolver = FileResolver::new(base_dir);
+    return parse_and_analyze(source, &resolver, warn_shadowing).map(|(_, warnings)| warnings);
 }
 
 /// Executes a linger program. On success, this program returns the return value of the main
 /// procedure as a String. If there is an error in any step of the program (tokenization, parsing,
 /// or interpreting), this function will return that error as a [String].
 pub fn interp<'a>(s: String) -> Result<String, String> {
-    let tokens = match tokenize(s.as_str()) {
-        Ok(tokens) => tokens,
-        Err(e) => return Err(e.to_string()),
-    };
-    let program = match parse_program(tokens.as_slice()) {
+    let program = match parse(s.as_str()) {
         Ok(program) => program,
-        Err(e) => return Err(e.to_string()),
+        Err(diagnostics) => return Err(diagnostics_to_string(diagnostics)),
     };
 
-    let writer = &mut Writer {
-        w: Box::new(std::io::stdout()),
-    };
+    let writer = &mut Writer::new(Box::new(std::io::stdout()));
 
     return match interp_program(program, writer) {
         Ok(value) => Ok(value.to_string()),
@@ -44,13 +354,9 @@ pub fn interp<'a>(s: String) -> Result<String, String> {
 }
 
 pub fn interp_to_file<'a>(s: String, path: &Path) -> Result<String, String> {
-    let tokens = match tokenize(s.as_str()) {
-        Ok(tokens) => tokens,
-        Err(e) => return Err(e.to_string()),
-    };
-    let program = match parse_program(tokens.as_slice()) {
+    let program = match parse(s.as_str()) {
         Ok(program) => program,
-        Err(e) => return Err(e.to_string()),
+        Err(diagnostics) => return Err(diagnostics_to_string(diagnostics)),
     };
 
     let file = match File::create(path) {
@@ -58,7 +364,7 @@ pub fn interp_to_file<'a>(s: String, path: &Path) -> Result<String, String> {
         Err(e) => return Err(e.to_string()),
     };
 
-    let writer = &mut Writer { w: Box::new(file) };
+    let writer = &mut Writer::new(Box::new(file));
 
     return match interp_program(program, writer) {
         Ok(value) => Ok(value.to_string()),
@@ -67,19 +373,51 @@ pub fn interp_to_file<'a>(s: String, path: &Path) -> Result<String, String> {
 }
 
 pub fn interp_to_buffer<'a>(s: String, buf: &mut Vec<u8>) -> Result<String, String> {
-    let tokens = match tokenize(s.as_str()) {
-        Ok(tokens) => tokens,
-        Err(e) => return Err(e.to_string()),
-    };
-    let program = match parse_program(tokens.as_slice()) {
+    let program = match parse(s.as_str()) {
         Ok(program) => program,
-        Err(e) => return Err(e.to_string()),
+        Err(diagnostics) => return Err(diagnostics_to_string(diagnostics)),
     };
 
-    let writer = &mut Writer { w: Box::new(buf) };
+    let writer = &mut Writer::new(Box::new(buf));
 
     return match interp_program(program, writer) {
         Ok(value) => Ok(value.to_string()),
         Err(e) => Err(e.to_string()),
     };
 }
+
+/// Executes a linger program with `options`, writing its output to `w`. On success, this function
+/// returns the return value of the main procedure as a String along with any warnings found in the
+/// program. If there is an error in any step of the program, this function will return that error
+/// as a [String].
+pub fn interp_with_options<'a>(
+    s: String,
+    options: &InterpOptions,
+    w: Box<dyn Write + 'a>,
+) -> Result<(String, Vec<Diagnostic>), String> {
+    let file_resolver = FileResolver::new(Path::new("."));
+    let resolver: &dyn ImportResolver = match &options.import_resolver {
+        Some(resolver) => resolver.as_ref(),
+        None => &file_resolver,
+    };
+    let analyzed = parse_and_analyze(s.as_str(), resolver, options.warn_shadowing);
+    let (program, warnings) = match analyzed {
+        Ok(result) => result,
+        Err(diagnostics) => return Err(diagnostics_to_string(diagnostics)),
+    };
+
+    let program = match &options.prelude {
+        Some(prelude) => program.with_prelude(prelude.clone()),
+        None => program,
+    };
+
+    let mut writer = Writer::new(w).with_number_format(options.number_format);
+    if let Some(max_output) = options.max_output {
+        writer = writer.with_max_output(max_output);
+    }
+
+    return match interp_program(program, &mut writer) {
+        Ok(value) => Ok((value.to_string_with(options.number_format), warnings)),
+        Err(e) => Err(e.to_string()),
+    };
+}
@@ -1,56 +1,401 @@
-use std::{env, fs, io::stdout, process::ExitCode};
+use std::{
+    env, fs,
+    io::{stderr, stdin, stdout},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+};
 
-use linger::{interpreter::interp_program, parser::parse_program, tokenizer::tokenize, Writer};
+use linger::{
+    cache::{decode_program, encode_program, is_compiled_program},
+    check_in_dir_with,
+    diagnostic::Diagnostic,
+    imports::{load_prelude, FileResolver},
+    interpreter::interp_program,
+    parse_and_analyze,
+    parser::{Module, Program},
+    post_mortem::inspect,
+    pretty::format_source,
+    replay::ReplayLog,
+    reporting::{Reporter, Style},
+    Writer,
+};
 
-fn main() -> ExitCode {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("usage: linger <FILE>");
-        return ExitCode::FAILURE;
+const USAGE: &str = "usage: linger [run] [OPTIONS] <FILE>
+       linger debug [OPTIONS] <FILE>
+       linger build [OPTIONS] <FILE> [-o <OUTPUT>]
+       linger fmt [OPTIONS] <FILE>
+
+options: [--check] [--deny-warnings] [--warn-shadowing] [--trace] [--prelude <FILE>] [--error-format=human|json]
+         [--record <LOG> | --replay <LOG>] [--post-mortem] [--tokens-json] [--ast-json]";
+
+/// Prints `warnings` to stderr. Returns `true` if the warnings should stop the program, which is
+/// the case when warnings are denied and there is at least one warning.
+fn report_warnings(reporter: &Reporter, warnings: &[Diagnostic], deny_warnings: bool) -> bool {
+    for warning in warnings {
+        eprintln!("{}", reporter.render(warning));
     }
+    return deny_warnings && !warnings.is_empty();
+}
 
-    let linger_file_name = args[1].as_str();
+fn report(reporter: &Reporter, diagnostic: Diagnostic) {
+    eprintln!("{}", reporter.render(&diagnostic));
+}
+
+/// Prints the tokens of the program `source` as JSON if `tokens` is true, and then its sugared
+/// syntax tree as JSON if `ast` is true, each on its own line. Any diagnostics are reported with
+/// `reporter`.
+#[cfg(feature = "serde")]
+fn print_json(source: &str, reporter: &Reporter, tokens: bool, ast: bool) -> ExitCode {
+    use linger::{
+        language::LanguageVersion,
+        parser::parse_sugared_program_with,
+        syntax_json::{program_to_json, tokens_to_json},
+        tokenizer::tokenize,
+    };
 
-    let linger_file_content = match fs::read_to_string(linger_file_name) {
-        Ok(content) => content,
+    let token_list = match tokenize(source) {
+        Ok(token_list) => token_list,
         Err(e) => {
-            eprintln!("error opening {linger_file_name}: {e}");
+            report(reporter, Diagnostic::from(e));
             return ExitCode::FAILURE;
         }
     };
+    if tokens {
+        println!("{}", tokens_to_json(token_list.as_slice()));
+    }
+    if ast {
+        let sugared_program = match LanguageVersion::parse(source)
+            .and_then(|language| parse_sugared_program_with(token_list.as_slice(), language))
+        {
+            Ok(p) => p,
+            Err(e) => {
+                report(reporter, Diagnostic::from(e));
+                return ExitCode::FAILURE;
+            }
+        };
+        println!("{}", program_to_json(&sugared_program));
+    }
+    return ExitCode::SUCCESS;
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_source: &str, _reporter: &Reporter, _tokens: bool, _ast: bool) -> ExitCode {
+    eprintln!("--tokens-json and --ast-json require the \"serde\" feature");
+    return ExitCode::FAILURE;
+}
 
-    let debug_tokens = false;
+/// Parses and analyzes the program `source` (see [parse_and_analyze]), resolving its imports
+/// relative to `base_dir`, and adds `prelude` to it. Any diagnostics are reported with `reporter`.
+/// Returns `None` if the program is invalid or if it has warnings and warnings are denied.
+fn compile(
+    source: &str,
+    base_dir: &Path,
+    prelude: Option<Module>,
+    reporter: &Reporter,
+    deny_warnings: bool,
+    warn_shadowing: bool,
+) -> Option<Program> {
     let debug_program = false;
-    let debug_value = false;
 
-    let tokens = match tokenize(linger_file_content.as_str()) {
-        Ok(t) => t,
-        Err(e) => {
-            eprintln!("{e}");
-            return ExitCode::FAILURE;
+    let resolver = FileResolver::new(base_dir);
+    let (program, warnings) = match parse_and_analyze(source, &resolver, warn_shadowing) {
+        Ok(result) => result,
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                report(reporter, diagnostic);
+            }
+            return None;
         }
     };
-    if debug_tokens {
-        dbg!(&tokens);
+    if report_warnings(reporter, &warnings, deny_warnings) {
+        return None;
+    }
+    if debug_program {
+        dbg!(&program);
+        return None;
+    }
+
+    return Some(match prelude {
+        Some(prelude) => program.with_prelude(prelude),
+        None => program,
+    });
+}
+
+/// The size of the stack of the thread which runs the program, half of which the program's
+/// procedure calls may use. This is enough for [MAX_CALL_DEPTH](linger::interpreter::MAX_CALL_DEPTH)
+/// nested procedure calls even in a debug build.
+const STACK_SIZE: usize = 256 << 20;
+
+fn main() -> ExitCode {
+    let runner = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(run)
+        .expect("should be able to spawn the thread which runs the program");
+    // a panic has already been reported by the thread
+    return runner.join().unwrap_or(ExitCode::FAILURE);
+}
+
+fn run() -> ExitCode {
+    let mut linger_file_name_option = None;
+    let mut output_file_name_option = None;
+    let mut check_only = false;
+    let mut deny_warnings = false;
+    let mut warn_shadowing = false;
+    let mut trace = false;
+    let mut post_mortem = false;
+    let mut format = false;
+    let mut tokens_json = false;
+    let mut ast_json = false;
+    let mut prelude_file_name_option = None;
+    let mut record_file_name_option = None;
+    let mut replay_file_name_option = None;
+    let mut style = Style::for_stderr();
+    let mut args = env::args().skip(1).peekable();
+    let build = match args.peek().map(String::as_str) {
+        Some("build") => {
+            args.next();
+            true
+        }
+        Some("run") => {
+            args.next();
+            false
+        }
+        // formatting prints the formatted source instead of running the program
+        Some("fmt") => {
+            args.next();
+            format = true;
+            false
+        }
+        // running under `debug` inspects the program if it stops with an error
+        Some("debug") => {
+            args.next();
+            post_mortem = true;
+            false
+        }
+        _ => false,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check_only = true,
+            "--deny-warnings" => deny_warnings = true,
+            "--warn-shadowing" => warn_shadowing = true,
+            "--trace" => trace = true,
+            "--post-mortem" => post_mortem = true,
+            "--tokens-json" => tokens_json = true,
+            "--ast-json" => ast_json = true,
+            "--prelude" => match args.next() {
+                Some(file_name) => prelude_file_name_option = Some(file_name),
+                None => {
+                    eprintln!("missing file for --prelude\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--record" => match args.next() {
+                Some(file_name) => record_file_name_option = Some(file_name),
+                None => {
+                    eprintln!("missing file for --record\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--replay" => match args.next() {
+                Some(file_name) => replay_file_name_option = Some(file_name),
+                None => {
+                    eprintln!("missing file for --replay\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "-o" if build => match args.next() {
+                Some(file_name) => output_file_name_option = Some(file_name),
+                None => {
+                    eprintln!("missing file for -o\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--error-format=human" => style = Style::for_stderr(),
+            "--error-format=json" => style = Style::Json,
+            flag if flag.starts_with('-') => {
+                eprintln!("unknown option {flag}\n{USAGE}");
+                return ExitCode::FAILURE;
+            }
+            _ => linger_file_name_option = Some(arg),
+        }
+    }
+
+    if record_file_name_option.is_some() && replay_file_name_option.is_some() {
+        eprintln!("--record and --replay cannot be used together\n{USAGE}");
         return ExitCode::FAILURE;
     }
 
-    let program = match parse_program(tokens.as_slice()) {
-        Ok(p) => p,
+    let linger_file_name = match linger_file_name_option {
+        Some(ref name) => name.as_str(),
+        None => {
+            eprintln!("{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let linger_file_bytes = match fs::read(linger_file_name) {
+        Ok(bytes) => bytes,
         Err(e) => {
-            eprintln!("{e}");
+            eprintln!("error opening {linger_file_name}: {e}");
             return ExitCode::FAILURE;
         }
     };
-    if debug_program {
-        dbg!(&program);
+    let compiled = is_compiled_program(&linger_file_bytes);
+    let linger_file_content = match compiled {
+        true => String::new(),
+        false => match String::from_utf8(linger_file_bytes.clone()) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("error opening {linger_file_name}: {e}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+
+    let reporter = Reporter::new(linger_file_name, linger_file_content.as_str()).with_style(style);
+
+    // imports are resolved relative to the directory of the file being run
+    let base_dir = Path::new(linger_file_name)
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+
+    let prelude = match prelude_file_name_option {
+        Some(ref prelude_file_name) => match load_prelude(Path::new(prelude_file_name)) {
+            Ok(prelude) => Some(prelude),
+            Err(e) => {
+                report(&reporter, Diagnostic::from(e));
+                return ExitCode::FAILURE;
+            }
+        },
+        None => None,
+    };
+
+    if compiled && (build || format || check_only || tokens_json || ast_json) {
+        eprintln!("{linger_file_name} is already compiled");
         return ExitCode::FAILURE;
     }
 
-    let value = match interp_program(program, &mut Writer::new(Box::new(stdout()))) {
+    if format {
+        return match format_source(linger_file_content.as_str()) {
+            Ok(formatted) => {
+                print!("{formatted}");
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                report(&reporter, Diagnostic::from(e));
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if tokens_json || ast_json {
+        return print_json(
+            linger_file_content.as_str(),
+            &reporter,
+            tokens_json,
+            ast_json,
+        );
+    }
+
+    if check_only {
+        return match check_in_dir_with(linger_file_content.as_str(), base_dir, warn_shadowing) {
+            Ok(warnings) if report_warnings(&reporter, &warnings, deny_warnings) => {
+                ExitCode::FAILURE
+            }
+            Ok(_) => ExitCode::SUCCESS,
+            Err(diagnostics) => {
+                for diagnostic in diagnostics {
+                    report(&reporter, diagnostic);
+                }
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let program = if compiled {
+        match decode_program(&linger_file_bytes) {
+            Ok(program) => match prelude {
+                Some(prelude) => program.with_prelude(prelude),
+                None => program,
+            },
+            Err(e) => {
+                report(&reporter, Diagnostic::from(e));
+                return ExitCode::FAILURE;
+            }
+        }
+    } else {
+        let source = linger_file_content.as_str();
+        match compile(
+            source,
+            base_dir,
+            prelude,
+            &reporter,
+            deny_warnings,
+            warn_shadowing,
+        ) {
+            Some(program) => program,
+            None => return ExitCode::FAILURE,
+        }
+    };
+
+    if build {
+        let output_file_name = match output_file_name_option {
+            Some(name) => PathBuf::from(name),
+            None => Path::new(linger_file_name).with_extension("lingc"),
+        };
+        return match fs::write(&output_file_name, encode_program(&program)) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("error writing {}: {e}", output_file_name.display());
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let debug_value = false;
+    let mut writer = Writer::new(Box::new(stdout())).with_call_stack_size(STACK_SIZE / 2);
+    if trace {
+        writer = writer.with_trace(Box::new(stderr()));
+    }
+    if post_mortem {
+        writer = writer.with_post_mortem();
+    }
+    if record_file_name_option.is_some() {
+        writer = writer.with_recording();
+    }
+    if let Some(ref replay_file_name) = replay_file_name_option {
+        let log = fs::read_to_string(replay_file_name)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ReplayLog::parse(&text));
+        match log {
+            Ok(log) => writer = writer.with_replay(log),
+            Err(e) => {
+                eprintln!("error reading replay log {replay_file_name}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let result = interp_program(program, &mut writer);
+
+    // the log is written even if the program fails, since that is usually the run to reproduce
+    if let (Some(record_file_name), Some(log)) = (&record_file_name_option, writer.recording()) {
+        if let Err(e) = fs::write(record_file_name, log.to_string()) {
+            eprintln!("error writing {record_file_name}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let value = match result {
         Ok(v) => v,
         Err(e) => {
-            eprintln!("{e}");
+            report(&reporter, Diagnostic::from(e));
+            if let Some(post_mortem) = writer.post_mortem() {
+                if let Err(e) = inspect(post_mortem, stdin().lock(), stderr()) {
+                    eprintln!("error inspecting {linger_file_name}: {e}");
+                }
+            }
             return ExitCode::FAILURE;
         }
     };
@@ -0,0 +1,49 @@
+//! The metadata of a Linger script, which is given in the header comment block at the start of the
+//! script, e.g.
+//!
+//! ```text
+//! #!/usr/bin/env linger
+//! // @name: greeter
+//! // @version: 1.2.0
+//! ```
+
+use crate::language::pragma_line;
+
+/// The metadata of a program, which is available to the program through the `script_name` and
+/// `script_version` builtins.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub name: Option<String>,
+    pub version: Option<String>,
+}
+
+impl Metadata {
+    /// Parses the header comment block of the program `source`, which is made up of the comment
+    /// lines at the start of the program (after the shebang line and the `#language` pragma, if
+    /// there are any). Lines of the form `// @key: value` set the value of `key`, and unknown keys
+    /// are ignored.
+    pub fn parse(source: &str) -> Self {
+        let mut metadata = Metadata::default();
+        let pragma_line = pragma_line(source);
+        let header = source
+            .lines()
+            .enumerate()
+            .skip_while(|(line_num, line)| {
+                (*line_num == 0 && line.starts_with("#!")) || Some(*line_num) == pragma_line
+            })
+            .map_while(|(_, line)| line.trim().strip_prefix("//"));
+
+        for comment in header {
+            let Some((key, value)) = comment.trim().split_once(':') else {
+                continue;
+            };
+            let value = Some(value.trim().to_string());
+            match key.trim() {
+                "@name" => metadata.name = value,
+                "@version" => metadata.version = value,
+                _ => {}
+            }
+        }
+        return metadata;
+    }
+}
@@ -0,0 +1,204 @@
+//! Mutation testing, which checks how thoroughly a program is tested by making small changes to it
+//! and checking that its tests notice each change. A change that no test notices points to a
+//! behavior of the program which is not tested.
+//!
+//! [mutants] makes every small change to a parsed program that it knows of, which are to flip a
+//! comparison operator (e.g. `<` to `<=`, or `==` to `!=`) and to make a number literal one larger
+//! or one smaller. The conditions of `requires` and `ensures` clauses are left as they are, since
+//! they check the behavior of the program rather than being part of it. Each [Mutant] can be
+//! printed back to source with [pretty_program](crate::pretty::pretty_program) to be run by the
+//! tests.
+
+use std::fmt;
+
+use crate::{
+    parser::{SugaredExpr, SugaredProgram, SugaredStatement},
+    tokenizer::Operator::{self, *},
+};
+
+/// A copy of a program with a single small change.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mutant {
+    /// What was changed, e.g. `"<" changed to "<="`.
+    pub description: String,
+    /// The line of the statement that was changed, or 0 if it is not in a statement, e.g. if it is
+    /// in a top-level constant.
+    pub line: usize,
+    /// The changed program.
+    pub program: SugaredProgram,
+}
+
+impl fmt::Display for Mutant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on line {}", self.description, self.line)
+    }
+}
+
+/// Returns a [Mutant] of `program` for every change that can be made to it (see
+/// [mutation](self)), in the order the changes appear in the program.
+pub fn mutants(program: &SugaredProgram) -> Vec<Mutant> {
+    // the changes are found by walking the program once, and then each one is made to its own copy
+    // of the program by walking the copy up to that change
+    let mut survey = Mutator::new(None);
+    let mut copy = program.clone();
+    survey.program(&mut copy);
+    return (survey.changes.into_iter().enumerate())
+        .map(|(index, (description, line))| {
+            let mut program = program.clone();
+            Mutator::new(Some(index)).program(&mut program);
+            Mutant {
+                description,
+                line,
+                program,
+            }
+        })
+        .collect();
+}
+
+/// Returns the comparison operator that `op` is flipped to, or `None` if `op` is not a comparison.
+fn flipped_comparison(op: Operator) -> Option<Operator> {
+    return match op {
+        LT => Some(LTE),
+        LTE => Some(LT),
+        GT => Some(GTE),
+        GTE => Some(GT),
+        Eq => Some(Ne),
+        Ne => Some(Eq),
+        _ => None,
+    };
+}
+
+/// A walk over a program which finds the changes that can be made to it, and makes one of them.
+struct Mutator {
+    /// The index of the change to make, or `None` to only find the changes.
+    target: Option<usize>,
+    /// The description and line of each change found so far.
+    changes: Vec<(String, usize)>,
+    /// The line of the statement being walked.
+    line: usize,
+}
+
+impl Mutator {
+    fn new(target: Option<usize>) -> Self {
+        return Self {
+            target,
+            changes: vec![],
+            line: 0,
+        };
+    }
+
+    /// Records a change with `description`, and returns true if it is the one to make.
+    fn change(&mut self, description: String) -> bool {
+        let is_target = self.target == Some(self.changes.len());
+        self.changes.push((description, self.line));
+        return is_target;
+    }
+
+    fn program(&mut self, program: &mut SugaredProgram) {
+        for (_, expr) in &mut program.constants {
+            self.expr(expr);
+        }
+        for procedure in &mut program.procedures {
+            self.line = 0;
+            self.statement(&mut procedure.body);
+        }
+    }
+
+    fn statements(&mut self, statements: &mut [SugaredStatement]) {
+        statements
+            .iter_mut()
+            .for_each(|statement| self.statement(statement));
+    }
+
+    fn statement(&mut self, statement: &mut SugaredStatement) {
+        match statement {
+            SugaredStatement::Expr(expr)
+            | SugaredStatement::Let(_, expr)
+            | SugaredStatement::LetPattern(_, expr)
+            | SugaredStatement::Const(_, expr)
+            | SugaredStatement::Assign(_, expr)
+            | SugaredStatement::OperatorAssignment(_, _, expr)
+            | SugaredStatement::Return(Some(expr))
+            | SugaredStatement::Break(Some(expr)) => self.expr(expr),
+            SugaredStatement::PlaceAssign(target, _, expr) => {
+                self.expr(target);
+                self.expr(expr);
+            }
+            SugaredStatement::MultiAssign(_, exprs) => exprs.iter_mut().for_each(|e| self.expr(e)),
+            SugaredStatement::Block(statements) => self.statements(statements),
+            SugaredStatement::If(cond, then_block, else_ifs, else_block) => {
+                self.expr(cond);
+                self.statement(then_block);
+                for (else_if_cond, else_if_block) in else_ifs {
+                    self.expr(else_if_cond);
+                    self.statement(else_if_block);
+                }
+                if let Some(else_block) = else_block {
+                    self.statement(else_block);
+                }
+            }
+            SugaredStatement::While(cond, body) => {
+                self.expr(cond);
+                self.statement(body);
+            }
+            SugaredStatement::Loop(body) => self.statement(body),
+            SugaredStatement::For(init, cond, update, body) => {
+                self.statement(init);
+                self.expr(cond);
+                self.statement(update);
+                self.statements(body);
+            }
+            SugaredStatement::Line(line, _) => self.line = *line,
+            SugaredStatement::Break(None)
+            | SugaredStatement::Continue
+            | SugaredStatement::Return(None) => {}
+        }
+    }
+
+    fn expr(&mut self, expr: &mut SugaredExpr) {
+        match expr {
+            SugaredExpr::Num(n) => {
+                let (larger, smaller) = (*n + 1.0, *n - 1.0);
+                if self.change(format!("{n} changed to {larger}")) {
+                    *n = larger;
+                } else if self.change(format!("{n} changed to {smaller}")) {
+                    *n = smaller;
+                }
+            }
+            SugaredExpr::Nil | SugaredExpr::Bool(_) | SugaredExpr::Str(_) | SugaredExpr::Var(_) => {
+            }
+            SugaredExpr::Binary(op, left, right, _) => {
+                self.expr(left);
+                if let Some(flipped) = flipped_comparison(*op) {
+                    if self.change(format!("\"{op}\" changed to \"{flipped}\"")) {
+                        *op = flipped;
+                    }
+                }
+                self.expr(right);
+            }
+            SugaredExpr::Index(left, right, _) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => self.expr(operand),
+            SugaredExpr::StructUpdate(_, base, fields) => {
+                self.expr(base);
+                fields.iter_mut().for_each(|(_, value)| self.expr(value));
+            }
+            SugaredExpr::PrimitiveCall(_, args, _) | SugaredExpr::List(args) => {
+                args.iter_mut().for_each(|arg| self.expr(arg))
+            }
+            SugaredExpr::Call(callee, args, _) => {
+                self.expr(callee);
+                args.iter_mut().for_each(|arg| self.expr(arg));
+            }
+            SugaredExpr::KeywordCall(callee, args, keyword_args, _) => {
+                self.expr(callee);
+                args.iter_mut().for_each(|arg| self.expr(arg));
+                keyword_args.iter_mut().for_each(|(_, arg)| self.expr(arg));
+            }
+            SugaredExpr::Lambda(_, body) => self.statement(body),
+            SugaredExpr::Statement(statement) => self.statement(statement),
+        }
+    }
+}
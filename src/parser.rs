@@ -1,14 +1,31 @@
-use crate::desugar::{desugar_statement, Procedure, Statement};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::desugar::{
+    desugar_expression, desugar_procedure, desugar_struct, shared_statements, Expr, Procedure,
+    Statement,
+};
+use crate::fold::{fold_constant, is_literal};
+use crate::language::LanguageVersion;
+use crate::symbol::Symbol;
+use crate::metadata::Metadata;
 use crate::tokenizer::AssignOp;
-use crate::tokenizer::Operator;
+use crate::tokenizer::{Operator, Span};
 use crate::{
-    error::ParseError::{self, *},
-    tokenizer::Token as T,
+    error::{
+        ParseError::{self, *},
+        TokenizerError,
+    },
+    tokenizer::{ContextualKeyword, Keyword, Token as T, TokenValue},
 };
 
-use self::procedures::parse_procs;
-use self::utils::unexpected_token;
+use self::cursor::TokenCursor;
+use self::procedures::parse_top_level;
+#[cfg(feature = "arbitrary")]
+pub(crate) use self::utils::tokens_to_source;
+pub use self::expressions::{binary_precedence, BINARY_OPERATORS};
+pub use self::utils::{MAX_CHAIN_LENGTH, MAX_NESTING_DEPTH};
 
+mod cursor;
 mod expressions;
 mod procedures;
 mod statements;
@@ -20,7 +37,100 @@ pub struct Program {
     /// The top-level procedures of the program, excluding the main procedure.
     pub procedures: Vec<Procedure>,
     /// The body of the main procedure of the program.
-    pub main: Statement,
+    pub main: Arc<Statement>,
+    /// The top-level constants of the program, in the order they are defined. These are evaluated
+    /// once before the main procedure is run.
+    pub constants: Vec<(String, Expr)>,
+    /// The modules imported by the program (see [resolve_imports](crate::imports::resolve_imports)).
+    pub modules: Vec<Module>,
+    /// The metadata given in the header of the program, which is empty for imported modules.
+    pub metadata: Metadata,
+}
+
+impl Program {
+    /// Returns true if the program has a top-level constant or procedure named `name`.
+    pub fn defines(&self, name: &str) -> bool {
+        return name == "main"
+            || self.constants.iter().any(|(constant, _)| constant == name)
+            || self.procedures.iter().any(|proc| proc.name == name);
+    }
+
+    /// Adds `prelude` to the modules of this program so that its exports are bound before the
+    /// program runs (see [load_prelude](crate::imports::load_prelude)). The top-level definitions
+    /// of this program take precedence over those of the prelude.
+    pub fn with_prelude(mut self, mut prelude: Module) -> Self {
+        prelude.exports.retain(|name| !self.defines(name));
+        self.modules.insert(0, prelude);
+        return self;
+    }
+}
+
+/// A module imported by a program with `import "<path>";`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Module {
+    /// The path of the module as written in the `import`.
+    pub path: String,
+    /// The alias of the module given with `as`, which qualifies the names of its exports.
+    pub alias: Option<String>,
+    /// The definitions of the module. Every procedure of the module, including `main`, is stored in
+    /// the `procedures` of this program, and its `main` field is an empty block.
+    pub program: Program,
+    /// The names bound in the importing program, which are the procedures marked `pub` for an
+    /// imported module, or every top-level definition for a prelude.
+    pub exports: Vec<String>,
+}
+
+/// Returns `name` qualified by the module alias `alias` (e.g. `math.hypot`), or `name` itself if
+/// there is no alias. Qualified names cannot collide with any other name, since identifiers cannot
+/// contain a dot.
+pub fn qualified_name(alias: Option<&str>, name: &str) -> String {
+    match alias {
+        Some(alias) => format!("{alias}.{name}"),
+        None => name.to_string(),
+    }
+}
+
+/// The top-level definitions of a program as written by the user.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SugaredProgram {
+    /// The modules imported with `import "<path>";` or `import "<path>" as <alias>;`.
+    pub imports: Vec<SugaredImport>,
+    /// The top-level constants of the program, e.g. `const TABLE = [1, 2, 3];`, in the order
+    /// they are defined.
+    pub constants: Vec<(String, SugaredExpr)>,
+    pub procedures: Vec<SugaredProcedure>,
+    /// The structs declared by the program, e.g. `struct Point { x, y }`.
+    pub structs: Vec<SugaredStruct>,
+}
+
+/// An `import` of a module. The path is relative to the importing file. If the import has an
+/// alias, the procedures exported by the module are bound as qualified names, e.g. `math.hypot`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SugaredImport {
+    pub path: String,
+    pub alias: Option<String>,
+}
+
+impl SugaredProgram {
+    /// Returns true if the program has a top-level constant, procedure, or struct named `name`.
+    pub fn defines(&self, name: &str) -> bool {
+        return self.constants.iter().any(|(constant, _)| constant == name)
+            || self.procedures.iter().any(|proc| proc.name == name)
+            || self
+                .structs
+                .iter()
+                .any(|definition| definition.name == name);
+    }
+}
+
+/// A struct declaration, e.g. `struct Point { x, y }`, which names a group of fields. The struct
+/// is constructed by calling it like a procedure whose parameters are its fields, e.g.
+/// `Point(1, 2)` or `Point(x = 1, y = 2)`, and the fields of an instance are read with a dot, e.g.
+/// `p.x`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SugaredStruct {
+    pub name: String,
+    pub fields: Vec<String>,
 }
 
 /// A representation for a procedure in the Linger programming language.
@@ -33,7 +143,42 @@ pub struct Program {
 pub struct SugaredProcedure {
     pub name: String,
     pub params: Vec<String>,
+    /// The `requires` and `ensures` clauses of the procedure, in the order they were written.
+    pub contracts: Vec<SugaredContract>,
     pub body: SugaredStatement,
+    /// Whether the procedure is marked `pub`, which exports it to the programs that import its
+    /// module.
+    pub public: bool,
+    /// Whether the procedure is marked `pure`, which promises that it has no side effects. The body
+    /// of a pure procedure is checked by [analysis::purity](crate::analysis::purity).
+    pub pure: bool,
+}
+
+/// A `requires(<expr>)` or `ensures(<expr>)` clause of a procedure definition. A `requires`
+/// condition is checked when the procedure is called, and an `ensures` condition is checked when it
+/// returns, with the return value bound to `result`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct SugaredContract {
+    pub kind: ContractKind,
+    pub condition: SugaredExpr,
+    /// The source text of the condition, used to describe the contract when it is violated.
+    pub source: String,
+}
+
+/// Whether a contract is a precondition (`requires`) or a postcondition (`ensures`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContractKind {
+    Requires,
+    Ensures,
+}
+
+impl std::fmt::Display for ContractKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ContractKind::Requires => write!(f, "requires"),
+            ContractKind::Ensures => write!(f, "ensures"),
+        }
+    }
 }
 
 /// A representation of a statement in the Linger programming language.
@@ -46,9 +191,21 @@ pub struct SugaredProcedure {
 pub enum SugaredStatement {
     Expr(SugaredExpr),
     Let(String, SugaredExpr),
+    /// A destructuring `let` statement, e.g. `let [first, second] = xs;`, which binds each name of
+    /// the pattern to a part of the value.
+    LetPattern(Pattern, SugaredExpr),
     Const(String, SugaredExpr),
     Assign(String, SugaredExpr),
     OperatorAssignment(AssignOp, String, SugaredExpr),
+    /// An assignment to an element of a list, a character of a string, a key of a map, or a field
+    /// of a struct instance, e.g. `xs[i] = v;`, `xs[i] += v;` or `p.x = v;`, with the indexing or
+    /// field expression which is assigned to, the operator of a compound assignment, and the
+    /// assigned value. The target and its indexes are evaluated once, even by a compound
+    /// assignment.
+    PlaceAssign(SugaredExpr, Option<AssignOp>, SugaredExpr),
+    /// An assignment of several values to several variables at once, e.g. `a, b = b, a;`. Every
+    /// value is evaluated before any variable is assigned.
+    MultiAssign(Vec<String>, Vec<SugaredExpr>),
     Block(Vec<SugaredStatement>),
     If(
         SugaredExpr,
@@ -57,15 +214,45 @@ pub enum SugaredStatement {
         Option<Box<SugaredStatement>>,
     ),
     While(SugaredExpr, Box<SugaredStatement>),
+    /// An unconditional loop, e.g. `loop { ... }`, which only ends with a `break` or `return`
+    /// statement.
+    Loop(Box<SugaredStatement>),
     For(
         Box<SugaredStatement>,
         SugaredExpr,
         Box<SugaredStatement>,
         Vec<SugaredStatement>,
     ),
-    Break,
+    /// A `break` statement, with the value that the loop it ends produces, if it has one.
+    Break(Option<SugaredExpr>),
     Continue,
     Return(Option<SugaredExpr>),
+    /// Marks the start of the statement after it in a block, which begins on the given line. The
+    /// source text is the part of that line which belongs to the statement. This is used to trace
+    /// the statements of a program as they are run.
+    Line(usize, String),
+}
+
+/// The pattern of a destructuring `let` statement.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Pattern {
+    /// A list pattern, e.g. `[first, second, rest...]`, which binds its names to the elements of a
+    /// list in order. The name followed by `...`, if there is one, is bound to a list of the
+    /// elements after those.
+    List(Vec<String>, Option<String>),
+    /// A map pattern, e.g. `{x, y}`, which binds each of its names to the value of the map at the
+    /// key with the same name.
+    Map(Vec<String>),
+}
+
+impl Pattern {
+    /// Returns the names bound by this pattern, in the order they are written.
+    pub fn names(&self) -> Vec<&str> {
+        return match self {
+            Pattern::List(names, rest) => names.iter().chain(rest).map(String::as_str).collect(),
+            Pattern::Map(names) => names.iter().map(String::as_str).collect(),
+        };
+    }
 }
 
 /// A representation of an expression in the Linger programming language.
@@ -81,12 +268,32 @@ pub enum SugaredExpr {
     Bool(bool),
     Str(String),
     Var(String),
-    Binary(Operator, Box<SugaredExpr>, Box<SugaredExpr>),
+    /// A binary operator applied to two operands, along with the spans of the operands.
+    Binary(Operator, Box<SugaredExpr>, Box<SugaredExpr>, (Span, Span)),
     Unary(Operator, Box<SugaredExpr>),
-    PrimitiveCall(Builtin, Vec<SugaredExpr>),
-    Call(Box<SugaredExpr>, Vec<SugaredExpr>),
+    /// A list literal, e.g. `[1, 2, 3]`.
+    List(Vec<SugaredExpr>),
+    /// A call of a built in procedure, along with the span of its name.
+    PrimitiveCall(Builtin, Vec<SugaredExpr>, Span),
+    /// A call of a procedure value, along with the span of the call, which is where an error about
+    /// its arguments is reported.
+    Call(Box<SugaredExpr>, Vec<SugaredExpr>, Span),
+    /// A call with keyword arguments, e.g. `plot(xs, style = "line")`, which are given after the
+    /// positional arguments and are bound to the parameters with their names. The span is the span
+    /// of the call, as in [SugaredExpr::Call].
+    KeywordCall(Box<SugaredExpr>, Vec<SugaredExpr>, Vec<(String, SugaredExpr)>, Span),
     Lambda(Vec<String>, Box<SugaredStatement>),
-    Index(Box<SugaredExpr>, Box<SugaredExpr>),
+    /// An indexing expression, e.g. `xs[i]`, along with the span of the index.
+    Index(Box<SugaredExpr>, Box<SugaredExpr>, Span),
+    /// A read of a field of a struct instance, e.g. `p.x`.
+    Field(Box<SugaredExpr>, String),
+    /// A functional update of a struct instance, e.g. `Point { ...p, x: 5 }`, which is a new
+    /// instance of the struct with the fields of the updated instance, except for the fields which
+    /// are given new values. The fields are the name and new value of each updated field, in the
+    /// order they are written.
+    StructUpdate(String, Box<SugaredExpr>, Vec<(String, SugaredExpr)>),
+    /// A block or `if` statement used as an expression, e.g. `let x = if (c) { 1 } else { 2 };`.
+    Statement(Box<SugaredStatement>),
 }
 
 /// A built in procedure in the Linger programming language.
@@ -98,33 +305,420 @@ pub enum Builtin {
     IsNil,
     Head,
     Rest,
+    Zip,
+    Enumerate,
+    Range,
+    Sort,
+    SortBy,
+    IndexOf,
+    Find,
+    Any,
+    All,
+    Flat,
+    FlatMap,
+    Unique,
+    Reverse,
+    Take,
+    Drop,
+    Copy,
+    Freeze,
+    DivMod,
+    Dict,
+    HasKey,
+    Set,
+    Keys,
+    Join,
+    Split,
+    EqualsIgnoreCase,
+    Compare,
+    Chars,
+    Words,
+    Lines,
+    #[cfg(feature = "csv")]
+    CsvParse,
+    #[cfg(feature = "csv")]
+    CsvStringify,
+    ReadFileBytes,
+    BytesLen,
+    ByteAt,
+    SliceBytes,
+    HexEncode,
+    HexDecode,
+    #[cfg(feature = "hash")]
+    Md5,
+    #[cfg(feature = "hash")]
+    Sha256,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    ParseNum,
+    ToStrRadix,
+    ParseInt,
+    Debug,
+    TypeAssert,
+    Panic,
+    Random,
+    NowMillis,
+    Input,
+    ScriptName,
+    ScriptVersion,
 }
 
 /// Parses a program from a list of tokens.
 pub fn parse_program(tokens: &[T]) -> Result<Program, ParseError> {
-    let (procedures, rest) = parse_procs(tokens)?;
+    let program = parse_sugared_program(tokens)?;
+    return desugar_program(program);
+}
+
+/// Parses the top-level definitions of a program from a list of tokens without desugaring them.
+/// This is used by passes which analyze the program as it was written by the user.
+pub fn parse_sugared_program(tokens: &[T]) -> Result<SugaredProgram, ParseError> {
+    return parse_sugared_program_with(tokens, LanguageVersion::V1);
+}
+
+/// Like [parse_sugared_program], except that the program is parsed by the rules of version
+/// `language` of the grammar (see [language](crate::language)).
+pub fn parse_sugared_program_with(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<SugaredProgram, ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let mut program = cursor.parse(|tokens| parse_top_level(tokens, language))?;
 
-    if !rest.is_empty() {
-        return Err(unexpected_token(rest)); // extra tokens
+    if !cursor.is_at_end() {
+        return Err(cursor.unexpected()); // extra tokens
     }
 
-    let desugared_procs = procedures.iter().map(|proc| Procedure {
-        name: proc.name.to_string(),
-        params: proc.params.clone(),
-        body: desugar_statement(proc.body.clone()),
-    });
+    resolve_field_accesses(&mut program);
+    return Ok(program);
+}
 
-    let (main_procs, procs): (Vec<Procedure>, Vec<Procedure>) = desugared_procs
-        .into_iter()
-        .partition(|proc| proc.name == "main");
+/// Like [parse_sugared_program_with], except that the tokens are pulled from `tokens`, e.g. a
+/// [TokenStream](crate::tokenizer::TokenStream), as they are needed rather than read from a slice
+/// of every token of the program. Each top-level definition is parsed as soon as its last token has
+/// been read, and its tokens are dropped before the next definition is read, so only the tokens of
+/// one definition are held at a time. An error from `tokens` is returned as a [Tokenizer] error
+/// once the parser reaches it, so a parse error before it is reported instead.
+pub fn parse_sugared_program_from(
+    mut tokens: impl Iterator<Item = Result<T, TokenizerError>>,
+    language: LanguageVersion,
+) -> Result<SugaredProgram, ParseError> {
+    let mut program = SugaredProgram::default();
+    let mut definition = vec![];
+    while read_definition(&mut tokens, &mut definition)? {
+        let definitions = parse_definition(&definition, language)?;
+        add_definitions(&mut program, definitions)?;
+        definition.clear();
+    }
+
+    resolve_field_accesses(&mut program);
+    return Ok(program);
+}
+
+/// A top-level definition parsed by [parse_definitions], along with the lines of its first and last
+/// tokens.
+pub type ParsedDefinition = (SugaredProgram, (usize, usize));
+
+/// Like [parse_sugared_program_from], except that each top-level definition is returned as a
+/// program of its own, in the order the definitions are written, along with the lines of its first
+/// and last tokens. This is used by [format_source](crate::pretty::format_source), which keeps
+/// some definitions as they are written.
+pub fn parse_definitions(
+    mut tokens: impl Iterator<Item = Result<T, TokenizerError>>,
+    language: LanguageVersion,
+) -> Result<Vec<ParsedDefinition>, ParseError> {
+    // every definition is also added to the whole program, which checks that names are unique
+    let mut program = SugaredProgram::default();
+    let mut parsed = vec![];
+    let mut definition = vec![];
+    while read_definition(&mut tokens, &mut definition)? {
+        let definitions = parse_definition(&definition, language)?;
+        let lines = (definition[0].1, definition[definition.len() - 1].1);
+        add_definitions(&mut program, definitions.clone())?;
+        parsed.push((definitions, lines));
+        definition.clear();
+    }
+
+    let aliases = import_aliases(&program);
+    for (definitions, _) in &mut parsed {
+        resolve_field_accesses_with(definitions, &aliases);
+    }
+    return Ok(parsed);
+}
 
-    let main_proc = match main_procs.first() {
-        Some(proc) => proc,
+/// Parses the tokens of a single top-level definition (see [read_definition]).
+fn parse_definition(
+    definition: &[T],
+    language: LanguageVersion,
+) -> Result<SugaredProgram, ParseError> {
+    let mut cursor = TokenCursor::new(definition);
+    let definitions = cursor.parse(|tokens| parse_top_level(tokens, language))?;
+    if !cursor.is_at_end() {
+        return Err(cursor.unexpected()); // extra tokens
+    }
+    return Ok(definitions);
+}
+
+/// Reads the tokens of the next top-level definition from `tokens` into `definition`, and returns
+/// false if there are no tokens left. A definition ends at a `;` outside of any brackets, such as
+/// the end of an import or a constant, or at the `}` which closes the body of a procedure or a
+/// struct. A closing bracket which is never opened ends the definition too, so that it is reported
+/// as an extra token.
+fn read_definition(
+    tokens: &mut impl Iterator<Item = Result<T, TokenizerError>>,
+    definition: &mut Vec<T>,
+) -> Result<bool, ParseError> {
+    let mut depth = 0;
+    for token in tokens {
+        let token = token.map_err(Tokenizer)?;
+        let ends_definition = match token.0 {
+            TokenValue::LPAREN | TokenValue::L_SQUARE_BRACKET | TokenValue::L_CURLY_BRACKET => {
+                depth += 1;
+                false
+            }
+            TokenValue::RPAREN | TokenValue::R_SQUARE_BRACKET => {
+                depth -= 1;
+                depth < 0
+            }
+            TokenValue::R_CURLY_BRACKET => {
+                depth -= 1;
+                // the initializer of a constant can contain a block, e.g. the body of a lambda,
+                // but the constant still ends at its `;`
+                depth < 0 || (depth == 0 && !ends_with_semicolon(&definition[0]))
+            }
+            TokenValue::SEMICOLON => depth <= 0,
+            _ => false,
+        };
+        definition.push(token);
+        if ends_definition {
+            return Ok(true);
+        }
+    }
+    return Ok(!definition.is_empty());
+}
+
+/// Returns true if the top-level definition which starts with `first` ends with a `;`.
+fn ends_with_semicolon(first: &T) -> bool {
+    return first.0 == TokenValue::KW(Keyword::Const)
+        || first.0.is_contextual(ContextualKeyword::Import);
+}
+
+/// Adds the top-level `definitions` to `program`, which holds the definitions before them. A name
+/// may only be defined once, which is checked in the same way as [parse_top_level] checks each
+/// definition against the definitions after it.
+fn add_definitions(
+    program: &mut SugaredProgram,
+    definitions: SugaredProgram,
+) -> Result<(), ParseError> {
+    let constant_names = definitions.constants.iter().map(|(name, _)| name);
+    let proc_names = definitions.procedures.iter().map(|proc| &proc.name);
+    let struct_names = definitions.structs.iter().map(|s| &s.name);
+    for name in constant_names.chain(proc_names).chain(struct_names) {
+        let is_proc = definitions.procedures.iter().any(|proc| &proc.name == name);
+        if is_proc && program.procedures.iter().any(|proc| &proc.name == name) {
+            return Err(MultipleSameNamedProcs(name.to_string()));
+        }
+        let is_alias = program
+            .imports
+            .iter()
+            .any(|import| import.alias.as_ref() == Some(name));
+        if is_alias || program.defines(name) {
+            return Err(DuplicateTopLevelName(name.to_string()));
+        }
+    }
+
+    program.imports.extend(definitions.imports);
+    program.constants.extend(definitions.constants);
+    program.procedures.extend(definitions.procedures);
+    program.structs.extend(definitions.structs);
+    return Ok(());
+}
+
+/// Turns every name of `program` which is written like a qualified name, but whose qualifier is
+/// not the alias of an import, into an access of a field, e.g. `p.x` where `p` is a variable (see
+/// [SugaredExpr::Field]). The parser cannot tell these apart when it reads them, since an import
+/// can be written after the procedures which use it.
+fn resolve_field_accesses(program: &mut SugaredProgram) {
+    let aliases = import_aliases(program);
+    resolve_field_accesses_with(program, &aliases);
+}
+
+/// Returns the aliases of the modules imported by `program`.
+fn import_aliases(program: &SugaredProgram) -> Vec<String> {
+    return program
+        .imports
+        .iter()
+        .filter_map(|import| import.alias.clone())
+        .collect();
+}
+
+/// Like [resolve_field_accesses], except that the qualifiers which are aliases are `aliases`.
+fn resolve_field_accesses_with(program: &mut SugaredProgram, aliases: &[String]) {
+    for (_, expr) in &mut program.constants {
+        resolve_field_accesses_in_expr(expr, aliases);
+    }
+    for procedure in &mut program.procedures {
+        for contract in &mut procedure.contracts {
+            resolve_field_accesses_in_expr(&mut contract.condition, aliases);
+        }
+        resolve_field_accesses_in_statement(&mut procedure.body, aliases);
+    }
+}
+
+fn resolve_field_accesses_in_statement(statement: &mut SugaredStatement, aliases: &[String]) {
+    match statement {
+        SugaredStatement::Expr(expr)
+        | SugaredStatement::Let(_, expr)
+        | SugaredStatement::LetPattern(_, expr)
+        | SugaredStatement::Const(_, expr)
+        | SugaredStatement::Assign(_, expr)
+        | SugaredStatement::OperatorAssignment(_, _, expr)
+        | SugaredStatement::Return(Some(expr))
+        | SugaredStatement::Break(Some(expr)) => resolve_field_accesses_in_expr(expr, aliases),
+        SugaredStatement::PlaceAssign(target, _, expr) => {
+            resolve_field_accesses_in_expr(target, aliases);
+            resolve_field_accesses_in_expr(expr, aliases);
+        }
+        SugaredStatement::MultiAssign(_, exprs) => {
+            for expr in exprs {
+                resolve_field_accesses_in_expr(expr, aliases);
+            }
+        }
+        SugaredStatement::Block(statements) => {
+            for statement in statements {
+                resolve_field_accesses_in_statement(statement, aliases);
+            }
+        }
+        SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => {
+            resolve_field_accesses_in_expr(cond, aliases);
+            resolve_field_accesses_in_statement(then_block, aliases);
+            for (else_if_cond, else_if_block) in else_ifs {
+                resolve_field_accesses_in_expr(else_if_cond, aliases);
+                resolve_field_accesses_in_statement(else_if_block, aliases);
+            }
+            if let Some(else_block) = else_block_option {
+                resolve_field_accesses_in_statement(else_block, aliases);
+            }
+        }
+        SugaredStatement::While(cond, body) => {
+            resolve_field_accesses_in_expr(cond, aliases);
+            resolve_field_accesses_in_statement(body, aliases);
+        }
+        SugaredStatement::Loop(body) => resolve_field_accesses_in_statement(body, aliases),
+        SugaredStatement::For(init, cond, update, body) => {
+            resolve_field_accesses_in_statement(init, aliases);
+            resolve_field_accesses_in_expr(cond, aliases);
+            resolve_field_accesses_in_statement(update, aliases);
+            for statement in body {
+                resolve_field_accesses_in_statement(statement, aliases);
+            }
+        }
+        SugaredStatement::Return(None)
+        | SugaredStatement::Break(None)
+        | SugaredStatement::Continue
+        | SugaredStatement::Line(..) => (),
+    }
+}
+
+fn resolve_field_accesses_in_expr(expr: &mut SugaredExpr, aliases: &[String]) {
+    match expr {
+        SugaredExpr::Var(name) => {
+            let Some((qualifier, field)) = name.split_once('.') else {
+                return;
+            };
+            if !aliases.iter().any(|alias| alias == qualifier) {
+                let object = SugaredExpr::Var(qualifier.to_string());
+                *expr = SugaredExpr::Field(Box::new(object), field.to_string());
+            }
+        }
+        SugaredExpr::Nil | SugaredExpr::Num(_) | SugaredExpr::Bool(_) | SugaredExpr::Str(_) => (),
+        SugaredExpr::Binary(_, left, right, _) | SugaredExpr::Index(left, right, _) => {
+            resolve_field_accesses_in_expr(left, aliases);
+            resolve_field_accesses_in_expr(right, aliases);
+        }
+        SugaredExpr::Unary(_, operand) | SugaredExpr::Field(operand, _) => {
+            resolve_field_accesses_in_expr(operand, aliases)
+        }
+        SugaredExpr::StructUpdate(_, base, fields) => {
+            resolve_field_accesses_in_expr(base, aliases);
+            for (_, value) in fields {
+                resolve_field_accesses_in_expr(value, aliases);
+            }
+        }
+        SugaredExpr::PrimitiveCall(_, args, _) | SugaredExpr::List(args) => {
+            for arg in args {
+                resolve_field_accesses_in_expr(arg, aliases);
+            }
+        }
+        SugaredExpr::Call(proc_expr, args, _) => {
+            resolve_field_accesses_in_expr(proc_expr, aliases);
+            for arg in args {
+                resolve_field_accesses_in_expr(arg, aliases);
+            }
+        }
+        SugaredExpr::KeywordCall(proc_expr, args, keyword_args, _) => {
+            resolve_field_accesses_in_expr(proc_expr, aliases);
+            for arg in args
+                .iter_mut()
+                .chain(keyword_args.iter_mut().map(|(_, arg)| arg))
+            {
+                resolve_field_accesses_in_expr(arg, aliases);
+            }
+        }
+        SugaredExpr::Lambda(_, body) => resolve_field_accesses_in_statement(body, aliases),
+        SugaredExpr::Statement(statement) => {
+            resolve_field_accesses_in_statement(statement, aliases)
+        }
+    }
+}
+
+/// Desugars the top-level definitions of a program into a [Program]. The imports of the program
+/// are not resolved.
+pub fn desugar_program(program: SugaredProgram) -> Result<Program, ParseError> {
+    let mut module_program = desugar_module(program);
+
+    let main_index = match module_program
+        .procedures
+        .iter()
+        .position(|proc| proc.name == "main")
+    {
+        Some(index) => index,
         None => return Err(NoMain),
     };
 
-    return Ok(Program {
-        procedures: procs,
-        main: main_proc.body.clone(),
-    });
+    module_program.main = module_program.procedures.remove(main_index).body;
+    return Ok(module_program);
+}
+
+/// Desugars the top-level definitions of an imported module into a [Program] which does not need a
+/// main procedure. Every procedure is kept in the `procedures` of the result, along with the
+/// constructor of every struct (see [desugar_struct]). The constants which are constant
+/// expressions are folded (see [fold_constant]).
+pub fn desugar_module(program: SugaredProgram) -> Program {
+    let mut folded = HashMap::new();
+    let constants = program
+        .constants
+        .into_iter()
+        .map(|(name, expr)| {
+            let expr = fold_constant(desugar_expression(expr), &folded);
+            if is_literal(&expr) {
+                folded.insert(Symbol::from(name.as_str()), expr.clone());
+            }
+            (name, expr)
+        })
+        .collect();
+
+    return Program {
+        procedures: program
+            .procedures
+            .into_iter()
+            .map(desugar_procedure)
+            .chain(program.structs.into_iter().map(desugar_struct))
+            .collect(),
+        main: Arc::new(Statement::Block(shared_statements([]))),
+        constants,
+        modules: vec![],
+        metadata: Metadata::default(),
+    };
 }
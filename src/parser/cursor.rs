@@ -0,0 +1,141 @@
+use crate::{
+    error::ParseError::{self, *},
+    tokenizer::{ContextualKeyword, Token as T, TokenValue},
+};
+
+use super::utils::unexpected_token;
+
+/// A position in a list of tokens which the parser reads from front to back. A cursor can look
+/// ahead any number of tokens without consuming them, and it remembers where it started so that the
+/// tokens a construct was parsed from can be recovered (see [TokenCursor::span]).
+///
+/// The parsing functions which take a slice of tokens and return the tokens after what they parsed
+/// can be run on a cursor with [TokenCursor::parse].
+#[derive(Clone, Copy, Debug)]
+pub struct TokenCursor<'a> {
+    tokens: &'a [T],
+    position: usize,
+}
+
+/// A position of a [TokenCursor] returned by [TokenCursor::mark].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mark(usize);
+
+impl<'a> TokenCursor<'a> {
+    pub fn new(tokens: &'a [T]) -> Self {
+        Self {
+            tokens,
+            position: 0,
+        }
+    }
+
+    /// Returns the tokens which have not been consumed yet.
+    pub fn rest(&self) -> &'a [T] {
+        return &self.tokens[self.position..];
+    }
+
+    /// Returns true if every token has been consumed.
+    pub fn is_at_end(&self) -> bool {
+        return self.position == self.tokens.len();
+    }
+
+    /// Returns the next token without consuming it.
+    pub fn peek(&self) -> Option<&'a T> {
+        return self.tokens.get(self.position);
+    }
+
+    /// Returns the value of the token `n` tokens ahead without consuming anything, so that
+    /// `peek_n(0)` is the value of the next token.
+    pub fn peek_n(&self, n: usize) -> Option<&'a TokenValue> {
+        return self.tokens.get(self.position + n).map(|T(value, ..)| value);
+    }
+
+    /// Returns true if the values of the next tokens are `values`, in order.
+    pub fn at(&self, values: &[TokenValue]) -> bool {
+        return values
+            .iter()
+            .enumerate()
+            .all(|(n, value)| self.peek_n(n) == Some(value));
+    }
+
+    /// Consumes and returns the next token, or returns `None` if every token has been consumed.
+    pub fn advance(&mut self) -> Option<&'a T> {
+        let token = self.peek()?;
+        self.position += 1;
+        return Some(token);
+    }
+
+    /// Consumes the next token if its value is `target`, and returns whether it was consumed.
+    pub fn eat(&mut self, target: TokenValue) -> bool {
+        if self.peek_n(0) == Some(&target) {
+            self.position += 1;
+            return true;
+        }
+        return false;
+    }
+
+    /// Consumes the next token if it is an identifier, and returns its name.
+    pub fn eat_id(&mut self) -> Option<&'a str> {
+        match self.peek_n(0) {
+            Some(TokenValue::ID(name)) => {
+                self.position += 1;
+                return Some(name);
+            }
+            _ => return None,
+        }
+    }
+
+    /// Consumes the next token if it is the contextual keyword `keyword`, and returns whether it was
+    /// consumed.
+    pub fn eat_contextual(&mut self, keyword: ContextualKeyword) -> bool {
+        match self.peek_n(0) {
+            Some(value) if value.is_contextual(keyword) => {
+                self.position += 1;
+                return true;
+            }
+            _ => return false,
+        }
+    }
+
+    /// Consumes the next token, which must have a value of `target`. Otherwise, this returns an
+    /// [Expected] error, or an [UnexpectedEOF] error if every token has been consumed, in the same
+    /// way as [consume_token](super::utils::consume_token).
+    pub fn expect(&mut self, target: TokenValue) -> Result<&'a T, ParseError> {
+        match self.peek() {
+            Some(token) if token.0 == target => {
+                self.position += 1;
+                return Ok(token);
+            }
+            Some(token) => return Err(Expected(target, token.clone())),
+            None => return Err(UnexpectedEOF),
+        }
+    }
+
+    /// Returns an [UnexpectedToken] error for the next token, or an [UnexpectedEOF] error if every
+    /// token has been consumed.
+    pub fn unexpected(&self) -> ParseError {
+        return unexpected_token(self.rest());
+    }
+
+    /// Runs `parser` on the tokens which have not been consumed yet, and consumes the tokens that it
+    /// parsed.
+    pub fn parse<R>(
+        &mut self,
+        parser: impl FnOnce(&'a [T]) -> Result<(R, &'a [T]), ParseError>,
+    ) -> Result<R, ParseError> {
+        let (result, rest) = parser(self.rest())?;
+        self.position = self.tokens.len() - rest.len();
+        return Ok(result);
+    }
+
+    /// Returns the current position of the cursor, to be passed to [TokenCursor::span] once the
+    /// construct which starts here has been parsed.
+    pub fn mark(&self) -> Mark {
+        return Mark(self.position);
+    }
+
+    /// Returns the tokens consumed since `mark`.
+    pub fn span(&self, mark: Mark) -> &'a [T] {
+        return &self.tokens[mark.0..self.position];
+    }
+}
@@ -1,48 +1,114 @@
-use crate::tokenizer::Operator::*;
+use crate::tokenizer::Operator::{self, *};
 use crate::{
     error::ParseError::{self, *},
-    tokenizer::{Keyword::*, Token as T, TokenValue::*},
+    language::LanguageVersion,
+    tokenizer::{ContextualKeyword, Keyword::*, Token as T, TokenValue::*},
 };
 
+use super::cursor::TokenCursor;
 use super::procedures::parse_params;
-use super::statements::parse_statement;
+use super::statements::{is_multi_assign, parse_statement};
 use super::utils::{
-    check_builtin, consume_token, match_operator, parse_binary_expr, unexpected_token,
+    binary_expression, check_builtin, consume_token, match_operator, unexpected_token,
+    NestingGuard, MAX_CHAIN_LENGTH,
 };
-use super::SugaredExpr;
+use super::{qualified_name, SugaredExpr, SugaredStatement};
 
-pub fn parse_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    parse_logical_or_expr(tokens)
-}
-
-pub fn parse_logical_or_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_logical_and_expr, vec![LogicOr], tokens);
-}
-
-pub fn parse_logical_and_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_equality_expr, vec![LogicAnd], tokens);
-}
+/// The binary operators, grouped by precedence from the loosest binding to the tightest binding.
+/// Every binary operator is left associative, so `a - b - c` is parsed as `(a - b) - c`. Adding an
+/// operator to a level of this table, or adding a level, is all it takes to parse a new operator.
+pub const BINARY_OPERATORS: &[&[Operator]] = &[
+    &[LogicOr],
+    &[LogicAnd],
+    &[Eq, Ne],
+    &[LT, GT, LTE, GTE],
+    &[BitOr],
+    &[BitXor],
+    &[BitAnd],
+    &[ShiftLeft, ShiftRight],
+    &[Plus, Minus],
+    &[Times, Mod, Div, FloorDiv],
+];
 
-pub fn parse_equality_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_relational_expr, vec![Eq, Ne], tokens);
+/// Returns the precedence of the binary operator `op`, which is its level in [BINARY_OPERATORS]
+/// counting from 1, or `None` if `op` is not a binary operator.
+pub fn binary_precedence(op: Operator) -> Option<u8> {
+    return BINARY_OPERATORS
+        .iter()
+        .position(|level| level.contains(&op))
+        .map(|index| index as u8 + 1);
 }
 
-pub fn parse_relational_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_additive_expr, vec![LT, GT, LTE, GTE], tokens);
+pub fn parse_expr(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &[T]), ParseError> {
+    let _guard = NestingGuard::enter(tokens)?;
+    let (cond, tokens) = parse_binary_expr(tokens, 1, &mut 0, language)?;
+    return match tokens {
+        [T(QUESTION, ..), tokens @ ..] => parse_conditional_expr(cond, tokens, language),
+        tokens => Ok((cond, tokens)),
+    };
 }
 
-pub fn parse_additive_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_multiplicative_expr, vec![Plus, Minus], tokens);
+/// Parses the branches of a conditional expression `cond ? a : b` which follow its question mark.
+/// The conditional operator binds looser than every binary operator and is right associative, so
+/// `a ? b : c ? d : e` is `a ? b : (c ? d : e)`. A conditional expression is sugar for an `if`
+/// statement used as an expression, i.e. `if (cond) { a } else { b }`.
+fn parse_conditional_expr(
+    cond: SugaredExpr,
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &[T]), ParseError> {
+    let (then_expr, tokens) = parse_expr(tokens, language)?;
+    let tokens = consume_token(COLON, tokens)?;
+    let (else_expr, tokens) = parse_expr(tokens, language)?;
+    let branch = |expr| Box::new(SugaredStatement::Block(vec![SugaredStatement::Expr(expr)]));
+    let if_statement =
+        SugaredStatement::If(cond, branch(then_expr), vec![], Some(branch(else_expr)));
+    return Ok((SugaredExpr::Statement(Box::new(if_statement)), tokens));
 }
 
-pub fn parse_multiplicative_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    return parse_binary_expr(parse_unary_expr, vec![Times, Mod, Div], tokens);
+/// Parses an expression made up of unary expressions joined by binary operators whose precedence
+/// is at least `min_precedence` (see [binary_precedence]). The operators of the chain are counted
+/// in `operators`, which is shared with the chains of its right operands, so that the whole chain
+/// is limited to [MAX_CHAIN_LENGTH] operators.
+fn parse_binary_expr<'a>(
+    tokens: &'a [T],
+    min_precedence: u8,
+    operators: &mut usize,
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &'a [T]), ParseError> {
+    let first_tokens = tokens;
+    let (mut expr, mut tokens) = parse_unary_expr(tokens, language)?;
+    loop {
+        let (op, precedence, rest) = match tokens {
+            [T(OP(op), ..), rest @ ..] => match binary_precedence(*op) {
+                Some(precedence) if precedence >= min_precedence => (*op, precedence, rest),
+                _ => return Ok((expr, tokens)),
+            },
+            _ => return Ok((expr, tokens)),
+        };
+        *operators += 1;
+        if *operators > MAX_CHAIN_LENGTH {
+            return Err(ChainTooLong(tokens[0].to_owned()));
+        }
+        // the right operand only binds the operators which bind tighter, so that the operators
+        // with the same precedence are left associative
+        let (right, right_rest) = parse_binary_expr(rest, precedence + 1, operators, language)?;
+        expr = binary_expression(op, expr, &first_tokens[0], right, &rest[0]);
+        tokens = right_rest;
+    }
 }
 
-pub fn parse_unary_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    match match_operator(vec![Minus, LogicNot].as_slice(), tokens) {
+pub fn parse_unary_expr(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &[T]), ParseError> {
+    match match_operator(vec![Minus, LogicNot, BitNot].as_slice(), tokens) {
         Some((operator, tokens)) => {
-            let (right, tokens) = parse_unary_expr(tokens)?;
+            let _guard = NestingGuard::enter(tokens)?;
+            let (right, tokens) = parse_unary_expr(tokens, language)?;
             return Ok((SugaredExpr::Unary(operator, Box::new(right)), tokens));
         }
         None => {
@@ -51,7 +117,7 @@ pub fn parse_unary_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError>
                 [T(DOUBLE_MINUS, ..), tokens @ ..] => (Some(PreDecrement), tokens),
                 tokens => (None, tokens),
             };
-            let (terminal_expr, tokens) = parse_call_or_index_expr(tokens)?;
+            let (terminal_expr, tokens) = parse_call_or_index_expr(tokens, language)?;
             match increment_op_option {
                 Some(op) => return Ok((SugaredExpr::Unary(op, Box::new(terminal_expr)), tokens)),
                 None => match tokens {
@@ -74,83 +140,235 @@ pub fn parse_unary_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError>
     }
 }
 
-pub fn parse_call_or_index_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
-    let (mut expr, mut tokens) = parse_terminal_expr(tokens)?;
+pub fn parse_call_or_index_expr(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &[T]), ParseError> {
+    let name_span = tokens.first().map(T::span).unwrap_or_default();
+    let (mut expr, mut tokens) = parse_terminal_expr(tokens, language)?;
     loop {
         (expr, tokens) = match tokens {
             [T(LPAREN, ..), rest @ ..] => {
-                let (args, rest) = parse_args(rest)?;
-                let call_expr = match check_builtin(&expr) {
-                    Some(builtin) => SugaredExpr::PrimitiveCall(builtin, args),
-                    None => SugaredExpr::Call(Box::new(expr), args),
+                let ((args, keyword_args), rest) = parse_args(rest, language)?;
+                let call_expr = match (check_builtin(&expr), keyword_args.is_empty()) {
+                    (Some(builtin), true) => SugaredExpr::PrimitiveCall(builtin, args, name_span),
+                    (Some(builtin), false) => {
+                        return Err(KeywordArgToBuiltin(builtin.name().to_string()))
+                    }
+                    (None, true) => SugaredExpr::Call(Box::new(expr), args, name_span),
+                    (None, false) => {
+                        SugaredExpr::KeywordCall(Box::new(expr), args, keyword_args, name_span)
+                    }
                 };
                 (call_expr, rest)
             }
             [T(L_SQUARE_BRACKET, ..), rest @ ..] => {
-                let (index_value_expr, rest) = parse_expr(rest)?;
+                let index_span = rest.first().map(T::span).unwrap_or_default();
+                let (index_value_expr, rest) = parse_expr(rest, language)?;
                 let rest = consume_token(R_SQUARE_BRACKET, rest)?;
-                let index_expr = SugaredExpr::Index(Box::new(expr), Box::new(index_value_expr));
+                let index_expr =
+                    SugaredExpr::Index(Box::new(expr), Box::new(index_value_expr), index_span);
                 (index_expr, rest)
             }
+            [T(DOT, ..), T(ID(field), ..), rest @ ..] => {
+                (SugaredExpr::Field(Box::new(expr), field.to_string()), rest)
+            }
             _ => break,
         }
     }
     return Ok((expr, tokens));
 }
 
-pub fn parse_terminal_expr(tokens: &[T]) -> Result<(SugaredExpr, &[T]), ParseError> {
+pub fn parse_terminal_expr(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &[T]), ParseError> {
     match tokens {
         [T(STR(s), ..), tokens @ ..] => Ok((SugaredExpr::Str(s.to_string()), tokens)),
+        // a block, `if` statement, or `loop` statement used as an expression
+        [T(first, ..), rest @ ..]
+            if matches!(first, L_CURLY_BRACKET)
+                || (matches!(first, KW(If)) && matches!(rest, [T(LPAREN, ..), ..]))
+                || (first.is_contextual(ContextualKeyword::Loop)
+                    && matches!(rest, [T(L_CURLY_BRACKET, ..), ..])) =>
+        {
+            match parse_statement(tokens, true, language)? {
+                (Some(statement), tokens) => {
+                    Ok((SugaredExpr::Statement(Box::new(statement)), tokens))
+                }
+                _ => Err(ExpectedStatement),
+            }
+        }
         [T(KW(True), ..), tokens @ ..] => Ok((SugaredExpr::Bool(true), tokens)),
         [T(KW(False), ..), tokens @ ..] => Ok((SugaredExpr::Bool(false), tokens)),
         [T(KW(Nil), ..), tokens @ ..] => Ok((SugaredExpr::Nil, tokens)),
         [T(KW(kw), ..), ..] => Err(KeywordAsVar(kw.to_string())),
+        // a block cannot start with `...`, so this is not a name followed by a block
+        [T(ID(name), ..), T(L_CURLY_BRACKET, ..), tokens @ ..]
+            if matches!(tokens, [T(DOT, ..), T(DOT, ..), T(DOT, ..), ..]) =>
+        {
+            parse_struct_update(name, &tokens[3..], language)
+        }
+        // a name qualified by the alias of an imported module, e.g. `math.hypot`, or an access
+        // of a field of a variable, which are told apart once the whole program is parsed
+        [T(ID(alias), ..), T(DOT, ..), T(ID(id), ..), tokens @ ..] => Ok((
+            SugaredExpr::Var(qualified_name(Some(alias), id)),
+            tokens,
+        )),
         [T(ID(id), ..), tokens @ ..] => Ok((SugaredExpr::Var(id.to_string()), tokens)),
-        [T(LPAREN, ..), tokens @ ..] => match parse_params(tokens) {
-            // if the next sequence of tokens is a params list, then parse a lambda expression
-            Ok((params, tokens)) => {
-                let tokens = consume_token(THIN_ARROW, tokens)?;
-                let (lambda_body, tokens) = match parse_statement(tokens, false)? {
+        [T(LPAREN, ..), tokens @ ..] => {
+            let params_result = parse_params(tokens);
+            // if the next sequence of tokens is a params list followed by an arrow, then parse a
+            // lambda expression
+            if let Ok((params, [T(THIN_ARROW, ..), tokens @ ..])) = params_result {
+                // the body of a lambda cannot be a multiple assignment unless it is in a block,
+                // since the names after the first one may be keyword arguments or values which
+                // follow the lambda, e.g. `f(g = () -> a, b = 1)`
+                if is_multi_assign(tokens) {
+                    let (expr, tokens) = parse_expr(tokens, language)?;
+                    let lambda_body = SugaredStatement::Expr(expr);
+                    return Ok((SugaredExpr::Lambda(params, Box::new(lambda_body)), tokens));
+                }
+                let (lambda_body, tokens) = match parse_statement(tokens, false, language)? {
                     (Some(statement), tokens) => (statement, tokens),
                     _ => return Err(ExpectedStatement),
                 };
                 return Ok((SugaredExpr::Lambda(params, Box::new(lambda_body)), tokens));
             }
-            // if the next sequence of tokens is a valid sequence of tokens, but not a params list,
-            // then parse a parenthesized expression
-            Err(UnexpectedToken(_)) => {
-                let (expr, tokens) = parse_expr(tokens)?;
-                let tokens = consume_token(RPAREN, tokens)?;
-                return Ok((expr, tokens));
+
+            // otherwise, parse a parenthesized expression, which may also look like the start of a
+            // params list, e.g. `(x)` or `(nil || x)`
+            let parenthesized_result = parse_expr(tokens, language)
+                .and_then(|(expr, tokens)| Ok((expr, consume_token(RPAREN, tokens)?)));
+            match (parenthesized_result, params_result) {
+                // a parenthesized expression followed by an arrow is an invalid params list
+                (Ok((_, [T(THIN_ARROW, ..), ..])), Err(e)) => Err(e),
+                (Ok(result), _) => Ok(result),
+                // if the next sequence of tokens is neither, report why it is not an expression,
+                // unless it is a params list which is only missing its arrow or a sequence of
+                // tokens which is invalid in a params list, e.g. `(if, let)`
+                (Err(_), Ok((_, tokens))) => Err(consume_token(THIN_ARROW, tokens)
+                    .expect_err("a params list without an arrow is not a lambda")),
+                (Err(e), Err(UnexpectedToken(_))) => Err(e),
+                (Err(_), Err(e)) => Err(e),
             }
-            // if the next sequence of tokens is not a valid sequence of tokens, return the error
-            Err(e) => return Err(e),
-        },
+        }
 
         [T(NUM(n), ..), tokens @ ..] => Ok((SugaredExpr::Num(*n), tokens)),
+        [T(L_SQUARE_BRACKET, ..), tokens @ ..] => {
+            let (elements, tokens) = parse_list_elements(tokens, language)?;
+            Ok((SugaredExpr::List(elements), tokens))
+        }
         tokens => Err(unexpected_token(tokens)),
     }
 }
 
-pub fn parse_args(tokens: &[T]) -> Result<(Vec<SugaredExpr>, &[T]), ParseError> {
-    match tokens {
-        [T(RPAREN, ..), tokens @ ..] => Ok((vec![], tokens)),
-        tokens => {
-            let (expr, tokens) = parse_expr(tokens)?;
-            let (mut rest_args, tokens) = parse_rest_args(tokens)?;
-
-            let mut vec = vec![expr];
-            vec.append(&mut rest_args);
-            return Ok((vec, tokens));
+/// The positional arguments and the keyword arguments of a call, e.g. `xs` and `style = "line"` in
+/// `plot(xs, style = "line")`.
+type Args = (Vec<SugaredExpr>, Vec<(String, SugaredExpr)>);
+
+/// Parses the arguments of a call up to and including its closing parenthesis. Keyword arguments
+/// must follow every positional argument and have distinct names.
+pub fn parse_args(tokens: &[T], language: LanguageVersion) -> Result<(Args, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let mut args = vec![];
+    let mut keyword_args: Vec<(String, SugaredExpr)> = vec![];
+    if cursor.eat(RPAREN) {
+        return Ok(((args, keyword_args), cursor.rest()));
+    }
+    loop {
+        match (cursor.peek_n(0), cursor.peek_n(1)) {
+            (Some(ID(name)), Some(ASSIGN)) => {
+                cursor.advance();
+                cursor.advance();
+                check_keyword_arg(&cursor)?;
+                let expr = cursor.parse(|tokens| parse_expr(tokens, language))?;
+                if keyword_args.iter().any(|(other, _)| other == name) {
+                    return Err(DuplicateKeywordArg(name.to_string()));
+                }
+                keyword_args.push((name.to_string(), expr));
+            }
+            _ => {
+                check_keyword_arg(&cursor)?;
+                let expr = cursor.parse(|tokens| parse_expr(tokens, language))?;
+                if let Some((name, _)) = keyword_args.last() {
+                    return Err(PositionalAfterKeywordArg(name.to_string()));
+                }
+                args.push(expr);
+            }
+        }
+
+        if cursor.eat(RPAREN) {
+            return Ok(((args, keyword_args), cursor.rest()));
         }
+        // a trailing comma is not allowed
+        if !cursor.at(&[COMMA]) || cursor.at(&[COMMA, RPAREN]) {
+            return Err(cursor.unexpected());
+        }
+        cursor.advance();
     }
 }
 
-pub fn parse_rest_args(tokens: &[T]) -> Result<(Vec<SugaredExpr>, &[T]), ParseError> {
-    match tokens {
-        [T(RPAREN, ..), tokens @ ..] => Ok((vec![], tokens)),
-        [T(COMMA, ..), T(RPAREN, ..), ..] => Err(unexpected_token(tokens)),
-        [T(COMMA, ..), tokens @ ..] => parse_args(tokens),
-        tokens => Err(unexpected_token(tokens)),
+/// Parses the elements of a list literal, e.g. `[1, 2, 3]`, up to and including its closing
+/// bracket.
+fn parse_list_elements(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(Vec<SugaredExpr>, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let mut elements = vec![];
+    if cursor.eat(R_SQUARE_BRACKET) {
+        return Ok((elements, cursor.rest()));
     }
+    loop {
+        elements.push(cursor.parse(|tokens| parse_expr(tokens, language))?);
+        if cursor.eat(R_SQUARE_BRACKET) {
+            return Ok((elements, cursor.rest()));
+        }
+        // a trailing comma is not allowed, as in the arguments of a call
+        if !cursor.at(&[COMMA]) || cursor.at(&[COMMA, R_SQUARE_BRACKET]) {
+            return Err(cursor.unexpected());
+        }
+        cursor.advance();
+    }
+}
+
+/// Parses the rest of a struct update of the struct `name`, e.g. `p, x: 5 }` of
+/// `Point { ...p, x: 5 }`, which follows the `...`.
+fn parse_struct_update<'a>(
+    name: &str,
+    tokens: &'a [T],
+    language: LanguageVersion,
+) -> Result<(SugaredExpr, &'a [T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let base = cursor.parse(|tokens| parse_expr(tokens, language))?;
+    let mut fields: Vec<(String, SugaredExpr)> = vec![];
+    while !cursor.eat(R_CURLY_BRACKET) {
+        cursor.expect(COMMA)?;
+        let field = match cursor.peek() {
+            Some(T(ID(field), ..)) => field.to_string(),
+            Some(T(KW(kw), ..)) => return Err(KeywordAsVar(kw.to_string())),
+            _ => return Err(cursor.unexpected()),
+        };
+        cursor.advance();
+        if fields.iter().any(|(other, _)| *other == field) {
+            return Err(DuplicateFieldUpdate(name.to_string(), field));
+        }
+        cursor.expect(COLON)?;
+        fields.push((field, cursor.parse(|tokens| parse_expr(tokens, language))?));
+    }
+    let update = SugaredExpr::StructUpdate(name.to_string(), Box::new(base), fields);
+    return Ok((update, cursor.rest()));
+}
+
+/// Returns a [KeywordAsArg] error if the argument at `cursor` is only a keyword which is not a
+/// value, e.g. the `if` in `f(true, if)`, which would otherwise be reported as a malformed `if`
+/// statement.
+fn check_keyword_arg(cursor: &TokenCursor) -> Result<(), ParseError> {
+    return match (cursor.peek(), cursor.peek_n(1)) {
+        (Some(token @ T(KW(kw), ..)), Some(COMMA | RPAREN)) if !matches!(kw, True | False | Nil) => {
+            Err(KeywordAsArg(token.clone()))
+        }
+        _ => Ok(()),
+    };
 }
@@ -1,50 +1,178 @@
 use crate::{
     error::ParseError::{self, *},
-    tokenizer::{Keyword::*, Token as T, TokenValue::*},
+    language::LanguageVersion,
+    tokenizer::{ContextualKeyword::*, Keyword::*, Token as T, TokenValue::*},
 };
 
-use super::utils::{ensure_block, unexpected_token};
+use super::cursor::TokenCursor;
+use super::expressions::parse_expr;
 use super::statements::parse_statement;
-use super::SugaredProcedure;
-
-pub fn parse_procs(tokens: &[T]) -> Result<(Vec<SugaredProcedure>, &[T]), ParseError> {
-    let (proc_option, tokens) = parse_proc(tokens)?;
-
-    match proc_option {
-        Some(proc) => {
-            let (mut rest_procs, tokens) = parse_procs(tokens)?;
-
-            if rest_procs
-                .clone()
-                .iter()
-                .find(|p| p.name == proc.name)
-                .is_some()
-            {
-                return Err(MultipleSameNamedProcs(proc.name.to_string()));
+use super::utils::{ensure_block, tokens_to_source, unexpected_token};
+use super::{
+    ContractKind, SugaredContract, SugaredImport, SugaredProcedure, SugaredProgram,
+    SugaredStatement, SugaredStruct,
+};
+
+/// Parses the top-level imports, procedures, and constants of a program. Every top-level
+/// definition must have a unique name. `import`, `pub`, `pure`, and `struct` are only treated as
+/// keywords at the top level, so they can still be used as identifiers elsewhere.
+pub fn parse_top_level(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredProgram, &[T]), ParseError> {
+    match tokens {
+        [T(import, ..), T(STR(path), ..), T(SEMICOLON, ..), tokens @ ..]
+            if import.is_contextual(Import) =>
+        {
+            let (mut program, tokens) = parse_top_level(tokens, language)?;
+            let import = SugaredImport {
+                path: path.to_string(),
+                alias: None,
+            };
+            program.imports.insert(0, import);
+            return Ok((program, tokens));
+        }
+        [T(import, ..), T(STR(path), ..), T(as_kw, ..), T(ID(alias), ..), T(SEMICOLON, ..), tokens @ ..]
+            if import.is_contextual(Import) && as_kw.is_contextual(As) =>
+        {
+            let (mut program, tokens) = parse_top_level(tokens, language)?;
+            if program.defines(alias) {
+                return Err(DuplicateTopLevelName(alias.to_string()));
+            }
+            let import = SugaredImport {
+                path: path.to_string(),
+                alias: Some(alias.to_string()),
+            };
+            program.imports.insert(0, import);
+            return Ok((program, tokens));
+        }
+        [T(KW(Const), ..), ..] => {
+            let (constant_option, tokens) = parse_statement(tokens, true, language)?;
+            let (name, expr) = match constant_option {
+                Some(SugaredStatement::Const(name, expr)) => (name, expr),
+                _ => return Err(unexpected_token(tokens)),
+            };
+
+            let (mut program, tokens) = parse_top_level(tokens, language)?;
+            if program.defines(&name) {
+                return Err(DuplicateTopLevelName(name));
+            }
+
+            program.constants.insert(0, (name, expr));
+            return Ok((program, tokens));
+        }
+        [T(struct_kw, ..), ..] if struct_kw.is_contextual(Struct) => {
+            let (definition, tokens) = parse_struct(tokens)?;
+            let (mut program, tokens) = parse_top_level(tokens, language)?;
+            if program.defines(&definition.name) {
+                return Err(DuplicateTopLevelName(definition.name));
+            }
+
+            program.structs.insert(0, definition);
+            return Ok((program, tokens));
+        }
+        [T(pub_kw, ..), rest @ ..] if pub_kw.is_contextual(Pub) => {
+            match parse_proc(rest, language)? {
+                (Some(proc), tokens) => parse_rest_top_level(
+                    SugaredProcedure {
+                        public: true,
+                        ..proc
+                    },
+                    tokens,
+                    language,
+                ),
+                (None, _) => Err(unexpected_token(rest)),
             }
+        }
+        tokens => match parse_proc(tokens, language)? {
+            (Some(proc), tokens) => parse_rest_top_level(proc, tokens, language),
+            (None, tokens) => Ok((SugaredProgram::default(), tokens)),
+        },
+    }
+}
 
-            let mut vec = vec![proc];
-            vec.append(&mut rest_procs);
-            return Ok((vec, tokens));
+/// Parses the top-level definitions following the procedure `proc` and adds `proc` to them.
+fn parse_rest_top_level(
+    proc: SugaredProcedure,
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredProgram, &[T]), ParseError> {
+    let (mut program, tokens) = parse_top_level(tokens, language)?;
+    if program.procedures.iter().any(|p| p.name == proc.name) {
+        return Err(MultipleSameNamedProcs(proc.name.to_string()));
+    }
+    if program.defines(&proc.name) {
+        return Err(DuplicateTopLevelName(proc.name.to_string()));
+    }
+
+    program.procedures.insert(0, proc);
+    return Ok((program, tokens));
+}
+
+/// Parses a struct declaration, e.g. `struct Point { x, y }`. The fields are separated by commas
+/// like the parameters of a procedure, and every field must have a different name.
+fn parse_struct(tokens: &[T]) -> Result<(SugaredStruct, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    cursor.advance(); // `struct`
+    let name = match cursor.eat_id() {
+        Some(name) => name.to_string(),
+        None => return Err(cursor.unexpected()),
+    };
+    cursor.expect(L_CURLY_BRACKET)?;
+
+    let mut fields: Vec<String> = vec![];
+    if cursor.eat(R_CURLY_BRACKET) {
+        return Ok((SugaredStruct { name, fields }, cursor.rest()));
+    }
+    loop {
+        let field = match cursor.peek_n(0) {
+            Some(KW(kw)) => return Err(KeywordAsParam(kw.to_string())),
+            Some(ID(field)) => field.to_string(),
+            _ => return Err(cursor.unexpected()),
+        };
+        cursor.advance();
+        if fields.contains(&field) {
+            return Err(DuplicateField(name, field));
+        }
+        fields.push(field);
+
+        if cursor.eat(R_CURLY_BRACKET) {
+            return Ok((SugaredStruct { name, fields }, cursor.rest()));
         }
-        None => Ok((vec![], tokens)),
+        // a trailing comma is not allowed
+        cursor.expect(COMMA)?;
     }
 }
 
-pub fn parse_proc(tokens: &[T]) -> Result<(Option<SugaredProcedure>, &[T]), ParseError> {
+pub fn parse_proc(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(Option<SugaredProcedure>, &[T]), ParseError> {
     match tokens {
+        [T(pure_kw, ..), rest @ ..]
+            if pure_kw.is_contextual(Pure) && matches!(rest, [T(KW(Proc), ..), ..]) =>
+        {
+            match parse_proc(rest, language)? {
+                (Some(proc), tokens) => Ok((Some(SugaredProcedure { pure: true, ..proc }), tokens)),
+                (None, _) => Err(unexpected_token(rest)),
+            }
+        }
         [T(KW(Proc), ..), T(KW(kw), ..), T(LPAREN, ..), ..] => Err(KeywordAsProc(kw.to_string())),
         [T(KW(Proc), ..), T(ID(name), ..), T(LPAREN, ..), rest @ ..] => {
             let (params, tokens) = parse_params(rest)?;
+            let (contracts, tokens) = parse_contracts(tokens, language)?;
 
-            let (body_block_option, tokens) = parse_statement(tokens, true)?;
+            let (body_block_option, tokens) = parse_statement(tokens, true, language)?;
             let body_block = ensure_block(body_block_option)?;
 
             Ok((
                 Some(SugaredProcedure {
                     name: name.to_string(),
                     params,
+                    contracts,
                     body: body_block,
+                    public: false,
+                    pure: false,
                 }),
                 tokens,
             ))
@@ -53,6 +181,38 @@ pub fn parse_proc(tokens: &[T]) -> Result<(Option<SugaredProcedure>, &[T]), Pars
     }
 }
 
+/// Parses the `requires(<expr>)` and `ensures(<expr>)` clauses between the parameters and the body
+/// of a procedure. `requires` and `ensures` are only treated as keywords in this position, so they
+/// can still be used as identifiers elsewhere.
+pub fn parse_contracts(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(Vec<SugaredContract>, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let mut contracts = vec![];
+    loop {
+        let kind = if cursor.eat_contextual(Requires) {
+            ContractKind::Requires
+        } else if cursor.eat_contextual(Ensures) {
+            ContractKind::Ensures
+        } else {
+            return Ok((contracts, cursor.rest()));
+        };
+        cursor.expect(LPAREN)?;
+
+        let start = cursor.mark();
+        let condition = cursor.parse(|tokens| parse_expr(tokens, language))?;
+        let source = tokens_to_source(cursor.span(start));
+        cursor.expect(RPAREN)?;
+
+        contracts.push(SugaredContract {
+            kind,
+            condition,
+            source,
+        });
+    }
+}
+
 pub fn parse_params(tokens: &[T]) -> Result<(Vec<String>, &[T]), ParseError> {
     match tokens {
         [T(RPAREN, ..), rest @ ..] => Ok((vec![], rest)),
@@ -1,43 +1,79 @@
 use crate::{
     error::ParseError::{self, *},
-    tokenizer::{Keyword::*, Token as T, TokenValue::*},
+    language::LanguageVersion,
+    tokenizer::{ContextualKeyword::*, Keyword::*, Token as T, TokenValue::*},
 };
 
 use super::{
+    cursor::TokenCursor,
     expressions::parse_expr,
     utils::{
-        conditionally_consume_semicolon, consume_token, ensure_block, is_assignment,
-        is_assignment_or_initialization,
+        can_omit_semicolon, conditionally_consume_semicolon, consume_token, ensure_block,
+        is_assignment,
+        is_assignment_or_initialization, tokens_to_source, NestingGuard,
     },
-    SugaredStatement,
+    Pattern, SugaredExpr, SugaredStatement,
 };
 
-pub fn parse_statements(tokens: &[T]) -> Result<(Vec<SugaredStatement>, &[T]), ParseError> {
-    let (statement_option, tokens) = parse_statement(tokens, true)?;
-
-    let statement = match statement_option {
-        Some(statement) => statement,
-        None => return Ok((vec![], tokens)),
-    };
+/// Parses the statements of a block up to and including its closing bracket. Each statement is
+/// preceded by a [Line](SugaredStatement::Line) marker with the line it starts on.
+pub fn parse_statements(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(Vec<SugaredStatement>, &[T]), ParseError> {
+    let mut statements = vec![];
+    let mut cursor = TokenCursor::new(tokens);
+    loop {
+        let start = cursor.mark();
+        match cursor.parse(|tokens| parse_statement(tokens, true, language))? {
+            Some(statement) => {
+                statements.push(line_marker(cursor.span(start)));
+                statements.push(statement);
+            }
+            None => return Ok((statements, cursor.rest())),
+        }
+    }
+}
 
-    let (mut rest_statements, tokens) = parse_statements(tokens)?;
-    let mut vec = vec![statement];
-    vec.append(&mut rest_statements);
-    Ok((vec, tokens))
+/// Creates a [Line](SugaredStatement::Line) marker for the statement made up of `tokens`.
+fn line_marker(tokens: &[T]) -> SugaredStatement {
+    let line = tokens.first().map_or(0, |T(_, line, _)| *line);
+    let line_length = tokens
+        .iter()
+        .take_while(|T(_, token_line, _)| *token_line == line)
+        .count();
+    return SugaredStatement::Line(line, tokens_to_source(&tokens[..line_length]));
 }
 
 pub fn parse_statement(
     tokens: &[T],
     parse_semicolon: bool,
+    language: LanguageVersion,
 ) -> Result<(Option<SugaredStatement>, &[T]), ParseError> {
+    let _guard = NestingGuard::enter(tokens)?;
+    let start = tokens;
     match tokens {
         [T(R_CURLY_BRACKET, ..), tokens @ ..] => Ok((None, tokens)),
+        [T(KW(Let), ..), tokens @ ..]
+            if matches!(tokens, [T(L_SQUARE_BRACKET | L_CURLY_BRACKET, ..), ..]) =>
+        {
+            let (pattern, tokens) = parse_pattern(tokens)?;
+            let tokens = consume_token(ASSIGN, tokens)?;
+            let (var_expr, tokens) = parse_expr(tokens, language)?;
+
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
+
+            Ok((
+                Some(SugaredStatement::LetPattern(pattern, var_expr)),
+                tokens,
+            ))
+        }
         [T(KW(Let), ..), T(KW(kw), ..), ..] => Err(KeywordAsVar(kw.to_string())),
         [T(KW(Const), ..), T(KW(kw), ..), ..] => Err(KeywordAsVar(kw.to_string())),
         [T(KW(Let), ..), T(ID(var_name), ..), T(ASSIGN, ..), tokens @ ..] => {
-            let (var_expr, tokens) = parse_expr(tokens)?;
+            let (var_expr, tokens) = parse_expr(tokens, language)?;
 
-            let tokens = conditionally_consume_semicolon(tokens, parse_semicolon)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
 
             Ok((
                 Some(SugaredStatement::Let(var_name.to_string(), var_expr)),
@@ -45,9 +81,9 @@ pub fn parse_statement(
             ))
         }
         [T(KW(Const), ..), T(ID(var_name), ..), T(ASSIGN, ..), tokens @ ..] => {
-            let (var_expr, tokens) = parse_expr(tokens)?;
+            let (var_expr, tokens) = parse_expr(tokens, language)?;
 
-            let tokens = conditionally_consume_semicolon(tokens, parse_semicolon)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
 
             Ok((
                 Some(SugaredStatement::Const(var_name.to_string(), var_expr)),
@@ -56,9 +92,9 @@ pub fn parse_statement(
         }
         [T(KW(kw), ..), T(ASSIGN, ..), ..] => Err(KeywordAsVar(kw.to_string())),
         [T(ID(var_name), ..), T(ASSIGN, ..), tokens @ ..] => {
-            let (var_expr, tokens) = parse_expr(tokens)?;
+            let (var_expr, tokens) = parse_expr(tokens, language)?;
 
-            let tokens = conditionally_consume_semicolon(tokens, parse_semicolon)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
 
             Ok((
                 Some(SugaredStatement::Assign(var_name.to_string(), var_expr)),
@@ -66,9 +102,9 @@ pub fn parse_statement(
             ))
         }
         [T(ID(var_name), ..), T(ASSIGN_OP(assign_op), ..), tokens @ ..] => {
-            let (var_expr, tokens) = parse_expr(tokens)?;
+            let (var_expr, tokens) = parse_expr(tokens, language)?;
 
-            let tokens = conditionally_consume_semicolon(tokens, parse_semicolon)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
 
             Ok((
                 Some(SugaredStatement::OperatorAssignment(
@@ -79,19 +115,24 @@ pub fn parse_statement(
                 tokens,
             ))
         }
+        [T(ID(_), ..), T(COMMA, ..), ..] if is_multi_assign(tokens) => {
+            let (statement, tokens) = parse_multi_assign(tokens, language)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
+            Ok((Some(statement), tokens))
+        }
         [T(KW(If), ..), T(LPAREN, ..), tokens @ ..] => {
-            let (cond_expr, tokens) = parse_expr(tokens)?;
+            let (cond_expr, tokens) = parse_expr(tokens, language)?;
             let tokens = consume_token(RPAREN, tokens)?;
-            let (then_block_option, mut tokens) = parse_statement(tokens, true)?;
+            let (then_block_option, mut tokens) = parse_statement(tokens, true, language)?;
             let then_block = ensure_block(then_block_option)?;
 
             let mut else_ifs = vec![];
             loop {
                 match tokens {
                     [T(KW(Else), ..), T(KW(If), ..), T(LPAREN, ..), rest @ ..] => {
-                        let (else_if_cond, rest) = parse_expr(rest)?;
+                        let (else_if_cond, rest) = parse_expr(rest, language)?;
                         let rest = consume_token(RPAREN, rest)?;
-                        let (else_if_block_option, rest) = parse_statement(rest, true)?;
+                        let (else_if_block_option, rest) = parse_statement(rest, true, language)?;
                         let else_if_block = ensure_block(else_if_block_option)?;
                         else_ifs.push((else_if_cond, else_if_block));
                         tokens = rest;
@@ -102,7 +143,7 @@ pub fn parse_statement(
 
             let (else_block_option, tokens) = match tokens {
                 [T(KW(Else), ..), tokens @ ..] => {
-                    let (else_block, tokens) = parse_statement(tokens, true)?;
+                    let (else_block, tokens) = parse_statement(tokens, true, language)?;
                     let else_block = ensure_block(else_block)?;
                     (Some(Box::new(else_block)), tokens)
                 }
@@ -120,9 +161,9 @@ pub fn parse_statement(
             ))
         }
         [T(KW(While), ..), T(LPAREN, ..), tokens @ ..] => {
-            let (while_cond_expr, tokens) = parse_expr(tokens)?;
+            let (while_cond_expr, tokens) = parse_expr(tokens, language)?;
             let tokens = consume_token(RPAREN, tokens)?;
-            let (while_block_option, tokens) = parse_statement(tokens, true)?;
+            let (while_block_option, tokens) = parse_statement(tokens, true, language)?;
             let while_block = ensure_block(while_block_option)?;
 
             Ok((
@@ -133,8 +174,16 @@ pub fn parse_statement(
                 tokens,
             ))
         }
+        [T(loop_kw, ..), tokens @ ..]
+            if loop_kw.is_contextual(Loop) && matches!(tokens, [T(L_CURLY_BRACKET, ..), ..]) =>
+        {
+            let (loop_block_option, tokens) = parse_statement(tokens, true, language)?;
+            let loop_block = ensure_block(loop_block_option)?;
+
+            Ok((Some(SugaredStatement::Loop(Box::new(loop_block))), tokens))
+        }
         [T(KW(For), ..), T(LPAREN, ..), tokens @ ..] => {
-            let (var_statement_option, tokens) = parse_statement(tokens, true)?;
+            let (var_statement_option, tokens) = parse_statement(tokens, true, language)?;
             let var_statement = match var_statement_option {
                 Some(statement) => {
                     if is_assignment_or_initialization(&statement) {
@@ -146,10 +195,10 @@ pub fn parse_statement(
                 None => return Err(ExpectedStatement),
             };
 
-            let (stop_cond_expr, tokens) = parse_expr(tokens)?;
+            let (stop_cond_expr, tokens) = parse_expr(tokens, language)?;
             let tokens = consume_token(SEMICOLON, tokens)?;
 
-            let (reassign_statement_option, tokens) = parse_statement(tokens, false)?;
+            let (reassign_statement_option, tokens) = parse_statement(tokens, false, language)?;
             let reassign_statement = match reassign_statement_option {
                 Some(statement) => {
                     if is_assignment(&statement) {
@@ -162,7 +211,7 @@ pub fn parse_statement(
             };
             let tokens = consume_token(RPAREN, tokens)?;
 
-            let (for_block_option, tokens) = parse_statement(tokens, true)?;
+            let (for_block_option, tokens) = parse_statement(tokens, true, language)?;
             let for_block_statements = match for_block_option {
                 Some(statement) => match statement {
                     SugaredStatement::Block(statements) => statements,
@@ -184,28 +233,168 @@ pub fn parse_statement(
         [T(KW(Return), ..), T(SEMICOLON, ..), tokens @ ..] => {
             Ok((Some(SugaredStatement::Return(None)), tokens))
         }
+        [T(KW(Return), ..), tokens @ ..] if can_omit_semicolon(start, tokens, language) => {
+            Ok((Some(SugaredStatement::Return(None)), tokens))
+        }
         [T(KW(Return), ..), tokens @ ..] => {
-            let (return_expr, tokens) = parse_expr(tokens)?;
-            let tokens = consume_token(SEMICOLON, tokens)?;
+            let (return_expr, tokens) = parse_expr(tokens, language)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, true, language)?;
             Ok((Some(SugaredStatement::Return(Some(return_expr))), tokens))
         }
+        [T(KW(Break), ..), T(SEMICOLON, ..), tokens @ ..] => {
+            Ok((Some(SugaredStatement::Break(None)), tokens))
+        }
+        [T(KW(Break), ..), tokens @ ..] if can_omit_semicolon(start, tokens, language) => {
+            Ok((Some(SugaredStatement::Break(None)), tokens))
+        }
         [T(KW(Break), ..), tokens @ ..] => {
-            let tokens = consume_token(SEMICOLON, tokens)?;
-            Ok((Some(SugaredStatement::Break), tokens))
+            let (break_expr, tokens) = parse_expr(tokens, language)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, true, language)?;
+            Ok((Some(SugaredStatement::Break(Some(break_expr))), tokens))
         }
         [T(KW(Continue), ..), tokens @ ..] => {
-            let tokens = consume_token(SEMICOLON, tokens)?;
+            let tokens = conditionally_consume_semicolon(start, tokens, true, language)?;
             Ok((Some(SugaredStatement::Continue), tokens))
         }
         [T(L_CURLY_BRACKET, ..), tokens @ ..] => {
-            let (statements, tokens) = parse_statements(tokens)?;
+            let (statements, tokens) = parse_statements(tokens, language)?;
             Ok((Some(SugaredStatement::Block(statements)), tokens))
         }
-        tokens => match parse_expr(tokens)? {
+        tokens => match parse_expr(tokens, language)? {
+            (target, [T(ASSIGN, ..), tokens @ ..]) if is_place_target(&target) => {
+                let (var_expr, tokens) = parse_expr(tokens, language)?;
+
+                let tokens =
+                    conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
+
+                Ok((
+                    Some(SugaredStatement::PlaceAssign(target, None, var_expr)),
+                    tokens,
+                ))
+            }
+            (target, [T(ASSIGN_OP(assign_op), ..), tokens @ ..]) if is_place_target(&target) => {
+                let (var_expr, tokens) = parse_expr(tokens, language)?;
+
+                let tokens =
+                    conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
+
+                Ok((
+                    Some(SugaredStatement::PlaceAssign(
+                        target,
+                        Some(*assign_op),
+                        var_expr,
+                    )),
+                    tokens,
+                ))
+            }
+            // the last expression of a block does not need a semicolon, e.g. `{ 1 }`
+            (expr, tokens @ [T(R_CURLY_BRACKET, ..), ..]) => {
+                Ok((Some(SugaredStatement::Expr(expr)), tokens))
+            }
             (expr, tokens) => {
-                let tokens = conditionally_consume_semicolon(tokens, parse_semicolon)?;
+                let tokens =
+                    conditionally_consume_semicolon(start, tokens, parse_semicolon, language)?;
                 Ok((Some(SugaredStatement::Expr(expr)), tokens))
             }
         },
     }
 }
+
+/// Returns true if `expr` can be the target of a [PlaceAssign](SugaredStatement::PlaceAssign),
+/// i.e. an element such as `xs[i]` or a field such as `p.x`. Until the whole program is parsed, a
+/// field of a variable is a qualified name (see [qualified_name](super::qualified_name)).
+fn is_place_target(expr: &SugaredExpr) -> bool {
+    return match expr {
+        SugaredExpr::Index(..) | SugaredExpr::Field(..) => true,
+        SugaredExpr::Var(name) => name.contains('.'),
+        _ => false,
+    };
+}
+
+/// Returns true if `tokens` start with a comma-separated list of names followed by `=`. Otherwise,
+/// the names are the start of an expression, e.g. the body of the lambda in `f((x) -> x, y)`.
+pub fn is_multi_assign(tokens: &[T]) -> bool {
+    let mut cursor = TokenCursor::new(tokens);
+    while cursor.eat_id().is_some() {
+        if cursor.eat(ASSIGN) {
+            return true;
+        }
+        if !cursor.eat(COMMA) {
+            return false;
+        }
+    }
+    return false;
+}
+
+/// Parses a multiple assignment, e.g. `a, b = b, a`, without its semicolon.
+fn parse_multi_assign(
+    tokens: &[T],
+    language: LanguageVersion,
+) -> Result<(SugaredStatement, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let mut names = vec![];
+    // the names are followed by `=`, which is checked by [is_multi_assign]
+    while let Some(name) = cursor.eat_id() {
+        names.push(name.to_string());
+        if cursor.eat(ASSIGN) {
+            break;
+        }
+        cursor.expect(COMMA)?;
+    }
+
+    let mut exprs = vec![cursor.parse(|tokens| parse_expr(tokens, language))?];
+    while cursor.eat(COMMA) {
+        exprs.push(cursor.parse(|tokens| parse_expr(tokens, language))?);
+    }
+
+    if names.len() != exprs.len() {
+        return Err(AssignmentCountMismatch(names.len(), exprs.len()));
+    }
+    return Ok((SugaredStatement::MultiAssign(names, exprs), cursor.rest()));
+}
+
+/// Parses the pattern of a destructuring `let` statement, e.g. `[first, second, rest...]` or
+/// `{x, y}`.
+fn parse_pattern(tokens: &[T]) -> Result<(Pattern, &[T]), ParseError> {
+    let mut cursor = TokenCursor::new(tokens);
+    let is_list = cursor.eat(L_SQUARE_BRACKET);
+    let closing_bracket = if is_list {
+        R_SQUARE_BRACKET
+    } else {
+        cursor.expect(L_CURLY_BRACKET)?;
+        R_CURLY_BRACKET
+    };
+
+    let mut names: Vec<String> = vec![];
+    let mut rest = None;
+    while !cursor.eat(closing_bracket.clone()) {
+        if !names.is_empty() {
+            cursor.expect(COMMA)?;
+        }
+        let name = match cursor.peek() {
+            Some(T(ID(name), ..)) => name.to_string(),
+            Some(T(KW(kw), ..)) => return Err(KeywordAsVar(kw.to_string())),
+            _ => return Err(cursor.unexpected()),
+        };
+        cursor.advance();
+        if names.contains(&name) {
+            return Err(DuplicatePatternName(name));
+        }
+        // the rest of a list pattern must be its last name
+        if is_list && cursor.at(&[DOT, DOT, DOT]) {
+            for _ in 0..3 {
+                cursor.advance();
+            }
+            cursor.expect(closing_bracket)?;
+            rest = Some(name);
+            break;
+        }
+        names.push(name);
+    }
+
+    let pattern = match is_list {
+        true => Pattern::List(names, rest),
+        false => Pattern::Map(names),
+    };
+    return Ok((pattern, cursor.rest()));
+}
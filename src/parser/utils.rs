@@ -1,3 +1,5 @@
+use std::cell::Cell;
+
 use crate::tokenizer::Operator::{self, *};
 use crate::{
     error::ParseError::{self, *},
@@ -7,8 +9,56 @@ use crate::{
     },
 };
 
+use crate::language::LanguageVersion;
+
 use super::{Builtin, SugaredExpr, SugaredStatement};
 
+/// The maximum depth to which expressions and statements can be nested (see [NestingGuard]).
+pub const MAX_NESTING_DEPTH: usize = 256;
+
+/// The maximum number of binary operators in a chain such as `1 + 2 * 3 - 4`. A chain is parsed in
+/// a loop rather than by nesting, but it is still parsed into a tree which later passes recurse
+/// into, so its length is limited separately from [MAX_NESTING_DEPTH].
+pub const MAX_CHAIN_LENGTH: usize = 1024;
+
+thread_local! {
+    static NESTING_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A level of nesting of the expression or statement being parsed. The parser, and every later pass
+/// over the parsed program, recurses into nested expressions and statements, so a program nested
+/// deeper than [MAX_NESTING_DEPTH] is rejected with a [NestingTooDeep] error rather than
+/// overflowing the stack. The operators of a chain such as `1 + 1 + ... + 1` do not count as levels
+/// of nesting, the length of a chain is limited by [MAX_CHAIN_LENGTH] instead.
+///
+/// The limit is chosen so that a program nested as deeply as it allows can be parsed and run on a
+/// thread with an 8 MiB stack, the usual size of a main thread, even in a debug build. Embedders
+/// which parse programs on threads with smaller stacks, such as the 2 MiB default of spawned
+/// threads, may need to give those threads a larger stack.
+pub struct NestingGuard;
+
+impl NestingGuard {
+    /// Enters a level of nesting which starts at `tokens`. The level is left when the guard is
+    /// dropped.
+    pub fn enter(tokens: &[T]) -> Result<Self, ParseError> {
+        let depth = NESTING_DEPTH.get() + 1;
+        if depth > MAX_NESTING_DEPTH {
+            return Err(match tokens {
+                [token, ..] => NestingTooDeep(token.to_owned()),
+                [] => UnexpectedEOF,
+            });
+        }
+        NESTING_DEPTH.set(depth);
+        return Ok(NestingGuard);
+    }
+}
+
+impl Drop for NestingGuard {
+    fn drop(&mut self) {
+        NESTING_DEPTH.set(NESTING_DEPTH.get() - 1);
+    }
+}
+
 /// A helper function to handle unexpected token patterns. This function returns an
 /// [UnexpectedToken Error](UnexpectedToken), or an [Unexpected End-of-File](UnexpectedEOF) if
 /// `tokens` is empty.
@@ -19,18 +69,47 @@ pub fn unexpected_token(tokens: &[T]) -> ParseError {
     }
 }
 
-/// A helper function to check if `s` matches one of the [Builtin] procedures.
+/// Reconstructs the source text of the expression or statement made up of `tokens`, e.g. for use
+/// in error messages. Tokens are separated by a single space, except after opening brackets, before
+/// closing brackets, commas, and semicolons, between a procedure and its arguments, and between a
+/// unary operator and its operand.
+pub fn tokens_to_source(tokens: &[T]) -> String {
+    let mut source = String::new();
+    let mut previous: Option<&TokenValue> = None;
+    let mut previous_is_unary = false;
+    for T(value, ..) in tokens {
+        let is_unary = matches!(
+            value,
+            OP(Minus | LogicNot | BitNot) | DOUBLE_PLUS | DOUBLE_MINUS
+        ) && matches!(
+            previous,
+            None | Some(
+                OP(_) | KW(_) | ASSIGN_OP(_) | LPAREN | L_SQUARE_BRACKET | COMMA | SEMICOLON
+            )
+        );
+        let needs_space = match (previous, value) {
+            (None, _) => false,
+            (Some(LPAREN | L_SQUARE_BRACKET | DOT), _) => false,
+            (_, RPAREN | R_SQUARE_BRACKET | COMMA | DOT | SEMICOLON) => false,
+            (Some(ID(_) | RPAREN | R_SQUARE_BRACKET), LPAREN | L_SQUARE_BRACKET) => false,
+            (Some(ID(_) | RPAREN | R_SQUARE_BRACKET), DOUBLE_PLUS | DOUBLE_MINUS) => false,
+            _ => !previous_is_unary,
+        };
+        if needs_space {
+            source.push(' ');
+        }
+        source.push_str(&value.to_string());
+        previous = Some(value);
+        previous_is_unary = is_unary;
+    }
+    return source;
+}
+
+/// A helper function to check if `expr` names one of the [Builtin] procedures (see
+/// [BUILTINS](crate::builtins::BUILTINS)).
 pub fn check_builtin(expr: &SugaredExpr) -> Option<Builtin> {
     match expr {
-        SugaredExpr::Var(name) => match name.as_str() {
-            "print" => Some(Builtin::Print),
-            "list" => Some(Builtin::List),
-            "is_empty" => Some(Builtin::IsEmpty),
-            "is_nil" => Some(Builtin::IsNil),
-            "head" => Some(Builtin::Head),
-            "rest" => Some(Builtin::Rest),
-            _ => None,
-        },
+        SugaredExpr::Var(name) => Builtin::from_name(name),
         _ => None,
     }
 }
@@ -48,19 +127,41 @@ pub fn consume_token(target: TokenValue, tokens: &[T]) -> Result<&[T], ParseErro
 
 /// This function conditionally tries to consume a [SEMICOLON] token if `should_consume` is true.
 /// If `should_consume` is true, then this function returns the result of [consume_token] with a
-/// `target` of [SEMICOLON]. If `should_consume` is false, then this function returns the `tokens`
-/// list unmodified.
-pub fn conditionally_consume_semicolon(
-    tokens: &[T],
+/// `target` of [SEMICOLON], unless the semicolon can be left out (see [can_omit_semicolon]). If
+/// `should_consume` is false, then this function returns the `tokens` list unmodified. The
+/// statement which the semicolon ends starts at `statement`.
+pub fn conditionally_consume_semicolon<'a>(
+    statement: &[T],
+    tokens: &'a [T],
     should_consume: bool,
-) -> Result<&[T], ParseError> {
-    if should_consume {
+    language: LanguageVersion,
+) -> Result<&'a [T], ParseError> {
+    if should_consume && !can_omit_semicolon(statement, tokens, language) {
         return consume_token(SEMICOLON, tokens);
+    } else if should_consume {
+        return Ok(consume_token(SEMICOLON, tokens).unwrap_or(tokens));
     } else {
         return Ok(tokens);
     }
 }
 
+/// Returns true if the semicolon after the statement which starts at `statement` and is followed by
+/// `tokens` can be left out in version `language` of the grammar. Since [V2](LanguageVersion::V2),
+/// this is the case when the statement is the last one on its line or in its block.
+pub fn can_omit_semicolon(statement: &[T], tokens: &[T], language: LanguageVersion) -> bool {
+    if language < LanguageVersion::V2 {
+        return false;
+    }
+    let last_line = match statement[..statement.len() - tokens.len()].last() {
+        Some(T(_, line, _)) => *line,
+        None => return false,
+    };
+    return match tokens {
+        [] | [T(R_CURLY_BRACKET, ..), ..] => true,
+        [T(_, line, _), ..] => *line > last_line,
+    };
+}
+
 /// This function tries to consume an [OP] token with an associated [Operator] found in `operators`.
 /// If such a token is successfully consumed, this function returns the token's operator and the
 /// list of tokens that comes after as a pair. If `tokens` does not start with such an operator,
@@ -81,35 +182,17 @@ pub fn match_operator<'a>(operators: &[Operator], tokens: &'a [T]) -> Option<(Op
     }
 }
 
-/// Type alias for the return value of a binary expression parsing function.
-type BinaryExpressionParser = fn(&[T]) -> Result<(SugaredExpr, &[T]), ParseError>;
-
-/// A helper function for parsing binary expressions.
-pub fn parse_binary_expr(
-    parse_expr: BinaryExpressionParser,
-    operators: Vec<Operator>,
-    tokens: &[T],
-) -> Result<(SugaredExpr, &[T]), ParseError> {
-    let (mut expr, mut tokens) = parse_expr(tokens)?;
-    loop {
-        match match_operator(operators.as_slice(), tokens) {
-            Some((op, rest)) => {
-                let (right, rest) = parse_expr(rest)?;
-                expr = binary_expression(op, expr, right);
-                tokens = rest;
-            }
-            None => return Ok((expr, tokens)),
-        }
-    }
-}
-
-/// A helper function for creating a [Binary Expression](SugaredExpr::Binary)
+/// A helper function for creating a [Binary Expression](SugaredExpr::Binary) whose operands start
+/// with the tokens `first_token` and `second_token`.
 pub fn binary_expression(
     op: Operator,
     first_arg: SugaredExpr,
+    first_token: &T,
     second_arg: SugaredExpr,
+    second_token: &T,
 ) -> SugaredExpr {
-    SugaredExpr::Binary(op, Box::new(first_arg), Box::new(second_arg))
+    let spans = (first_token.span(), second_token.span());
+    SugaredExpr::Binary(op, Box::new(first_arg), Box::new(second_arg), spans)
 }
 
 /// Ensures that `statement_option` is a Some variant which contains a
@@ -131,6 +214,8 @@ pub fn is_assignment(statement: &SugaredStatement) -> bool {
     match statement {
         SugaredStatement::Assign(_, _) => true,
         SugaredStatement::OperatorAssignment(_, _, _) => true,
+        SugaredStatement::PlaceAssign(_, _, _) => true,
+        SugaredStatement::MultiAssign(_, _) => true,
         SugaredStatement::Expr(expr) => match expr {
             SugaredExpr::Unary(op, _) => match op {
                 PreIncrement | PostIncrement | PreDecrement | PostDecrement => true,
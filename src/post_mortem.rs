@@ -0,0 +1,96 @@
+//! Post-mortem debugging, which lets the state of a program be inspected after it stops with a
+//! runtime error. Once it is enabled with [Writer::with_post_mortem](crate::Writer::with_post_mortem),
+//! the interpreter records where the error occurred as it propagates out of the program, and the
+//! recorded [PostMortem] can then be browsed with [inspect].
+
+use std::io::{self, BufRead, Write};
+
+use crate::environment::Environment;
+
+/// The state of a program at the point where it stopped with a runtime error.
+#[derive(Clone, Debug, Default)]
+pub struct PostMortem {
+    /// The line of the statement which was being executed when the error occurred, or 0 if the
+    /// error occurred before any statement was executed.
+    pub line: usize,
+    /// The environment of the innermost block or procedure call that the error occurred in, or
+    /// `None` if it occurred outside of any procedure, such as in a top-level constant.
+    pub env: Option<Environment>,
+    /// The names of the procedures which were being called when the error occurred, innermost
+    /// first. A lambda is named after the variable it was bound to or called through, or is listed
+    /// as `<lambda>` if it has no name.
+    pub call_stack: Vec<String>,
+}
+
+impl PostMortem {
+    /// Records `env` as the environment the error occurred in, along with the `line` being
+    /// executed. Only the first environment is kept, since the error reaches the innermost block
+    /// first.
+    pub(crate) fn record_env(&mut self, env: &Environment, line: usize) {
+        if self.env.is_none() {
+            self.env = Some(env.clone());
+            self.line = line;
+        }
+    }
+
+    /// Records that the error propagated out of a call to the procedure `name`.
+    pub(crate) fn record_call(&mut self, name: String) {
+        self.call_stack.push(name);
+    }
+}
+
+/// The commands understood by [inspect].
+const HELP: &str = "commands:
+  env           show the variables in scope where the error occurred
+  stack         show the procedures which were being called, innermost first
+  print <name>  show the value of a variable
+  help          show this message
+  quit          stop inspecting";
+
+/// Runs an interactive inspector for `post_mortem`, which reads commands from `input` and writes
+/// their results to `output` until it reads `quit` or reaches the end of `input`. A prompt is
+/// written before each command is read.
+pub fn inspect(
+    post_mortem: &PostMortem,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    writeln!(
+        output,
+        "stopped on line {} (type \"help\" for the commands)",
+        post_mortem.line
+    )?;
+    let mut lines = input.lines();
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let Some(line) = lines.next() else {
+            writeln!(output)?;
+            return Ok(());
+        };
+        let line = line?;
+        match line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [] => (),
+            ["env"] => match &post_mortem.env {
+                Some(env) => writeln!(output, "{env}")?,
+                None => writeln!(output, "the error occurred outside of any procedure")?,
+            },
+            ["stack"] => {
+                for (depth, name) in post_mortem.call_stack.iter().enumerate() {
+                    writeln!(output, "#{depth} {name}")?;
+                }
+            }
+            ["print", name] => match post_mortem.env.as_ref().map(|env| env.get(*name)) {
+                Some(Ok(value)) => writeln!(output, "{}", value.to_debug_string())?,
+                _ => writeln!(output, "unknown variable \"{name}\"")?,
+            },
+            ["help"] => writeln!(output, "{HELP}")?,
+            ["quit"] => return Ok(()),
+            _ => writeln!(
+                output,
+                "unknown command \"{}\" (type \"help\" for the commands)",
+                line.trim()
+            )?,
+        }
+    }
+}
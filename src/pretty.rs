@@ -0,0 +1,537 @@
+//! Printing parsed programs back to source text. The printed source parses to the same program it
+//! was printed from, except for the [Line](SugaredStatement::Line) markers, which are not printed
+//! and are recreated from the layout of the printed source. Parentheses are only printed where they
+//! are needed to keep the structure of an expression, and blocks are indented by two spaces.
+//!
+//! [format_source] formats the source of a whole program in the same way, except for the lines
+//! between `// fmt: off` and `// fmt: on` comments, which are kept as they are written.
+
+use crate::{
+    error::ParseError,
+    language::{LanguageVersion, PRAGMA},
+    parser::{
+        self, parse_definitions, ParsedDefinition, Pattern, SugaredContract, SugaredExpr,
+        SugaredImport, SugaredProcedure, SugaredProgram, SugaredStatement, SugaredStruct,
+        BINARY_OPERATORS,
+    },
+    tokenizer::{
+        Operator::{self, *},
+        TokenStream,
+    },
+};
+
+const INDENT: &str = "  ";
+/// The comment which starts a region of lines that [format_source] keeps as they are written.
+const FMT_OFF: &str = "// fmt: off";
+/// The comment which ends a region started by [FMT_OFF].
+const FMT_ON: &str = "// fmt: on";
+
+/// The precedence of a lambda, which extends as far to the right as possible.
+const LAMBDA_PRECEDENCE: u8 = 0;
+/// The precedence of prefix and postfix unary expressions, which bind tighter than every binary
+/// operator.
+const UNARY_PRECEDENCE: u8 = BINARY_OPERATORS.len() as u8 + 1;
+/// The precedence of calls, indexing, and field accesses.
+const POSTFIX_PRECEDENCE: u8 = UNARY_PRECEDENCE + 1;
+/// The precedence of literals, variables, and statements used as expressions.
+const ATOM_PRECEDENCE: u8 = POSTFIX_PRECEDENCE + 1;
+
+/// Returns the source text of `program`. Imports are printed first, followed by constants, structs,
+/// and then procedures.
+pub fn pretty_program(program: &SugaredProgram) -> String {
+    let mut source = String::new();
+    for SugaredImport { path, alias } in &program.imports {
+        source.push_str(&format!("import {}", pretty_string(path)));
+        if let Some(alias) = alias {
+            source.push_str(&format!(" as {alias}"));
+        }
+        source.push_str(";\n");
+    }
+    for (name, expr) in &program.constants {
+        source.push_str(&format!("const {name} = {};\n", expr_at(expr, 0, 0)));
+    }
+    for SugaredStruct { name, fields } in &program.structs {
+        source.push_str(&format!("struct {name} {{ {} }}\n", fields.join(", ")));
+    }
+    for proc in &program.procedures {
+        source.push_str(&pretty_procedure(proc));
+        source.push('\n');
+    }
+    return source;
+}
+
+/// Returns the source text of the procedure definition `proc`.
+pub fn pretty_procedure(proc: &SugaredProcedure) -> String {
+    let public = if proc.public { "pub " } else { "" };
+    let pure = if proc.pure { "pure " } else { "" };
+    let contracts: String = proc
+        .contracts
+        .iter()
+        .map(
+            |SugaredContract {
+                 kind, condition, ..
+             }| { format!(" {kind}({})", expr_at(condition, 0, 0)) },
+        )
+        .collect();
+    return format!(
+        "{public}{pure}proc {}({}){contracts} {}",
+        proc.name,
+        proc.params.join(", "),
+        statement(&proc.body, 0)
+    );
+}
+
+/// Formats the source of a program. Each top-level definition is printed as by [pretty_program], in
+/// the order they are written and separated by blank lines, except that consecutive imports and
+/// consecutive constants are not. The shebang, the `#language` pragma, and the line comments at the
+/// start of the source (e.g. its [Metadata](crate::metadata::Metadata)) are kept.
+///
+/// The lines from a `// fmt: off` comment to the next `// fmt: on` comment, or to the end of the
+/// source if there is none, are kept as they are written. Since definitions are formatted as a
+/// whole, a region which starts or ends inside a definition is extended to the whole definition.
+/// Any other comment is dropped.
+pub fn format_source(source: &str) -> Result<String, ParseError> {
+    let language = LanguageVersion::parse(source)?;
+    let definitions = parse_definitions(TokenStream::new(source), language)?;
+    let lines: Vec<&str> = source.split('\n').collect();
+
+    let mut chunks = vec![];
+    let header = header_lines(&lines);
+    if !header.is_empty() {
+        chunks.push((0, Chunk::Verbatim(header.join("\n"))));
+    }
+    let regions = unformatted_regions(&lines, &definitions);
+    for &(first, last) in &regions {
+        let region = trim_blank_lines(&lines[first - 1..last]);
+        chunks.push((first, Chunk::Verbatim(region.join("\n"))));
+    }
+    for (program, (first, last)) in definitions {
+        if !regions
+            .iter()
+            .any(|&region| overlaps(region, (first, last)))
+        {
+            chunks.push((first, Chunk::Definition(program)));
+        }
+    }
+    chunks.sort_by_key(|(line, _)| *line);
+
+    let mut formatted = String::new();
+    let mut previous: Option<&Chunk> = None;
+    for (_, chunk) in &chunks {
+        if let Some(previous) = previous {
+            formatted.push_str(if chunk.groups_with(previous) {
+                "\n"
+            } else {
+                "\n\n"
+            });
+        }
+        match chunk {
+            Chunk::Verbatim(text) => formatted.push_str(text),
+            Chunk::Definition(program) => formatted.push_str(pretty_program(program).trim_end()),
+        }
+        previous = Some(chunk);
+    }
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+    return Ok(formatted);
+}
+
+/// Returns the source text of `statement`, including its semicolon if it has one.
+pub fn pretty_statement(statement: &SugaredStatement) -> String {
+    return self::statement(statement, 0);
+}
+
+/// Returns the source text of `expr`.
+pub fn pretty_expr(expr: &SugaredExpr) -> String {
+    return expr_at(expr, 0, 0);
+}
+
+/// Returns the source text of `statement` indented by `indent` levels after its first line.
+fn statement(statement: &SugaredStatement, indent: usize) -> String {
+    return match statement {
+        SugaredStatement::Block(_)
+        | SugaredStatement::If(..)
+        | SugaredStatement::While(..)
+        | SugaredStatement::Loop(..)
+        | SugaredStatement::For(..)
+        | SugaredStatement::Line(..) => statement_without_semicolon(statement, indent),
+        statement => format!("{};", statement_without_semicolon(statement, indent)),
+    };
+}
+
+/// Returns the source text of `statement` without the semicolon which ends simple statements, as
+/// they are written in the header of a `for` loop or as the body of a lambda.
+fn statement_without_semicolon(statement: &SugaredStatement, indent: usize) -> String {
+    return match statement {
+        SugaredStatement::Expr(expr) => {
+            // an expression statement cannot start with a block or `if`, which would be parsed as
+            // a statement instead
+            if starts_with_statement(expr) {
+                format!("({})", expr_at(expr, 0, indent))
+            } else {
+                expr_at(expr, 0, indent)
+            }
+        }
+        SugaredStatement::Let(name, expr) => format!("let {name} = {}", expr_at(expr, 0, indent)),
+        SugaredStatement::LetPattern(pattern, expr) => {
+            format!(
+                "let {} = {}",
+                self::pattern(pattern),
+                expr_at(expr, 0, indent)
+            )
+        }
+        SugaredStatement::Const(name, expr) => {
+            format!("const {name} = {}", expr_at(expr, 0, indent))
+        }
+        SugaredStatement::Assign(name, expr) => format!("{name} = {}", expr_at(expr, 0, indent)),
+        SugaredStatement::OperatorAssignment(op, name, expr) => {
+            format!("{name} {op} {}", expr_at(expr, 0, indent))
+        }
+        SugaredStatement::PlaceAssign(target, op, expr) => {
+            let target_source = expr_at(target, POSTFIX_PRECEDENCE, indent);
+            // like an expression statement, the target cannot start with a block or `if`
+            let target_source = if starts_with_statement(target) {
+                format!("({target_source})")
+            } else {
+                target_source
+            };
+            match op {
+                Some(op) => format!("{target_source} {op} {}", expr_at(expr, 0, indent)),
+                None => format!("{target_source} = {}", expr_at(expr, 0, indent)),
+            }
+        }
+        SugaredStatement::MultiAssign(names, exprs) => {
+            let exprs: Vec<String> = exprs.iter().map(|expr| expr_at(expr, 0, indent)).collect();
+            format!("{} = {}", names.join(", "), exprs.join(", "))
+        }
+        SugaredStatement::Block(statements) => block(statements, indent),
+        SugaredStatement::If(cond, then_block, else_ifs, else_block) => {
+            let mut source = format!(
+                "if ({}) {}",
+                expr_at(cond, 0, indent),
+                self::statement(then_block, indent)
+            );
+            for (else_if_cond, else_if_block) in else_ifs {
+                source.push_str(&format!(
+                    " else if ({}) {}",
+                    expr_at(else_if_cond, 0, indent),
+                    self::statement(else_if_block, indent)
+                ));
+            }
+            if let Some(else_block) = else_block {
+                source.push_str(&format!(" else {}", self::statement(else_block, indent)));
+            }
+            source
+        }
+        SugaredStatement::While(cond, body) => format!(
+            "while ({}) {}",
+            expr_at(cond, 0, indent),
+            self::statement(body, indent)
+        ),
+        SugaredStatement::Loop(body) => format!("loop {}", self::statement(body, indent)),
+        SugaredStatement::For(init, cond, update, body) => format!(
+            "for ({} {}; {}) {}",
+            self::statement(init, indent),
+            expr_at(cond, 0, indent),
+            statement_without_semicolon(update, indent),
+            block(body, indent)
+        ),
+        SugaredStatement::Break(None) => "break".to_string(),
+        SugaredStatement::Break(Some(expr)) => format!("break {}", expr_at(expr, 0, indent)),
+        SugaredStatement::Continue => "continue".to_string(),
+        SugaredStatement::Return(None) => "return".to_string(),
+        SugaredStatement::Return(Some(expr)) => format!("return {}", expr_at(expr, 0, indent)),
+        SugaredStatement::Line(..) => String::new(),
+    };
+}
+
+/// A part of a source formatted by [format_source].
+enum Chunk {
+    /// Lines which are kept as they are written.
+    Verbatim(String),
+    /// A top-level definition, which is printed by [pretty_program].
+    Definition(SugaredProgram),
+}
+
+impl Chunk {
+    /// Returns true if the chunk is not separated from the `previous` chunk by a blank line, which
+    /// is the case for consecutive imports and for consecutive constants.
+    fn groups_with(&self, previous: &Chunk) -> bool {
+        let (Chunk::Definition(program), Chunk::Definition(previous)) = (self, previous) else {
+            return false;
+        };
+        let only_imports = |program: &SugaredProgram| {
+            program.constants.is_empty()
+                && program.structs.is_empty()
+                && program.procedures.is_empty()
+        };
+        let only_constants = |program: &SugaredProgram| {
+            program.imports.is_empty()
+                && program.structs.is_empty()
+                && program.procedures.is_empty()
+        };
+        return (only_imports(program) && only_imports(previous))
+            || (only_constants(program) && only_constants(previous));
+    }
+}
+
+/// Returns the lines at the start of a source which [format_source] keeps: the shebang, the
+/// pragma, and line comments, up to the first [FMT_OFF] comment and without trailing blank lines.
+fn header_lines<'a>(lines: &[&'a str]) -> Vec<&'a str> {
+    let header: Vec<&str> = lines
+        .iter()
+        .enumerate()
+        .take_while(|(line_num, line)| {
+            let line = line.trim();
+            line != FMT_OFF
+                && ((*line_num == 0 && line.starts_with("#!"))
+                    || line.starts_with(PRAGMA)
+                    || line.starts_with("//")
+                    || line.is_empty())
+        })
+        .map(|(_, line)| *line)
+        .collect();
+    return trim_blank_lines(&header).to_vec();
+}
+
+/// Returns the first and last lines (counted from 1) of the regions of `lines` which are kept as
+/// they are written, in order. Each region starts at an [FMT_OFF] comment and ends at the next
+/// [FMT_ON] comment, and is extended to cover every definition which it overlaps.
+fn unformatted_regions(lines: &[&str], definitions: &[ParsedDefinition]) -> Vec<(usize, usize)> {
+    let mut regions: Vec<(usize, usize)> = vec![];
+    let mut off = None;
+    for (line_num, line) in lines.iter().enumerate() {
+        match (off, line.trim()) {
+            (None, FMT_OFF) => off = Some(line_num + 1),
+            (Some(first), FMT_ON) => {
+                regions.push((first, line_num + 1));
+                off = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(first) = off {
+        regions.push((first, lines.len()));
+    }
+
+    for region in &mut regions {
+        loop {
+            let (mut first, mut last) = *region;
+            for &(_, definition) in definitions {
+                if overlaps(*region, definition) {
+                    first = first.min(definition.0);
+                    last = last.max(definition.1);
+                }
+            }
+            if (first, last) == *region {
+                break;
+            }
+            *region = (first, last);
+        }
+    }
+
+    // extending the regions can make them overlap
+    let mut merged: Vec<(usize, usize)> = vec![];
+    for (first, last) in regions {
+        match merged.last_mut() {
+            Some(previous) if first <= previous.1 => previous.1 = previous.1.max(last),
+            _ => merged.push((first, last)),
+        }
+    }
+    return merged;
+}
+
+/// Returns true if the ranges of lines `a` and `b` have a line in common.
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    return a.0 <= b.1 && b.0 <= a.1;
+}
+
+/// Returns `lines` without its trailing blank lines.
+fn trim_blank_lines<'a, 'b>(lines: &'b [&'a str]) -> &'b [&'a str] {
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |last| last + 1);
+    return &lines[..end];
+}
+
+/// Returns the source text of the pattern of a destructuring `let` statement.
+fn pattern(pattern: &Pattern) -> String {
+    return match pattern {
+        Pattern::List(names, rest) => {
+            let mut names = names.clone();
+            names.extend(rest.iter().map(|rest| format!("{rest}...")));
+            format!("[{}]", names.join(", "))
+        }
+        Pattern::Map(names) => format!("{{{}}}", names.join(", ")),
+    };
+}
+
+/// Returns the source text of a block of `statements` whose closing bracket is indented by `indent`
+/// levels.
+fn block(statements: &[SugaredStatement], indent: usize) -> String {
+    let lines: Vec<String> = statements
+        .iter()
+        .filter(|statement| !matches!(statement, SugaredStatement::Line(..)))
+        .map(|statement| {
+            format!(
+                "{}{}\n",
+                INDENT.repeat(indent + 1),
+                self::statement(statement, indent + 1)
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        return "{}".to_string();
+    }
+    return format!("{{\n{}{}}}", lines.concat(), INDENT.repeat(indent));
+}
+
+/// Returns the source text of `expr`, which is parenthesized if its precedence is lower than
+/// `min_precedence`.
+fn expr_at(expr: &SugaredExpr, min_precedence: u8, indent: usize) -> String {
+    let source = match expr {
+        SugaredExpr::Nil => "nil".to_string(),
+        SugaredExpr::Num(n) => n.to_string(),
+        SugaredExpr::Bool(b) => b.to_string(),
+        SugaredExpr::Str(s) => pretty_string(s),
+        SugaredExpr::Var(name) => name.to_string(),
+        SugaredExpr::Binary(op, left, right, _) => {
+            let precedence = binary_precedence(*op);
+            // binary operators are left-associative
+            format!(
+                "{} {op} {}",
+                expr_at(left, precedence, indent),
+                expr_at(right, precedence + 1, indent)
+            )
+        }
+        SugaredExpr::Unary(op @ (PostIncrement | PostDecrement), operand) => {
+            format!("{}{op}", expr_at(operand, POSTFIX_PRECEDENCE, indent))
+        }
+        SugaredExpr::Unary(op @ (PreIncrement | PreDecrement), operand) => {
+            format!("{op}{}", expr_at(operand, POSTFIX_PRECEDENCE, indent))
+        }
+        SugaredExpr::Unary(op, operand) => {
+            let operand = expr_at(operand, UNARY_PRECEDENCE, indent);
+            // `- -x` would be tokenized as `--x` without the space
+            if operand.starts_with('-') {
+                format!("{op} {operand}")
+            } else {
+                format!("{op}{operand}")
+            }
+        }
+        SugaredExpr::List(elements) => format!("[{}]", args_source(elements, indent)),
+        SugaredExpr::PrimitiveCall(builtin, args, _) => {
+            format!("{}({})", builtin.name(), args_source(args, indent))
+        }
+        SugaredExpr::Call(callee, args, _) => format!(
+            "{}({})",
+            expr_at(callee, POSTFIX_PRECEDENCE, indent),
+            args_source(args, indent)
+        ),
+        SugaredExpr::KeywordCall(callee, args, keyword_args, _) => {
+            let mut args: Vec<String> = args.iter().map(|arg| expr_at(arg, 0, indent)).collect();
+            args.extend(
+                keyword_args
+                    .iter()
+                    .map(|(name, arg)| format!("{name} = {}", expr_at(arg, 0, indent))),
+            );
+            format!(
+                "{}({})",
+                expr_at(callee, POSTFIX_PRECEDENCE, indent),
+                args.join(", ")
+            )
+        }
+        SugaredExpr::Lambda(params, body) => format!(
+            "({}) -> {}",
+            params.join(", "),
+            statement_without_semicolon(body, indent)
+        ),
+        SugaredExpr::Index(indexable, index, _) => format!(
+            "{}[{}]",
+            expr_at(indexable, POSTFIX_PRECEDENCE, indent),
+            expr_at(index, 0, indent)
+        ),
+        SugaredExpr::Field(object, field) => {
+            format!("{}.{field}", expr_at(object, POSTFIX_PRECEDENCE, indent))
+        }
+        SugaredExpr::StructUpdate(name, base, fields) => {
+            let mut parts = vec![format!("...{}", expr_at(base, 0, indent))];
+            parts.extend(
+                fields
+                    .iter()
+                    .map(|(field, value)| format!("{field}: {}", expr_at(value, 0, indent))),
+            );
+            format!("{name} {{ {} }}", parts.join(", "))
+        }
+        SugaredExpr::Statement(statement) => self::statement(statement, indent),
+    };
+    if precedence(expr) < min_precedence {
+        return format!("({source})");
+    }
+    return source;
+}
+
+fn args_source(args: &[SugaredExpr], indent: usize) -> String {
+    let args: Vec<String> = args.iter().map(|arg| expr_at(arg, 0, indent)).collect();
+    return args.join(", ");
+}
+
+/// Returns how tightly `expr` binds to its operands, from [LAMBDA_PRECEDENCE] to
+/// [ATOM_PRECEDENCE].
+fn precedence(expr: &SugaredExpr) -> u8 {
+    return match expr {
+        SugaredExpr::Lambda(..) => LAMBDA_PRECEDENCE,
+        SugaredExpr::Binary(op, ..) => binary_precedence(*op),
+        SugaredExpr::Unary(..) => UNARY_PRECEDENCE,
+        SugaredExpr::PrimitiveCall(..)
+        | SugaredExpr::Call(..)
+        | SugaredExpr::KeywordCall(..)
+        | SugaredExpr::Index(..)
+        | SugaredExpr::Field(..) => POSTFIX_PRECEDENCE,
+        _ => ATOM_PRECEDENCE,
+    };
+}
+
+/// Returns the precedence of the binary operator `op` (see [BINARY_OPERATORS]), which is between
+/// [LAMBDA_PRECEDENCE] and [UNARY_PRECEDENCE].
+fn binary_precedence(op: Operator) -> u8 {
+    return parser::binary_precedence(op).unwrap_or(UNARY_PRECEDENCE);
+}
+
+/// Returns true if the source text of `expr` starts with a block or `if` statement.
+fn starts_with_statement(expr: &SugaredExpr) -> bool {
+    return match expr {
+        SugaredExpr::Statement(_) => true,
+        SugaredExpr::Binary(op, left, ..) => {
+            precedence(left) >= binary_precedence(*op) && starts_with_statement(left)
+        }
+        SugaredExpr::Unary(PostIncrement | PostDecrement, operand)
+        | SugaredExpr::Call(operand, _, _)
+        | SugaredExpr::KeywordCall(operand, ..)
+        | SugaredExpr::Index(operand, _, _)
+        | SugaredExpr::Field(operand, _) => {
+            precedence(operand) >= POSTFIX_PRECEDENCE && starts_with_statement(operand)
+        }
+        _ => false,
+    };
+}
+
+/// Returns `s` as a string literal, escaping the characters which cannot appear in one as they are.
+pub(crate) fn pretty_string(s: &str) -> String {
+    let mut literal = String::from('"');
+    for c in s.chars() {
+        match c {
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            '\0' => literal.push_str("\\0"),
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    return literal;
+}
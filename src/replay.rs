@@ -0,0 +1,258 @@
+//! Recording and replaying the results of the builtins which can return something different every
+//! time a program is run: `random`, `now_millis`, and `input`. A run can record these results into
+//! a [ReplayLog] (see [Writer::with_recording](crate::Writer::with_recording)), and a later run can
+//! replay the log (see [Writer::with_replay](crate::Writer::with_replay)) so that it behaves exactly
+//! like the recorded run, e.g. to reproduce a failure that only happens sometimes.
+//!
+//! A log is written as text with one result per line, in the order the builtins were called:
+//!
+//! ```text
+//! random 0.8128314370599604
+//! now_millis 1760000000000
+//! input "hello"
+//! input nil
+//! ```
+
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    fmt,
+    hash::{BuildHasher, Hasher},
+    io::{stdin, BufRead},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::error::RuntimeError;
+
+/// The result of one call to a nondeterministic builtin.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// A number returned by `random`.
+    Random(f64),
+    /// A time returned by `now_millis`.
+    NowMillis(f64),
+    /// A line returned by `input`, or `None` if there was no more input.
+    Input(Option<String>),
+}
+
+impl Event {
+    /// The name of the builtin which produced this event.
+    pub fn builtin_name(&self) -> &'static str {
+        match self {
+            Event::Random(_) => "random",
+            Event::NowMillis(_) => "now_millis",
+            Event::Input(_) => "input",
+        }
+    }
+}
+
+/// The results of the nondeterministic builtins called during one run of a program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayLog {
+    pub events: Vec<Event>,
+}
+
+impl ReplayLog {
+    /// Parses a log written by the [Display](fmt::Display) implementation of [ReplayLog]. On
+    /// failure, this function returns a description of the first line that could not be parsed.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut events = vec![];
+        for (index, line) in text.lines().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            let event = match line.split_once(' ') {
+                Some(("random", n)) => n.parse().ok().map(Event::Random),
+                Some(("now_millis", n)) => n.parse().ok().map(Event::NowMillis),
+                Some(("input", "nil")) => Some(Event::Input(None)),
+                Some(("input", quoted)) => unquote(quoted).map(|line| Event::Input(Some(line))),
+                _ => None,
+            };
+            match event {
+                Some(event) => events.push(event),
+                None => return Err(format!("invalid event on line {}: {line}", index + 1)),
+            }
+        }
+        return Ok(Self { events });
+    }
+}
+
+impl fmt::Display for ReplayLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            let name = event.builtin_name();
+            match event {
+                Event::Random(n) | Event::NowMillis(n) => writeln!(f, "{name} {n}")?,
+                Event::Input(Some(line)) => writeln!(f, "{name} {line:?}")?,
+                Event::Input(None) => writeln!(f, "{name} nil")?,
+            }
+        }
+        return Ok(());
+    }
+}
+
+/// Reverses the escaping done by the [Debug](fmt::Debug) implementation of [str].
+fn unquote(quoted: &str) -> Option<String> {
+    let mut chars = quoted.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut unquoted = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unquoted.push(c);
+            continue;
+        }
+        unquoted.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            c @ ('\\' | '"' | '\'') => c,
+            'u' => {
+                let digits: String = chars.by_ref().take_while(|c| *c != '}').collect();
+                let code = u32::from_str_radix(digits.strip_prefix('{')?, 16).ok()?;
+                char::from_u32(code)?
+            }
+            _ => return None,
+        });
+    }
+    return Some(unquoted);
+}
+
+/// Where the results of the nondeterministic builtins come from during a run.
+#[derive(Debug, Default)]
+enum Source {
+    /// The results are computed as the builtins are called.
+    #[default]
+    Live,
+    /// The results are computed as the builtins are called and kept in the log.
+    Recording(Vec<Event>),
+    /// The results are taken from the front of the log.
+    Replaying(VecDeque<Event>),
+}
+
+/// The nondeterministic builtins of one run of a program, which are live unless they are being
+/// recorded or replayed.
+#[derive(Debug, Default)]
+pub(crate) struct Nondeterminism {
+    source: Source,
+    /// The state of the generator used by `random`.
+    random_state: Option<u64>,
+}
+
+impl Nondeterminism {
+    pub(crate) fn recording() -> Self {
+        return Self {
+            source: Source::Recording(vec![]),
+            random_state: None,
+        };
+    }
+
+    pub(crate) fn replaying(log: ReplayLog) -> Self {
+        return Self {
+            source: Source::Replaying(log.events.into()),
+            random_state: None,
+        };
+    }
+
+    /// The results recorded so far, or `None` if the results are not being recorded.
+    pub(crate) fn recorded(&self) -> Option<ReplayLog> {
+        return match &self.source {
+            Source::Recording(events) => Some(ReplayLog {
+                events: events.clone(),
+            }),
+            _ => None,
+        };
+    }
+
+    /// Returns a number in the range `[0, 1)` for the `random` builtin.
+    pub(crate) fn random(&mut self) -> Result<f64, RuntimeError> {
+        let random_state = &mut self.random_state;
+        let random = || Event::Random(next_random(random_state));
+        return match self.source.next("random", random)? {
+            Event::Random(n) => Ok(n),
+            event => unreachable!("{} is not a random event", event.builtin_name()),
+        };
+    }
+
+    /// Returns the number of milliseconds since the Unix epoch for the `now_millis` builtin.
+    pub(crate) fn now_millis(&mut self) -> Result<f64, RuntimeError> {
+        let now = || {
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_millis());
+            Event::NowMillis(millis as f64)
+        };
+        return match self.source.next("now_millis", now)? {
+            Event::NowMillis(n) => Ok(n),
+            event => unreachable!("{} is not a now_millis event", event.builtin_name()),
+        };
+    }
+
+    /// Returns the next line of the standard input without its line break for the `input`
+    /// builtin, or `None` at the end of the input.
+    pub(crate) fn input(&mut self) -> Result<Option<String>, RuntimeError> {
+        let mut error = None;
+        let read_line = || {
+            let mut line = String::new();
+            match stdin().lock().read_line(&mut line) {
+                Ok(0) => Event::Input(None),
+                Ok(_) => {
+                    if line.ends_with('\n') {
+                        line.pop();
+                        if line.ends_with('\r') {
+                            line.pop();
+                        }
+                    }
+                    Event::Input(Some(line))
+                }
+                Err(e) => {
+                    error = Some(e.to_string());
+                    Event::Input(None)
+                }
+            }
+        };
+        let event = self.source.next("input", read_line)?;
+        if let Some(reason) = error {
+            return Err(RuntimeError::FileError("<stdin>".to_string(), reason));
+        }
+        return match event {
+            Event::Input(line) => Ok(line),
+            event => unreachable!("{} is not an input event", event.builtin_name()),
+        };
+    }
+}
+
+impl Source {
+    /// Returns the result of calling the builtin `name`, which is computed by `live` unless it is
+    /// being replayed.
+    fn next(&mut self, name: &str, live: impl FnOnce() -> Event) -> Result<Event, RuntimeError> {
+        return match self {
+            Source::Live => Ok(live()),
+            Source::Recording(events) => {
+                let event = live();
+                events.push(event.clone());
+                Ok(event)
+            }
+            Source::Replaying(events) => match events.pop_front() {
+                Some(event) if event.builtin_name() == name => Ok(event),
+                Some(event) => Err(RuntimeError::ReplayDiverged(format!(
+                    "{name} was called where the log has a call to {}",
+                    event.builtin_name()
+                ))),
+                None => Err(RuntimeError::ReplayDiverged(format!(
+                    "{name} was called after the end of the log"
+                ))),
+            },
+        };
+    }
+}
+
+/// Advances the xorshift64* generator `state`, which is seeded randomly on first use, and returns
+/// a number in the range `[0, 1)` made from the top 53 bits of its output.
+fn next_random(state: &mut Option<u64>) -> f64 {
+    // the hasher of a new RandomState is keyed randomly, and xorshift needs a nonzero state
+    let state = state.get_or_insert_with(|| RandomState::new().build_hasher().finish() | 1);
+    *state ^= *state >> 12;
+    *state ^= *state << 25;
+    *state ^= *state >> 27;
+    let output = state.wrapping_mul(0x2545f4914f6cdd1d);
+    return (output >> 11) as f64 / (1u64 << 53) as f64;
+}
@@ -0,0 +1,161 @@
+//! Rendering of [Diagnostics](Diagnostic) for people to read. When the output is a terminal,
+//! diagnostics are rendered with colors, the line of source they refer to with a caret under the
+//! position, and wrapped help text. Otherwise they are rendered as plain text.
+
+use std::io::IsTerminal;
+
+use crate::diagnostic::{Diagnostic, Severity};
+
+/// The width that help text is wrapped to when the width of the terminal is unknown.
+const DEFAULT_WIDTH: usize = 80;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// How diagnostics are rendered.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// The message of an error, or the message of a warning prefixed with `warning: `, followed by
+    /// its help text.
+    Plain,
+    /// Colored output which shows the source line a diagnostic refers to.
+    Rich,
+    /// One JSON object per diagnostic (see [Diagnostic::to_json]), for tools such as editors.
+    Json,
+}
+
+impl Style {
+    /// Returns the style to use for diagnostics printed to stderr, which is [Style::Rich] when
+    /// stderr is a terminal and the `NO_COLOR` environment variable is not set.
+    pub fn for_stderr() -> Self {
+        if std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none() {
+            return Style::Rich;
+        }
+        return Style::Plain;
+    }
+}
+
+/// Renders the diagnostics of a single source file.
+#[derive(Clone, Copy, Debug)]
+pub struct Reporter<'a> {
+    file_name: &'a str,
+    source: &'a str,
+    style: Style,
+    width: usize,
+}
+
+impl<'a> Reporter<'a> {
+    /// Creates a reporter for diagnostics about `source`, which was read from `file_name`. The
+    /// reporter renders [plain](Style::Plain) text until another style is set.
+    pub fn new(file_name: &'a str, source: &'a str) -> Self {
+        let width = std::env::var("COLUMNS")
+            .ok()
+            .and_then(|columns| columns.parse().ok())
+            .unwrap_or(DEFAULT_WIDTH);
+        Self {
+            file_name,
+            source,
+            style: Style::Plain,
+            width,
+        }
+    }
+
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        return self;
+    }
+
+    /// Sets the width that help text is wrapped to.
+    pub fn with_width(mut self, width: usize) -> Self {
+        self.width = width;
+        return self;
+    }
+
+    /// Renders `diagnostic` as text without a trailing newline.
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        return match self.style {
+            Style::Plain => self.render_plain(diagnostic),
+            Style::Rich => self.render_rich(diagnostic),
+            Style::Json => diagnostic.to_json(),
+        };
+    }
+
+    fn render_plain(&self, diagnostic: &Diagnostic) -> String {
+        let mut text = match diagnostic.severity {
+            Severity::Error => diagnostic.message.to_string(),
+            Severity::Warning => format!("warning: {}", diagnostic.message),
+        };
+        if let Some(help) = &diagnostic.help {
+            text.push_str(&format!("\nhelp: {help}"));
+        }
+        return text;
+    }
+
+    fn render_rich(&self, diagnostic: &Diagnostic) -> String {
+        let (label, color) = match diagnostic.severity {
+            Severity::Error => ("error", RED),
+            Severity::Warning => ("warning", YELLOW),
+        };
+        let mut text = format!("{color}{label}{RESET}{BOLD}: {}{RESET}", diagnostic.message);
+
+        let line = diagnostic.position.and_then(|(line_num, col_num)| {
+            let line = self.source.lines().nth(line_num.checked_sub(1)?)?;
+            Some((line_num, col_num, line))
+        });
+        if let Some((line_num, col_num, line)) = line {
+            let gutter = " ".repeat(line_num.to_string().len());
+            // the tabs before the caret are kept so that it lines up with its column however wide
+            // the tabs are shown
+            let caret_indent: String = (line.chars().take(col_num.saturating_sub(1)))
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            text.push_str(&format!(
+                "\n{gutter}{BLUE}-->{RESET} {}:{line_num}:{col_num}",
+                self.file_name
+            ));
+            text.push_str(&format!("\n{gutter} {BLUE}|{RESET}"));
+            text.push_str(&format!("\n{BLUE}{line_num} |{RESET} {line}"));
+            text.push_str(&format!(
+                "\n{gutter} {BLUE}|{RESET} {caret_indent}{color}^{RESET}"
+            ));
+        }
+
+        if let Some(help) = &diagnostic.help {
+            let prefix = "help: ";
+            let lines = wrap(help, self.width.saturating_sub(prefix.len()));
+            for (index, line) in lines.iter().enumerate() {
+                if index == 0 {
+                    text.push_str(&format!("\n{CYAN}help{RESET}: {line}"));
+                } else {
+                    text.push_str(&format!("\n{}{line}", " ".repeat(prefix.len())));
+                }
+            }
+        }
+        return text;
+    }
+}
+
+/// Splits `text` into lines of at most `width` characters, breaking at spaces. Words longer than
+/// `width` are kept on a line of their own.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > width {
+            lines.push(current);
+            current = String::new();
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    return lines;
+}
@@ -0,0 +1,97 @@
+//! Interned identifiers. Variable names are compared and hashed constantly while a program runs, so
+//! the desugared program and the [Environment](crate::environment::Environment) refer to them by
+//! [Symbol], which is a small copyable handle for a string stored once in a global interner.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{OnceLock, RwLock},
+};
+
+/// An interned string.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+#[derive(Default)]
+struct Interner {
+    symbols: HashMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+fn interner() -> &'static RwLock<Interner> {
+    static INTERNER: OnceLock<RwLock<Interner>> = OnceLock::new();
+    return INTERNER.get_or_init(Default::default);
+}
+
+impl Symbol {
+    /// Returns the symbol for `name`, interning it if it has not been interned before. Interned
+    /// strings are never freed, which is fine since a program only has a limited number of
+    /// distinct identifiers.
+    pub fn intern(name: &str) -> Self {
+        if let Some(symbol) = interner()
+            .read()
+            .expect("interner lock should not be poisoned")
+            .symbols
+            .get(name)
+        {
+            return *symbol;
+        }
+
+        let mut interner = interner()
+            .write()
+            .expect("interner lock should not be poisoned");
+        // another thread may have interned the name since the read lock was released
+        if let Some(symbol) = interner.symbols.get(name) {
+            return *symbol;
+        }
+        let symbol = Symbol(interner.names.len() as u32);
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        interner.names.push(name);
+        interner.symbols.insert(name, symbol);
+        return symbol;
+    }
+
+    /// Returns the string this symbol was interned from.
+    pub fn as_str(self) -> &'static str {
+        return interner()
+            .read()
+            .expect("interner lock should not be poisoned")
+            .names[self.0 as usize];
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::intern(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::intern(&name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
@@ -0,0 +1,271 @@
+//! JSON representations of the tokens and the sugared syntax tree of a program, so that tools
+//! written in other languages, such as formatters and autograders, can see a program the way the
+//! front end of the interpreter does. This module is only compiled with the `serde` feature.
+//!
+//! Every node of the syntax tree becomes an object whose `"type"` is the name of its variant, e.g.
+//! `{"type": "Var", "name": "x"}`. Operators and keywords are given as they are written in source.
+
+use serde_json::{json, Value};
+
+use crate::{
+    parser::{
+        Pattern, SugaredContract, SugaredExpr, SugaredImport, SugaredProcedure, SugaredProgram,
+        SugaredStatement,
+    },
+    tokenizer::{Operator, Token as T, TokenValue},
+};
+
+/// Returns `tokens` as a JSON array with an object per token, which has the `"kind"` of the token,
+/// its `"text"` as written in source, its `"line"` and `"col"`, and for identifiers, strings, and
+/// numbers, its `"value"`.
+pub fn tokens_to_json(tokens: &[T]) -> Value {
+    return Value::Array(tokens.iter().map(token_to_json).collect());
+}
+
+fn token_to_json(token: &T) -> Value {
+    let T(value, line, col) = token;
+    let mut object = json!({
+        "kind": token_kind(value),
+        "text": value.to_string(),
+        "line": line,
+        "col": col,
+    });
+    match value {
+        TokenValue::ID(s) | TokenValue::STR(s) => object["value"] = json!(s),
+        TokenValue::NUM(n) => object["value"] = json!(n),
+        _ => (),
+    }
+    return object;
+}
+
+/// Returns the name of the variant of `value`.
+fn token_kind(value: &TokenValue) -> &'static str {
+    return match value {
+        TokenValue::ID(_) => "ID",
+        TokenValue::STR(_) => "STR",
+        TokenValue::NUM(_) => "NUM",
+        TokenValue::ASSIGN => "ASSIGN",
+        TokenValue::OP(_) => "OP",
+        TokenValue::KW(_) => "KW",
+        TokenValue::ASSIGN_OP(_) => "ASSIGN_OP",
+        TokenValue::LPAREN => "LPAREN",
+        TokenValue::RPAREN => "RPAREN",
+        TokenValue::L_CURLY_BRACKET => "L_CURLY_BRACKET",
+        TokenValue::R_CURLY_BRACKET => "R_CURLY_BRACKET",
+        TokenValue::L_SQUARE_BRACKET => "L_SQUARE_BRACKET",
+        TokenValue::R_SQUARE_BRACKET => "R_SQUARE_BRACKET",
+        TokenValue::SEMICOLON => "SEMICOLON",
+        TokenValue::QUOTE => "QUOTE",
+        TokenValue::COMMA => "COMMA",
+        TokenValue::THIN_ARROW => "THIN_ARROW",
+        TokenValue::DOUBLE_SLASH => "DOUBLE_SLASH",
+        TokenValue::DOUBLE_PLUS => "DOUBLE_PLUS",
+        TokenValue::DOUBLE_MINUS => "DOUBLE_MINUS",
+        TokenValue::DOT => "DOT",
+        TokenValue::QUESTION => "QUESTION",
+        TokenValue::COLON => "COLON",
+    };
+}
+
+/// Returns the sugared syntax tree of `program` as a JSON object with its `"imports"`,
+/// `"constants"`, `"procedures"`, and `"structs"`.
+pub fn program_to_json(program: &SugaredProgram) -> Value {
+    return json!({
+        "imports": program.imports.iter().map(import_to_json).collect::<Vec<Value>>(),
+        "constants": program
+            .constants
+            .iter()
+            .map(|(name, value)| json!({ "name": name, "value": expr_to_json(value) }))
+            .collect::<Vec<Value>>(),
+        "procedures": program.procedures.iter().map(procedure_to_json).collect::<Vec<Value>>(),
+        "structs": program
+            .structs
+            .iter()
+            .map(|definition| json!({ "name": definition.name, "fields": definition.fields }))
+            .collect::<Vec<Value>>(),
+    });
+}
+
+fn import_to_json(import: &SugaredImport) -> Value {
+    return json!({ "path": import.path, "alias": import.alias });
+}
+
+fn procedure_to_json(procedure: &SugaredProcedure) -> Value {
+    return json!({
+        "name": procedure.name,
+        "params": procedure.params,
+        "contracts": procedure.contracts.iter().map(contract_to_json).collect::<Vec<Value>>(),
+        "body": statement_to_json(&procedure.body),
+        "public": procedure.public,
+        "pure": procedure.pure,
+    });
+}
+
+fn contract_to_json(contract: &SugaredContract) -> Value {
+    return json!({
+        "kind": contract.kind.to_string(),
+        "condition": expr_to_json(&contract.condition),
+        "source": contract.source,
+    });
+}
+
+fn pattern_to_json(pattern: &Pattern) -> Value {
+    return match pattern {
+        Pattern::List(names, rest) => json!({ "type": "List", "names": names, "rest": rest }),
+        Pattern::Map(names) => json!({ "type": "Map", "names": names }),
+    };
+}
+
+fn statements_to_json(statements: &[SugaredStatement]) -> Vec<Value> {
+    return statements.iter().map(statement_to_json).collect();
+}
+
+/// Returns `statement` as a JSON object (see [syntax_json](self)).
+pub fn statement_to_json(statement: &SugaredStatement) -> Value {
+    return match statement {
+        SugaredStatement::Expr(expr) => json!({ "type": "Expr", "expr": expr_to_json(expr) }),
+        SugaredStatement::Let(name, value) => {
+            json!({ "type": "Let", "name": name, "value": expr_to_json(value) })
+        }
+        SugaredStatement::LetPattern(pattern, value) => json!({
+            "type": "LetPattern",
+            "pattern": pattern_to_json(pattern),
+            "value": expr_to_json(value),
+        }),
+        SugaredStatement::Const(name, value) => {
+            json!({ "type": "Const", "name": name, "value": expr_to_json(value) })
+        }
+        SugaredStatement::Assign(name, value) => {
+            json!({ "type": "Assign", "name": name, "value": expr_to_json(value) })
+        }
+        SugaredStatement::OperatorAssignment(op, name, value) => json!({
+            "type": "OperatorAssignment",
+            "operator": op.to_string(),
+            "name": name,
+            "value": expr_to_json(value),
+        }),
+        SugaredStatement::PlaceAssign(target, op, value) => json!({
+            "type": "PlaceAssign",
+            "target": expr_to_json(target),
+            "operator": op.map(|op| op.to_string()),
+            "value": expr_to_json(value),
+        }),
+        SugaredStatement::MultiAssign(names, values) => json!({
+            "type": "MultiAssign",
+            "names": names,
+            "values": values.iter().map(expr_to_json).collect::<Vec<Value>>(),
+        }),
+        SugaredStatement::Block(statements) => {
+            json!({ "type": "Block", "statements": statements_to_json(statements) })
+        }
+        SugaredStatement::If(cond, then_block, else_ifs, else_block_option) => json!({
+            "type": "If",
+            "condition": expr_to_json(cond),
+            "then": statement_to_json(then_block),
+            "else_ifs": else_ifs
+                .iter()
+                .map(|(cond, block)| {
+                    json!({ "condition": expr_to_json(cond), "then": statement_to_json(block) })
+                })
+                .collect::<Vec<Value>>(),
+            "else": else_block_option.as_deref().map(statement_to_json),
+        }),
+        SugaredStatement::While(cond, body) => json!({
+            "type": "While",
+            "condition": expr_to_json(cond),
+            "body": statement_to_json(body),
+        }),
+        SugaredStatement::Loop(body) => json!({ "type": "Loop", "body": statement_to_json(body) }),
+        SugaredStatement::For(init, cond, update, body) => json!({
+            "type": "For",
+            "init": statement_to_json(init),
+            "condition": expr_to_json(cond),
+            "update": statement_to_json(update),
+            "body": statements_to_json(body),
+        }),
+        SugaredStatement::Break(value) => {
+            json!({ "type": "Break", "value": value.as_ref().map(expr_to_json) })
+        }
+        SugaredStatement::Continue => json!({ "type": "Continue" }),
+        SugaredStatement::Return(value) => {
+            json!({ "type": "Return", "value": value.as_ref().map(expr_to_json) })
+        }
+        SugaredStatement::Line(line, source) => {
+            json!({ "type": "Line", "line": line, "source": source })
+        }
+    };
+}
+
+/// Returns `expr` as a JSON object (see [syntax_json](self)). The object of a unary operator also
+/// says whether it is `"postfix"`, since `x++` and `++x` are both written with `++`.
+pub fn expr_to_json(expr: &SugaredExpr) -> Value {
+    return match expr {
+        SugaredExpr::Nil => json!({ "type": "Nil" }),
+        SugaredExpr::Num(n) => json!({ "type": "Num", "value": n }),
+        SugaredExpr::Bool(b) => json!({ "type": "Bool", "value": b }),
+        SugaredExpr::Str(s) => json!({ "type": "Str", "value": s }),
+        SugaredExpr::Var(name) => json!({ "type": "Var", "name": name }),
+        SugaredExpr::Binary(op, left, right, _) => json!({
+            "type": "Binary",
+            "operator": op.to_string(),
+            "left": expr_to_json(left),
+            "right": expr_to_json(right),
+        }),
+        SugaredExpr::Unary(op, operand) => json!({
+            "type": "Unary",
+            "operator": op.to_string(),
+            "postfix": matches!(op, Operator::PostIncrement | Operator::PostDecrement),
+            "operand": expr_to_json(operand),
+        }),
+        SugaredExpr::List(elements) => json!({
+            "type": "List",
+            "elements": elements.iter().map(expr_to_json).collect::<Vec<Value>>(),
+        }),
+        SugaredExpr::PrimitiveCall(builtin, args, _) => json!({
+            "type": "PrimitiveCall",
+            "builtin": builtin.name(),
+            "args": args.iter().map(expr_to_json).collect::<Vec<Value>>(),
+        }),
+        SugaredExpr::Call(callee, args, _) => json!({
+            "type": "Call",
+            "callee": expr_to_json(callee),
+            "args": args.iter().map(expr_to_json).collect::<Vec<Value>>(),
+        }),
+        SugaredExpr::KeywordCall(callee, args, keyword_args, _) => json!({
+            "type": "KeywordCall",
+            "callee": expr_to_json(callee),
+            "args": args.iter().map(expr_to_json).collect::<Vec<Value>>(),
+            "keyword_args": keyword_args
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": expr_to_json(value) }))
+                .collect::<Vec<Value>>(),
+        }),
+        SugaredExpr::Lambda(params, body) => json!({
+            "type": "Lambda",
+            "params": params,
+            "body": statement_to_json(body),
+        }),
+        SugaredExpr::Index(indexable, index, _) => json!({
+            "type": "Index",
+            "indexable": expr_to_json(indexable),
+            "index": expr_to_json(index),
+        }),
+        SugaredExpr::Field(object, field) => json!({
+            "type": "Field",
+            "object": expr_to_json(object),
+            "field": field,
+        }),
+        SugaredExpr::StructUpdate(name, base, fields) => json!({
+            "type": "StructUpdate",
+            "struct": name,
+            "base": expr_to_json(base),
+            "fields": fields
+                .iter()
+                .map(|(name, value)| json!({ "name": name, "value": expr_to_json(value) }))
+                .collect::<Vec<Value>>(),
+        }),
+        SugaredExpr::Statement(statement) => {
+            json!({ "type": "Statement", "statement": statement_to_json(statement) })
+        }
+    };
+}
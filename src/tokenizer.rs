@@ -1,13 +1,50 @@
-use std::fmt;
+use std::{fmt, iter::Enumerate, str::Split};
 
 use regex::{Match, Regex};
 
 use crate::error::TokenizerError::{self, *};
+use crate::language::pragma_line;
 
 /// A Linger token.
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
 pub struct Token(pub TokenValue, pub usize, pub usize);
 
+impl Token {
+    /// Returns where this token is in the source.
+    pub fn span(&self) -> Span {
+        return Span {
+            line: self.1,
+            column: self.2,
+        };
+    }
+}
+
+/// The line and column of the first token of an expression, which is where errors about the
+/// expression are reported. A span with a line of 0 is not in the source, such as the span of an
+/// expression created by desugaring. Spans are ignored when syntax trees are compared, so that
+/// the same program parsed from differently formatted source is equal.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// Returns the line and column of this span, or `None` if it is not in the source.
+    pub fn position(&self) -> Option<(usize, usize)> {
+        if self.line == 0 {
+            return None;
+        }
+        return Some((self.line, self.column));
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        return true;
+    }
+}
+
 /// A Linger token value. This is an enum which represents the type of the
 /// token along with any associated data with that type.
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
@@ -34,6 +71,8 @@ pub enum TokenValue {
     DOUBLE_PLUS,
     DOUBLE_MINUS,
     DOT,
+    QUESTION,
+    COLON,
 }
 
 /// An operator. This enum represents all of the valid operators in the Linger
@@ -52,9 +91,16 @@ pub enum Operator {
     GTE,
     Mod,
     Div,
+    FloorDiv,
     LogicOr,
     LogicAnd,
     LogicNot,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
     PreIncrement,
     PostIncrement,
     PreDecrement,
@@ -65,6 +111,9 @@ pub enum Operator {
 pub enum AssignOp {
     Plus,
     Minus,
+    Times,
+    Div,
+    Mod,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
@@ -84,6 +133,22 @@ pub enum Keyword {
     Nil,
 }
 
+/// A word which is only a keyword in certain positions of a program, e.g. `import` at the start of
+/// a top-level definition. A contextual keyword is tokenized as an [ID](TokenValue::ID), so it can
+/// still be used as a name everywhere else, and a new one can be added to the language without
+/// breaking the programs which already use it as a name.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
+pub enum ContextualKeyword {
+    Import,
+    As,
+    Pub,
+    Pure,
+    Struct,
+    Requires,
+    Ensures,
+    Loop,
+}
+
 const WHITESPACE_REGEX: &str = r"[[:space:]]+";
 const ASSIGN_REGEX: &str = r"=";
 const THIN_ARROW_REGEX: &str = r"->";
@@ -100,6 +165,7 @@ const MINUS_REGEX: &str = r"\-";
 const STAR_REGEX: &str = r"\*";
 const SLASH_REGEX: &str = r"/";
 const DOUBLE_SLASH_REGEX: &str = r"//";
+const FLOOR_DIV_REGEX: &str = r"~/";
 const DOUBLE_PLUS_REGEX: &str = r"\+\+";
 const DOUBLE_MINUS_REGEX: &str = r"\-\-";
 const MOD_REGEX: &str = "%";
@@ -117,82 +183,175 @@ const LOGIC_AND_REGEX: &str = "&&";
 const LOGIC_NOT_REGEX: &str = "!";
 const ASSIGNMENT_PLUS_REGEX: &str = r"\+=";
 const ASSIGNMENT_MINUS_REGEX: &str = r"\-=";
+const ASSIGNMENT_TIMES_REGEX: &str = r"\*=";
+const ASSIGNMENT_DIV_REGEX: &str = "/=";
+const ASSIGNMENT_MOD_REGEX: &str = "%=";
 const DOT_REGEX: &str = r"\.";
+const BIT_AND_REGEX: &str = "&";
+const BIT_OR_REGEX: &str = r"\|";
+const BIT_XOR_REGEX: &str = r"\^";
+const BIT_NOT_REGEX: &str = "~";
+const SHIFT_LEFT_REGEX: &str = "<<";
+const SHIFT_RIGHT_REGEX: &str = ">>";
+const QUESTION_REGEX: &str = r"\?";
+const COLON_REGEX: &str = ":";
 
-/// Returns the [Tokens](Token) which make up the program `s`.
+/// Returns the [Tokens](Token) which make up the program `s`. A shebang line (e.g.
+/// `#!/usr/bin/env linger`) at the start of the program is skipped so that scripts can be made
+/// executable, and so is the `#language` pragma (see [language](crate::language)).
 pub fn tokenize(s: &str) -> Result<Vec<Token>, TokenizerError> {
-    let enumerated_lines = s.split("\n").enumerate();
-    let mut tokens: Vec<Token> = vec![];
-    for (line_num, line) in enumerated_lines {
-        let mut tokenized_line = tokenize_helper(line, line_num + 1, 1)?;
-        tokens.append(&mut tokenized_line)
-    }
-    Ok(tokens)
+    return TokenStream::new(s).collect();
 }
 
-/// Returns the [Tokens](Token) which make up the program `s`. This is a helper function which is
-/// wrapped by [tokenize]. This function also takes a line and column number which are passed to
-/// created token structures.
-fn tokenize_helper(s: &str, line_num: usize, col_num: usize) -> Result<Vec<Token>, TokenizerError> {
-    if s.len() == 0 {
-        return Ok(vec![]);
+/// An iterator over the [Tokens](Token) which make up a program, which tokenizes the program as
+/// its tokens are asked for rather than all at once (see [tokenize]). It yields `None` after the
+/// first error.
+pub struct TokenStream<'a> {
+    lines: Enumerate<Split<'a, &'static str>>,
+    pragma_line: Option<usize>,
+    /// The rest of the current line, which has not been tokenized yet.
+    rest: &'a str,
+    line_num: usize,
+    col_num: usize,
+    /// The number of block comments which are open at the start of `rest`.
+    comment_depth: usize,
+    done: bool,
+}
+
+impl<'a> TokenStream<'a> {
+    pub fn new(s: &'a str) -> Self {
+        return TokenStream {
+            lines: s.split("\n").enumerate(),
+            pragma_line: pragma_line(s),
+            rest: "",
+            line_num: 0,
+            col_num: 1,
+            comment_depth: 0,
+            done: false,
+        };
+    }
+
+    /// Moves on to the next line which is not skipped, and returns false if there are no more
+    /// lines.
+    fn next_line(&mut self) -> bool {
+        for (line_num, line) in self.lines.by_ref() {
+            if self.comment_depth == 0
+                && ((line_num == 0 && line.starts_with("#!")) || Some(line_num) == self.pragma_line)
+            {
+                continue;
+            }
+            self.rest = line;
+            self.line_num = line_num + 1;
+            self.col_num = 1;
+            return true;
+        }
+        return false;
+    }
+
+    /// Returns the next token in the rest of the current line, or `None` if there are no more
+    /// tokens on it.
+    fn next_in_line(&mut self) -> Result<Option<Token>, TokenizerError> {
+        while !self.rest.is_empty() {
+            // block comments can be nested, so the tokenizer only leaves a comment once each "/*"
+            // in it has been closed by a "*/"
+            let comment_length = if self.rest.starts_with("/*") {
+                self.comment_depth += 1;
+                2
+            } else if self.comment_depth > 0 && self.rest.starts_with("*/") {
+                self.comment_depth -= 1;
+                2
+            } else if self.comment_depth > 0 {
+                self.rest.chars().next().map_or(1, char::len_utf8)
+            } else {
+                0
+            };
+            if comment_length > 0 {
+                self.advance(comment_length);
+                continue;
+            }
+
+            let (token_value_option, token_length) = get_token_value(self.rest)?;
+            let col_num = self.col_num;
+            match token_value_option {
+                None => self.advance(token_length),
+                Some(TokenValue::QUOTE) => {
+                    let (string_token_content, end) = string_literal(&self.rest[token_length..])?;
+                    // the literal is measured as it is written in source, with its quotes and
+                    // escape sequences, rather than by its content
+                    self.advance(token_length + end);
+                    let value = TokenValue::STR(string_token_content);
+                    return Ok(Some(Token(value, self.line_num, col_num)));
+                }
+                Some(TokenValue::DOUBLE_SLASH) => self.rest = "",
+                Some(token_value) => {
+                    self.advance(token_length);
+                    return Ok(Some(Token(token_value, self.line_num, col_num)));
+                }
+            }
+        }
+        return Ok(None);
     }
 
-    let (token_value_option, token_length) = get_token_value(s)?;
-    let token_value = match token_value_option {
-        Some(token) => token,
-        None => return tokenize_helper(&s[token_length..], line_num, col_num + token_length),
-    };
-
-    match token_value {
-        TokenValue::QUOTE => {
-            let s = &s[token_length..];
-            let mut string_token_content = String::new();
-            let mut enumerated_character_iter = s.chars().enumerate();
-            while let Some((index, char)) = enumerated_character_iter.next() {
-                match char {
-                    '"' => {
-                        let mut tokens = vec![Token(
-                            TokenValue::STR(string_token_content.to_string()),
-                            line_num,
-                            col_num,
-                        )];
-                        let mut rest_tokens = tokenize_helper(
-                            &s[index + 1..],
-                            line_num,
-                            // the "plus 2" is to account for the opening and closing quotes for the string literal
-                            col_num + string_token_content.len() + 2,
-                        )?;
-                        tokens.append(&mut rest_tokens);
-                        return Ok(tokens);
+    /// Skips the next `length` bytes of the current line. Columns count characters rather than
+    /// bytes, so a tab or a multi-byte character is a single column.
+    fn advance(&mut self, length: usize) {
+        self.col_num += self.rest[..length].chars().count();
+        self.rest = &self.rest[length..];
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Result<Token, TokenizerError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            match self.next_in_line() {
+                Ok(Some(token)) => return Some(Ok(token)),
+                Ok(None) if self.next_line() => continue,
+                Ok(None) => {
+                    self.done = true;
+                    if self.comment_depth > 0 {
+                        return Some(Err(UnterminatedBlockComment));
                     }
-                    '\\' => match enumerated_character_iter.nth(0) {
-                        Some((_, escaped_char)) => match escaped_char {
-                            'n' => string_token_content.push('\n'),
-                            'r' => string_token_content.push('\r'),
-                            't' => string_token_content.push('\t'),
-                            '\\' => string_token_content.push('\\'),
-                            '0' => string_token_content.push('\0'),
-                            '"' => string_token_content.push('"'),
-                            '\'' => string_token_content.push('\''),
-                            c => return Err(InvalidEscapeSequence(c)),
-                        },
-                        None => return Err(UnterminatedStringLiteral),
-                    },
-                    _ => string_token_content.push(char),
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
                 }
             }
-            return Err(UnterminatedStringLiteral);
         }
-        TokenValue::DOUBLE_SLASH => return Ok(vec![]),
-        token_value => {
-            let mut tokens = vec![Token(token_value, line_num, col_num)];
-            let mut rest_tokens =
-                tokenize_helper(&s[token_length..], line_num, col_num + token_length)?;
-            tokens.append(&mut rest_tokens);
-            return Ok(tokens);
+    }
+}
+
+/// Returns the content of the string literal at the start of `s`, which follows its opening quote,
+/// and the index in `s` just after its closing quote.
+fn string_literal(s: &str) -> Result<(String, usize), TokenizerError> {
+    let mut string_token_content = String::new();
+    let mut enumerated_character_iter = s.char_indices();
+    while let Some((index, char)) = enumerated_character_iter.next() {
+        match char {
+            '"' => return Ok((string_token_content, index + 1)),
+            '\\' => match enumerated_character_iter.nth(0) {
+                Some((_, escaped_char)) => match escaped_char {
+                    'n' => string_token_content.push('\n'),
+                    'r' => string_token_content.push('\r'),
+                    't' => string_token_content.push('\t'),
+                    '\\' => string_token_content.push('\\'),
+                    '0' => string_token_content.push('\0'),
+                    '"' => string_token_content.push('"'),
+                    '\'' => string_token_content.push('\''),
+                    c => return Err(InvalidEscapeSequence(c)),
+                },
+                None => return Err(UnterminatedStringLiteral),
+            },
+            _ => string_token_content.push(char),
         }
     }
+    return Err(UnterminatedStringLiteral);
 }
 
 /// Tries to get a token beginning at the start of `s`. On success, this function returns an option
@@ -236,6 +395,10 @@ fn get_token_value(s: &str) -> Result<(Option<TokenValue>, usize), TokenizerErro
         Ok((Some(TokenValue::OP(Operator::Ne)), mat.end()))
     } else if let Some(mat) = find(EQ_REGEX, s) {
         Ok((Some(TokenValue::OP(Operator::Eq)), mat.end()))
+    } else if let Some(mat) = find(SHIFT_LEFT_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::ShiftLeft)), mat.end()))
+    } else if let Some(mat) = find(SHIFT_RIGHT_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::ShiftRight)), mat.end()))
     } else if let Some(mat) = find(LTE_REGEX, s) {
         Ok((Some(TokenValue::OP(Operator::LTE)), mat.end()))
     } else if let Some(mat) = find(GTE_REGEX, s) {
@@ -246,6 +409,8 @@ fn get_token_value(s: &str) -> Result<(Option<TokenValue>, usize), TokenizerErro
         Ok((Some(TokenValue::OP(Operator::LogicOr)), mat.end()))
     } else if let Some(mat) = find(DOUBLE_SLASH_REGEX, s) {
         Ok((Some(TokenValue::DOUBLE_SLASH), mat.end()))
+    } else if let Some(mat) = find(FLOOR_DIV_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::FloorDiv)), mat.end()))
     } else if let Some(mat) = find(THIN_ARROW_REGEX, s) {
         Ok((Some(TokenValue::THIN_ARROW), mat.end()))
     } else if let Some(mat) = find(DOUBLE_PLUS_REGEX, s) {
@@ -256,6 +421,12 @@ fn get_token_value(s: &str) -> Result<(Option<TokenValue>, usize), TokenizerErro
         Ok((Some(TokenValue::ASSIGN_OP(AssignOp::Plus)), mat.end()))
     } else if let Some(mat) = find(ASSIGNMENT_MINUS_REGEX, s) {
         Ok((Some(TokenValue::ASSIGN_OP(AssignOp::Minus)), mat.end()))
+    } else if let Some(mat) = find(ASSIGNMENT_TIMES_REGEX, s) {
+        Ok((Some(TokenValue::ASSIGN_OP(AssignOp::Times)), mat.end()))
+    } else if let Some(mat) = find(ASSIGNMENT_DIV_REGEX, s) {
+        Ok((Some(TokenValue::ASSIGN_OP(AssignOp::Div)), mat.end()))
+    } else if let Some(mat) = find(ASSIGNMENT_MOD_REGEX, s) {
+        Ok((Some(TokenValue::ASSIGN_OP(AssignOp::Mod)), mat.end()))
 
     // ONE-CHARACTER TOKENS
     } else if let Some(mat) = find(ASSIGN_REGEX, s) {
@@ -264,6 +435,14 @@ fn get_token_value(s: &str) -> Result<(Option<TokenValue>, usize), TokenizerErro
         Ok((Some(TokenValue::OP(Operator::LT)), mat.end()))
     } else if let Some(mat) = find(GT_REGEX, s) {
         Ok((Some(TokenValue::OP(Operator::GT)), mat.end()))
+    } else if let Some(mat) = find(BIT_AND_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::BitAnd)), mat.end()))
+    } else if let Some(mat) = find(BIT_OR_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::BitOr)), mat.end()))
+    } else if let Some(mat) = find(BIT_XOR_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::BitXor)), mat.end()))
+    } else if let Some(mat) = find(BIT_NOT_REGEX, s) {
+        Ok((Some(TokenValue::OP(Operator::BitNot)), mat.end()))
     } else if let Some(mat) = find(STAR_REGEX, s) {
         Ok((Some(TokenValue::OP(Operator::Times)), mat.end()))
     } else if let Some(mat) = find(MOD_REGEX, s) {
@@ -294,6 +473,10 @@ fn get_token_value(s: &str) -> Result<(Option<TokenValue>, usize), TokenizerErro
         Ok((Some(TokenValue::QUOTE), mat.end()))
     } else if let Some(mat) = find(LOGIC_NOT_REGEX, s) {
         Ok((Some(TokenValue::OP(Operator::LogicNot)), mat.end()))
+    } else if let Some(mat) = find(QUESTION_REGEX, s) {
+        Ok((Some(TokenValue::QUESTION), mat.end()))
+    } else if let Some(mat) = find(COLON_REGEX, s) {
+        Ok((Some(TokenValue::COLON), mat.end()))
 
     // VARIABLE-LENGTH TOKENS
     } else if let Some(mat) = find(ID_REGEX, s) {
@@ -333,6 +516,22 @@ impl fmt::Display for AssignOp {
         match self {
             AssignOp::Plus => write!(f, "+="),
             AssignOp::Minus => write!(f, "-="),
+            AssignOp::Times => write!(f, "*="),
+            AssignOp::Div => write!(f, "/="),
+            AssignOp::Mod => write!(f, "%="),
+        }
+    }
+}
+
+impl AssignOp {
+    /// Returns the binary operator which this assignment operator applies, e.g. `+` for `+=`.
+    pub fn binary_operator(&self) -> Operator {
+        match self {
+            AssignOp::Plus => Operator::Plus,
+            AssignOp::Minus => Operator::Minus,
+            AssignOp::Times => Operator::Times,
+            AssignOp::Div => Operator::Div,
+            AssignOp::Mod => Operator::Mod,
         }
     }
 }
@@ -353,7 +552,14 @@ impl fmt::Display for Operator {
             Operator::LogicOr => write!(f, "||"),
             Operator::LogicAnd => write!(f, "&&"),
             Operator::Div => write!(f, "/"),
+            Operator::FloorDiv => write!(f, "~/"),
             Operator::LogicNot => write!(f, "!"),
+            Operator::BitAnd => write!(f, "&"),
+            Operator::BitOr => write!(f, "|"),
+            Operator::BitXor => write!(f, "^"),
+            Operator::BitNot => write!(f, "~"),
+            Operator::ShiftLeft => write!(f, "<<"),
+            Operator::ShiftRight => write!(f, ">>"),
             Operator::PreIncrement => write!(f, "++"),
             Operator::PostIncrement => write!(f, "++"),
             Operator::PreDecrement => write!(f, "--"),
@@ -382,6 +588,32 @@ impl fmt::Display for Keyword {
     }
 }
 
+impl fmt::Display for ContextualKeyword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextualKeyword::Import => write!(f, "import"),
+            ContextualKeyword::As => write!(f, "as"),
+            ContextualKeyword::Pub => write!(f, "pub"),
+            ContextualKeyword::Pure => write!(f, "pure"),
+            ContextualKeyword::Struct => write!(f, "struct"),
+            ContextualKeyword::Requires => write!(f, "requires"),
+            ContextualKeyword::Ensures => write!(f, "ensures"),
+            ContextualKeyword::Loop => write!(f, "loop"),
+        }
+    }
+}
+
+impl TokenValue {
+    /// Returns true if this token is an identifier spelled the same as the contextual keyword
+    /// `keyword`, which the parser treats as that keyword where it expects one.
+    pub fn is_contextual(&self, keyword: ContextualKeyword) -> bool {
+        match self {
+            TokenValue::ID(id) => *id == keyword.to_string(),
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for TokenValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -404,6 +636,8 @@ impl fmt::Display for TokenValue {
             TokenValue::DOUBLE_MINUS => write!(f, "--"),
             TokenValue::ASSIGN_OP(op) => write!(f, "{op}"),
             TokenValue::DOT => write!(f, "."),
+            TokenValue::QUESTION => write!(f, "?"),
+            TokenValue::COLON => write!(f, ":"),
             TokenValue::L_SQUARE_BRACKET => write!(f, "["),
             TokenValue::R_SQUARE_BRACKET => write!(f, "]"),
         }
@@ -0,0 +1,54 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use linger::desugar::{shared_exprs, shared_statements, Expr, Statement};
+
+/// An allocator which counts the allocations made by the current thread, so that tests running on
+/// other threads at the same time do not affect the count.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        return System.alloc(layout);
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of allocations made while running `f`.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    drop(f());
+    return ALLOCATIONS.with(Cell::get) - before;
+}
+
+#[test]
+fn empty_blocks_and_argument_lists_do_not_allocate() {
+    // the shared empty slices are created by the first call
+    drop((shared_exprs([]), shared_statements([])));
+
+    assert_eq!(count_allocations(|| shared_exprs([])), 0);
+    assert_eq!(count_allocations(|| shared_statements([])), 0);
+}
+
+#[test]
+fn argument_lists_allocate_once() {
+    for len in 1..=4 {
+        let args = (0..len).map(|n| Expr::Num(n as f64));
+        assert_eq!(count_allocations(|| shared_exprs(args)), 1);
+    }
+    let statements = [Statement::Continue, Statement::Return(None)];
+    assert_eq!(count_allocations(|| shared_statements(statements)), 1);
+}
@@ -1,7 +1,10 @@
 use std::process::Command;
 
 use assert_cmd::prelude::*;
-use linger::error::{ParseError, RuntimeError};
+use linger::{
+    error::{ParseError, RuntimeError},
+    interpreter::Value,
+};
 use predicates::prelude::predicate::str::{contains, starts_with};
 
 fn file_name_to_path(s: &str) -> String {
@@ -87,3 +90,62 @@ fn err_reassign_top_level_proc() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn multiple_assignment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("multiple_assignment"));
+    cmd.assert().success().stdout("2 1 2 0 2 55");
+
+    Ok(())
+}
+
+#[test]
+fn err_multiple_assignment_count() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-multiple_assignment_count"));
+    cmd.assert()
+        .failure()
+        .stderr(contains(ParseError::AssignmentCountMismatch(2, 3).to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn destructuring() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("destructuring"));
+    cmd.assert().success().stdout("3 9 1 2 [3, 4] x [] 30 nil");
+
+    Ok(())
+}
+
+#[test]
+fn err_pattern_longer_than_list() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-pattern_longer_than_list"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::IndexOutOfBounds(Value::Num(1.0), 1, Default::default()).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_pattern_name() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_pattern_name"));
+    cmd.assert().failure().stderr(contains(
+        ParseError::DuplicatePatternName("a".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,88 @@
+use std::{env, fs, process::Command};
+
+use assert_cmd::prelude::*;
+use linger::{
+    cache::{decode_program, encode_program},
+    error::ParseError,
+    parse,
+};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/build/{}", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn build_and_run() -> TestResult {
+    let output = env::temp_dir().join(format!("linger-build-{}.lingc", std::process::id()));
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+    cmd.arg("build")
+        .arg(file_name_to_path("greeter.ling"))
+        .arg("-o")
+        .arg(&output);
+    cmd.assert().success().stdout("");
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+    cmd.arg("run").arg(&output);
+    cmd.assert().success().stdout("hello greeter 6");
+
+    fs::remove_file(&output)?;
+    Ok(())
+}
+
+#[test]
+fn err_incompatible_compiled_program() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("run")
+        .arg(file_name_to_path("err-incompatible.lingc"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::InvalidCompiledProgram(
+            "compiled by an incompatible version of linger".to_string(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn compiled_program_round_trip() -> TestResult {
+    // some of the programs are nested as deeply as the parser allows, which takes more stack than
+    // a test thread has in a debug build, so they are parsed on a thread with the 8 MiB stack of a
+    // main thread like the one `linger-core` runs on
+    let round_trip = std::thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(round_trip_test_programs)?;
+    round_trip.join().expect("round trip should not panic")?;
+
+    Ok(())
+}
+
+fn round_trip_test_programs() -> Result<(), String> {
+    for category in [
+        "control_flow",
+        "contracts",
+        "lists",
+        "maps",
+        "operators",
+        "procedures",
+    ] {
+        let entries =
+            fs::read_dir(format!("test_programs/{category}")).map_err(|e| e.to_string())?;
+        for entry in entries {
+            let path = entry.map_err(|e| e.to_string())?.path();
+            let source = fs::read_to_string(path).map_err(|e| e.to_string())?;
+            let Ok(program) = parse(&source) else {
+                continue;
+            };
+            let decoded = decode_program(&encode_program(&program)).map_err(|e| e.to_string())?;
+            assert_eq!(decoded, program);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::{error::RuntimeError, interpreter::Value};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/bytes/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn read_file_bytes() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("read_file_bytes"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("<bytes 89504e4700ff10> 7 137 255 504e47"));
+
+    Ok(())
+}
+
+#[test]
+fn hex() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("hex"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("<bytes 00ff7a> 00ff7a true 0"));
+
+    Ok(())
+}
+
+#[test]
+fn err_byte_at_out_of_bounds() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-byte_at_out_of_bounds"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::IndexOutOfBounds(Value::Num(2.0), 2, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_byte_at_out_of_bounds_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-byte_at_out_of_bounds"));
+    cmd.assert().failure().stdout("").stderr(
+        "{\"severity\":\"error\",\"message\":\"index 2 is out of bounds for length 2\",\"position\":{\"line\":2,\"column\":9},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_hex_decode_odd_length() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-hex_decode_odd_length"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("abc".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_read_missing_file() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-read_missing_file"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        "could not read file \"test_programs/bytes/missing.bin\"",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_expected_bytes() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-expected_bytes"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ExpectedBytes(Value::Str("abc".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
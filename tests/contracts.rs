@@ -0,0 +1,62 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::{error::RuntimeError, interpreter::Value, parser::ContractKind};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/contracts/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn contracts_satisfied() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("contracts_satisfied"));
+    cmd.assert().success().stdout("5 0 10 not a keyword here");
+
+    Ok(())
+}
+
+#[test]
+fn err_requires_violated() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-requires_violated"));
+    cmd.assert().failure().stdout("4").stderr(starts_with(
+        "precondition of \"sqrt_floor\" failed: requires(n >= 0)",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_ensures_violated() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-ensures_violated"));
+    cmd.assert().failure().stdout("3").stderr(starts_with(
+        RuntimeError::ContractViolation(
+            ContractKind::Ensures,
+            "abs".to_string(),
+            "result >= 0".to_string(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_contract_not_bool() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-contract_not_bool"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ExpectedBool(Value::Num(1.0)).to_string(),
+    ));
+
+    Ok(())
+}
@@ -1,7 +1,8 @@
-use std::process::Command;
+use std::{fs::File, process::Command};
 
 use assert_cmd::prelude::*;
-use predicates::prelude::predicate::str::contains;
+use linger::error::RuntimeError;
+use predicates::prelude::predicate::str::{contains, starts_with};
 
 fn file_name_to_path(s: &str) -> String {
     return format!("test_programs/control_flow/{}.ling", s);
@@ -68,3 +69,98 @@ fn nested_return() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn if_and_block_expressions() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("if_expression"));
+    cmd.assert().success().stdout("-1 0 1 42 1 true nil done");
+
+    Ok(())
+}
+
+#[test]
+fn conditional_expressions() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("conditional_expression"));
+    cmd.assert().success().stdout("-1 0 1 yes 11 1");
+
+    Ok(())
+}
+
+#[test]
+fn err_return_in_expression() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-return_in_expression"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(RuntimeError::ReturnInExpression.to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn err_break_in_expression() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-break_in_expression"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(RuntimeError::BreakNotInLoop.to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn trace() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--trace").arg(file_name_to_path("trace"));
+    cmd.assert().success().stdout("done").stderr(
+        "[line 2] let i = 0;
+[line 3] while (i < 2) {
+[line 3] condition is true
+[line 4] i++;
+[line 3] condition is true
+[line 4] i++;
+[line 3] condition is false
+[line 6] if (i == 2) {
+[line 6] condition is true
+[line 7] print(\"done\");
+",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn post_mortem() -> TestResult {
+    let commands = "test_programs/control_flow/post_mortem_commands.txt";
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("debug")
+        .arg(file_name_to_path("post_mortem"))
+        .stdin(File::open(commands)?);
+    cmd.assert().failure().stdout("").stderr(
+        "bad argument \"!\"
+stopped on line 12 (type \"help\" for the commands)
+> #0 check
+#1 main
+> 3
+> 2
+> unknown variable \"missing\"
+> ",
+    );
+
+    // without --post-mortem, the error is only reported
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("post_mortem"))
+        .stdin(File::open(commands)?);
+    cmd.assert().failure().stderr("bad argument \"!\"\n");
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "csv")]
+
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::error::RuntimeError;
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/csv/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn csv_parse() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("csv_parse"));
+    cmd.assert().success().stdout(starts_with(
+        "[[name, quote], [ada, hello, world], [bob, say \"hi\"]] \
+         [{name: ada, quote: hello, world}, {name: bob, quote: say \"hi\"}]",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn csv_stringify() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("csv_stringify"));
+    cmd.assert().success().stdout(starts_with(
+        "a,1,true\n\"x,y\",\"say \"\"hi\"\"\",\na,b\n1,2\n3,4\ntrue",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_unterminated_quote() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-unterminated_quote"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::InvalidCsv("unterminated quoted field on line 2".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_header_mismatch() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-header_mismatch"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::InvalidCsv("record 3 has 1 of the 2 fields in the header".to_string())
+            .to_string(),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,600 @@
+use std::{collections::HashMap, fs, path::Path, sync::Arc, thread};
+
+use linger::{
+    check,
+    diagnostic::{Diagnostic, Severity},
+    environment::{BindingChange, Environment},
+    equivalence::{alpha_equivalent, difference, Difference},
+    error::{ParseError, RuntimeError},
+    imports::{load_prelude, ImportResolver},
+    interp_with_options,
+    interpreter::{
+        interp_program, interp_program_with_env, NumberFormat, Value, DEFAULT_CALL_STACK_SIZE,
+    },
+    isolate::Interpreter,
+    language::LanguageVersion,
+    mutation::mutants,
+    parse, parse_with_resolver,
+    parser::{parse_program, parse_sugared_program, parse_sugared_program_with},
+    pretty::pretty_program,
+    symbol::Symbol,
+    tokenizer::tokenize,
+    InterpOptions, Writer,
+};
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/embedding/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn shared_program_across_threads() -> TestResult {
+    let source = fs::read_to_string(file_name_to_path("shared_program"))?;
+    let tokens = tokenize(source.as_str()).map_err(|e| e.to_string())?;
+    let program = Arc::new(parse_program(tokens.as_slice()).map_err(|e| e.to_string())?);
+
+    let handles = (0..4)
+        .map(|_| {
+            let program = Arc::clone(&program);
+            // the tree-walking interpreter recurses deeply, so give each thread the same stack
+            // size as the main thread
+            thread::Builder::new()
+                .stack_size(8 * 1024 * 1024)
+                .spawn(move || {
+                    let mut buf: Vec<u8> = vec![];
+                    interp_program((*program).clone(), &mut Writer::new(Box::new(&mut buf)))
+                        .expect("program should run without error");
+                    String::from_utf8(buf).expect("output should be valid utf-8")
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for handle in handles {
+        assert_eq!(handle.join().expect("thread should not panic"), "55");
+    }
+
+    Ok(())
+}
+
+fn run_on_thread(
+    file_name: &str,
+    stack_size: usize,
+    call_stack_size: usize,
+) -> Result<Result<Value, RuntimeError>, Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(file_name_to_path(file_name))?;
+    let tokens = tokenize(source.as_str()).map_err(|e| e.to_string())?;
+    let program = parse_program(tokens.as_slice()).map_err(|e| e.to_string())?;
+    let handle = thread::Builder::new().stack_size(stack_size).spawn(move || {
+        let mut buf: Vec<u8> = vec![];
+        let mut writer = Writer::new(Box::new(&mut buf)).with_call_stack_size(call_stack_size);
+        return interp_program(program, &mut writer);
+    })?;
+    return Ok(handle.join().expect("thread should not overflow its stack"));
+}
+
+#[test]
+fn err_call_too_deep_on_main_thread_stack() -> TestResult {
+    // non-tail recursion which goes past the stack budget, however much stack a call takes in this
+    // build, is reported rather than overflowing an 8 MiB stack
+    let result = run_on_thread("err-call_too_deep", 8 << 20, DEFAULT_CALL_STACK_SIZE)?;
+    assert!(matches!(result, Err(RuntimeError::CallTooDeep)));
+
+    Ok(())
+}
+
+#[test]
+fn err_call_too_deep_on_small_stack() -> TestResult {
+    let result = run_on_thread("err-call_too_deep", 2 << 20, 1 << 20)?;
+    assert!(matches!(result, Err(RuntimeError::CallTooDeep)));
+
+    Ok(())
+}
+
+#[test]
+fn environment_display() -> TestResult {
+    let source = fs::read_to_string(file_name_to_path("environment_display"))?;
+    let tokens = tokenize(source.as_str()).map_err(|e| e.to_string())?;
+    let program = parse_program(tokens.as_slice()).map_err(|e| e.to_string())?;
+
+    let mut env = Environment::new(program.procedures);
+    env.insert_new_mutable_value("count".to_string(), Value::Num(3.0));
+    env.insert_new_constant_value("name".to_string(), Value::Str("linger".to_string()));
+    env.insert_new_constant_value("long".to_string(), Value::Str("x".repeat(100)));
+
+    assert_eq!(env.iter().count(), 3);
+    assert_eq!(
+        env.to_string(),
+        format!(
+            "let count = 3\nconst long = {}...\nconst name = linger\nproc add/2\nproc fib/1",
+            "x".repeat(60)
+        )
+    );
+
+    Ok(())
+}
+
+#[test]
+fn environment_diff() -> TestResult {
+    let before = Environment::builder()
+        .define_mutable("count", Value::Num(1.0))
+        .define_mutable("nan", Value::Num(f64::NAN))
+        .define("name", Value::Str("linger".to_string()))
+        .define("old", Value::Nil)
+        .build();
+    let after = Environment::builder()
+        .define_mutable("count", Value::Num(2.0))
+        .define_mutable("nan", Value::Num(f64::NAN))
+        .define("name", Value::Str("linger".to_string()))
+        .define_mutable("added", Value::Bool(true))
+        .build();
+
+    assert_eq!(before.diff(&before), vec![]);
+    assert_eq!(
+        before.diff(&after),
+        vec![
+            BindingChange::Added(Symbol::from("added"), Value::Bool(true)),
+            BindingChange::Changed(Symbol::from("count"), Value::Num(1.0), Value::Num(2.0)),
+            BindingChange::Removed(Symbol::from("old"), Value::Nil),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn environment_builder() -> TestResult {
+    let helpers_source = fs::read_to_string(file_name_to_path("host_procedures"))?;
+    let helpers_tokens = tokenize(helpers_source.as_str()).map_err(|e| e.to_string())?;
+    let helpers = parse_program(helpers_tokens.as_slice()).map_err(|e| e.to_string())?;
+
+    let mut builder = Environment::builder()
+        .define("greeting", Value::Str("hello".to_string()))
+        .define_mutable("count", Value::Num(20.0));
+    for procedure in helpers.procedures {
+        builder = builder.define_proc(procedure);
+    }
+
+    let source = fs::read_to_string(file_name_to_path("preset_bindings"))?;
+    let tokens = tokenize(source.as_str()).map_err(|e| e.to_string())?;
+    let program = parse_program(tokens.as_slice()).map_err(|e| e.to_string())?;
+
+    let mut buf: Vec<u8> = vec![];
+    interp_program_with_env(
+        program,
+        builder.build(),
+        &mut Writer::new(Box::new(&mut buf)),
+    )
+    .map_err(|e| e.to_string())?;
+
+    assert_eq!(String::from_utf8(buf)?, "hello 42 21");
+
+    Ok(())
+}
+
+#[test]
+fn check_reports_diagnostics() -> TestResult {
+    assert_eq!(check("proc main() { print(1); }"), Ok(vec![]));
+
+    let diagnostics = match check("proc main() {\n  let a = 5\n}") {
+        Ok(_) => return Err("expected the program to be invalid".into()),
+        Err(diagnostics) => diagnostics,
+    };
+    assert_eq!(diagnostics.len(), 1);
+
+    let Diagnostic {
+        severity, position, ..
+    } = &diagnostics[0];
+    assert_eq!(*severity, Severity::Error);
+    assert_eq!(*position, Some((3, 1)));
+
+    Ok(())
+}
+
+#[test]
+fn check_reports_warnings() -> TestResult {
+    let source = "proc main() {\n  return 1;\n  print(2);\n}";
+    let warnings = match check(source) {
+        Ok(warnings) => warnings,
+        Err(_) => return Err("expected the program to be valid".into()),
+    };
+    assert_eq!(
+        warnings,
+        vec![Diagnostic::warning(
+            "unreachable statement after \"return\"".to_string(),
+            None
+        )]
+    );
+
+    let (value, warnings) = interp_with_options(
+        source.to_string(),
+        &InterpOptions::default(),
+        Box::new(vec![]),
+    )?;
+    assert_eq!(value, "1");
+    assert_eq!(warnings.len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn warn_shadowing_option() -> TestResult {
+    let source = "proc main() {\n  let a = 1;\n  {\n    let a = 2;\n  }\n  return a;\n}";
+    let (_, warnings) = interp_with_options(
+        source.to_string(),
+        &InterpOptions::default(),
+        Box::new(vec![]),
+    )?;
+    assert_eq!(warnings, vec![]);
+
+    let options = InterpOptions {
+        warn_shadowing: true,
+        ..Default::default()
+    };
+    let (value, warnings) = interp_with_options(source.to_string(), &options, Box::new(vec![]))?;
+    assert_eq!(value, "1");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "\"a\" on line 4 shadows the variable declared on line 2"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn max_output_option() -> TestResult {
+    let source = "proc main() { while (true) { print(\"hello\"); } }";
+    let options = InterpOptions {
+        max_output: Some(12),
+        ..Default::default()
+    };
+    let mut output: Vec<u8> = vec![];
+    let result = interp_with_options(source.to_string(), &options, Box::new(&mut output));
+    assert_eq!(
+        result,
+        Err(RuntimeError::OutputLimitExceeded(12).to_string())
+    );
+    assert_eq!(String::from_utf8(output)?, "hellohello");
+
+    let options = InterpOptions {
+        max_output: Some(5),
+        ..Default::default()
+    };
+    let (value, _) = interp_with_options(
+        "proc main() { print(\"hello\"); return 1; }".to_string(),
+        &options,
+        Box::new(vec![]),
+    )?;
+    assert_eq!(value, "1");
+
+    Ok(())
+}
+
+#[test]
+fn float_printing() -> TestResult {
+    let source = fs::read_to_string(file_name_to_path("float_printing"))?;
+
+    let mut shortest: Vec<u8> = vec![];
+    interp_with_options(
+        source.to_string(),
+        &InterpOptions::default(),
+        Box::new(&mut shortest),
+    )?;
+    assert_eq!(
+        String::from_utf8(shortest)?,
+        "0.30000000000000004 0.3333333333333333 5 -0 [1.2100000000000002]"
+    );
+
+    let options = InterpOptions {
+        number_format: NumberFormat::Significant(12),
+        ..Default::default()
+    };
+    let mut significant: Vec<u8> = vec![];
+    interp_with_options(source, &options, Box::new(&mut significant))?;
+    assert_eq!(
+        String::from_utf8(significant)?,
+        "0.3 0.333333333333 5 0 [1.21]"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn prelude_option() -> TestResult {
+    let options = InterpOptions {
+        prelude: Some(
+            load_prelude(Path::new("test_programs/prelude/prelude.ling"))
+                .map_err(|e| e.to_string())?,
+        ),
+        ..Default::default()
+    };
+    let mut output: Vec<u8> = vec![];
+    interp_with_options(
+        "proc main() { greet(square(3)); }".to_string(),
+        &options,
+        Box::new(&mut output),
+    )?;
+    assert_eq!(String::from_utf8(output)?, "hello 9");
+
+    Ok(())
+}
+
+#[test]
+fn closures_capture_referenced_variables() -> TestResult {
+    let source = "proc main() {
+      let unused = list(1, 2, 3);
+      const offset = 10;
+      let scale = 2;
+      return (x) -> {
+        let y = x * scale;
+        return (z) -> y + z + offset;
+      };
+    }";
+    let tokens = tokenize(source).map_err(|e| e.to_string())?;
+    let program = parse_program(tokens.as_slice()).map_err(|e| e.to_string())?;
+    let closure =
+        interp_program(program, &mut Writer::new(Box::new(vec![]))).map_err(|e| e.to_string())?;
+
+    let Value::Proc(closure) = closure else {
+        return Err("expected main to return a procedure".into());
+    };
+    let mut captured: Vec<&str> = closure.env.iter().map(|(name, _)| name.as_str()).collect();
+    captured.sort();
+    assert_eq!(captured, vec!["offset", "scale"]);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_interop() -> TestResult {
+    use serde_json::json;
+
+    let config = json!({
+        "name": "linger",
+        "tags": ["fast", "small"],
+        "owner": null,
+        "limits": { "retries": 2 },
+    });
+    let env = Environment::builder()
+        .define("config", Value::from(config))
+        .build();
+
+    let source = fs::read_to_string(file_name_to_path("json_interop"))?;
+    let tokens = tokenize(source.as_str()).map_err(|e| e.to_string())?;
+    let program = parse_program(tokens.as_slice()).map_err(|e| e.to_string())?;
+
+    let mut buf: Vec<u8> = vec![];
+    let result = interp_program_with_env(program, env, &mut Writer::new(Box::new(&mut buf)))
+        .map_err(|e| e.to_string())?;
+    assert_eq!(String::from_utf8(buf)?, "linger small true 3");
+
+    let result = serde_json::Value::try_from(result).map_err(|e| e.to_string())?;
+    assert_eq!(
+        result,
+        json!({ "7": "seven", "count": 3, "items": ["a", null], "ok": true, "ratio": 0.5 })
+    );
+    assert_eq!(
+        result.to_string(),
+        r#"{"7":"seven","count":3,"items":["a",null],"ok":true,"ratio":0.5}"#
+    );
+
+    let not_json = Value::Num(f64::INFINITY);
+    assert_eq!(
+        serde_json::Value::try_from(not_json).map_err(|e| e.to_string()),
+        Err(RuntimeError::NotJson(Value::Num(f64::INFINITY)).to_string())
+    );
+
+    Ok(())
+}
+
+/// Resolves imports to sources held in memory, as a host serving modules from a database would.
+struct MemoryResolver(HashMap<&'static str, &'static str>);
+
+impl ImportResolver for MemoryResolver {
+    fn load(&self, name: &str) -> Result<String, String> {
+        match self.0.get(name) {
+            Some(source) => Ok(source.to_string()),
+            None => Err(format!("no module named \"{name}\"")),
+        }
+    }
+}
+
+#[test]
+fn import_resolver() -> TestResult {
+    let resolver = MemoryResolver(HashMap::from([
+        (
+            "greetings",
+            "import \"names\"; pub proc greet() { return \"hello \" + name(); }",
+        ),
+        ("names", "pub proc name() { return \"linger\"; }"),
+        ("cycle", "import \"cycle\"; pub proc f() {}"),
+    ]));
+
+    let options = InterpOptions {
+        import_resolver: Some(Arc::new(resolver)),
+        ..Default::default()
+    };
+    let mut output: Vec<u8> = vec![];
+    interp_with_options(
+        "import \"greetings\" as g; proc main() { print(g.greet()); }".to_string(),
+        &options,
+        Box::new(&mut output),
+    )?;
+    assert_eq!(String::from_utf8(output)?, "hello linger");
+
+    let resolver = options
+        .import_resolver
+        .as_deref()
+        .expect("resolver was set");
+    let missing = parse_with_resolver("import \"missing\"; proc main() {}", resolver);
+    assert_eq!(
+        diagnostic_messages(missing),
+        vec![ParseError::ImportFailed(
+            "missing".to_string(),
+            "no module named \"missing\"".to_string()
+        )
+        .to_string()]
+    );
+    let cycle = parse_with_resolver("import \"cycle\"; proc main() {}", resolver);
+    assert_eq!(
+        diagnostic_messages(cycle),
+        vec!["cyclic import of \"cycle\""]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn alpha_equivalence() -> TestResult {
+    let parse_file = |name: &str| {
+        let source = fs::read_to_string(file_name_to_path(name)).map_err(|e| e.to_string())?;
+        return parse(source.as_str()).map_err(|_| "program should parse".to_string());
+    };
+    let parse_source = |source: &str| parse(source).map_err(|_| "program should parse");
+    let reference = parse_file("equivalence_reference")?;
+    let renamed = parse_file("equivalence_renamed")?;
+    let different = parse_file("equivalence_different")?;
+
+    assert!(alpha_equivalent(&reference, &renamed));
+    assert!(alpha_equivalent(&renamed, &reference));
+    assert_eq!(
+        difference(&reference, &different),
+        Some(Difference {
+            procedure: Some("sum_to".to_string()),
+            line: 5,
+        })
+    );
+
+    // a name is only equivalent to the name bound at the same point of the other program
+    let swapped = parse_source("proc main() { let a = 1; let b = 2; print(b - a); }")?;
+    let unswapped = parse_source("proc main() { let a = 1; let b = 2; print(a - b); }")?;
+    assert!(!alpha_equivalent(&swapped, &unswapped));
+
+    Ok(())
+}
+
+#[test]
+fn language_version_per_parse() -> TestResult {
+    let source = "proc main() {\n  let x = 1\n  print(x)\n}";
+    let tokens = tokenize(source).map_err(|e| e.to_string())?;
+
+    // the version is only used by the parse it is given to, so parses of programs written in
+    // different versions can be interleaved on the same thread
+    assert!(parse_sugared_program_with(&tokens, LanguageVersion::V2).is_ok());
+    assert!(parse_sugared_program(&tokens).is_err());
+    assert!(parse_sugared_program_with(&tokens, LanguageVersion::V2).is_ok());
+    assert!(parse_sugared_program_with(&tokens, LanguageVersion::V1).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn mutation_testing() -> TestResult {
+    let source =
+        "proc is_adult(age) {\n  return age >= 18;\n}\n\nproc main() {\n  print(is_adult(20));\n}";
+    let tokens = tokenize(source).map_err(|e| e.to_string())?;
+    let program = parse_sugared_program(&tokens).map_err(|e| e.to_string())?;
+
+    let mutants = mutants(&program);
+    let descriptions: Vec<String> = mutants.iter().map(|m| m.to_string()).collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            "\">=\" changed to \">\" on line 2",
+            "18 changed to 19 on line 2",
+            "18 changed to 17 on line 2",
+            "20 changed to 21 on line 6",
+            "20 changed to 19 on line 6",
+        ]
+    );
+    // each mutant only has its own change
+    assert!(pretty_program(&mutants[0].program).contains("return age > 18;"));
+    assert!(pretty_program(&mutants[2].program).contains("return age >= 17;"));
+    assert!(pretty_program(&mutants[2].program).contains("is_adult(20)"));
+
+    Ok(())
+}
+
+fn diagnostic_messages<T>(result: Result<T, Vec<Diagnostic>>) -> Vec<String> {
+    match result {
+        Ok(_) => vec![],
+        Err(diagnostics) => diagnostics.iter().map(|d| d.to_string()).collect(),
+    }
+}
+
+#[test]
+fn isolates() -> TestResult {
+    let helpers_source = fs::read_to_string(file_name_to_path("host_procedures"))?;
+    let helpers_tokens = tokenize(helpers_source.as_str()).map_err(|e| e.to_string())?;
+    let helpers = parse_program(helpers_tokens.as_slice()).map_err(|e| e.to_string())?;
+    let mut natives = Environment::builder().define_mutable("count", Value::Num(1.0));
+    for procedure in helpers.procedures {
+        natives = natives.define_proc(procedure);
+    }
+    let interpreter = Interpreter::new(natives.build());
+
+    let first = fs::read_to_string(file_name_to_path("isolate_first"))?;
+    let second = fs::read_to_string(file_name_to_path("isolate_second"))?;
+    let parse_source = |source: &str| parse(source).map_err(|_| "program should parse");
+
+    // globals defined by a program stay bound in its isolate for the programs run after it
+    let mut isolate = interpreter.isolate();
+    let mut output: Vec<u8> = vec![];
+    {
+        let writer = &mut Writer::new(Box::new(&mut output));
+        isolate
+            .run(parse_source(&first)?, writer)
+            .map_err(|e| e.to_string())?;
+        isolate
+            .run(parse_source(&second)?, writer)
+            .map_err(|e| e.to_string())?;
+    }
+    assert_eq!(String::from_utf8(output)?, "first 4 first 9");
+    assert_eq!(isolate.get("count"), Some(Value::Num(3.0)));
+
+    // another isolate shares the native procedures but none of the globals of the first isolate
+    let mut other = interpreter.isolate();
+    assert_eq!(other.get("count"), Some(Value::Num(1.0)));
+    let mut output: Vec<u8> = vec![];
+    let result = other.run(
+        parse_source(&second)?,
+        &mut Writer::new(Box::new(&mut output)),
+    );
+    assert_eq!(
+        result.map_err(|e| e.to_string()),
+        Err(RuntimeError::UnknownVariable("label".to_string()).to_string())
+    );
+    assert_eq!(other.get("triple"), None);
+    assert!(other.get("double").is_some());
+
+    Ok(())
+}
+
+#[test]
+fn program_nodes_are_freed() -> TestResult {
+    let source = fs::read_to_string(file_name_to_path("escaping_lambda"))?;
+    let program = parse(source.as_str()).map_err(|_| "program should parse")?;
+    let main = Arc::downgrade(&program.main);
+    let make_adder = Arc::downgrade(&program.procedures[0].body);
+
+    let value = interp_program(program, &mut Writer::new(Box::new(std::io::sink())))
+        .map_err(|e| e.to_string())?;
+    let lambda = match &value {
+        Value::Proc(closure) => Arc::downgrade(&closure.body),
+        _ => return Err(format!("expected a lambda, but got {value:?}").into()),
+    };
+
+    // the main procedure is freed with the program, but the returned lambda keeps its own body and
+    // the procedures of the environment it closes over alive
+    assert!(main.upgrade().is_none());
+    assert!(lambda.upgrade().is_some());
+    assert!(make_adder.upgrade().is_some());
+
+    drop(value);
+    assert!(lambda.upgrade().is_none());
+    assert!(make_adder.upgrade().is_none());
+
+    Ok(())
+}
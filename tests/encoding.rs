@@ -0,0 +1,74 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::{error::RuntimeError, interpreter::Value};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/encoding/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[cfg(feature = "hash")]
+#[test]
+fn hashing() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("hashing"));
+    cmd.assert().success().stdout(starts_with(
+        "d41d8cd98f00b204e9800998ecf8427e 9e107d9d372bb6826bd81d3542a419d6 \
+         ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad \
+         e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn base64() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("base64"));
+    cmd.assert().success().stdout(starts_with(
+        "dXNlcjpww6Rzc3dvcmQ= /w==  <bytes 757365723a70c3a47373776f7264> <bytes ff>",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn url() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("url"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("a%20b%26c%3Dd%2F%C3%A9~ a b&c=d/é~ 1+1=2"));
+
+    Ok(())
+}
+
+#[test]
+fn err_invalid_base64() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-invalid_base64"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("a*b=".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_invalid_url_escape() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-invalid_url_escape"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("100%".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
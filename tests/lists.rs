@@ -1,7 +1,11 @@
 use std::process::Command;
 
 use assert_cmd::prelude::*;
-use linger::{error::RuntimeError, interpreter::Value};
+use linger::{
+    error::RuntimeError,
+    interpreter::{Value, MAX_RANGE_LENGTH},
+    tokenizer::Operator,
+};
 use predicates::prelude::predicate::str::starts_with;
 
 fn file_name_to_path(s: &str) -> String {
@@ -20,6 +24,18 @@ fn list_initialization() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn deprecated_list_builtin() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("deprecated_list_builtin"));
+    cmd.assert().success().stdout(starts_with("[1, 2, 3]")).stderr(
+        "warning: \"list(...)\" is deprecated and will be removed @ (2, 12)\nhelp: write the elements in a list literal instead, e.g. \"[1, 2]\" rather than \"list(1, 2)\"\n",
+    );
+
+    Ok(())
+}
+
 #[test]
 fn list_indexing() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -30,6 +46,30 @@ fn list_indexing() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn index_assignment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("index_assignment"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[10, 3, [3, 40]] [1, 0] {a: 2}"));
+
+    Ok(())
+}
+
+#[test]
+fn index_compound_assignment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("index_compound_assignment"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[3, -3, [6, 0.5]] [11, 20] 1 {a: 2}"));
+
+    Ok(())
+}
+
 #[test]
 fn list_concatenation() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -62,6 +102,113 @@ fn rest() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn zip() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("zip"));
+    cmd.assert().success().stdout(starts_with("[[a, 1], [b, 2]]"));
+
+    Ok(())
+}
+
+#[test]
+fn enumerate() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("enumerate"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[[0, a], [1, b], [2, c]] []"));
+
+    Ok(())
+}
+
+#[test]
+fn range() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("range"));
+    cmd.assert().success().stdout(starts_with(
+        "[0, 1, 2, 3] [2, 3, 4] [10, 7, 4, 1] [] [0, 2, 4] [] [-9007199254740992, 0]",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn sort() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("sort"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[1, 2, 3] [apple, fig, pear] []"));
+
+    Ok(())
+}
+
+#[test]
+fn sort_by() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("sort_by"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[3, 2, 1] [[1, b], [1, d], [2, a], [2, c]]"));
+
+    Ok(())
+}
+
+#[test]
+fn sort_by_inconsistent_comparator() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    let sorted = |n: usize| format!("{:?}", (0..n).collect::<Vec<_>>());
+    cmd.arg(file_name_to_path("sort_by_inconsistent"));
+    cmd.assert()
+        .success()
+        .stdout(format!("{} {}", sorted(100), sorted(40)));
+
+    Ok(())
+}
+
+#[test]
+fn searching() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("searching"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("3 nil 1 15 nil true false true false true"));
+
+    Ok(())
+}
+
+#[test]
+fn list_utilities() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("list_utilities"));
+    cmd.assert().success().stdout(starts_with(
+        "[1, 2, 3, [4]] [1, 10, 2, 20, 3, 30, 4, 40] [3, 1, 2] [4, 3, 2, 1] cba [1, 2] [1, 2, 3, 4] [4] []",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn copy_and_freeze() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("copy_and_freeze"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("[1, 2] [1, 2, 3] [4]"));
+
+    Ok(())
+}
+
 #[test]
 fn err_head_non_list() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -103,20 +250,41 @@ fn err_index_out_of_bounds() -> TestResult {
     let mut cmd_higher = Command::cargo_bin("linger-core")?;
 
     cmd_higher.arg(file_name_to_path("err-index_out_of_bounds_higher"));
-    cmd_higher
-        .assert()
-        .failure()
-        .stdout("")
-        .stderr(starts_with(RuntimeError::IndexOutOfBounds(3).to_string()));
+    cmd_higher.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::IndexOutOfBounds(Value::Num(3.0), 3, Default::default()).to_string(),
+    ));
 
     let mut cmd_lower = Command::cargo_bin("linger-core")?;
 
     cmd_lower.arg(file_name_to_path("err-index_out_of_bounds_lower"));
-    cmd_lower
-        .assert()
-        .failure()
-        .stdout("")
-        .stderr(starts_with(RuntimeError::IndexOutOfBounds(-1).to_string()));
+    cmd_lower.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::IndexOutOfBounds(Value::Num(-1.0), 3, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_index_out_of_bounds_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-index_out_of_bounds_higher"));
+    cmd.assert().failure().stdout("").stderr(
+        "{\"severity\":\"error\",\"message\":\"index 3 is out of bounds for length 3\",\"position\":{\"line\":3,\"column\":8},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_index_assignment_out_of_bounds() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-index_assignment_out_of_bounds"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::IndexOutOfBounds(Value::Num(2.0), 2, Default::default()).to_string(),
+    ));
 
     Ok(())
 }
@@ -139,3 +307,141 @@ fn err_index_not_an_integer() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn err_range_zero_step() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-range_zero_step"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Num(0.0)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_range_too_large() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-range_too_large"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::RangeTooLarge(MAX_RANGE_LENGTH).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_sort_mixed_types() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-sort_mixed_types"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::CannotCompare(
+            Operator::LT,
+            Value::Num(1.0),
+            Value::Str("a".to_string()),
+            Default::default(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_sort_by_comparator_not_num() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-sort_by_comparator_not_num"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Bool(false)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_predicate_not_bool() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-predicate_not_bool"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ExpectedBool(Value::Num(2.0)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_flat_map_non_list() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-flat_map_non_list"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ExpectedList(Value::Num(1.0)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_reassign_frozen() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-reassign_frozen"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ReassignConstant("xs".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_index_assignment_frozen() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-index_assignment_frozen"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ReassignConstant("xs".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn join_split() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("join_split"));
+    cmd.assert().success().stdout(starts_with(
+        "[name, age, , city] name;age;;city true 1-true-x-[2] [a, b] true",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_split_empty_separator() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-split_empty_separator"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_join_non_string_separator() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-join_non_string_separator"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::ExpectedStr(Value::Num(1.0)).to_string(),
+    ));
+
+    Ok(())
+}
@@ -92,6 +92,39 @@ fn for_with_existing_initial_value() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn loop_statement() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("loop"));
+    cmd.assert().success().stdout("2 1 16").stderr("");
+
+    Ok(())
+}
+
+#[test]
+fn loop_without_break_warning() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--check")
+        .arg(file_name_to_path("loop_without_break"));
+    cmd.assert().success().stdout("").stderr(
+        "warning: \"loop\" never ends, since it has no reachable \"break\" or \"return\"\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn break_with_value() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("break_with_value"));
+    cmd.assert().success().stdout("1 -1 nil 25");
+
+    Ok(())
+}
+
 #[test]
 fn err_break_not_in_loop() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::{error::RuntimeError, interpreter::Value};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/maps/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn map_keys() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("map_keys"));
+    cmd.assert().success().stdout(starts_with(
+        "{1: uno, 2: two, name: linger, true: yes} uno yes nil true false [1, 2, name, true]",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_invalid_map_key() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-invalid_map_key"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::InvalidMapKey(Value::List(vec![Value::Num(1.0)])).to_string(),
+    ));
+
+    Ok(())
+}
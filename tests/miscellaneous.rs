@@ -3,9 +3,13 @@ use std::process::Command;
 use assert_cmd::prelude::*;
 use linger::{
     error::{ParseError, RuntimeError, TokenizerError},
-    interpreter::Value,
+    interpreter::{parse_num, NumberFormat, Value},
+    language::LanguageVersion,
+    parser::{parse_sugared_program_from, parse_sugared_program_with, Builtin},
+    tokenizer::{tokenize, Token, TokenStream, TokenValue},
 };
 use predicates::prelude::predicate::str::{contains, starts_with};
+use proptest::prelude::*;
 
 fn file_name_to_path(s: &str) -> String {
     return format!("test_programs/miscellaneous/{}.ling", s);
@@ -23,6 +27,139 @@ fn comments() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn block_comments() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("block_comments"));
+    cmd.assert().success().stdout("3/* not a comment */");
+
+    Ok(())
+}
+
+#[test]
+fn block_comment_positions() -> TestResult {
+    let tokens = tokenize("/* one\ntwo /* three */\n */ x /**/ y").map_err(|e| e.to_string())?;
+    assert_eq!(
+        tokens,
+        vec![
+            Token(TokenValue::ID("x".to_string()), 3, 5),
+            Token(TokenValue::ID("y".to_string()), 3, 12),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn token_positions_after_strings_and_tabs() -> TestResult {
+    // columns count characters as they are written in source, so the escapes and the multi-byte
+    // character in the string, and the tab, each move the later tokens by the right amount
+    let tokens = tokenize("\tlet s = \"a\\\"\u{e9}\\n\"; x").map_err(|e| e.to_string())?;
+    let positions: Vec<(usize, usize)> = tokens
+        .iter()
+        .map(|Token(_, line, col)| (*line, *col))
+        .collect();
+    assert_eq!(
+        positions,
+        vec![(1, 2), (1, 6), (1, 8), (1, 10), (1, 18), (1, 20)]
+    );
+    assert_eq!(tokens[3].0, TokenValue::STR("a\"\u{e9}\n".to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn token_stream() -> TestResult {
+    // the tokens before an error are yielded before the rest of the program is tokenized
+    let mut stream = TokenStream::new("x\n\"unterminated\ny");
+    assert_eq!(
+        stream.next().transpose().map_err(|e| e.to_string())?,
+        Some(Token(TokenValue::ID("x".to_string()), 1, 1))
+    );
+    assert!(matches!(
+        stream.next(),
+        Some(Err(TokenizerError::UnterminatedStringLiteral))
+    ));
+    assert!(stream.next().is_none());
+
+    // the stream can be stopped early, e.g. to read only the first tokens of a program
+    let source = "#!/usr/bin/env linger\nproc main() { /* } */ print(\"}\"); }";
+    let positions = (TokenStream::new(source).take(5))
+        .map(|token| token.map(|Token(_, line, col)| (line, col)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    assert_eq!(positions, vec![(2, 1), (2, 6), (2, 10), (2, 11), (2, 13)]);
+
+    Ok(())
+}
+
+/// Returns the paths of every test program.
+fn glob_test_programs() -> std::io::Result<Vec<std::path::PathBuf>> {
+    let mut paths = vec![];
+    for dir in std::fs::read_dir("test_programs")? {
+        for file in std::fs::read_dir(dir?.path())? {
+            let path = file?.path();
+            if path.extension().is_some_and(|extension| extension == "ling") {
+                paths.push(path);
+            }
+        }
+    }
+    return Ok(paths);
+}
+
+#[test]
+fn parse_token_stream() -> TestResult {
+    // a program parses in the same way from a stream of tokens as from a slice of them. Some of the
+    // test programs are nested deeply, so they are parsed with the stack size of a main thread
+    let parse_test_programs = || -> Result<(), String> {
+        for path in glob_test_programs().map_err(|e| e.to_string())? {
+            let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            let (Ok(language), Ok(tokens)) = (LanguageVersion::parse(&source), tokenize(&source))
+            else {
+                continue;
+            };
+            let from_slice =
+                parse_sugared_program_with(&tokens, language).map_err(|e| e.to_string());
+            let from_stream = parse_sugared_program_from(tokens.into_iter().map(Ok), language)
+                .map_err(|e| e.to_string());
+            assert_eq!(from_stream, from_slice, "{}", path.display());
+        }
+        Ok(())
+    };
+    std::thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(parse_test_programs)?
+        .join()
+        .expect("parsing should not panic")?;
+
+    // only the tokens up to the end of the definition with an error are read, so the later
+    // unterminated string is never reached
+    let source = "proc main() { print(1 +); }\nproc f() { return 1; }\n\"unterminated";
+    let mut read = 0;
+    let stream = TokenStream::new(source).inspect(|_| read += 1);
+    let result = parse_sugared_program_from(stream, LanguageVersion::V1);
+    assert!(matches!(result, Err(ParseError::UnexpectedToken(..))));
+    assert_eq!(read, 12);
+
+    Ok(())
+}
+
+#[test]
+fn err_unterminated_block_comment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-unterminated_block_comment"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            TokenizerError::UnterminatedBlockComment.to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
 #[test]
 fn string_indexing() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -59,6 +196,37 @@ fn is_nil() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn statement_values() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+    cmd.arg(file_name_to_path("statement_values"));
+    cmd.assert()
+        .success()
+        .stdout("true 5 true 2 true true true true");
+
+    Ok(())
+}
+
+#[test]
+fn shebang_metadata() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("shebang_metadata"));
+    cmd.assert().success().stdout("greeter 1.2.0");
+
+    Ok(())
+}
+
+#[test]
+fn no_metadata() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("no_metadata"));
+    cmd.assert().success().stdout("nil nil");
+
+    Ok(())
+}
+
 #[test]
 fn err_is_empty_non_list() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -74,6 +242,46 @@ fn err_is_empty_non_list() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn err_builtin_arity() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-builtin_arity"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BuiltinArgMismatch(Builtin::Head, 2, Default::default()).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_builtin_arity_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-builtin_arity"));
+    cmd.assert().failure().stdout("").stderr(
+        "{\"severity\":\"error\",\"message\":\"procedure \\\"head\\\" expected 1 args, instead got 2 (usage: head(list))\",\"position\":{\"line\":2,\"column\":9},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_builtin_arity_range() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-builtin_arity_range"));
+    cmd.assert().failure().stderr(starts_with(
+        "procedure \"range\" expected 1 to 3 args, instead got 4 (usage: range([start,] stop[, step]))",
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn err_missing_main() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -147,3 +355,232 @@ fn err_unexpected_eof() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[test]
+fn check_valid_program() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--check").arg(file_name_to_path("is_nil_true"));
+    cmd.assert().success().stdout("").stderr("");
+
+    Ok(())
+}
+
+#[test]
+fn err_check_invalid_program() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--check")
+        .arg(file_name_to_path("err-missing_semicolon"));
+    cmd.assert()
+        .failure()
+        .stdout("")
+        .stderr(starts_with("expected token \";\""));
+
+    Ok(())
+}
+
+#[test]
+fn err_json_error_format() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-missing_semicolon"));
+    cmd.assert().failure().stderr(
+        "{\"severity\":\"error\",\"message\":\"expected token \\\";\\\" @ (3, 1), instead got \\\"}\\\"\",\"position\":{\"line\":3,\"column\":1},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn unreachable_code_warning() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("unreachable_code"));
+    cmd.assert()
+        .success()
+        .stdout("0")
+        .stderr("warning: unreachable statement after \"break\"\n");
+
+    Ok(())
+}
+
+#[test]
+fn err_unreachable_code_deny_warnings() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--deny-warnings")
+        .arg(file_name_to_path("unreachable_code"));
+    cmd.assert()
+        .failure()
+        .stdout("")
+        .stderr("warning: unreachable statement after \"break\"\n");
+
+    Ok(())
+}
+
+#[test]
+fn unused_result_warning() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("unused_result"));
+    cmd.assert().success().stdout("2 4").stderr(
+        "warning: unused result of \"y + 1\"\n\
+         help: assign the result to a variable, or remove the expression\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn number_printing() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("number_printing"));
+    cmd.assert().success().stdout(
+        "[0.30000000000000004, 0.3333333333333333, -2.5, 100, 1e-7, 1e22]true 1.5e-7 -0.25",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn debug() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("debug"));
+    cmd.assert().success().stdout(
+        r#"[nil, "nil", 1.5, true, "say \"hi\"\n", <proc add(a, b)>, <lambda inc(x)>, <lambda(x)>] {"key": []} "plain""#,
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_parse_num() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-parse_num"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::BadArg(Value::Str("1,5".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn shortest_number_format_round_trips(n in any::<f64>()) {
+        let parsed = parse_num(&NumberFormat::Shortest.format(n));
+        if n.is_nan() {
+            prop_assert!(parsed.is_some_and(f64::is_nan));
+        } else {
+            // compare the bits so that `-0` must round trip to `-0`
+            prop_assert_eq!(parsed.map(f64::to_bits), Some(n.to_bits()));
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn syntax_json() -> TestResult {
+    use serde_json::{json, Value as Json};
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--tokens-json")
+        .arg("--ast-json")
+        .arg(file_name_to_path("syntax_json"));
+    let output = cmd.assert().success().get_output().stdout.clone();
+    let output = String::from_utf8(output)?;
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2);
+
+    let tokens: Json = serde_json::from_str(lines[0])?;
+    assert_eq!(tokens.as_array().map(Vec::len), Some(24));
+    assert_eq!(
+        tokens[10],
+        json!({ "kind": "NUM", "text": "1", "value": 1.0, "line": 2, "col": 17 })
+    );
+
+    let ast: Json = serde_json::from_str(lines[1])?;
+    let statements = &ast["procedures"][0]["body"]["statements"];
+    assert_eq!(ast["procedures"][0]["name"], "main");
+    assert_eq!(
+        statements[3],
+        json!({
+            "type": "Expr",
+            "expr": {
+                "type": "PrimitiveCall",
+                "builtin": "print",
+                "args": [{
+                    "type": "Index",
+                    "indexable": { "type": "Var", "name": "xs" },
+                    "index": { "type": "Num", "value": 0.0 },
+                }],
+            },
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn type_assert() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("type_assert"));
+    cmd.assert().success().stdout("6 nil");
+
+    Ok(())
+}
+
+#[test]
+fn err_panic() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-panic"));
+    let call_stack = vec!["check", "outer", "main"];
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::Panic(
+                "negative number".to_string(),
+                3,
+                call_stack.into_iter().map(String::from).collect(),
+            )
+            .to_string(),
+        ))
+        .stdout("1");
+
+    Ok(())
+}
+
+#[test]
+fn err_type_assert() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-type_assert"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::TypeAssertionFailed(
+                Some("xs".to_string()),
+                "list".to_string(),
+                Value::Num(5.0),
+            )
+            .to_string(),
+        ))
+        .stdout("");
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-type_assert_expression"));
+    cmd.assert().failure().stderr(starts_with(
+        RuntimeError::TypeAssertionFailed(None, "num".to_string(), Value::Str("a".to_string()))
+            .to_string(),
+    ));
+
+    Ok(())
+}
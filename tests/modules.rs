@@ -0,0 +1,120 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::error::ParseError;
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/modules/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn import_pub() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("import_pub"));
+    cmd.assert().success().stdout("12 14");
+
+    Ok(())
+}
+
+#[test]
+fn shadow_private() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("shadow_private"));
+    cmd.assert().success().stdout("5 4 5");
+
+    Ok(())
+}
+
+#[test]
+fn err_private_procedure() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-private_procedure"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        ParseError::PrivateProcedure("double".to_string(), "lib/geometry.ling".to_string())
+            .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_check_private_procedure() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--check")
+        .arg(file_name_to_path("err-private_procedure"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        ParseError::PrivateProcedure("double".to_string(), "lib/geometry.ling".to_string())
+            .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_cyclic_import() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-cyclic_import"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::CyclicImport("cycle_a.ling".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_missing_module() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-missing_module"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with("could not import \"lib/missing.ling\""));
+
+    Ok(())
+}
+
+#[test]
+fn import_alias() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("import_alias"));
+    cmd.assert().success().stdout("9 12 6 30");
+
+    Ok(())
+}
+
+#[test]
+fn err_private_qualified_procedure() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-private_qualified_procedure"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        ParseError::PrivateProcedure(
+            "geometry.double".to_string(),
+            "lib/geometry.ling".to_string(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_alias() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_alias"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::DuplicateTopLevelName("lib".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
@@ -1,8 +1,12 @@
 use std::process::Command;
 
 use assert_cmd::prelude::*;
-use linger::error::RuntimeError;
-use linger::interpreter::Value;
+use linger::error::{ParseError, RuntimeError};
+use linger::interpreter::{Value, MAX_REPETITION_LENGTH};
+use linger::parser::{
+    binary_precedence, parse_sugared_program, SugaredExpr, SugaredStatement, BINARY_OPERATORS,
+};
+use linger::tokenizer::{tokenize, Operator, Token, TokenValue};
 use predicates::{
     prelude::{predicate::str::contains, PredicateBooleanExt},
     str::starts_with,
@@ -55,7 +59,7 @@ fn assignment_operators() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
 
     cmd.arg(file_name_to_path("assignment_operators"));
-    cmd.assert().success().stdout(contains("15 -5"));
+    cmd.assert().success().stdout(contains("15 -5 -15 -7.5 -1.5"));
 
     Ok(())
 }
@@ -72,6 +76,16 @@ fn operator_precedence() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn parentheses() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("parentheses"));
+    cmd.assert().success().stdout(contains("8 10 true false 5"));
+
+    Ok(())
+}
+
 #[test]
 fn short_circuiting() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -82,6 +96,103 @@ fn short_circuiting() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn long_operator_chain() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("long_operator_chain"));
+    cmd.assert().success().stdout("45150");
+
+    Ok(())
+}
+
+#[test]
+fn err_operator_chain_too_long() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-operator_chain_too_long"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            ParseError::ChainTooLong(Token(TokenValue::OP(Operator::Plus), 28, 103)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn evaluation_order() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("evaluation_order"));
+    cmd.assert()
+        .success()
+        .stdout("-1 [2, 3] [4, 5, 6] 7 109 2 true 0 11 [1, 0]");
+
+    Ok(())
+}
+
+#[test]
+fn repetition() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("repetition"));
+    cmd.assert()
+        .success()
+        .stdout(contains("ababab  [0, 0, 0] [1, 2, 1, 2]"));
+
+    Ok(())
+}
+
+#[test]
+fn floor_division() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("floor_division"));
+    cmd.assert()
+        .success()
+        .stdout(contains("3 -4 3 9 [3, 1] [-4, 1]"));
+
+    Ok(())
+}
+
+#[test]
+fn comparisons() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("comparisons"));
+    cmd.assert()
+        .success()
+        .stdout(contains("true true true false true false"));
+
+    Ok(())
+}
+
+#[test]
+fn bitwise_operators() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("bitwise_operators"));
+    cmd.assert()
+        .success()
+        .stdout(contains("4 7 3 -6 24 3 -4 13 true true"));
+
+    Ok(())
+}
+
+#[test]
+fn bitwise_exact_integers() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("bitwise_exact_integers"));
+    cmd.assert().success().stdout(contains(
+        "4503599627370497 9007199254740991 2 -9007199254740992",
+    ));
+
+    Ok(())
+}
+
 #[test]
 fn err_bad_arg_plus_bool() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -111,3 +222,258 @@ fn err_assignment_operator_on_const() -> TestResult {
 
     Ok(())
 }
+
+#[test]
+fn err_repetition_negative_count() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-repetition_negative_count"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArg(Value::Num(-1.0)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_repetition_too_large() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-repetition_too_large"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::RepetitionTooLarge(MAX_REPETITION_LENGTH).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_floor_division_by_zero() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-floor_division_by_zero"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(RuntimeError::DivisionByZero.to_string()))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_bitwise_non_integer() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-bitwise_non_integer"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::ExpectedInteger(Value::Num(1.5)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_shift_too_far() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-shift_too_far"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArg(Value::Num(64.0)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_compare_mixed_types() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-compare_mixed_types"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            "cannot compare num \"5\" with str \"a\" using \"<\"",
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_nesting_too_deep() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-nesting_too_deep"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            ParseError::NestingTooDeep(Token(TokenValue::LPAREN, 2, 262)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+/// Returns the expression returned by the main procedure of `source`.
+fn returned_expr(source: &str) -> Result<SugaredExpr, Box<dyn std::error::Error>> {
+    let tokens = tokenize(source).map_err(|e| e.to_string())?;
+    let program = parse_sugared_program(&tokens).map_err(|e| e.to_string())?;
+    let main = program.procedures.into_iter().find(|proc| proc.name == "main");
+    return match main.map(|proc| proc.body) {
+        Some(SugaredStatement::Block(statements)) => match statements.last() {
+            Some(SugaredStatement::Return(Some(expr))) => Ok(expr.clone()),
+            _ => Err("main should end with a return statement".into()),
+        },
+        _ => Err("main should be a block".into()),
+    };
+}
+
+#[test]
+fn binary_operator_precedence() -> TestResult {
+    let var = |name: &str| Box::new(SugaredExpr::Var(name.to_string()));
+    // spans are ignored when syntax trees are compared
+    let binary = |op, left, right| SugaredExpr::Binary(op, left, right, Default::default());
+    let operators = BINARY_OPERATORS.iter().flat_map(|level| level.iter());
+    // every pair of operators is checked, so a new operator in the table is tested automatically
+    for first in operators.clone() {
+        for second in operators.clone() {
+            let source = format!(
+                "proc main() {{ return a {} b {} c; }}",
+                TokenValue::OP(*first),
+                TokenValue::OP(*second)
+            );
+            let expected = if binary_precedence(*first) >= binary_precedence(*second) {
+                binary(
+                    *second,
+                    Box::new(binary(*first, var("a"), var("b"))),
+                    var("c"),
+                )
+            } else {
+                binary(
+                    *first,
+                    var("a"),
+                    Box::new(binary(*second, var("b"), var("c"))),
+                )
+            };
+            assert_eq!(returned_expr(&source)?, expected, "{source}");
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn err_compare_mixed_types_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-compare_mixed_types"));
+    cmd.assert().failure().stdout("").stderr(
+        "{\"severity\":\"error\",\"message\":\"cannot compare num \\\"5\\\" with str \\\"a\\\" using \\\"<\\\"\",\"position\":{\"line\":2,\"column\":9},\"help\":\"the right operand, which is a str, starts on line 2, column 13\"}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_compare_strings() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-compare_strings"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            "cannot compare str \"apple\" with str \"banana\" using \"<\"",
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_compare_lists() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-compare_lists"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            "cannot compare list \"[1, 2]\" with list \"[1, 2]\" using \"==\"",
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_bitwise_too_large() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-bitwise_too_large"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArg(Value::Num(1e20)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_bitwise_result_not_exact() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-bitwise_result_not_exact"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArgs(vec![Value::Num(9007199254740992.0), Value::Num(1.0)])
+                .to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_bitwise_not_not_exact() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-bitwise_not_not_exact"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArg(Value::Num(9007199254740992.0)).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
+
+#[test]
+fn err_shift_not_exact() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-shift_not_exact"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            RuntimeError::BadArgs(vec![Value::Num(1.0), Value::Num(63.0)]).to_string(),
+        ))
+        .stdout("");
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::error::{ParseError, RuntimeError};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/prelude/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn use_prelude() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--prelude").arg(file_name_to_path("prelude"));
+    cmd.arg(file_name_to_path("use_prelude"));
+    cmd.assert().success().stdout("16 hello world");
+
+    Ok(())
+}
+
+#[test]
+fn override_prelude() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--prelude").arg(file_name_to_path("prelude"));
+    cmd.arg(file_name_to_path("override_prelude"));
+    cmd.assert().success().stdout("hi 8 hello there");
+
+    Ok(())
+}
+
+#[test]
+fn err_without_prelude() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("use_prelude"));
+    cmd.assert().failure().stderr(starts_with(
+        RuntimeError::UnknownVariable("square".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_missing_prelude() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--prelude").arg(file_name_to_path("missing"));
+    cmd.arg(file_name_to_path("use_prelude"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::ImportFailed(file_name_to_path("missing"), String::new()).to_string(),
+    ));
+
+    Ok(())
+}
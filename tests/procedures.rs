@@ -1,8 +1,11 @@
 use std::process::Command;
 
 use assert_cmd::prelude::*;
-use linger::error::{ParseError, RuntimeError};
-use predicates::prelude::predicate::str::contains;
+use linger::{
+    error::{ParseError, RuntimeError},
+    tokenizer::{Keyword, Token, TokenValue},
+};
+use predicates::prelude::predicate::str::{contains, starts_with};
 
 fn file_name_to_path(s: &str) -> String {
     return format!("test_programs/procedures/{}.ling", s);
@@ -32,6 +35,16 @@ fn closures() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn escaping_closures() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("escaping_closures"));
+    cmd.assert().success().stdout("6 1 2 1 0 20 7 120");
+
+    Ok(())
+}
+
 #[test]
 fn higher_order_procedure() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -76,6 +89,21 @@ fn err_keyword_as_param_top_level_proc() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn err_keyword_as_arg() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-keyword_as_arg"));
+    cmd.assert()
+        .failure()
+        .stderr(starts_with(
+            ParseError::KeywordAsArg(Token(TokenValue::KW(Keyword::If), 6, 20)).to_string(),
+        ))
+        .stderr(contains("(\"if\") to pass it as a string"));
+
+    Ok(())
+}
+
 #[test]
 fn err_keyword_as_param_top_level_lambda() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -94,8 +122,179 @@ fn err_arg_mismatch() -> TestResult {
 
     cmd.arg(file_name_to_path("err-arg_mismatch"));
     cmd.assert().failure().stderr(contains(
-        RuntimeError::ArgMismatch("foo".to_string(), 2, 0).to_string(),
+        RuntimeError::ArgMismatch("foo".to_string(), 2, 0, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn mutual_recursion() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("mutual_recursion"));
+    cmd.assert().success().stdout("true true");
+
+    Ok(())
+}
+
+#[test]
+fn tail_recursion() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("tail_recursion"));
+    cmd.assert().success().stdout("1250025000 done");
+
+    Ok(())
+}
+
+#[test]
+fn deep_recursion() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("deep_recursion"));
+    cmd.assert().success().stdout("999");
+
+    Ok(())
+}
+
+#[test]
+fn err_call_too_deep() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-call_too_deep"));
+    cmd.assert()
+        .failure()
+        .stderr(contains(RuntimeError::CallTooDeep.to_string()));
+
+    Ok(())
+}
+
+#[test]
+fn err_arg_mismatch_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-arg_mismatch"));
+    cmd.assert().failure().stderr(
+        "{\"severity\":\"error\",\"message\":\"procedure \\\"foo\\\" expected 2 args, instead got 0\",\"position\":{\"line\":3,\"column\":3},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_arg_mismatch_tail_call_position() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--error-format=json");
+    cmd.arg(file_name_to_path("err-arg_mismatch_tail_call"));
+    cmd.assert().failure().stderr(
+        "{\"severity\":\"error\",\"message\":\"procedure \\\"f\\\" expected 1 args, instead got 2\",\"position\":{\"line\":6,\"column\":10},\"help\":null}\n",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_arg_mismatch_named_lambda() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    // the lambda is named after the variable it is bound to, not the parameter it is called through
+    cmd.arg(file_name_to_path("err-arg_mismatch_named_lambda"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::ArgMismatch("add".to_string(), 2, 1, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn keyword_args() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("keyword_args"));
+    cmd.assert()
+        .success()
+        .stdout("[[1, 2], line, 3] [[], dots, nil] hello world 4");
+
+    Ok(())
+}
+
+#[test]
+fn err_keyword_args_missing_param() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-keyword_args_missing_param"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::ArgMismatch("plot".to_string(), 3, 2, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_unknown_param() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-unknown_param"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::UnknownParam("plot".to_string(), "colour".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_arg() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_arg"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::DuplicateArg("plot".to_string(), "xs".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_positional_after_keyword_arg() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-positional_after_keyword_arg"));
+    cmd.assert().failure().stderr(contains(
+        ParseError::PositionalAfterKeywordArg("style".to_string()).to_string(),
     ));
 
     Ok(())
 }
+
+#[test]
+fn pure_procedures() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("pure_procedures"));
+    cmd.assert().success().stdout("14 1");
+
+    Ok(())
+}
+
+#[test]
+fn err_pure_procedures() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-pure_procedures"));
+    cmd.assert()
+        .failure()
+        .stderr(contains(
+            "pure procedure \"double\" calls \"log\" on line 8, which is not pure",
+        ))
+        .stderr(contains(
+            "pure procedure \"double\" calls the impure builtin \"print\" on line 9",
+        ))
+        .stderr(contains(
+            "pure procedure \"double\" assigns to \"CALLS\" on line 10, which is not declared in it",
+        ));
+
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::{
+    env, fs,
+    fs::File,
+    process::{Command, Stdio},
+};
+
+use assert_cmd::prelude::*;
+use linger::error::RuntimeError;
+use predicates::prelude::predicate::str::contains;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/replay/{}", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn record_and_replay() -> TestResult {
+    let log = env::temp_dir().join(format!("linger-replay-{}.log", std::process::id()));
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+    cmd.arg("--record")
+        .arg(&log)
+        .arg(file_name_to_path("guess.ling"))
+        .stdin(File::open(file_name_to_path("guess_input.txt"))?);
+    let recorded = cmd.assert().success().get_output().stdout.clone();
+    assert!(String::from_utf8(recorded.clone())?.starts_with("Ada "));
+
+    // the replayed run reads nothing from stdin and gets the same random number
+    let mut cmd = Command::cargo_bin("linger-core")?;
+    cmd.arg("--replay")
+        .arg(&log)
+        .arg(file_name_to_path("guess.ling"))
+        .stdin(Stdio::null());
+    cmd.assert().success().stdout(recorded);
+
+    fs::remove_file(&log)?;
+    Ok(())
+}
+
+#[test]
+fn replay_log() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--replay")
+        .arg(file_name_to_path("guess.log"))
+        .arg(file_name_to_path("guess.ling"));
+    cmd.assert()
+        .success()
+        .stdout("Grace \"G\" Hopper 4 true true");
+
+    Ok(())
+}
+
+#[test]
+fn err_replay_diverged() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--replay")
+        .arg(file_name_to_path("err-diverged.log"))
+        .arg(file_name_to_path("guess.ling"));
+    cmd.assert().failure().stderr(contains(
+        RuntimeError::ReplayDiverged(
+            "input was called where the log has a call to random".to_string(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
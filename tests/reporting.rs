@@ -0,0 +1,68 @@
+use linger::{
+    diagnostic::Diagnostic,
+    reporting::{Reporter, Style},
+};
+
+const SOURCE: &str = "proc main() {\n  let a = list(1);\n}";
+
+fn diagnostic() -> Diagnostic {
+    Diagnostic::warning("\"list(...)\" is deprecated".to_string(), Some((2, 11)))
+        .with_help("write a list literal instead".to_string())
+}
+
+#[test]
+fn plain_report() {
+    let reporter = Reporter::new("main.ling", SOURCE);
+    assert_eq!(
+        reporter.render(&diagnostic()),
+        "warning: \"list(...)\" is deprecated\nhelp: write a list literal instead"
+    );
+    assert_eq!(
+        reporter.render(&Diagnostic::error("something failed".to_string(), None)),
+        "something failed"
+    );
+}
+
+#[test]
+fn rich_report() {
+    let reporter = Reporter::new("main.ling", SOURCE)
+        .with_style(Style::Rich)
+        .with_width(24);
+    assert_eq!(
+        reporter.render(&diagnostic()),
+        [
+            "\x1b[1;33mwarning\x1b[0m\x1b[1m: \"list(...)\" is deprecated\x1b[0m",
+            " \x1b[1;34m-->\x1b[0m main.ling:2:11",
+            "  \x1b[1;34m|\x1b[0m",
+            "\x1b[1;34m2 |\x1b[0m   let a = list(1);",
+            "  \x1b[1;34m|\x1b[0m           \x1b[1;33m^\x1b[0m",
+            "\x1b[1;36mhelp\x1b[0m: write a list",
+            "      literal instead",
+        ]
+        .join("\n")
+    );
+}
+
+#[test]
+fn rich_report_with_tabs() {
+    let source = "proc main() {\n\tlet a = list(1);\n}";
+    let reporter = Reporter::new("main.ling", source).with_style(Style::Rich);
+    let diagnostic = Diagnostic::warning("\"list(...)\" is deprecated".to_string(), Some((2, 10)));
+    // the caret keeps the tab that comes before it, so that it is under the same column
+    assert!(reporter
+        .render(&diagnostic)
+        .contains("  \x1b[1;34m|\x1b[0m \t        \x1b[1;33m^\x1b[0m"));
+}
+
+#[test]
+fn json_report() {
+    let reporter = Reporter::new("main.ling", SOURCE).with_style(Style::Json);
+    assert_eq!(
+        reporter.render(&diagnostic()),
+        "{\"severity\":\"warning\",\"message\":\"\\\"list(...)\\\" is deprecated\",\"position\":{\"line\":2,\"column\":11},\"help\":\"write a list literal instead\"}"
+    );
+    assert_eq!(
+        Diagnostic::error("a\tb\n".to_string(), None).to_json(),
+        "{\"severity\":\"error\",\"message\":\"a\\tb\\n\",\"position\":null,\"help\":null}"
+    );
+}
@@ -0,0 +1,102 @@
+#![cfg(feature = "arbitrary")]
+
+use std::{fs, thread};
+
+use linger::{
+    arbitrary::{self, without_line_markers},
+    language::LanguageVersion,
+    parser::{desugar_module, parse_sugared_program_with, SugaredProgram},
+    pretty::{format_source, pretty_program},
+    tokenizer::tokenize,
+};
+use proptest::prelude::*;
+
+/// Parses `source` in its language version and removes the line markers from the result, which the
+/// generated programs do not have.
+fn parse(source: &str) -> Result<SugaredProgram, String> {
+    let tokens = tokenize(source).map_err(|e| e.to_string())?;
+    let language = LanguageVersion::parse(source).map_err(|e| e.to_string())?;
+    let program = parse_sugared_program_with(&tokens, language).map_err(|e| e.to_string())?;
+    return Ok(without_line_markers(program));
+}
+
+proptest! {
+    // the tokenizer is slow enough that the default of 256 cases takes minutes
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn parse_pretty_program(program in arbitrary::program()) {
+        let source = pretty_program(&program);
+        prop_assert_eq!(parse(&source), Ok(program), "source:\n{}", source);
+    }
+
+    #[test]
+    fn pretty_parsed_program(program in arbitrary::program()) {
+        let source = pretty_program(&program);
+        let reparsed = parse(&source).map_err(TestCaseError::fail)?;
+        prop_assert_eq!(pretty_program(&reparsed), source);
+    }
+
+    #[test]
+    fn desugar_parsed_program(program in arbitrary::program()) {
+        let reparsed = parse(&pretty_program(&program)).map_err(TestCaseError::fail)?;
+        prop_assert_eq!(desugar_module(reparsed), desugar_module(program.clone()));
+        // desugaring does not depend on anything but the program, e.g. the symbols interned so far
+        prop_assert_eq!(desugar_module(program.clone()), desugar_module(program));
+    }
+}
+
+/// Printing a parsed program is idempotent for the test programs with line comments and (nested)
+/// block comments, which are dropped when the program is first printed.
+#[test]
+fn pretty_commented_test_programs() -> Result<(), Box<dyn std::error::Error>> {
+    for dir in fs::read_dir("test_programs")? {
+        for file in fs::read_dir(dir?.path())? {
+            let path = file?.path();
+            if path.extension().is_none_or(|extension| extension != "ling") {
+                continue;
+            }
+            let original = fs::read_to_string(&path)?;
+            if !original.contains("//") && !original.contains("/*") {
+                continue;
+            }
+            // e.g. programs which are expected to fail to parse
+            let Ok(program) = parse(&original) else {
+                continue;
+            };
+            let source = pretty_program(&program);
+            let reparsed = parse(&source).map_err(|e| format!("{}: {e}", path.display()))?;
+            assert_eq!(pretty_program(&reparsed), source, "{}", path.display());
+        }
+    }
+    Ok(())
+}
+
+/// Formatting a source is idempotent for the test programs, including the lines between
+/// `// fmt: off` and `// fmt: on` comments, which are kept as they are written.
+#[test]
+fn format_test_programs() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // like the CLI, since some of the programs are nested too deeply for the default stack size
+    let formatter = thread::Builder::new().stack_size(256 << 20).spawn(|| {
+        for dir in fs::read_dir("test_programs")? {
+            for file in fs::read_dir(dir?.path())? {
+                let path = file?.path();
+                if path.extension().is_none_or(|extension| extension != "ling") {
+                    continue;
+                }
+                let original = fs::read_to_string(&path)?;
+                // e.g. programs which are expected to fail to parse
+                let Ok(source) = format_source(&original) else {
+                    continue;
+                };
+                let reformatted =
+                    format_source(&source).map_err(|e| format!("{}: {e}", path.display()))?;
+                assert_eq!(reformatted, source, "{}", path.display());
+                assert_eq!(parse(&source)?, parse(&original)?, "{}", path.display());
+            }
+        }
+        Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    })?;
+    formatter.join().expect("the formatter should not panic")?;
+    Ok(())
+}
@@ -20,6 +20,30 @@ fn shadowing() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn shadowing_warning() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("shadowing_warning"));
+    cmd.assert().success().stdout("3").stderr("");
+
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("--check")
+        .arg("--warn-shadowing")
+        .arg(file_name_to_path("shadowing_warning"));
+    cmd.assert().success().stdout("").stderr(
+        "warning: \"a\" on line 4 shadows the variable declared on line 2\n\
+         help: rename the new variable, or assign to the existing one if that was intended\n\
+         warning: \"x\" on line 11 shadows a parameter of \"scale\"\n\
+         help: rename the new variable, or assign to the existing one if that was intended\n\
+         warning: \"y\" on line 13 shadows a parameter of a lambda\n\
+         help: rename the new variable, or assign to the existing one if that was intended\n",
+    );
+
+    Ok(())
+}
+
 #[test]
 fn const_shadowing() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -40,6 +64,16 @@ fn reassignment_in_block() -> TestResult {
     Ok(())
 }
 
+#[test]
+fn shadowing_in_block() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("shadowing_in_block"));
+    cmd.assert().success().stdout("2 [5, 2]");
+
+    Ok(())
+}
+
 #[test]
 fn procedure_shadowing() -> TestResult {
     let mut cmd = Command::cargo_bin("linger-core")?;
@@ -0,0 +1,148 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::{error::RuntimeError, interpreter::Value, tokenizer::Operator};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/strings/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn compare() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("compare"));
+    cmd.assert()
+        .success()
+        .stdout(starts_with("true false -1 1 -1 1 0"));
+
+    Ok(())
+}
+
+#[test]
+fn chars_words_lines() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("chars_words_lines"));
+    cmd.assert().success().stdout(starts_with(
+        "[h, é, l, l, o] [] [the, quick, brown, fox, jumps] [] \
+         [  the quick\tbrown, fox  , , jumps] [no newline]",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn radix() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("radix"));
+    cmd.assert().success().stdout(starts_with(
+        "ff -1010 0 z 255 -10 35 123456789 -20000000000000 9007199254740992",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn index_assignment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("index_assignment"));
+    cmd.assert().success().stdout(starts_with("Jello"));
+
+    Ok(())
+}
+
+#[test]
+fn err_compare_mixed_types() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-compare_mixed_types"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::CannotCompare(
+            Operator::LT,
+            Value::Num(1.0),
+            Value::Str("a".to_string()),
+            Default::default(),
+        )
+        .to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_index_assignment_not_a_character() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-index_assignment_not_a_character"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("Jj".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_index_out_of_bounds() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-index_out_of_bounds"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::IndexOutOfBounds(Value::Num(3.0), 3, Default::default()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_parse_int_bad_digit() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-parse_int_bad_digit"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("102".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_to_str_radix_bad_base() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-to_str_radix_bad_base"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Num(37.0)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_to_str_radix_not_exact() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-to_str_radix_not_exact"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Num(123456789012345678901234.0)).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_parse_int_not_exact() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-parse_int_not_exact"));
+    cmd.assert().failure().stdout("").stderr(starts_with(
+        RuntimeError::BadArg(Value::Str("20000000000001".to_string())).to_string(),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::process::Command;
+
+use assert_cmd::prelude::*;
+use linger::error::ParseError;
+use predicates::prelude::predicate::str::contains;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/structs/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn structs() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("structs"));
+    cmd.assert().success().stdout("3 25 4 6");
+
+    Ok(())
+}
+
+#[test]
+fn struct_printing() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("struct_printing"));
+    cmd.assert()
+        .success()
+        .stdout("Point {x: 1, y: 2} Point {x: \"a\", y: Empty {}} true false 2");
+
+    Ok(())
+}
+
+#[test]
+fn field_assignment() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("field_assignment"));
+    cmd.assert()
+        .success()
+        .stdout("Point {x: 4, y: 30} Point {x: 1, y: 2} 7 Point {x: [1, 2], y: [0, 4]}");
+
+    Ok(())
+}
+
+#[test]
+fn err_no_such_field() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-no_such_field"));
+    cmd.assert()
+        .failure()
+        .stderr(contains("struct \"Point\" has no field \"z\""));
+
+    Ok(())
+}
+
+#[test]
+fn err_assign_no_such_field() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-assign_no_such_field"));
+    cmd.assert()
+        .failure()
+        .stderr(contains("struct \"Point\" has no field \"z\""));
+
+    Ok(())
+}
+
+#[test]
+fn struct_update() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("struct_update"));
+    cmd.assert().success().stdout(
+        "Point {x: 1, y: 2} Point {x: 5, y: 2} Point {x: 5, y: 2} Person {name: Ada, age: 37, city: Paris}",
+    );
+
+    Ok(())
+}
+
+#[test]
+fn err_struct_update_wrong_struct() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-struct_update_wrong_struct"));
+    cmd.assert().failure().stderr(contains(
+        "expected an instance of struct \"Point\", but got the struct \"Size {x: 1, y: 2}\"",
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_struct_update_no_such_field() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-struct_update_no_such_field"));
+    cmd.assert()
+        .failure()
+        .stderr(contains("struct \"Point\" has no field \"z\""));
+
+    Ok(())
+}
+
+#[test]
+fn err_field_of_non_struct() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-field_of_non_struct"));
+    cmd.assert()
+        .failure()
+        .stderr(contains("the num \"5\" has no field \"x\""));
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_field() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_field"));
+    cmd.assert().failure().stderr(contains(
+        ParseError::DuplicateField("Point".to_string(), "x".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_field_update() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_field_update"));
+    cmd.assert().failure().stderr(contains(
+        ParseError::DuplicateFieldUpdate("Point".to_string(), "x".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::{fs, process::Command};
+
+use assert_cmd::prelude::*;
+use linger::{
+    desugar::Expr,
+    error::{ParseError, RuntimeError},
+    parse,
+};
+use predicates::prelude::predicate::str::starts_with;
+
+fn file_name_to_path(s: &str) -> String {
+    return format!("test_programs/top_level/{}.ling", s);
+}
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn lookup_table() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("lookup_table"));
+    cmd.assert().success().stdout("two 2 4");
+
+    Ok(())
+}
+
+#[test]
+fn err_reassign_constant() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-reassign_constant"));
+    cmd.assert().failure().stderr(starts_with(
+        RuntimeError::ReassignConstant("LIMIT".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn err_duplicate_name() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-duplicate_name"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::DuplicateTopLevelName("helper".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn constant_folding() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("constant_folding"));
+    cmd.assert().success().stdout("80 40 ---> true 2 -5");
+
+    // constants which only apply operators to literals and earlier constants are folded
+    let source = fs::read_to_string(file_name_to_path("constant_folding"))?;
+    let program = parse(&source).map_err(|_| "program should parse")?;
+    let constants: Vec<(&str, &Expr)> = program
+        .constants
+        .iter()
+        .map(|(name, expr)| (name.as_str(), expr))
+        .collect();
+    assert_eq!(constants[0], ("WIDTH", &Expr::Num(80.0)));
+    assert_eq!(constants[1], ("HALF", &Expr::Num(40.0)));
+    assert_eq!(constants[2], ("RULE", &Expr::Str("--->".to_string())));
+    assert_eq!(constants[3], ("WIDE", &Expr::Bool(true)));
+    // a call is not a constant expression, so neither is an expression which uses its result
+    assert!(matches!(constants[4].1, Expr::PrimitiveCall(..)));
+    assert!(matches!(constants[5].1, Expr::Binary(..)));
+
+    Ok(())
+}
+
+#[test]
+fn contextual_keywords() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("contextual_keywords"));
+    cmd.assert().success().stdout("pub 1 2");
+
+    Ok(())
+}
+
+#[test]
+fn language_2() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("language_2"));
+    cmd.assert().success().stdout("no-semicolons 6 nil 1");
+
+    Ok(())
+}
+
+#[test]
+fn err_unknown_language_version() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("err-unknown_language_version"));
+    cmd.assert().failure().stderr(starts_with(
+        ParseError::UnknownLanguageVersion("3".to_string()).to_string(),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn fmt_directives() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg(file_name_to_path("fmt_directives"));
+    cmd.assert().success().stdout("30 6");
+
+    Ok(())
+}
+
+#[test]
+fn fmt_directives_format() -> TestResult {
+    let mut cmd = Command::cargo_bin("linger-core")?;
+
+    cmd.arg("fmt").arg(file_name_to_path("fmt_directives"));
+    cmd.assert().success().stdout(
+        "// a program whose tables are kept as they are written by `linger fmt`
+
+const WIDTH = 3;
+
+// fmt: off
+const TABLE = [
+  [1,   2,   3],
+  [10,  20,  30]
+];
+// fmt: on
+
+const HEIGHT = 2;
+
+proc main() {
+  // fmt: off
+  print(TABLE[1][2]  ,  WIDTH * HEIGHT);
+  // fmt: on
+}
+
+proc unused(x) {
+  return x + 1;
+}
+",
+    );
+
+    Ok(())
+}